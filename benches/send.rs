@@ -0,0 +1,75 @@
+//! Host timings of the send paths of the producer.
+//!
+//! ```text
+//! cargo bench -F producer
+//! ```
+//! The absolute numbers say little about a microcontroller, see `examples/bench_cycles.rs`
+//! for cycles on the target, but they compare the paths with each other and show
+//! regressions, e.g. when the accesses to the indices change.
+
+use core::fmt::Write;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ramlink::producer::RB;
+
+/// How long each path is repeated
+const RUN: Duration = Duration::from_millis(500);
+
+/// Sent by each iteration. It fits in both `RB<64>` and `RB<63>`, so that nothing blocks.
+const DATA: [u8; 62] = [0x55; 62];
+
+/// Calls `send` until [`RUN`] is over, and prints the time it took per byte, `send`
+/// sending `bytes` bytes per call
+fn bench(name: &str, bytes: usize, mut send: impl FnMut()) {
+    for _ in 0..1000 {
+        send();
+    }
+    let start = Instant::now();
+    let mut calls = 0u64;
+    while start.elapsed() < RUN {
+        for _ in 0..1000 {
+            send();
+        }
+        calls += 1000;
+    }
+    let per_byte = start.elapsed().as_secs_f64() * 1e9 / (calls * bytes as u64) as f64;
+    println!("{name:<32} {per_byte:>8.2} ns/byte");
+}
+
+fn main() {
+    // Each iteration empties the ring buffer first, which only writes both indices
+    let mut rb = RB::<64>::new();
+    bench("send_bytes_blocking, RB<64>", DATA.len(), || {
+        rb.reset();
+        rb.send_bytes_blocking(black_box(&DATA));
+    });
+
+    let mut rb = RB::<64>::new();
+    bench("try_send_bytes, RB<64>", DATA.len(), || {
+        rb.reset();
+        black_box(rb.try_send_bytes(black_box(&DATA)));
+    });
+
+    // The indices wrap around with a comparison instead of a mask
+    let mut rb = RB::<63>::new();
+    bench("try_send_bytes, RB<63>", DATA.len(), || {
+        rb.reset();
+        black_box(rb.try_send_bytes(black_box(&DATA)));
+    });
+
+    let mut rb = RB::<64>::new();
+    bench("try_send_byte, RB<64>", DATA.len(), || {
+        rb.reset();
+        for &byte in black_box(&DATA) {
+            black_box(rb.try_send_byte(byte));
+        }
+    });
+
+    // "t=-1234\n" is 8 bytes
+    let mut rb = RB::<64>::new();
+    bench("fmt::Write, RB<64>", 8, || {
+        rb.reset();
+        writeln!(rb, "t={}", black_box(-1234)).unwrap();
+    });
+}
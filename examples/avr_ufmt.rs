@@ -0,0 +1,54 @@
+//! Sends a temperature reading with `ufmt` instead of `core::fmt`.
+//!
+//! `core::fmt` pulls a lot of formatting machinery in, which hurts on small AVR parts.
+//! To compare, build this example for your part, then swap `report_ufmt` for `report_fmt`
+//! in `main` and build it again:
+//! ```text
+//! RUSTFLAGS="-C target-cpu=atmega328p -C panic=abort" cargo +nightly build --release \
+//!     --example avr_ufmt -F producer,ufmt --target avr-none -Zbuild-std=core
+//! avr-size target/avr-none/release/examples/avr_ufmt.elf
+//! ```
+//! On the host, this simply formats one reading and prints how many bytes were queued.
+
+#![cfg_attr(target_arch = "avr", no_std, no_main)]
+
+use core::fmt::Write;
+use core::hint::black_box;
+
+use ramlink::producer::RB;
+use ufmt::uwrite;
+
+/// Formats `temp` with `ufmt`
+#[allow(dead_code)]
+fn report_ufmt(rb: &mut RB<32>, temp: i16) {
+    uwrite!(rb, "t={}\n", temp).ok();
+}
+
+/// Formats `temp` with `core::fmt`, for comparison
+#[allow(dead_code)]
+fn report_fmt(rb: &mut RB<32>, temp: i16) {
+    writeln!(rb, "t={}", temp).ok();
+}
+
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub extern "C" fn main() -> ! {
+    let mut rb = RB::<32>::new();
+    loop {
+        report_ufmt(&mut rb, black_box(21));
+        black_box(&rb);
+    }
+}
+
+#[cfg(target_arch = "avr")]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[cfg(not(target_arch = "avr"))]
+fn main() {
+    let mut rb = RB::<32>::new();
+    report_ufmt(&mut rb, black_box(21));
+    println!("queued {} bytes", rb.len());
+}
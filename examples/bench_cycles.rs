@@ -0,0 +1,152 @@
+//! Counts the cycles the send paths of the producer take on the target.
+//!
+//! The counts end up in `BENCH_CYCLES`, one per path, each for sending [`BYTES`] bytes to
+//! an empty ring buffer. They are read with a debugger once `main` is looping:
+//! ```text
+//! RUSTFLAGS="-C target-cpu=atmega328p -C panic=abort" cargo +nightly build --release \
+//!     --example bench_cycles -F producer --target avr-none -Zbuild-std=core
+//! (gdb) print BENCH_CYCLES
+//! ```
+//! AVR counts with Timer1 running at the CPU clock, as found on the ATmega328P, and
+//! Cortex-M with the DWT cycle counter; on Cortex-M, the startup code of the board must
+//! call `main`. The count includes reading the counter, a few cycles.
+//!
+//! On the host, this runs each path once and prints how many bytes were queued; see
+//! `benches/send.rs` for timings.
+
+#![cfg_attr(any(target_arch = "avr", target_arch = "arm"), no_std, no_main)]
+
+use core::fmt::Write;
+use core::hint::black_box;
+
+use ramlink::producer::RB;
+
+/// Bytes sent by each path
+const BYTES: usize = 16;
+
+/// Cycles taken by `send_bytes_blocking`, `try_send_bytes` on a ring buffer whose size is
+/// a power of two, then on one whose size is not, then `fmt::Write`
+#[no_mangle]
+static mut BENCH_CYCLES: [u32; 4] = [0; 4];
+
+#[cfg(target_arch = "avr")]
+mod counter {
+    /// Timer1 registers of the ATmega328P
+    const TCCR1A: *mut u8 = 0x80 as *mut u8;
+    const TCCR1B: *mut u8 = 0x81 as *mut u8;
+    const TCNT1L: *mut u8 = 0x84 as *mut u8;
+    const TCNT1H: *mut u8 = 0x85 as *mut u8;
+
+    /// Runs Timer1 at the CPU clock
+    pub fn start() {
+        unsafe {
+            core::ptr::write_volatile(TCCR1A, 0);
+            core::ptr::write_volatile(TCCR1B, 1);
+        }
+    }
+
+    /// Returns the cycles counted, wrapping around at 2^16
+    pub fn now() -> u32 {
+        // Reading the low byte latches the high one
+        let low = unsafe { core::ptr::read_volatile(TCNT1L) };
+        let high = unsafe { core::ptr::read_volatile(TCNT1H) };
+        u16::from_le_bytes([low, high]) as u32
+    }
+
+    pub fn elapsed(start: u32) -> u32 {
+        (now() as u16).wrapping_sub(start as u16) as u32
+    }
+}
+
+#[cfg(target_arch = "arm")]
+mod counter {
+    const DEMCR: *mut u32 = 0xe000_edfc as *mut u32;
+    const DWT_LAR: *mut u32 = 0xe000_1fb0 as *mut u32;
+    const DWT_CTRL: *mut u32 = 0xe000_1000 as *mut u32;
+    const DWT_CYCCNT: *mut u32 = 0xe000_1004 as *mut u32;
+
+    /// Enables the trace unit, unlocks the DWT (needed on the Cortex-M7), and starts its
+    /// cycle counter
+    pub fn start() {
+        unsafe {
+            core::ptr::write_volatile(DEMCR, core::ptr::read_volatile(DEMCR) | 1 << 24);
+            core::ptr::write_volatile(DWT_LAR, 0xc5ac_ce55);
+            core::ptr::write_volatile(DWT_CYCCNT, 0);
+            core::ptr::write_volatile(DWT_CTRL, core::ptr::read_volatile(DWT_CTRL) | 1);
+        }
+    }
+
+    pub fn now() -> u32 {
+        unsafe { core::ptr::read_volatile(DWT_CYCCNT) }
+    }
+
+    pub fn elapsed(start: u32) -> u32 {
+        now().wrapping_sub(start)
+    }
+}
+
+/// No cycle counter on the host
+#[cfg(not(any(target_arch = "avr", target_arch = "arm")))]
+mod counter {
+    pub fn start() {}
+
+    pub fn now() -> u32 {
+        0
+    }
+
+    pub fn elapsed(_start: u32) -> u32 {
+        0
+    }
+}
+
+/// Empties `rb`, then counts the cycles `send` takes on it
+fn measure<const SIZE: usize>(rb: &mut RB<SIZE>, send: impl FnOnce(&mut RB<SIZE>)) -> u32 {
+    rb.reset();
+    let start = counter::now();
+    send(rb);
+    counter::elapsed(start)
+}
+
+/// Measures every path, and returns the number of bytes queued by the last one
+fn run() -> usize {
+    let data = black_box([0x55; BYTES]);
+    let mut rb = RB::<32>::new();
+    let mut rb_odd = RB::<31>::new();
+    counter::start();
+
+    let cycles = [
+        measure(&mut rb, |rb| rb.send_bytes_blocking(&data)),
+        measure(&mut rb, |rb| {
+            black_box(rb.try_send_bytes(&data));
+        }),
+        measure(&mut rb_odd, |rb| {
+            black_box(rb.try_send_bytes(&data));
+        }),
+        // "t=-12345678901\n" is 16 bytes
+        measure(&mut rb, |rb| {
+            writeln!(rb, "t={}", black_box(-12345678901i64)).ok();
+        }),
+    ];
+    unsafe { core::ptr::write_volatile(core::ptr::addr_of_mut!(BENCH_CYCLES), cycles) };
+    rb.len()
+}
+
+#[cfg(any(target_arch = "avr", target_arch = "arm"))]
+#[no_mangle]
+pub extern "C" fn main() -> ! {
+    run();
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(any(target_arch = "avr", target_arch = "arm"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[cfg(not(any(target_arch = "avr", target_arch = "arm")))]
+fn main() {
+    println!("queued {} bytes", run());
+}
@@ -0,0 +1,97 @@
+//! Logs with defmt into a ring buffer, on a Cortex-M.
+//!
+//! The defmt table ends up in the ELF file, which the linker script of defmt lays out:
+//! ```text
+//! DEFMT_LOG=debug RUSTFLAGS="-C link-arg=-Tdefmt.x" cargo +nightly build --release \
+//!     --example defmt_cortex_m -F defmt --target thumbv7em-none-eabihf -Zbuild-std=core
+//! ```
+//! Without `DEFMT_LOG`, defmt only keeps the errors. The host then finds `DEFMT_RB` and
+//! decodes the frames with that same ELF file, see `DefmtReader` in the consumer. As in
+//! `bench_cycles`, the startup code of the board must call `main`.
+//!
+//! On the host, this logs the same frames and prints how many bytes were queued.
+
+#![cfg_attr(target_arch = "arm", no_std, no_main)]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use ramlink::producer::AtomicRB;
+
+/// Read by the host, which finds it by its symbol
+#[no_mangle]
+static DEFMT_RB: AtomicRB<256> = AtomicRB::new();
+
+/// Number of frames logged, the timestamp of each, so that the host sees those dropped
+static FRAMES: AtomicU32 = AtomicU32::new(0);
+
+defmt::timestamp!("#{=u32}", FRAMES.fetch_add(1, Ordering::Relaxed));
+
+/// The critical section of the logger, masking interrupts on the single core of the board.
+/// A HAL or `cortex-m` with its `critical-section-single-core` feature provides the same.
+#[cfg(target_arch = "arm")]
+mod critical_section_impl {
+    use core::arch::asm;
+
+    /// Number of critical sections entered and not left, only changed with interrupts masked
+    static mut DEPTH: usize = 0;
+    /// Whether interrupts were enabled when the outermost critical section was entered
+    static mut WAS_ENABLED: bool = false;
+
+    struct SingleCore;
+    critical_section::set_impl!(SingleCore);
+
+    unsafe impl critical_section::Impl for SingleCore {
+        unsafe fn acquire() {
+            let primask: u32;
+            asm!("mrs {}, PRIMASK", out(reg) primask, options(nomem, nostack, preserves_flags));
+            asm!("cpsid i", options(nomem, nostack, preserves_flags));
+            if DEPTH == 0 {
+                WAS_ENABLED = primask & 1 == 0;
+            }
+            DEPTH += 1;
+        }
+
+        unsafe fn release(_: ()) {
+            DEPTH -= 1;
+            if DEPTH == 0 && WAS_ENABLED {
+                asm!("cpsie i", options(nomem, nostack, preserves_flags));
+            }
+        }
+    }
+}
+
+/// Logs a few frames, and returns the number of bytes queued
+fn run() -> usize {
+    ramlink::producer::defmt::init(&DEFMT_RB);
+
+    defmt::info!("booted, ring buffer of {=usize} bytes", DEFMT_RB.capacity());
+    for motor in 0..3_u8 {
+        let current: i16 = 120 * motor as i16 - 100;
+        if current < 0 {
+            defmt::warn!("stall on motor {=u8}", motor);
+        } else {
+            defmt::debug!("motor {=u8}: {=i16} mA", motor, current);
+        }
+    }
+    DEFMT_RB.len()
+}
+
+#[cfg(target_arch = "arm")]
+#[no_mangle]
+pub extern "C" fn main() -> ! {
+    run();
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(target_arch = "arm")]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn main() {
+    println!("queued {} bytes", run());
+}
@@ -0,0 +1,63 @@
+//! Streams a ring buffer to stdout through the first debug probe found by probe-rs.
+//!
+//! The chip is named as probe-rs knows it, see `probe-rs chip list`, and the address of the
+//! ring buffer is that of its symbol, e.g. from `nm firmware.elf | grep RING_BUF`:
+//! ```text
+//! cargo run --example probe_rs_dump -F probe-rs -- STM32F411RETx 0x20000100
+//! ```
+//! The firmware keeps running; stop with Ctrl-C.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use probe_rs::probe::list::Lister;
+use probe_rs::Permissions;
+use ramlink::consumer::adapters::ProbeRsReader;
+use ramlink::consumer::ProducerDevice;
+
+/// Time between reads while the ring buffer is empty
+const POLL: Duration = Duration::from_millis(10);
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [chip, address] = &args[..] else {
+        eprintln!("Usage: probe_rs_dump CHIP ADDRESS");
+        return ExitCode::FAILURE;
+    };
+    let Ok(address) = usize::from_str_radix(address.trim_start_matches("0x"), 16) else {
+        eprintln!("invalid address {address}, expected hexadecimal");
+        return ExitCode::FAILURE;
+    };
+
+    match dump(chip, address) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Attaches to `chip` through the first probe, then copies the bytes of the ring buffer at
+/// `address` to stdout until writing fails
+fn dump(chip: &str, address: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let probes = Lister::new().list_all();
+    let probe = probes.first().ok_or("no debug probe found")?.open()?;
+    let mut session = probe.attach(chip, Permissions::default())?;
+    let reader = ProbeRsReader::new(session.core(0)?);
+    let mut device = ProducerDevice::new(reader, address)?;
+
+    let mut stdout = std::io::stdout().lock();
+    let mut result = Ok(());
+    device.run(POLL, |data| {
+        result = stdout.write_all(data).and_then(|()| stdout.flush());
+        if result.is_ok() {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    })?;
+    Ok(result?)
+}
@@ -0,0 +1,503 @@
+//! Streams a ring buffer to stdout or to a file, through a debug interface, until Ctrl-C.
+//!
+//! ```text
+//! ramlink-dump --elf firmware.elf --backend openocd --lines
+//! ramlink-dump --address 0x3f0e --backend gdb --target localhost:3333 --hex
+//! ramlink-dump --elf firmware.elf --backend probe-rs --chip STM32F411RETx --lines
+//! ramlink-dump --elf firmware.elf --symbol CHANNELS --channel trace --hex
+//! ramlink-dump --elf firmware.elf --tagged
+//! ramlink-dump --elf firmware.elf --hex --capture session.cap
+//! ramlink-dump --replay session.cap --lines
+//! ramlink-dump --elf firmware.elf --format json
+//! ```
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use getopts::{Matches, Options};
+#[cfg(feature = "probe-rs")]
+use probe_rs::{probe::list::Lister, Permissions};
+#[cfg(feature = "probe-rs")]
+use ramlink::consumer::adapters::ProbeRsReader;
+use ramlink::consumer::adapters::{GdbRspReader, OpenOcdReader, DEFAULT_PORT};
+use ramlink::consumer::{
+    address_from_elf, CaptureWriter, ConsumerError, ConsumerErrorKind, ControlBlockReader,
+    JsonSink, MemoryReader, PollPolicy, ProducerDevice, ReadEvent, Record, ReplayError,
+    ReplayReader,
+};
+
+/// Set on Ctrl-C, so that the ring buffer is detached before exiting: a producer using
+/// `send_bytes_auto` would block forever otherwise
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Timeout of the connection to the backend, and of each of its transfers
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default port of GDB servers, e.g. OpenOCD's or JLinkGDBServer's
+const GDB_PORT: u16 = 3333;
+
+/// Where the ring buffer is
+enum Location {
+    Elf {
+        path: PathBuf,
+        symbol: String,
+    },
+    Address(usize),
+    /// A capture file written with `--capture`, instead of a device
+    Replay(PathBuf),
+}
+
+/// How the bytes are written
+#[derive(Clone, Copy)]
+enum Format {
+    /// As they are
+    Raw,
+    /// Hexdump, 16 bytes per line
+    Hex,
+    /// Lines of text, without invalid UTF-8
+    Lines,
+    /// Records sent with `send_text` as lines, and the others as hexdumps
+    Tagged,
+    /// JSON lines of the chunks read and of the bytes dropped
+    Json,
+}
+
+struct Config {
+    location: Location,
+    /// Channel to read, if the location is that of a control block
+    channel: Option<String>,
+    poll: PollPolicy,
+    format: Format,
+    output: Box<dyn Write>,
+    /// Where the bytes are recorded as well, with the time they were read
+    capture: Option<CaptureWriter<File>>,
+}
+
+fn options() -> Options {
+    let mut options = Options::new();
+    options.optopt(
+        "",
+        "elf",
+        "ELF file of the firmware, to look the ring buffer up",
+        "PATH",
+    );
+    options.optopt(
+        "",
+        "symbol",
+        "ring buffer in the ELF file (default RING_BUF)",
+        "NAME",
+    );
+    options.optopt(
+        "",
+        "address",
+        "address of the ring buffer, instead of --elf",
+        "ADDR",
+    );
+    options.optopt(
+        "",
+        "channel",
+        "read this channel of the control block at --elf or --address",
+        "NAME",
+    );
+    options.optopt(
+        "",
+        "backend",
+        "openocd (default), gdb or probe-rs",
+        "BACKEND",
+    );
+    options.optopt("", "target", "host:port of the backend", "HOST:PORT");
+    options.optopt(
+        "",
+        "chip",
+        "target chip of --backend probe-rs, as in `probe-rs chip list`",
+        "CHIP",
+    );
+    options.optopt("", "chunk", "bytes per transfer of the backend", "BYTES");
+    options.optopt("", "poll-ms", "interval between reads (default 10)", "MS");
+    options.optopt(
+        "",
+        "max-poll-ms",
+        "back off up to MS while the ring buffer is empty (default --poll-ms)",
+        "MS",
+    );
+    options.optflag("", "raw", "write the bytes as they are (default)");
+    options.optflag("", "hex", "write a hexdump");
+    options.optflag("", "lines", "write lines of text");
+    options.optflag(
+        "",
+        "tagged",
+        "write text records as lines and binary ones as hexdumps",
+    );
+    options.optopt(
+        "",
+        "format",
+        "raw, hex, lines, tagged, or json for a JSON object per chunk or gap",
+        "FORMAT",
+    );
+    options.optopt("o", "output", "write to FILE instead of stdout", "FILE");
+    options.optopt(
+        "",
+        "capture",
+        "also record the bytes read to FILE, with --raw, --hex or json, for --replay",
+        "FILE",
+    );
+    options.optopt(
+        "",
+        "replay",
+        "read a file written with --capture instead of a device",
+        "FILE",
+    );
+    options.optflag("h", "help", "print this help");
+    options
+}
+
+/// Parses an address in hexadecimal with a `0x` prefix, or in decimal
+fn parse_address(address: &str) -> Result<usize, String> {
+    match address.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => address.parse(),
+    }
+    .map_err(|e| format!("invalid address {address}: {e}"))
+}
+
+fn config(matches: &Matches) -> Result<Config, Box<dyn Error>> {
+    let location = match (
+        matches.opt_str("elf"),
+        matches.opt_str("address"),
+        matches.opt_str("replay"),
+    ) {
+        (Some(path), None, None) => Location::Elf {
+            path: path.into(),
+            symbol: matches
+                .opt_str("symbol")
+                .unwrap_or_else(|| "RING_BUF".into()),
+        },
+        (None, Some(address), None) => Location::Address(parse_address(&address)?),
+        (None, None, Some(path)) => {
+            if matches.opt_present("channel") {
+                return Err("--channel can't be given with --replay".into());
+            }
+            Location::Replay(path.into())
+        }
+        _ => return Err("one of --elf, --address and --replay must be given".into()),
+    };
+    let millis = |name: &str, default| match matches.opt_str(name) {
+        Some(ms) => Ok(Duration::from_millis(
+            ms.parse().map_err(|e| format!("--{name}: {e}"))?,
+        )),
+        None => Ok::<_, String>(default),
+    };
+    let min = millis("poll-ms", Duration::from_millis(10))?;
+    let poll = PollPolicy::adaptive(min, millis("max-poll-ms", min)?);
+    let format = match (
+        matches.opt_present("raw"),
+        matches.opt_present("hex"),
+        matches.opt_present("lines"),
+        matches.opt_present("tagged"),
+        matches.opt_str("format").as_deref(),
+    ) {
+        (_, false, false, false, None | Some("raw")) => Format::Raw,
+        (false, true, false, false, None) | (false, false, false, false, Some("hex")) => {
+            Format::Hex
+        }
+        (false, false, true, false, None) | (false, false, false, false, Some("lines")) => {
+            Format::Lines
+        }
+        (false, false, false, true, None) | (false, false, false, false, Some("tagged")) => {
+            Format::Tagged
+        }
+        (false, false, false, false, Some("json")) => Format::Json,
+        (false, false, false, false, Some(format)) => {
+            return Err(format!("unknown format {format}").into())
+        }
+        _ => {
+            return Err(
+                "only one of --raw, --hex, --lines, --tagged and --format can be given".into(),
+            )
+        }
+    };
+    let output: Box<dyn Write> = match matches.opt_str("output") {
+        Some(path) => Box::new(File::create(&path).map_err(|e| format!("{path}: {e}"))?),
+        None => Box::new(io::stdout()),
+    };
+    let capture = match matches.opt_str("capture") {
+        Some(_) if matches!(format, Format::Lines | Format::Tagged) => {
+            return Err("--capture can only be given with --raw, --hex or --format json".into())
+        }
+        Some(path) => {
+            let file = File::create(&path).map_err(|e| format!("{path}: {e}"))?;
+            Some(CaptureWriter::new(file).map_err(|e| format!("{path}: {e}"))?)
+        }
+        None => None,
+    };
+    Ok(Config {
+        location,
+        channel: matches.opt_str("channel"),
+        poll,
+        format,
+        output,
+        capture,
+    })
+}
+
+/// Writes `data` as lines of a hexdump, `offset` being the number of bytes before it
+fn write_hex(output: &mut dyn Write, offset: u64, data: &[u8]) -> io::Result<()> {
+    for (i, line) in data.chunks(16).enumerate() {
+        write!(output, "{:08x} ", offset + 16 * i as u64)?;
+        for byte in line {
+            write!(output, " {byte:02x}")?;
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(
+            output,
+            "{:width$}  |{ascii}|",
+            "",
+            width = 3 * (16 - line.len())
+        )?;
+    }
+    output.flush()
+}
+
+/// Attaches to the ring buffer through `reader`, and writes what it reads until [`STOP`]
+/// is set
+fn stream<M>(reader: M, config: Config) -> Result<(), Box<dyn Error>>
+where
+    M: MemoryReader,
+    M::Error: Debug + 'static,
+{
+    let address = match &config.location {
+        Location::Elf { path, symbol } => {
+            address_from_elf(path, symbol).map_err(|e| format!("{}: {e}", path.display()))?
+        }
+        Location::Address(address) => *address,
+        Location::Replay(_) => 0,
+    };
+    let mut device = match &config.channel {
+        Some(name) => {
+            let mut block = ControlBlockReader::new(reader, address)?;
+            let channel = block.find(name).map_err(|e| format!("{name}: {e}"))?;
+            ProducerDevice::new(block.into_inner(), channel.address)?
+        }
+        None => ProducerDevice::new(reader, address)?,
+    };
+    let mut output = config.output;
+    let mut capture = config.capture;
+
+    if let Format::Lines = config.format {
+        let mut lines = device.read_lines();
+        let mut empty_reads = 0;
+        while !STOP.load(Ordering::Relaxed) {
+            match lines.try_next_line()? {
+                Some(line) => {
+                    writeln!(output, "{line}")?;
+                    empty_reads = 0;
+                }
+                None => {
+                    output.flush()?;
+                    std::thread::sleep(config.poll.interval(empty_reads));
+                    empty_reads = empty_reads.saturating_add(1);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if let Format::Tagged = config.format {
+        let mut records = device.tagged_records();
+        let mut empty_reads = 0;
+        while !STOP.load(Ordering::Relaxed) {
+            match records.try_next_record()? {
+                Some(Record::Text(text)) => writeln!(output, "{text}")?,
+                Some(Record::Binary(data)) => write_hex(&mut output, 0, &data)?,
+                Some(Record::User(tag, data)) => {
+                    writeln!(output, "[{tag:#04x}]")?;
+                    write_hex(&mut output, 0, &data)?;
+                }
+                None => {
+                    output.flush()?;
+                    std::thread::sleep(config.poll.interval(empty_reads));
+                    empty_reads = empty_reads.saturating_add(1);
+                    continue;
+                }
+            }
+            empty_reads = 0;
+        }
+        return Ok(());
+    }
+
+    if let Format::Json = config.format {
+        let mut sink = JsonSink::new(output);
+        if let Some(name) = config.channel {
+            sink = sink.with_channel(name);
+        }
+        let mut empty_reads = 0;
+        while !STOP.load(Ordering::Relaxed) {
+            let events = device.read_events()?;
+            if events.is_empty() {
+                std::thread::sleep(config.poll.interval(empty_reads));
+                empty_reads = empty_reads.saturating_add(1);
+                continue;
+            }
+            for event in &events {
+                sink.write_read_event(event)?;
+                if let (ReadEvent::Data(data), Some(capture)) = (event, &mut capture) {
+                    capture.write_chunk(data)?;
+                }
+            }
+            empty_reads = 0;
+        }
+        return Ok(());
+    }
+
+    let mut offset = 0;
+    let mut result = Ok(());
+    device.run_until(config.poll, &STOP, |data| {
+        result = match config.format {
+            Format::Hex => write_hex(&mut output, offset, data),
+            _ => output.write_all(data).and_then(|()| output.flush()),
+        }
+        .and_then(|()| match &mut capture {
+            Some(capture) => capture.write_chunk(data),
+            None => Ok(()),
+        });
+        offset += data.len() as u64;
+        match result {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    })?;
+    Ok(result?)
+}
+
+/// Returns `true` if `e` is the end of the capture file of `--replay`
+fn is_end_of_replay(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<ConsumerError<ReplayError>>()
+        .is_some_and(|e| {
+            matches!(
+                e.kind(),
+                ConsumerErrorKind::ReadMemoryError(ReplayError::Finished)
+            )
+        })
+}
+
+#[cfg(unix)]
+fn stop_on_ctrl_c() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        STOP.store(true, Ordering::Relaxed);
+    }
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_on_ctrl_c() {}
+
+fn run(matches: &Matches) -> Result<(), Box<dyn Error>> {
+    let config = config(matches)?;
+    let target = matches.opt_str("target");
+    let chunk = match matches.opt_str("chunk") {
+        Some(bytes) => Some(bytes.parse().map_err(|e| format!("--chunk: {e}"))?),
+        None => None,
+    };
+    stop_on_ctrl_c();
+    if let Location::Replay(path) = &config.location {
+        let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let reader = ReplayReader::new(BufReader::new(file))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        // The end of the capture is that of the stream
+        return match stream(reader, config) {
+            Err(e) if is_end_of_replay(&*e) => Ok(()),
+            result => result,
+        };
+    }
+    match matches.opt_str("backend").as_deref().unwrap_or("openocd") {
+        "openocd" => {
+            let target = target.unwrap_or_else(|| format!("localhost:{DEFAULT_PORT}"));
+            let mut reader =
+                OpenOcdReader::connect(&*target, TIMEOUT).map_err(|e| format!("{target}: {e}"))?;
+            if let Some(chunk) = chunk {
+                reader.set_chunk_size(chunk);
+            }
+            stream(reader, config)
+        }
+        "gdb" => {
+            let target = target.unwrap_or_else(|| format!("localhost:{GDB_PORT}"));
+            let mut reader =
+                GdbRspReader::connect(&*target, TIMEOUT).map_err(|e| format!("{target}: {e}"))?;
+            if let Some(chunk) = chunk {
+                reader.set_chunk_size(chunk);
+            }
+            stream(reader, config)
+        }
+        #[cfg(feature = "probe-rs")]
+        "probe-rs" => {
+            if target.is_some() {
+                return Err("--target can't be given with --backend probe-rs".into());
+            }
+            let chip = matches
+                .opt_str("chip")
+                .ok_or("--backend probe-rs needs --chip")?;
+            // The first probe found, as there is usually a single one plugged in
+            let probes = Lister::new().list_all();
+            let probe = probes.first().ok_or("no debug probe found")?.open()?;
+            let mut session = probe
+                .attach(chip.as_str(), Permissions::default())
+                .map_err(|e| format!("{chip}: {e}"))?;
+            let mut reader = ProbeRsReader::new(session.core(0)?);
+            if let Some(chunk) = chunk {
+                reader.set_chunk_size(chunk);
+            }
+            stream(reader, config)
+        }
+        #[cfg(not(feature = "probe-rs"))]
+        "probe-rs" => Err(
+            "ramlink-dump was built without the probe-rs feature, use --backend openocd or gdb"
+                .into(),
+        ),
+        backend => Err(format!("unknown backend {backend}").into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let options = options();
+    let usage = options
+        .usage("Usage: ramlink-dump (--elf PATH | --address ADDR | --replay FILE) [options]");
+    let matches = match options.parse(std::env::args().skip(1)) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("{e}\n\n{usage}");
+            return ExitCode::from(2);
+        }
+    };
+    if matches.opt_present("help") {
+        print!("{usage}");
+        return ExitCode::SUCCESS;
+    }
+    match run(&matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ramlink-dump: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
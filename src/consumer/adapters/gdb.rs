@@ -0,0 +1,331 @@
+//! Reader talking to a GDB stub with the remote serial protocol.
+
+use core::fmt;
+use std::format;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::consumer::MemoryReader;
+
+/// Default of [`GdbRspReader::set_chunk_size`], unless the stub asks for smaller packets
+const CHUNK: usize = 512;
+
+/// Error of [`GdbRspReader`]
+#[derive(Debug)]
+pub enum GdbError {
+    /// The connection to the stub failed, or was closed
+    Io(io::Error),
+    /// The stub replied with an error, e.g. `E01` for memory it can't access, or with
+    /// something else than expected
+    Reply(String),
+}
+
+impl From<io::Error> for GdbError {
+    fn from(e: io::Error) -> Self {
+        GdbError::Io(e)
+    }
+}
+
+impl fmt::Display for GdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GdbError::Io(e) => write!(f, "connection to the GDB stub failed: {e}"),
+            GdbError::Reply(reply) => write!(f, "unexpected reply from the GDB stub: {reply}"),
+        }
+    }
+}
+
+impl std::error::Error for GdbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GdbError::Io(e) => Some(e),
+            GdbError::Reply(_) => None,
+        }
+    }
+}
+
+/// Reads and writes the memory of the target through a GDB stub, e.g. a Black Magic Probe,
+/// QEMU's `-gdb tcp::1234`, or JLinkGDBServer. Only the `m` and `M` packets are sent: the
+/// target is never halted nor resumed, so it must already be in a state where the stub
+/// accesses its memory, which for many stubs means running in the background, or halted.
+///
+/// Acknowledgements are turned off when the stub supports it, as they double the round
+/// trips.
+///
+/// Here, against a tiny stub:
+/// ```
+/// # use std::io::{Read, Write};
+/// # use std::net::TcpStream;
+/// # fn packet(stream: &mut TcpStream, data: &str) {
+/// #     let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+/// #     write!(stream, "${data}#{sum:02x}").unwrap();
+/// # }
+/// # fn gdb_stub(listener: TcpListener, mut ram: Vec<u8>) -> Vec<String> {
+/// #     let (mut stream, _) = listener.accept().unwrap();
+/// #     let (mut packets, mut ack) = (Vec::new(), true);
+/// #     let mut byte = [0];
+/// #     loop {
+/// #         if stream.read(&mut byte).unwrap() == 0 {
+/// #             return packets;
+/// #         }
+/// #         if byte[0] != b'$' {
+/// #             continue;
+/// #         }
+/// #         let mut data = Vec::new();
+/// #         while stream.read(&mut byte).unwrap() == 1 && byte[0] != b'#' {
+/// #             data.push(byte[0]);
+/// #         }
+/// #         let mut sum = [0; 2];
+/// #         stream.read_exact(&mut sum).unwrap();
+/// #         let data = String::from_utf8(data).unwrap();
+/// #         let expected = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+/// #         assert_eq!(format!("{expected:02x}").as_bytes(), sum);
+/// #         if ack {
+/// #             stream.write_all(b"+").unwrap();
+/// #         }
+/// #         let hex = |w: &str| usize::from_str_radix(w, 16).unwrap();
+/// #         let reply = match data.as_bytes()[0] {
+/// #             b'q' => "PacketSize=100;QStartNoAckMode+".to_string(),
+/// #             b'Q' => "OK".to_string(),
+/// #             b'm' => {
+/// #                 let (address, len) = data[1..].split_once(',').unwrap();
+/// #                 let (address, len) = (hex(address), hex(len));
+/// #                 match ram.get(address..address + len) {
+/// #                     // Run-length encoded, as some stubs do
+/// #                     Some(bytes) if bytes.iter().all(|&b| b == 0) && (8..48).contains(&len) => {
+/// #                         format!("0*{}", (29 + 2 * len - 1) as u8 as char)
+/// #                     }
+/// #                     Some(bytes) => bytes.iter().map(|b| format!("{b:02x}")).collect(),
+/// #                     None => "E01".to_string(),
+/// #                 }
+/// #             }
+/// #             b'M' => {
+/// #                 let (header, bytes) = data[1..].split_once(':').unwrap();
+/// #                 let address = hex(header.split_once(',').unwrap().0);
+/// #                 for i in 0..bytes.len() / 2 {
+/// #                     ram[address + i] = hex(&bytes[2 * i..2 * i + 2]) as u8;
+/// #                 }
+/// #                 "OK".to_string()
+/// #             }
+/// #             _ => String::new(),
+/// #         };
+/// #         packet(&mut stream, &reply);
+/// #         if ack {
+/// #             stream.read_exact(&mut byte).unwrap();
+/// #             assert_eq!(byte[0], b'+');
+/// #         }
+/// #         ack &= data != "QStartNoAckMode";
+/// #         packets.push(data);
+/// #     }
+/// # }
+/// use ramlink::consumer::adapters::GdbRspReader;
+/// use ramlink::consumer::MemoryReader;
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let stub = std::thread::spawn(move || gdb_stub(listener, vec![0; 1024]));
+///
+/// let mut reader = GdbRspReader::connect(address, Duration::from_secs(1)).unwrap();
+/// let mut read = [0; 8];
+/// reader.read_memory(0x10, &mut read).unwrap();
+/// assert_eq!(read, [0; 8]);
+/// reader.write_memory_slice(0x10, &[1, 2, 0xff]).unwrap();
+/// reader.read_memory(0x10, &mut read[..4]).unwrap();
+/// assert_eq!(read[..4], [1, 2, 0xff, 0]);
+///
+/// let err = reader.read_memory(0x2000_0000, &mut read).unwrap_err();
+/// assert_eq!(err.to_string(), "unexpected reply from the GDB stub: E01");
+///
+/// reader.set_chunk_size(5);
+/// reader.read_memory(0x10, &mut read).unwrap();
+///
+/// drop(reader);
+/// let packets = stub.join().unwrap();
+/// assert_eq!(packets[..3], ["qSupported", "QStartNoAckMode", "m10,8"]);
+/// assert_eq!(packets[3], "M10,3:0102ff");
+/// assert_eq!(packets[packets.len() - 2..], ["m10,5", "m15,3"]);
+/// ```
+pub struct GdbRspReader<S = TcpStream> {
+    stream: S,
+    /// Whether packets are still acknowledged
+    ack: bool,
+    /// Bytes read or written per packet
+    chunk: usize,
+    /// Most bytes a packet can carry, as allowed by the stub
+    max_chunk: usize,
+}
+
+impl GdbRspReader {
+    /// Connects to the GDB stub at `address`, e.g. `("localhost", 1234)`, and negotiates
+    /// the packet size and acknowledgements. `timeout` applies to the connection, and then
+    /// to each packet.
+    pub fn connect(address: impl ToSocketAddrs, timeout: Duration) -> Result<Self, GdbError> {
+        let mut error = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Self::from_stream(stream);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        let error =
+            error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"));
+        Err(GdbError::Io(error))
+    }
+}
+
+impl<S: Read + Write> GdbRspReader<S> {
+    /// Talks to the GDB stub over an already open `stream`, e.g. a serial port for a Black
+    /// Magic Probe, after negotiating the packet size and acknowledgements
+    pub fn from_stream(stream: S) -> Result<Self, GdbError> {
+        let mut reader = GdbRspReader {
+            stream,
+            ack: true,
+            chunk: CHUNK,
+            max_chunk: usize::MAX,
+        };
+        let supported = reader.command("qSupported")?;
+        for feature in supported.split(';') {
+            if let Some(size) = feature.strip_prefix("PacketSize=") {
+                let size = usize::from_str_radix(size, 16)
+                    .map_err(|_| GdbError::Reply(supported.clone()))?;
+                // `m` replies take two characters per byte, plus the framing
+                reader.max_chunk = (size.saturating_sub(32) / 2).max(1);
+                reader.chunk = CHUNK.min(reader.max_chunk);
+            }
+        }
+        if supported.split(';').any(|f| f == "QStartNoAckMode+")
+            && reader.command("QStartNoAckMode")? == "OK"
+        {
+            reader.ack = false;
+        }
+        Ok(reader)
+    }
+
+    /// Sets how many bytes are read or written per packet, 512 by default. It is capped by
+    /// the packet size the stub advertised, so this mostly makes packets smaller, e.g. for
+    /// probes that stall on long transfers.
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.clamp(1, self.max_chunk);
+    }
+
+    /// Sends a packet, and returns the data of the reply
+    pub fn command(&mut self, data: &str) -> Result<String, GdbError> {
+        let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        let packet = format!("${data}#{sum:02x}");
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            self.stream.flush()?;
+            if !self.ack || self.read_byte()? == b'+' {
+                break;
+            }
+        }
+        self.read_packet()
+    }
+
+    fn read_byte(&mut self) -> Result<u8, GdbError> {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Reads a packet, asking for it again while its checksum is wrong, and returns its
+    /// data, run-length decoded
+    fn read_packet(&mut self) -> Result<String, GdbError> {
+        loop {
+            while self.read_byte()? != b'$' {}
+            let mut data = Vec::new();
+            let mut sum = 0u8;
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => {
+                        sum = sum.wrapping_add(byte);
+                        data.push(byte);
+                    }
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let valid = core::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(sum);
+            if !self.ack {
+                return decode(&data);
+            }
+            if valid {
+                self.stream.write_all(b"+")?;
+                return decode(&data);
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+}
+
+/// Expands the run-length encoding of a packet: `x*n` stands for `x` followed by `n - 29`
+/// more `x`
+fn decode(data: &[u8]) -> Result<String, GdbError> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'*' {
+            let (Some(&last), Some(&count)) = (decoded.last(), bytes.next()) else {
+                return Err(GdbError::Reply(String::from_utf8_lossy(data).into_owned()));
+            };
+            decoded.extend(core::iter::repeat(last).take((count as usize).saturating_sub(29)));
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|e| GdbError::Reply(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+}
+
+impl<S: Read + Write> MemoryReader for GdbRspReader<S> {
+    type Error = GdbError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), GdbError> {
+        let chunk = self.chunk;
+        for (i, part) in buffer.chunks_mut(chunk).enumerate() {
+            let address = address + i * chunk;
+            let reply = self.command(&format!("m{address:x},{:x}", part.len()))?;
+            if reply.len() != 2 * part.len() {
+                return Err(GdbError::Reply(reply));
+            }
+            for (j, byte) in part.iter_mut().enumerate() {
+                *byte = reply
+                    .get(2 * j..2 * j + 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| GdbError::Reply(reply.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), GdbError> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), GdbError> {
+        let chunk = self.chunk;
+        for (i, part) in data.chunks(chunk).enumerate() {
+            let address = address + i * chunk;
+            let hex: String = part.iter().map(|byte| format!("{byte:02x}")).collect();
+            let reply = self.command(&format!("M{address:x},{:x}:{hex}", part.len()))?;
+            if reply != "OK" {
+                return Err(GdbError::Reply(reply));
+            }
+        }
+        Ok(())
+    }
+}
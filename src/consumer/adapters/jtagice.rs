@@ -0,0 +1,406 @@
+//! Reader talking to an Atmel JTAGICE mkII over its serial port.
+
+use core::fmt;
+use core::ops::Range;
+use std::io::{self, Read, Write};
+use std::vec;
+use std::vec::Vec;
+
+use crate::consumer::MemoryReader;
+
+/// Default of [`JtagIceMkiiReader::set_chunk_size`]
+const CHUNK: usize = 256;
+
+/// Frame delimiters, see AVR067
+const MESSAGE_START: u8 = 0x1b;
+const TOKEN: u8 = 0x0e;
+/// Sequence number of the events the JTAGICE sends on its own, e.g. on a breakpoint
+const EVENT_SEQUENCE: u16 = 0xffff;
+/// Larger bodies are taken for garbage rather than allocated
+const MAX_BODY: usize = 0x1_0000;
+
+const CMND_WRITE_MEMORY: u8 = 0x04;
+const CMND_READ_MEMORY: u8 = 0x05;
+const CMND_GET_SIGN_ON: u8 = 0x01;
+const CMND_SET_PARAMETER: u8 = 0x02;
+const RSP_OK: u8 = 0x80;
+const RSP_MEMORY: u8 = 0x82;
+const RSP_SIGN_ON: u8 = 0x86;
+const PAR_EMULATOR_MODE: u8 = 0x03;
+const MTYPE_SRAM: u8 = 0x20;
+
+/// Offset of the data space in the addresses of AVR ELF files
+const DATA_SPACE: usize = 0x80_0000;
+
+/// Error of [`JtagIceMkiiReader`]
+#[derive(Debug)]
+pub enum JtagIceError {
+    /// The serial port failed, or the JTAGICE did not reply in time
+    Io(io::Error),
+    /// A reply had a wrong token, length or CRC
+    Frame,
+    /// The JTAGICE replied with this status instead, e.g. `0xa4` if the emulator mode is
+    /// not set, `0xa5` if it can't access the memory while the target runs, or `0xab` if
+    /// the target is not powered
+    Reply(u8),
+    /// The access is not within the SRAM of the part given to the reader
+    OutOfSram {
+        /// The address of the access, as given to the reader
+        address: usize,
+        /// Its length
+        len: usize,
+    },
+}
+
+impl From<io::Error> for JtagIceError {
+    fn from(e: io::Error) -> Self {
+        JtagIceError::Io(e)
+    }
+}
+
+impl fmt::Display for JtagIceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JtagIceError::Io(e) => write!(f, "connection to the JTAGICE failed: {e}"),
+            JtagIceError::Frame => write!(f, "malformed reply from the JTAGICE"),
+            JtagIceError::Reply(status) => write!(f, "JTAGICE replied with status {status:#04x}"),
+            JtagIceError::OutOfSram { address, len } => {
+                write!(
+                    f,
+                    "{address:#x}..{:#x} is not within the SRAM",
+                    address + len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for JtagIceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JtagIceError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// How the JTAGICE talks to the target, set with [`JtagIceMkiiReader::set_emulator_mode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatorMode {
+    /// debugWIRE, e.g. on the ATmega328P
+    DebugWire,
+    /// JTAG, on the larger megaAVRs
+    Jtag,
+    /// PDI, on the XMEGAs
+    Pdi,
+}
+
+/// Reads and writes the SRAM of an AVR through an Atmel JTAGICE mkII connected to a serial
+/// port, with the mkII protocol of AVR067. The JTAGICE must not be used by another program
+/// meanwhile: with avarice running, use [`GdbRspReader`](super::GdbRspReader) on its GDB
+/// port instead.
+///
+/// The AVR data space is 16 bits wide: addresses are those of the part, e.g. `0x0100`, or
+/// those of its ELF files, e.g. `0x800100`, whose offset is dropped, so that
+/// [`ProducerDevice::new_from_elf`](crate::consumer::ProducerDevice::new_from_elf) works
+/// as is. Accesses outside of `sram`, the SRAM of the part given to the constructor, fail
+/// with [`JtagIceError::OutOfSram`] before reaching the JTAGICE, so that a wrong address
+/// never writes to the registers mapped below the SRAM.
+///
+/// Here, against a fake JTAGICE:
+/// ```
+/// # use std::io::{Read, Write};
+/// # fn crc(data: &[u8]) -> u16 {
+/// #     let mut crc = 0xffffu16;
+/// #     for &byte in data {
+/// #         crc ^= u16::from(byte);
+/// #         for _ in 0..8 {
+/// #             crc = if crc & 1 != 0 { (crc >> 1) ^ 0x8408 } else { crc >> 1 };
+/// #         }
+/// #     }
+/// #     crc
+/// # }
+/// # fn send(stream: &mut TcpStream, sequence: u16, body: &[u8]) {
+/// #     let mut frame = vec![0x1b];
+/// #     frame.extend(sequence.to_le_bytes());
+/// #     frame.extend((body.len() as u32).to_le_bytes());
+/// #     frame.push(0x0e);
+/// #     frame.extend(body);
+/// #     frame.extend(crc(&frame).to_le_bytes());
+/// #     stream.write_all(&frame).unwrap();
+/// # }
+/// # fn fake_jtagice(listener: TcpListener, mut sram: Vec<u8>) -> usize {
+/// #     let (mut stream, _) = listener.accept().unwrap();
+/// #     let mut commands = 0;
+/// #     loop {
+/// #         let mut header = [0; 8];
+/// #         if stream.read_exact(&mut header).is_err() {
+/// #             return commands;
+/// #         }
+/// #         commands += 1;
+/// #         let sequence = u16::from_le_bytes([header[1], header[2]]);
+/// #         let mut body = vec![0; u32::from_le_bytes(header[3..7].try_into().unwrap()) as usize + 2];
+/// #         stream.read_exact(&mut body).unwrap();
+/// #         let u32_at = |i: usize| u32::from_le_bytes(body[i..i + 4].try_into().unwrap()) as usize;
+/// #         // An event, which the reader skips
+/// #         send(&mut stream, 0xffff, &[0xe0]);
+/// #         match body[0] {
+/// #             0x01 => send(&mut stream, sequence, b"\x86JTAGICE mkII"),
+/// #             0x02 => send(&mut stream, sequence, &[0x80]),
+/// #             0x05 => {
+/// #                 let (len, address) = (u32_at(2), u32_at(6) - 0x100);
+/// #                 send(&mut stream, sequence, &[&[0x82], &sram[address..address + len]].concat())
+/// #             }
+/// #             0x04 => {
+/// #                 let (len, address) = (u32_at(2), u32_at(6) - 0x100);
+/// #                 sram[address..address + len].copy_from_slice(&body[10..10 + len]);
+/// #                 send(&mut stream, sequence, &[0x80])
+/// #             }
+/// #             _ => send(&mut stream, sequence, &[0xaa]),
+/// #         }
+/// #     }
+/// # }
+/// use ramlink::consumer::adapters::{EmulatorMode, JtagIceError, JtagIceMkiiReader};
+/// use ramlink::consumer::MemoryReader;
+/// use std::net::{TcpListener, TcpStream};
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let jtagice = std::thread::spawn(move || fake_jtagice(listener, vec![0; 0x800]));
+///
+/// // Usually `JtagIceMkiiReader::open("/dev/ttyUSB0", 0x0100..0x0900)`, for an ATmega328P
+/// let stream = TcpStream::connect(address).unwrap();
+/// let mut reader = JtagIceMkiiReader::from_stream(stream, 0x0100..0x0900).unwrap();
+/// reader.set_emulator_mode(EmulatorMode::DebugWire).unwrap();
+///
+/// let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+/// reader.write_memory_slice(0x0200, &data).unwrap();
+/// let mut read = vec![0; 300];
+/// // The same bytes, at their address in the ELF file
+/// reader.read_memory(0x80_0200, &mut read).unwrap();
+/// assert_eq!(read, data);
+///
+/// let err = reader.read_memory(0x08f0, &mut read).unwrap_err();
+/// assert!(matches!(err, JtagIceError::OutOfSram { address: 0x08f0, len: 300 }));
+/// let err = reader.write_memory(0x5d, 0).unwrap_err();
+/// assert_eq!(err.to_string(), "0x5d..0x5e is not within the SRAM");
+///
+/// drop(reader);
+/// // Sign-on, emulator mode, then 2 transfers each way
+/// assert_eq!(jtagice.join().unwrap(), 6);
+/// ```
+pub struct JtagIceMkiiReader<S = std::fs::File> {
+    stream: S,
+    /// Sequence number of the next command
+    sequence: u16,
+    /// Data space addresses of the SRAM
+    sram: Range<usize>,
+    /// Bytes transferred per command
+    chunk: usize,
+}
+
+#[cfg(unix)]
+impl JtagIceMkiiReader {
+    /// Opens the serial port at `path`, e.g. `/dev/ttyUSB0`, at the 19200 baud the JTAGICE
+    /// starts at, and signs on. `sram` is the range of data space addresses of the SRAM of
+    /// the part, e.g. `0x0100..0x0900` for an ATmega328P.
+    pub fn open(
+        path: impl AsRef<std::path::Path>,
+        sram: Range<usize>,
+    ) -> Result<Self, JtagIceError> {
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        let port = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        let fd = port.as_raw_fd();
+        // SAFETY: fd stays open as long as port, and termios is filled by tcgetattr before
+        // being used
+        unsafe {
+            let mut termios: libc::termios = core::mem::zeroed();
+            if libc::tcgetattr(fd, &mut termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::cfmakeraw(&mut termios);
+            libc::cfsetispeed(&mut termios, libc::B19200);
+            libc::cfsetospeed(&mut termios, libc::B19200);
+            termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+            // Reads give up after a second of silence
+            termios.c_cc[libc::VMIN] = 0;
+            termios.c_cc[libc::VTIME] = 10;
+            if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::tcflush(fd, libc::TCIOFLUSH);
+        }
+        Self::from_stream(port, sram)
+    }
+}
+
+impl<S: Read + Write> JtagIceMkiiReader<S> {
+    /// Talks to a JTAGICE over an already open and configured `stream`, and signs on.
+    /// `sram` is as for [`JtagIceMkiiReader::open`].
+    pub fn from_stream(stream: S, sram: Range<usize>) -> Result<Self, JtagIceError> {
+        let mut reader = JtagIceMkiiReader {
+            stream,
+            sequence: 0,
+            sram,
+            chunk: CHUNK,
+        };
+        reader.expect(&[CMND_GET_SIGN_ON], RSP_SIGN_ON)?;
+        Ok(reader)
+    }
+
+    /// Sets how the JTAGICE talks to the target. It keeps its mode across sessions: this is
+    /// only needed once, or after using it for another part.
+    pub fn set_emulator_mode(&mut self, mode: EmulatorMode) -> Result<(), JtagIceError> {
+        let mode = match mode {
+            EmulatorMode::DebugWire => 0x00,
+            EmulatorMode::Jtag => 0x01,
+            EmulatorMode::Pdi => 0x06,
+        };
+        self.expect(&[CMND_SET_PARAMETER, PAR_EMULATOR_MODE, mode], RSP_OK)?;
+        Ok(())
+    }
+
+    /// Sets how many bytes are transferred per command, 256 by default
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.max(1);
+    }
+
+    /// Sends a command of the mkII protocol, e.g. `[0x01]` to sign on, and returns the
+    /// reply, starting with its status, e.g. `0x86`
+    pub fn command(&mut self, body: &[u8]) -> Result<Vec<u8>, JtagIceError> {
+        let sequence = self.sequence;
+        // 0xffff is left to events
+        self.sequence = (self.sequence + 1) % EVENT_SEQUENCE;
+        let mut frame = Vec::with_capacity(10 + body.len());
+        frame.push(MESSAGE_START);
+        frame.extend_from_slice(&sequence.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.push(TOKEN);
+        frame.extend_from_slice(body);
+        frame.extend_from_slice(&crc(&frame).to_le_bytes());
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        loop {
+            let (reply_sequence, reply) = self.receive()?;
+            // Skips events, and the late replies of commands that timed out
+            if reply_sequence == sequence {
+                return Ok(reply);
+            }
+        }
+    }
+
+    /// Sends `body`, and returns the reply after its `status`
+    fn expect(&mut self, body: &[u8], status: u8) -> Result<Vec<u8>, JtagIceError> {
+        let mut reply = self.command(body)?;
+        match reply.first() {
+            Some(&s) if s == status => {
+                reply.remove(0);
+                Ok(reply)
+            }
+            Some(&s) => Err(JtagIceError::Reply(s)),
+            None => Err(JtagIceError::Frame),
+        }
+    }
+
+    /// Receives a frame, and returns its sequence number and body
+    fn receive(&mut self) -> Result<(u16, Vec<u8>), JtagIceError> {
+        let mut header = [0; 8];
+        // Skips what's left of a previous frame
+        while header[0] != MESSAGE_START {
+            self.stream.read_exact(&mut header[..1])?;
+        }
+        self.stream.read_exact(&mut header[1..])?;
+        let sequence = u16::from_le_bytes([header[1], header[2]]);
+        let size = u32::from_le_bytes([header[3], header[4], header[5], header[6]]) as usize;
+        if header[7] != TOKEN || size > MAX_BODY {
+            return Err(JtagIceError::Frame);
+        }
+        let mut body = vec![0; size + 2];
+        self.stream.read_exact(&mut body)?;
+        let mut checked = header.to_vec();
+        checked.extend_from_slice(&body[..size]);
+        if crc(&checked).to_le_bytes() != body[size..] {
+            return Err(JtagIceError::Frame);
+        }
+        body.truncate(size);
+        Ok((sequence, body))
+    }
+
+    /// Returns the data space address of `len` bytes at `address`, if they are all SRAM
+    fn sram_address(&self, address: usize, len: usize) -> Result<u32, JtagIceError> {
+        let data = match address {
+            0..=0xffff => address,
+            DATA_SPACE..=0x80_ffff => address - DATA_SPACE,
+            _ => return Err(JtagIceError::OutOfSram { address, len }),
+        };
+        if data < self.sram.start || data + len > self.sram.end {
+            return Err(JtagIceError::OutOfSram { address, len });
+        }
+        Ok(data as u32)
+    }
+
+    /// Returns the header of a memory command for `len` bytes at `address`
+    fn memory_command(command: u8, address: u32, len: usize) -> Vec<u8> {
+        let mut body = vec![command, MTYPE_SRAM];
+        body.extend_from_slice(&(len as u32).to_le_bytes());
+        body.extend_from_slice(&address.to_le_bytes());
+        body
+    }
+}
+
+/// CRC of the mkII frames: CRC-CCITT, reflected, without final XOR
+fn crc(data: &[u8]) -> u16 {
+    let mut crc = 0xffff;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+impl<S: Read + Write> MemoryReader for JtagIceMkiiReader<S> {
+    type Error = JtagIceError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), JtagIceError> {
+        let start = self.sram_address(address, buffer.len())?;
+        let size = self.chunk;
+        for (i, chunk) in buffer.chunks_mut(size).enumerate() {
+            let command =
+                Self::memory_command(CMND_READ_MEMORY, start + (i * size) as u32, chunk.len());
+            let reply = self.expect(&command, RSP_MEMORY)?;
+            if reply.len() != chunk.len() {
+                return Err(JtagIceError::Frame);
+            }
+            chunk.copy_from_slice(&reply);
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), JtagIceError> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), JtagIceError> {
+        let start = self.sram_address(address, data.len())?;
+        let size = self.chunk;
+        for (i, chunk) in data.chunks(size).enumerate() {
+            let mut command =
+                Self::memory_command(CMND_WRITE_MEMORY, start + (i * size) as u32, chunk.len());
+            command.extend_from_slice(chunk);
+            self.expect(&command, RSP_OK)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+//! [`MemoryReader`](super::MemoryReader) implementations for common debug interfaces.
+
+mod gdb;
+#[cfg(feature = "avr-jtagice")]
+mod jtagice;
+mod openocd;
+#[cfg(feature = "probe-rs")]
+mod probe_rs;
+mod qmp;
+mod renode;
+#[cfg(feature = "serial-updi")]
+mod updi;
+
+#[cfg(feature = "probe-rs")]
+pub use self::probe_rs::{ProbeRsError, ProbeRsReader};
+pub use gdb::{GdbError, GdbRspReader};
+#[cfg(feature = "avr-jtagice")]
+pub use jtagice::{EmulatorMode, JtagIceError, JtagIceMkiiReader};
+pub use openocd::{OpenOcdError, OpenOcdReader, DEFAULT_PORT};
+pub use qmp::{QmpError, QmpReader};
+pub use renode::{RenodeError, RenodeReader};
+#[cfg(feature = "serial-updi")]
+pub use updi::{SerialUpdiReader, UpdiError};
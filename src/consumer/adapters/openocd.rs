@@ -0,0 +1,236 @@
+//! Reader talking to OpenOCD through its TCL RPC server.
+
+use core::fmt;
+use std::format;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::consumer::MemoryReader;
+
+/// Default port of the TCL RPC server of OpenOCD, see its `tcl_port` command
+pub const DEFAULT_PORT: u16 = 6666;
+
+/// Terminates commands and replies of the TCL RPC protocol
+const TERMINATOR: u8 = 0x1a;
+
+/// Default of [`OpenOcdReader::set_chunk_size`]. OpenOCD replies with about 5 characters per byte.
+const CHUNK: usize = 1024;
+
+/// Error of [`OpenOcdReader`]
+#[derive(Debug)]
+pub enum OpenOcdError {
+    /// The connection to OpenOCD failed, or was closed
+    Io(io::Error),
+    /// OpenOCD did not reply as expected, usually with an error message, which is kept
+    Command(String),
+}
+
+impl From<io::Error> for OpenOcdError {
+    fn from(e: io::Error) -> Self {
+        OpenOcdError::Io(e)
+    }
+}
+
+impl fmt::Display for OpenOcdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenOcdError::Io(e) => write!(f, "connection to OpenOCD failed: {e}"),
+            OpenOcdError::Command(reply) => write!(f, "OpenOCD command failed: {reply}"),
+        }
+    }
+}
+
+impl std::error::Error for OpenOcdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OpenOcdError::Io(e) => Some(e),
+            OpenOcdError::Command(_) => None,
+        }
+    }
+}
+
+/// Reads and writes the memory of the target through OpenOCD, which must be running with
+/// its TCL RPC server enabled, as it is by default on port [`DEFAULT_PORT`]. It uses the
+/// `read_memory` and `write_memory` commands, from OpenOCD 0.11 onwards.
+///
+/// Here, against a fake OpenOCD server:
+/// ```
+/// # use std::io::{BufRead, BufReader, Write};
+/// # fn fake_openocd(listener: TcpListener, mut ram: Vec<u8>) {
+/// #     let (stream, _) = listener.accept().unwrap();
+/// #     let mut reader = BufReader::new(stream.try_clone().unwrap());
+/// #     let mut stream = stream;
+/// #     loop {
+/// #         let mut command = Vec::new();
+/// #         if reader.read_until(0x1a, &mut command).unwrap() == 0 {
+/// #             return;
+/// #         }
+/// #         let command = String::from_utf8(command[..command.len() - 1].to_vec()).unwrap();
+/// #         let words: Vec<&str> = command.split([' ', '{', '}']).filter(|w| !w.is_empty()).collect();
+/// #         if words[0] == "shutdown" {
+/// #             stream.write_all(b"shutdown command invoked\x1a").unwrap();
+/// #             return;
+/// #         }
+/// #         let hex = |w: &str| usize::from_str_radix(w.trim_start_matches("0x"), 16).unwrap();
+/// #         let address = hex(words[1]);
+/// #         let reply = match words[0] {
+/// #             "read_memory" if address + words[3].parse::<usize>().unwrap() <= ram.len() => {
+/// #                 let len: usize = words[3].parse().unwrap();
+/// #                 let bytes = &ram[address..address + len];
+/// #                 bytes.iter().map(|b| format!("{b:#x}")).collect::<Vec<_>>().join(" ")
+/// #             }
+/// #             "write_memory" => {
+/// #                 for (i, word) in words[3..].iter().enumerate() {
+/// #                     ram[address + i] = hex(word) as u8;
+/// #                 }
+/// #                 String::new()
+/// #             }
+/// #             _ => "read_memory: failed to read memory".to_string(),
+/// #         };
+/// #         stream.write_all(reply.as_bytes()).unwrap();
+/// #         stream.write_all(&[0x1a]).unwrap();
+/// #     }
+/// # }
+/// use ramlink::consumer::adapters::{OpenOcdError, OpenOcdReader};
+/// use ramlink::consumer::MemoryReader;
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let server = std::thread::spawn(move || fake_openocd(listener, vec![0; 4096]));
+///
+/// // Usually `OpenOcdReader::connect(("localhost", DEFAULT_PORT), ...)`
+/// let mut reader = OpenOcdReader::connect(address, Duration::from_secs(1)).unwrap();
+/// let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+/// reader.write_memory_slice(0x10, &data).unwrap();
+/// let mut read = vec![0; 3000];
+/// reader.read_memory(0x10, &mut read).unwrap();
+/// assert_eq!(read, data);
+///
+/// reader.set_chunk_size(7);
+/// let mut read = vec![0; 100];
+/// reader.read_memory(0x10, &mut read).unwrap();
+/// assert_eq!(read, data[..100]);
+///
+/// let err = reader.read_memory(0x2000_0000, &mut read).unwrap_err();
+/// assert_eq!(err.to_string(), "OpenOCD command failed: read_memory: failed to read memory");
+///
+/// // OpenOCD exits
+/// reader.command("shutdown").unwrap();
+/// server.join().unwrap();
+/// let err = reader.read_memory(0x10, &mut read).unwrap_err();
+/// assert!(matches!(err, OpenOcdError::Io(_)));
+/// ```
+pub struct OpenOcdReader<S = TcpStream> {
+    stream: S,
+    /// Reply being received
+    reply: Vec<u8>,
+    /// Bytes read or written per command
+    chunk: usize,
+}
+
+impl OpenOcdReader {
+    /// Connects to the TCL RPC server of OpenOCD at `address`, e.g.
+    /// `("localhost", DEFAULT_PORT)`. `timeout` applies to the connection, and then to
+    /// each command.
+    pub fn connect(address: impl ToSocketAddrs, timeout: Duration) -> io::Result<Self> {
+        let mut error = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Ok(Self::from_stream(stream));
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address")))
+    }
+}
+
+impl<S: Read + Write> OpenOcdReader<S> {
+    /// Talks to OpenOCD over an already open `stream`
+    pub fn from_stream(stream: S) -> Self {
+        OpenOcdReader {
+            stream,
+            reply: Vec::new(),
+            chunk: CHUNK,
+        }
+    }
+
+    /// Sets how many bytes are read or written per command, 1024 by default. Longer
+    /// commands take fewer round trips, but OpenOCD holds the whole reply in memory, and
+    /// some adapters time out on long transfers.
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.max(1);
+    }
+
+    /// Runs a TCL command, and returns what it printed
+    pub fn command(&mut self, command: &str) -> Result<String, OpenOcdError> {
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(&[TERMINATOR])?;
+        self.stream.flush()?;
+
+        self.reply.clear();
+        let mut buf = [0; 4096];
+        while self.reply.last() != Some(&TERMINATOR) {
+            let read = self.stream.read(&mut buf)?;
+            if read == 0 {
+                let closed = io::Error::new(io::ErrorKind::UnexpectedEof, "closed by OpenOCD");
+                return Err(OpenOcdError::Io(closed));
+            }
+            self.reply.extend_from_slice(&buf[..read]);
+        }
+        self.reply.pop();
+        Ok(String::from_utf8_lossy(&self.reply).into_owned())
+    }
+}
+
+impl<S: Read + Write> MemoryReader for OpenOcdReader<S> {
+    type Error = OpenOcdError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), OpenOcdError> {
+        let size = self.chunk;
+        for (i, chunk) in buffer.chunks_mut(size).enumerate() {
+            let address = address + i * size;
+            let reply = self.command(&format!("read_memory {address:#x} 8 {}", chunk.len()))?;
+            let mut words = reply.split_ascii_whitespace();
+            for byte in chunk.iter_mut() {
+                *byte = words
+                    .next()
+                    .and_then(|word| u8::from_str_radix(word.trim_start_matches("0x"), 16).ok())
+                    .ok_or_else(|| OpenOcdError::Command(reply.clone()))?;
+            }
+            if words.next().is_some() {
+                return Err(OpenOcdError::Command(reply));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), OpenOcdError> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), OpenOcdError> {
+        let size = self.chunk;
+        for (i, chunk) in data.chunks(size).enumerate() {
+            let address = address + i * size;
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("{byte:#x}")).collect();
+            let reply = self.command(&format!(
+                "write_memory {address:#x} 8 {{{}}}",
+                bytes.join(" ")
+            ))?;
+            if !reply.trim().is_empty() {
+                return Err(OpenOcdError::Command(reply));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! Reader talking to a debug probe through probe-rs.
+
+use core::fmt;
+
+use probe_rs::{Core, MemoryInterface};
+
+use crate::consumer::MemoryReader;
+
+/// Default of [`ProbeRsReader::set_chunk_size`], more than most ring buffers hold
+const CHUNK: usize = 1024;
+
+/// Error of [`ProbeRsReader`]
+#[derive(Debug)]
+pub enum ProbeRsError {
+    /// The probe failed, e.g. it was unplugged or lost the connection to the target
+    Probe(probe_rs::Error),
+    /// The target did not answer in time, e.g. while it sleeps or is held in reset
+    Timeout,
+    /// The target refused an access, e.g. outside of its RAM
+    Access {
+        /// The address of the access, as given to the reader
+        address: usize,
+        /// Its length
+        len: usize,
+        /// What probe-rs reported
+        error: probe_rs::Error,
+    },
+}
+
+impl ProbeRsError {
+    /// Tells apart the failures of the probe from those of the access of `len` bytes at
+    /// `address`
+    fn new(error: probe_rs::Error, address: usize, len: usize) -> Self {
+        match error {
+            probe_rs::Error::Probe(_) => ProbeRsError::Probe(error),
+            probe_rs::Error::Timeout => ProbeRsError::Timeout,
+            error => ProbeRsError::Access {
+                address,
+                len,
+                error,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ProbeRsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeRsError::Probe(e) => write!(f, "debug probe failed: {e}"),
+            ProbeRsError::Timeout => write!(f, "the target did not answer the debug probe"),
+            ProbeRsError::Access {
+                address,
+                len,
+                error,
+            } => write!(
+                f,
+                "failed to access {address:#x}..{:#x}: {error}",
+                address + len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProbeRsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProbeRsError::Probe(e) | ProbeRsError::Access { error: e, .. } => Some(e),
+            ProbeRsError::Timeout => None,
+        }
+    }
+}
+
+/// Reads and writes the memory of a core through [probe-rs](https://probe.rs), which drives
+/// most SWD and JTAG probes: ST-Link, J-Link, CMSIS-DAP, ESP32 USB-JTAG, ... Reads and
+/// writes are block transfers of `read_8` or `write_8` of up to
+/// [`ProbeRsReader::set_chunk_size`] bytes, which probe-rs splits into word accesses where
+/// the target allows it.
+///
+/// On Cortex-M, the memory is read through the debug access port while the core keeps
+/// running. Other architectures may need the core to be halted, see
+/// [`ProbeRsReader::core`].
+///
+/// probe-rs needs a newer Rust than this crate: 1.89 as of probe-rs 0.32.
+///
+/// Here, streaming the ring buffer of an STM32F411 to stdout, see also
+/// `examples/probe_rs_dump.rs`:
+/// ```no_run
+/// use core::ops::ControlFlow;
+/// use probe_rs::{probe::list::Lister, Permissions};
+/// use ramlink::consumer::adapters::ProbeRsReader;
+/// use ramlink::consumer::ProducerDevice;
+/// use std::io::Write;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let probe = Lister::new().list_all()[0].open()?;
+/// let mut session = probe.attach("STM32F411RETx", Permissions::default())?;
+/// let mut reader = ProbeRsReader::new(session.core(0)?);
+/// reader.set_chunk_size(256);
+/// let mut device = ProducerDevice::new(reader, 0x2000_0000)?;
+/// device.run(Duration::from_millis(10), |data| {
+///     std::io::stdout().write_all(data).unwrap();
+///     ControlFlow::Continue(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProbeRsReader<'a> {
+    core: Core<'a>,
+    /// Bytes read or written per transfer
+    chunk: usize,
+}
+
+impl<'a> ProbeRsReader<'a> {
+    /// Reads and writes the memory of `core`, e.g. `session.core(0)?`
+    pub fn new(core: Core<'a>) -> Self {
+        ProbeRsReader { core, chunk: CHUNK }
+    }
+
+    /// Sets how many bytes are read or written per transfer, 1024 by default. Some probes
+    /// and targets time out on long transfers, e.g. while the core sleeps.
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.max(1);
+    }
+
+    /// Returns the core, e.g. to halt it
+    pub fn core(&mut self) -> &mut Core<'a> {
+        &mut self.core
+    }
+
+    /// Returns the core
+    pub fn into_inner(self) -> Core<'a> {
+        self.core
+    }
+}
+
+impl MemoryReader for ProbeRsReader<'_> {
+    type Error = ProbeRsError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), ProbeRsError> {
+        let size = self.chunk;
+        for (i, chunk) in buffer.chunks_mut(size).enumerate() {
+            let address = address + i * size;
+            self.core
+                .read_8(address as u64, chunk)
+                .map_err(|e| ProbeRsError::new(e, address, chunk.len()))?;
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), ProbeRsError> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), ProbeRsError> {
+        let size = self.chunk;
+        for (i, chunk) in data.chunks(size).enumerate() {
+            let address = address + i * size;
+            self.core
+                .write_8(address as u64, chunk)
+                .map_err(|e| ProbeRsError::new(e, address, chunk.len()))?;
+        }
+        // Writes may be posted until flushed, and the consumer index must reach the target
+        self.core
+            .flush()
+            .map_err(|e| ProbeRsError::new(e, address, data.len()))
+    }
+}
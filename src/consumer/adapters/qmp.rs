@@ -0,0 +1,327 @@
+//! Reader looking at the RAM of a QEMU guest through the QEMU Machine Protocol.
+
+use core::fmt;
+use std::format;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::consumer::MemoryReader;
+
+/// Default of [`QmpReader::set_chunk_size`]. `xp` prints about 5 characters per byte.
+const CHUNK: usize = 1024;
+
+/// Error of [`QmpReader`]
+#[derive(Debug)]
+pub enum QmpError {
+    /// The connection to QEMU failed, or was closed
+    Io(io::Error),
+    /// QEMU did not reply as expected, e.g. with an error or a monitor message, which is
+    /// kept
+    Reply(String),
+    /// QEMU's monitor has no command writing guest memory
+    WriteUnsupported,
+}
+
+impl From<io::Error> for QmpError {
+    fn from(e: io::Error) -> Self {
+        QmpError::Io(e)
+    }
+}
+
+impl fmt::Display for QmpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QmpError::Io(e) => write!(f, "connection to QEMU failed: {e}"),
+            QmpError::Reply(reply) => write!(f, "unexpected reply from QEMU: {reply}"),
+            QmpError::WriteUnsupported => {
+                write!(f, "guest memory can't be written through the QEMU monitor")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QmpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QmpError::Io(e) => Some(e),
+            QmpError::Reply(_) | QmpError::WriteUnsupported => None,
+        }
+    }
+}
+
+/// Reads the RAM of a QEMU guest while it runs, through QMP, e.g. that of a
+/// `qemu-system-avr` or `qemu-system-arm` started with
+/// `-qmp tcp:localhost:4444,server,wait=off`. QMP has no command returning guest memory,
+/// so each read runs the human monitor's `xp` command, which reads physical memory,
+/// through `human-monitor-command`.
+///
+/// The monitor can't write guest memory either: writes fail with
+/// [`QmpError::WriteUnsupported`]. A [`ProducerDevice`](crate::consumer::ProducerDevice)
+/// writes the consumer index, and can't attach through this reader alone: it needs
+/// [`GdbRspReader`](super::GdbRspReader), with QEMU's `-gdb` option, which pauses the guest.
+/// This reader looks at the ring buffers without disturbing the guest, e.g. to find them
+/// with [`scan_for_rb`](crate::consumer::scan_for_rb).
+///
+/// The addresses of the firmware may not be the physical addresses of the guest: see
+/// [`QmpReader::set_ram_base`].
+///
+/// Here, against a fake QEMU:
+/// ```
+/// # use std::io::{BufRead, BufReader, Write};
+/// # fn fake_qemu(listener: TcpListener, ram: Vec<u8>) -> Vec<String> {
+/// #     let (stream, _) = listener.accept().unwrap();
+/// #     let mut reader = BufReader::new(stream.try_clone().unwrap());
+/// #     let mut stream = stream;
+/// #     let mut commands = Vec::new();
+/// #     stream.write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\r\n").unwrap();
+/// #     loop {
+/// #         let mut line = String::new();
+/// #         if reader.read_line(&mut line).unwrap() == 0 {
+/// #             return commands;
+/// #         }
+/// #         let command = match line.split_once("\"command-line\": \"") {
+/// #             Some((_, rest)) => rest.split('"').next().unwrap().to_string(),
+/// #             None => {
+/// #                 stream.write_all(b"{\"return\": {}}\r\n").unwrap();
+/// #                 continue;
+/// #             }
+/// #         };
+/// #         commands.push(command.clone());
+/// #         let words: Vec<&str> = command.split_whitespace().collect();
+/// #         let len: usize = words[1].trim_start_matches('/').trim_end_matches("xb").parse().unwrap();
+/// #         let address = usize::from_str_radix(words[2].trim_start_matches("0x"), 16).unwrap();
+/// #         let output = match ram.get(address.wrapping_sub(0x800000)..).and_then(|ram| ram.get(..len)) {
+/// #             Some(bytes) => bytes
+/// #                 .chunks(8)
+/// #                 .enumerate()
+/// #                 .map(|(i, line)| {
+/// #                     let bytes: Vec<_> = line.iter().map(|b| format!("{b:#04x}")).collect();
+/// #                     format!("{:016x}: {}\\r\\n", address + 8 * i, bytes.join(" "))
+/// #                 })
+/// #                 .collect(),
+/// #             None => "Cannot access memory\\r\\n".to_string(),
+/// #         };
+/// #         // An event may come before any reply
+/// #         stream.write_all(b"{\"timestamp\": {\"seconds\": 1}, \"event\": \"RESUME\"}\r\n").unwrap();
+/// #         write!(stream, "{{\"return\": \"{output}\"}}\r\n").unwrap();
+/// #     }
+/// # }
+/// use ramlink::consumer::adapters::{QmpError, QmpReader};
+/// use ramlink::consumer::MemoryReader;
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// // The SRAM of an AVR guest is at 0x800000 in QEMU
+/// let ram: Vec<u8> = (0..=255).collect();
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let qemu = std::thread::spawn(move || fake_qemu(listener, ram));
+///
+/// // Usually `QmpReader::connect(("localhost", 4444), ...)`
+/// let mut reader = QmpReader::connect(address, Duration::from_secs(1)).unwrap();
+/// reader.set_ram_base(0, 0x800000);
+/// let mut read = [0; 20];
+/// reader.read_memory(0x10, &mut read).unwrap();
+/// assert_eq!(read[..3], [0x10, 0x11, 0x12]);
+/// assert_eq!(read[19], 0x23);
+///
+/// let err = reader.read_memory(0x1000, &mut read).unwrap_err();
+/// assert_eq!(err.to_string(), "unexpected reply from QEMU: Cannot access memory\r\n");
+/// let err = reader.write_memory(0x10, 1).unwrap_err();
+/// assert!(matches!(err, QmpError::WriteUnsupported));
+///
+/// drop(reader);
+/// let commands = qemu.join().unwrap();
+/// assert_eq!(commands, ["xp /20xb 0x800010", "xp /20xb 0x801000"]);
+/// ```
+pub struct QmpReader<S = TcpStream> {
+    stream: S,
+    /// Bytes received but not parsed yet
+    received: Vec<u8>,
+    /// Bytes read per command
+    chunk: usize,
+    /// See [`QmpReader::set_ram_base`]
+    ram_base: (usize, usize),
+}
+
+impl QmpReader {
+    /// Connects to the QMP server of QEMU at `address`, e.g. `("localhost", 4444)`, and
+    /// negotiates the capabilities. `timeout` applies to the connection, and then to each
+    /// command.
+    pub fn connect(address: impl ToSocketAddrs, timeout: Duration) -> Result<Self, QmpError> {
+        let mut error = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Self::from_stream(stream);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        let error =
+            error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"));
+        Err(QmpError::Io(error))
+    }
+}
+
+impl<S: Read + Write> QmpReader<S> {
+    /// Talks to QEMU over an already open `stream`, e.g. a `UnixStream` for
+    /// `-qmp unix:qmp.sock,server,wait=off`, after reading its greeting and negotiating
+    /// the capabilities
+    pub fn from_stream(stream: S) -> Result<Self, QmpError> {
+        let mut reader = QmpReader {
+            stream,
+            received: Vec::new(),
+            chunk: CHUNK,
+            ram_base: (0, 0),
+        };
+        let greeting = reader.read_message()?;
+        if !greeting.contains("\"QMP\"") {
+            return Err(QmpError::Reply(greeting));
+        }
+        reader.execute("{\"execute\": \"qmp_capabilities\"}")?;
+        Ok(reader)
+    }
+
+    /// Sets how many bytes are read per command, 1024 by default
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.max(1);
+    }
+
+    /// Reads the addresses from `address_base` on at the physical addresses from
+    /// `physical_base` on, e.g. `set_ram_base(0, 0x800000)` for an AVR guest, whose data
+    /// space QEMU maps at 0x800000, when the address of the ring buffer is that of the
+    /// data space. Addresses are read as they are by default.
+    pub fn set_ram_base(&mut self, address_base: usize, physical_base: usize) {
+        self.ram_base = (address_base, physical_base);
+    }
+
+    /// Runs a command of the human monitor, e.g. `info registers`, and returns what it
+    /// printed
+    pub fn command(&mut self, command_line: &str) -> Result<String, QmpError> {
+        let reply = self.execute(&format!(
+            "{{\"execute\": \"human-monitor-command\", \"arguments\": {{\"command-line\": \"{}\"}}}}",
+            escape(command_line)
+        ))?;
+        json_string(&reply, "return").ok_or(QmpError::Reply(reply))
+    }
+
+    /// Sends a QMP command, and returns its reply, skipping the events that come before
+    fn execute(&mut self, command: &str) -> Result<String, QmpError> {
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(b"\r\n")?;
+        self.stream.flush()?;
+        loop {
+            let message = self.read_message()?;
+            if message.contains("\"return\"") {
+                return Ok(message);
+            }
+            if message.contains("\"error\"") {
+                let desc = json_string(&message, "desc").unwrap_or(message);
+                return Err(QmpError::Reply(desc));
+            }
+        }
+    }
+
+    /// Reads a message, QEMU sending one JSON object per line
+    fn read_message(&mut self) -> Result<String, QmpError> {
+        let mut buf = [0; 4096];
+        loop {
+            if let Some(end) = self.received.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.received.drain(..=end).collect();
+                let line: String = String::from_utf8_lossy(&line).trim().into();
+                if !line.is_empty() {
+                    return Ok(line);
+                }
+                continue;
+            }
+            let read = self.stream.read(&mut buf)?;
+            if read == 0 {
+                let closed = io::Error::new(io::ErrorKind::UnexpectedEof, "closed by QEMU");
+                return Err(QmpError::Io(closed));
+            }
+            self.received.extend_from_slice(&buf[..read]);
+        }
+    }
+}
+
+/// Escapes `text` as the content of a JSON string
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the first string value of `key` in the JSON `message`, unescaped
+fn json_string(message: &str, key: &str) -> Option<String> {
+    let (_, rest) = message.split_once(&format!("\"{key}\""))?;
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = rest.strip_prefix('"')?.chars();
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+impl<S: Read + Write> MemoryReader for QmpReader<S> {
+    type Error = QmpError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), QmpError> {
+        let (address_base, physical_base) = self.ram_base;
+        let address = address
+            .wrapping_sub(address_base)
+            .wrapping_add(physical_base);
+        let size = self.chunk;
+        for (i, chunk) in buffer.chunks_mut(size).enumerate() {
+            let address = address + i * size;
+            let output = self.command(&format!("xp /{}xb {address:#x}", chunk.len()))?;
+            // Lines of `address: 0x.. 0x..`
+            let mut words = output
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .flat_map(|(_, bytes)| bytes.split_ascii_whitespace());
+            for byte in chunk.iter_mut() {
+                *byte = words
+                    .next()
+                    .and_then(|word| u8::from_str_radix(word.strip_prefix("0x")?, 16).ok())
+                    .ok_or_else(|| QmpError::Reply(output.clone()))?;
+            }
+            if words.next().is_some() {
+                return Err(QmpError::Reply(output));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, _address: usize, _value: u8) -> Result<(), QmpError> {
+        Err(QmpError::WriteUnsupported)
+    }
+}
@@ -0,0 +1,392 @@
+//! Reader talking to the monitor of the Renode simulator.
+
+use core::fmt;
+use std::format;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::consumer::MemoryReader;
+
+/// Default of [`RenodeReader::set_chunk_size`]. `ReadBytes` prints about 6 characters per
+/// byte.
+const CHUNK: usize = 256;
+
+/// Starts a telnet command, see RFC 854
+const IAC: u8 = 255;
+/// Telnet subnegotiation start and end
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+/// Error of [`RenodeReader`]
+#[derive(Debug)]
+pub enum RenodeError {
+    /// The connection to Renode failed, or was closed
+    Io(io::Error),
+    /// Renode did not print what was expected, usually an error message, which is kept
+    Reply(String),
+}
+
+impl From<io::Error> for RenodeError {
+    fn from(e: io::Error) -> Self {
+        RenodeError::Io(e)
+    }
+}
+
+impl fmt::Display for RenodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenodeError::Io(e) => write!(f, "connection to Renode failed: {e}"),
+            RenodeError::Reply(reply) => write!(f, "unexpected reply from Renode: {reply}"),
+        }
+    }
+}
+
+impl std::error::Error for RenodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenodeError::Io(e) => Some(e),
+            RenodeError::Reply(_) => None,
+        }
+    }
+}
+
+/// Reads and writes the memory of a machine simulated by Renode, through its monitor, e.g.
+/// that of `renode --port 1234`, with the `ReadBytes`, `ReadByte` and `WriteByte` commands
+/// of the system bus. The simulation keeps running meanwhile.
+///
+/// Every command is a round trip through the monitor, which parses and runs it, so it is
+/// much slower than a transfer of a debug probe. Reads take one `ReadBytes` command per
+/// chunk of [`RenodeReader::set_chunk_size`] bytes, and a
+/// [`ProducerDevice`](crate::consumer::ProducerDevice) read thus takes up to four commands,
+/// plus one per byte of the consumer index written back. Renode versions without
+/// `ReadBytes` are read with one `ReadByte` per byte, which makes reading a full ring
+/// buffer take as many round trips as it has bytes: there, keep the ring buffer small, or
+/// poll it often so that few bytes are waiting. In both cases,
+/// [`ProducerDevice::set_consumer_cache`](crate::consumer::ProducerDevice::set_consumer_cache)
+/// and [`ProducerDevice::set_ack_threshold`](crate::consumer::ProducerDevice::set_ack_threshold)
+/// save commands.
+///
+/// Here, against a fake Renode, which counts the commands:
+/// ```
+/// # use std::io::{BufRead, BufReader, Write};
+/// # fn fake_renode(listener: TcpListener, mut ram: Vec<u8>) -> Vec<String> {
+/// #     let mut commands = Vec::new();
+/// #     // The second client gets an old Renode, without ReadBytes
+/// #     for legacy in [false, true] {
+/// #         let (stream, _) = listener.accept().unwrap();
+/// #         let mut reader = BufReader::new(stream.try_clone().unwrap());
+/// #         let mut stream = stream;
+/// #         // Telnet negotiation, then a colored prompt
+/// #         stream.write_all(&[255, 251, 1, 255, 251, 3]).unwrap();
+/// #         stream.write_all(b"\x1b[1;37mRenode\x1b[0m\r\n(monitor) ").unwrap();
+/// #         let mut prompt = "monitor".to_string();
+/// #         loop {
+/// #             let mut line = String::new();
+/// #             if reader.read_line(&mut line).unwrap() == 0 {
+/// #                 break;
+/// #             }
+/// #             let command = line.trim().to_string();
+/// #             commands.push(command.clone());
+/// #             let words: Vec<&str> = command.split_whitespace().collect();
+/// #             let hex = |w: &str| usize::from_str_radix(w.trim_start_matches("0x"), 16).unwrap();
+/// #             let output = match words[..] {
+/// #                 ["mach", "set", name] => {
+/// #                     prompt = name.trim_matches('"').to_string();
+/// #                     String::new()
+/// #                 }
+/// #                 ["sysbus", "ReadBytes", ..] if legacy => {
+/// #                     "\x1b[31mNo such command or device: ReadBytes\x1b[0m\r\n".to_string()
+/// #                 }
+/// #                 ["sysbus", "ReadBytes", address, len] => {
+/// #                     let bytes = &ram[hex(address) - 0x2000_0000..][..len.parse().unwrap()];
+/// #                     let bytes: Vec<_> = bytes.iter().map(|b| format!("0x{b:02X}, ")).collect();
+/// #                     format!("[\r\n{}\r\n]\r\n", bytes.concat())
+/// #                 }
+/// #                 ["sysbus", "ReadByte", address] => {
+/// #                     format!("0x{:02X}\r\n", ram[hex(address) - 0x2000_0000])
+/// #                 }
+/// #                 ["sysbus", "WriteByte", address, value] => {
+/// #                     ram[hex(address) - 0x2000_0000] = hex(value) as u8;
+/// #                     String::new()
+/// #                 }
+/// #                 _ => "\x1b[31mNo such command or device\x1b[0m\r\n".to_string(),
+/// #             };
+/// #             // The monitor echoes the command
+/// #             write!(stream, "{command}\r\n{output}({prompt}) ").unwrap();
+/// #         }
+/// #     }
+/// #     commands
+/// # }
+/// use ramlink::consumer::adapters::RenodeReader;
+/// use ramlink::consumer::MemoryReader;
+/// use std::net::TcpListener;
+/// use std::time::Duration;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+/// let renode = std::thread::spawn(move || fake_renode(listener, vec![0; 256]));
+///
+/// // Usually `RenodeReader::connect(("localhost", 1234), ...)`
+/// let mut reader = RenodeReader::connect(address, Duration::from_secs(1)).unwrap();
+/// reader.set_machine("stm32").unwrap();
+/// reader.write_memory_slice(0x2000_0010, &[1, 2, 0xff]).unwrap();
+/// let mut read = [0; 20];
+/// reader.set_chunk_size(8);
+/// reader.read_memory(0x2000_0008, &mut read).unwrap();
+/// assert_eq!(read[8..12], [1, 2, 0xff, 0]);
+///
+/// let err = reader.command("sysbus Frobnicate").unwrap_err();
+/// assert_eq!(err.to_string(), "unexpected reply from Renode: No such command or device");
+///
+/// // Without ReadBytes, each byte takes a command
+/// drop(reader);
+/// let mut reader = RenodeReader::connect(address, Duration::from_secs(1)).unwrap();
+/// let mut read = [0; 4];
+/// reader.read_memory(0x2000_0010, &mut read).unwrap();
+/// assert_eq!(read, [1, 2, 0xff, 0]);
+/// reader.read_memory(0x2000_0010, &mut read).unwrap();
+///
+/// drop(reader);
+/// let commands = renode.join().unwrap();
+/// assert_eq!(
+///     commands[..8],
+///     [
+///         "mach set \"stm32\"",
+///         "sysbus WriteByte 0x20000010 0x01",
+///         "sysbus WriteByte 0x20000011 0x02",
+///         "sysbus WriteByte 0x20000012 0xff",
+///         "sysbus ReadBytes 0x20000008 8",
+///         "sysbus ReadBytes 0x20000010 8",
+///         "sysbus ReadBytes 0x20000018 4",
+///         "sysbus Frobnicate",
+///     ]
+/// );
+/// // `ReadBytes` is only tried once
+/// assert_eq!(commands[8], "sysbus ReadBytes 0x20000010 4");
+/// assert_eq!(commands.len(), 8 + 1 + 4 + 4);
+/// assert_eq!(commands[13..], [
+///     "sysbus ReadByte 0x20000010",
+///     "sysbus ReadByte 0x20000011",
+///     "sysbus ReadByte 0x20000012",
+///     "sysbus ReadByte 0x20000013",
+/// ]);
+/// ```
+pub struct RenodeReader<S = TcpStream> {
+    stream: S,
+    /// Text received but not returned yet, without the telnet commands
+    received: Vec<u8>,
+    /// Name of the bus the commands are sent to
+    bus: String,
+    /// Bytes read per `ReadBytes` command
+    chunk: usize,
+    /// Set once `ReadBytes` failed where `ReadByte` worked, e.g. on an old Renode
+    per_byte: bool,
+}
+
+impl RenodeReader {
+    /// Connects to the monitor of Renode at `address`, e.g. `("localhost", 1234)`.
+    /// `timeout` applies to the connection, and then to each command.
+    pub fn connect(address: impl ToSocketAddrs, timeout: Duration) -> Result<Self, RenodeError> {
+        let mut error = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Self::from_stream(stream);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        let error =
+            error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"));
+        Err(RenodeError::Io(error))
+    }
+}
+
+impl<S: Read + Write> RenodeReader<S> {
+    /// Talks to the monitor over an already open `stream`, once it printed its prompt
+    pub fn from_stream(stream: S) -> Result<Self, RenodeError> {
+        let mut reader = RenodeReader {
+            stream,
+            received: Vec::new(),
+            bus: "sysbus".into(),
+            chunk: CHUNK,
+            per_byte: false,
+        };
+        reader.read_until_prompt()?;
+        Ok(reader)
+    }
+
+    /// Selects the machine whose memory is accessed, by its name in the simulation, e.g.
+    /// when the script creates several. The current one is used otherwise.
+    pub fn set_machine(&mut self, name: &str) -> Result<(), RenodeError> {
+        let output = self.command(&format!("mach set \"{name}\""))?;
+        if !output.is_empty() {
+            return Err(RenodeError::Reply(output));
+        }
+        Ok(())
+    }
+
+    /// Sets the bus the memory is accessed through, `sysbus` by default
+    pub fn set_bus(&mut self, name: &str) {
+        self.bus = name.into();
+    }
+
+    /// Sets how many bytes are read per `ReadBytes` command, 256 by default
+    pub fn set_chunk_size(&mut self, bytes: usize) {
+        self.chunk = bytes.max(1);
+    }
+
+    /// Runs a monitor command, and returns what it printed, without colors. Fails with
+    /// [`RenodeError::Reply`] if Renode printed an error.
+    pub fn command(&mut self, command: &str) -> Result<String, RenodeError> {
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(b"\n")?;
+        self.stream.flush()?;
+        let output = self.read_until_prompt()?;
+        // The monitor echoes the command
+        let output = output
+            .split_once('\n')
+            .filter(|(echo, _)| echo.trim() == command)
+            .map_or(output.as_str(), |(_, output)| output)
+            .trim();
+        if output.starts_with("No such command")
+            || output.starts_with("There was an error")
+            || output.contains("Exception")
+        {
+            return Err(RenodeError::Reply(output.into()));
+        }
+        Ok(output.into())
+    }
+
+    /// Reads until the monitor prints its prompt, e.g. `(monitor) ` or `(machine-0) `, and
+    /// returns what came before, without telnet commands, colors and carriage returns
+    fn read_until_prompt(&mut self) -> Result<String, RenodeError> {
+        let mut buf = [0; 4096];
+        loop {
+            let text = clean(&self.received);
+            if let Some(start) = prompt_start(&text) {
+                self.received.clear();
+                return Ok(text[..start].into());
+            }
+            let read = self.stream.read(&mut buf)?;
+            if read == 0 {
+                let closed = io::Error::new(io::ErrorKind::UnexpectedEof, "closed by Renode");
+                return Err(RenodeError::Io(closed));
+            }
+            self.received.extend_from_slice(&buf[..read]);
+        }
+    }
+
+    /// Reads `buffer.len()` bytes at `address` with a single `ReadBytes`
+    fn read_bytes(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), RenodeError> {
+        let output = self.command(&format!(
+            "{} ReadBytes {address:#x} {}",
+            self.bus,
+            buffer.len()
+        ))?;
+        // A list of `0x..,`, within brackets
+        let mut words = output
+            .split(|c: char| c == ',' || c == '[' || c == ']' || c.is_ascii_whitespace())
+            .filter(|word| !word.is_empty());
+        for byte in buffer.iter_mut() {
+            *byte = words
+                .next()
+                .and_then(parse_hex)
+                .ok_or_else(|| RenodeError::Reply(output.clone()))?;
+        }
+        if words.next().is_some() {
+            return Err(RenodeError::Reply(output));
+        }
+        Ok(())
+    }
+
+    /// Reads `buffer.len()` bytes at `address` with one `ReadByte` each
+    fn read_each_byte(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), RenodeError> {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            let output = self.command(&format!("{} ReadByte {:#x}", self.bus, address + i))?;
+            *byte = parse_hex(&output).ok_or(RenodeError::Reply(output))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a byte printed by the monitor, e.g. `0x2A`
+fn parse_hex(word: &str) -> Option<u8> {
+    u8::from_str_radix(word.trim().strip_prefix("0x")?, 16).ok()
+}
+
+/// Returns `received` as text, without telnet commands, ANSI escape sequences, and
+/// carriage returns
+fn clean(received: &[u8]) -> String {
+    let mut text = Vec::with_capacity(received.len());
+    let mut bytes = received.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            IAC => match bytes.next() {
+                Some(IAC) => text.push(IAC),
+                // Subnegotiation, up to IAC SE
+                Some(SB) => {
+                    while let Some(byte) = bytes.next() {
+                        if byte == IAC && bytes.next_if_eq(&SE).is_some() {
+                            break;
+                        }
+                    }
+                }
+                // WILL, WONT, DO and DONT take an option
+                Some(251..=254) => {
+                    bytes.next();
+                }
+                _ => {}
+            },
+            // CSI sequences end with a byte within 0x40..=0x7e
+            0x1b => {
+                if bytes.next_if_eq(&b'[').is_some() {
+                    while bytes.next().is_some_and(|b| !(0x40..=0x7e).contains(&b)) {}
+                }
+            }
+            b'\r' => {}
+            byte => text.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// Returns where the prompt ending `text` starts, if it does end with one
+fn prompt_start(text: &str) -> Option<usize> {
+    let start = text.rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[start..];
+    (line.starts_with('(') && line.ends_with(") ")).then_some(start)
+}
+
+impl<S: Read + Write> MemoryReader for RenodeReader<S> {
+    type Error = RenodeError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), RenodeError> {
+        let size = self.chunk;
+        for (i, chunk) in buffer.chunks_mut(size).enumerate() {
+            let address = address + i * size;
+            if self.per_byte {
+                self.read_each_byte(address, chunk)?;
+            } else if let Err(e) = self.read_bytes(address, chunk) {
+                // Only fall back for good if the bytes can be read otherwise
+                self.read_each_byte(address, chunk).map_err(|_| e)?;
+                self.per_byte = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), RenodeError> {
+        let output = self.command(&format!("{} WriteByte {address:#x} {value:#04x}", self.bus))?;
+        if !output.is_empty() {
+            return Err(RenodeError::Reply(output));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! Reader talking to the UPDI of an AVR through a serial adapter.
+
+use core::fmt;
+use std::io::{self, Read, Write};
+use std::vec;
+
+use crate::consumer::MemoryReader;
+
+/// Starts every instruction
+const SYNCH: u8 = 0x55;
+const ACK: u8 = 0x40;
+
+/// Instructions, see the UPDI chapter of the datasheet of any tinyAVR 0-series
+const LD: u8 = 0x20;
+const ST: u8 = 0x60;
+const LDCS: u8 = 0x80;
+const STCS: u8 = 0xc0;
+const REPEAT: u8 = 0xa0;
+const KEY: u8 = 0xe0;
+/// Operands
+const PTR_INC: u8 = 0x04;
+const PTR_ADDRESS: u8 = 0x08;
+const DATA_8: u8 = 0x00;
+const DATA_16: u8 = 0x01;
+const DATA_24: u8 = 0x02;
+const KEY_SIB_16: u8 = 0x05;
+
+/// Control and status registers
+const STATUSA: u8 = 0x00;
+const CTRLA: u8 = 0x02;
+const CTRLB: u8 = 0x03;
+/// Inter-byte delay, which slow adapters need between the bytes of a reply
+const CTRLA_IBDLY: u8 = 1 << 7;
+/// Disables the collision detection, which the echo of the adapter would trigger
+const CTRLB_CCDETDIS: u8 = 1 << 3;
+
+/// Bytes transferred per `REPEAT`, its maximum
+const CHUNK: usize = 256;
+/// Offset of the data space in the addresses of AVR ELF files
+const DATA_SPACE: usize = 0x80_0000;
+
+/// Error of [`SerialUpdiReader`]
+#[derive(Debug)]
+pub enum UpdiError {
+    /// The serial port failed, or the target did not reply in time
+    Io(io::Error),
+    /// The bytes sent were not read back as they were: the adapter does not echo, or
+    /// something else drives the line
+    Echo,
+    /// The target replied with this instead of an ACK
+    NoAck(u8),
+    /// The UPDI of the target replied, but not as expected: its status is 0, or its System
+    /// Information Block is not printable
+    Link,
+    /// The access is not within the data space
+    OutOfRange {
+        /// The address of the access, as given to the reader
+        address: usize,
+        /// Its length
+        len: usize,
+    },
+}
+
+impl From<io::Error> for UpdiError {
+    fn from(e: io::Error) -> Self {
+        UpdiError::Io(e)
+    }
+}
+
+impl fmt::Display for UpdiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdiError::Io(e) => write!(f, "connection to the UPDI adapter failed: {e}"),
+            UpdiError::Echo => write!(f, "the UPDI adapter did not echo what was sent"),
+            UpdiError::NoAck(reply) => write!(f, "UPDI replied {reply:#04x} instead of an ACK"),
+            UpdiError::Link => write!(f, "the UPDI of the target replied unexpectedly"),
+            UpdiError::OutOfRange { address, len } => {
+                write!(
+                    f,
+                    "{address:#x}..{:#x} is not within the data space",
+                    address + len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for UpdiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UpdiError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Reads and writes the data space of an AVR with UPDI, e.g. a tinyAVR 0/1/2, megaAVR 0 or
+/// AVR Dx, through a serial-UPDI adapter: a USB serial converter whose TX and RX are tied
+/// to the UPDI pin through a resistor, as used by pymcuprog. The adapter thus echoes every
+/// byte sent, which is checked.
+///
+/// The target keeps running: attaching only configures the UPDI link and reads the System
+/// Information Block (SIB), without the NVMPROG key or a reset request, which are what halt
+/// or reset it when programming. Reads and writes use the pointer of UPDI with
+/// auto-increment, and take three instructions per 256 bytes, then one round trip per
+/// byte written, for its ACK.
+///
+/// Addresses are those of the data space, e.g. `0x3f0e`, or those of ELF files, e.g.
+/// `0x803f0e`, whose offset is dropped.
+///
+/// Here, against a serial mock replaying the bytes exchanged with an ATtiny402:
+/// ```
+/// # use std::collections::VecDeque;
+/// # use std::io::{self, Read, Write};
+/// # use std::sync::{Arc, Mutex};
+/// # /// Replays `(sent, reply)` exchanges, echoing what is written as a serial-UPDI adapter
+/// # struct ScriptedSerial {
+/// #     script: Arc<Mutex<VecDeque<(&'static [u8], &'static [u8])>>>,
+/// #     sent: Vec<u8>,
+/// #     incoming: VecDeque<u8>,
+/// # }
+/// # impl Write for ScriptedSerial {
+/// #     fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+/// #         self.sent.extend_from_slice(data);
+/// #         self.incoming.extend(data);
+/// #         let mut script = self.script.lock().unwrap();
+/// #         while let Some(&(sent, reply)) = script.front() {
+/// #             if self.sent.len() < sent.len() {
+/// #                 break;
+/// #             }
+/// #             assert_eq!(self.sent[..sent.len()], *sent);
+/// #             self.sent.drain(..sent.len());
+/// #             self.incoming.extend(reply);
+/// #             script.pop_front();
+/// #         }
+/// #         Ok(data.len())
+/// #     }
+/// #     fn flush(&mut self) -> io::Result<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// # impl Read for ScriptedSerial {
+/// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+/// #         if self.incoming.is_empty() {
+/// #             return Err(io::ErrorKind::TimedOut.into());
+/// #         }
+/// #         let len = buf.len().min(self.incoming.len());
+/// #         for (byte, incoming) in buf.iter_mut().zip(self.incoming.drain(..len)) {
+/// #             *byte = incoming;
+/// #         }
+/// #         Ok(len)
+/// #     }
+/// # }
+/// use ramlink::consumer::adapters::{SerialUpdiReader, UpdiError};
+/// use ramlink::consumer::MemoryReader;
+///
+/// let script: &[(&[u8], &[u8])] = &[
+///     // Attaching: CTRLB, CTRLA, STATUSA, then the SIB
+///     (&[0x55, 0xc3, 0x08], &[]),
+///     (&[0x55, 0xc2, 0x80], &[]),
+///     (&[0x55, 0x80], &[0x30]),
+///     (&[0x55, 0xe5], b"tinyAVR P:0D:1-3"),
+///     // Reading 4 bytes at 0x3f0e: pointer, repeat, load with increment
+///     (&[0x55, 0x69, 0x0e, 0x3f], &[0x40]),
+///     (&[0x55, 0xa0, 0x03], &[]),
+///     (&[0x55, 0x24], &[1, 2, 3, 4]),
+///     // Writing 2 bytes at 0x3f10: each is acknowledged
+///     (&[0x55, 0x69, 0x10, 0x3f], &[0x40]),
+///     (&[0x55, 0xa0, 0x01], &[]),
+///     (&[0x55, 0x64, 0xaa], &[0x40]),
+///     (&[0xbb], &[0x40]),
+///     // Writing a byte the target does not acknowledge
+///     (&[0x55, 0x69, 0x12, 0x3f], &[0x40]),
+///     (&[0x55, 0x64, 0x00], &[]),
+/// ];
+/// let script = Arc::new(Mutex::new(script.iter().copied().collect()));
+/// let serial = ScriptedSerial { script: script.clone(), sent: Vec::new(), incoming: VecDeque::new() };
+///
+/// // Usually `SerialUpdiReader::open("/dev/ttyUSB0")`
+/// let mut reader = SerialUpdiReader::from_stream(serial).unwrap();
+/// assert_eq!(reader.sib(), b"tinyAVR P:0D:1-3");
+///
+/// let mut read = [0; 4];
+/// // The address of an ELF file
+/// reader.read_memory(0x80_3f0e, &mut read).unwrap();
+/// assert_eq!(read, [1, 2, 3, 4]);
+/// reader.write_memory_slice(0x3f10, &[0xaa, 0xbb]).unwrap();
+///
+/// let err = reader.write_memory(0x3f12, 0).unwrap_err();
+/// assert!(matches!(err, UpdiError::Io(_)));
+/// let err = reader.write_memory(0x1_0000, 0).unwrap_err();
+/// assert_eq!(err.to_string(), "0x10000..0x10001 is not within the data space");
+/// assert!(script.lock().unwrap().is_empty());
+/// ```
+pub struct SerialUpdiReader<S = std::fs::File> {
+    stream: S,
+    /// System Information Block
+    sib: [u8; 16],
+    /// Whether addresses take 3 bytes, from version 2 of the NVM controller, on the AVR Dx
+    wide: bool,
+}
+
+#[cfg(unix)]
+impl SerialUpdiReader {
+    /// Opens the serial port of the adapter at `path`, e.g. `/dev/ttyUSB0`, at 115200 baud,
+    /// 8E2 as UPDI expects, sends a break to reset the UPDI link (but not the target), and
+    /// attaches.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, UpdiError> {
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        let port = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        let fd = port.as_raw_fd();
+        // SAFETY: fd stays open as long as port, and termios is filled by tcgetattr before
+        // being used
+        unsafe {
+            let mut termios: libc::termios = core::mem::zeroed();
+            if libc::tcgetattr(fd, &mut termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::cfmakeraw(&mut termios);
+            libc::cfsetispeed(&mut termios, libc::B115200);
+            libc::cfsetospeed(&mut termios, libc::B115200);
+            termios.c_cflag |= libc::PARENB | libc::CSTOPB | libc::CLOCAL | libc::CREAD;
+            termios.c_cflag &= !libc::PARODD;
+            // Reads give up after a tenth of a second of silence
+            termios.c_cc[libc::VMIN] = 0;
+            termios.c_cc[libc::VTIME] = 1;
+            if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            libc::tcsendbreak(fd, 0);
+            // Drops the echo of the break
+            libc::tcdrain(fd);
+            libc::tcflush(fd, libc::TCIFLUSH);
+        }
+        Self::from_stream(port)
+    }
+}
+
+impl<S: Read + Write> SerialUpdiReader<S> {
+    /// Attaches through an already open and configured `stream`, which echoes what is
+    /// written to it
+    pub fn from_stream(stream: S) -> Result<Self, UpdiError> {
+        let mut reader = SerialUpdiReader {
+            stream,
+            sib: [0; 16],
+            wide: false,
+        };
+        reader.send(&[SYNCH, STCS | CTRLB, CTRLB_CCDETDIS])?;
+        reader.send(&[SYNCH, STCS | CTRLA, CTRLA_IBDLY])?;
+        reader.send(&[SYNCH, LDCS | STATUSA])?;
+        if reader.receive_byte()? == 0 {
+            return Err(UpdiError::Link);
+        }
+        reader.send(&[SYNCH, KEY | KEY_SIB_16])?;
+        reader.stream.read_exact(&mut reader.sib)?;
+        if !reader
+            .sib
+            .iter()
+            .all(|b| b.is_ascii_graphic() || *b == b' ')
+        {
+            return Err(UpdiError::Link);
+        }
+        // e.g. `P:0` on the tinyAVRs, `P:2` on the AVR Dx
+        reader.wide = reader.sib[8..10] == *b"P:" && reader.sib[10] >= b'2';
+        Ok(reader)
+    }
+
+    /// Returns the System Information Block of the target, e.g. `tinyAVR P:0D:1-3`: its
+    /// family, and the versions of its NVM controller, debugger, and oscillator
+    pub fn sib(&self) -> &[u8; 16] {
+        &self.sib
+    }
+
+    /// Sends a 64-bit `key`, as written in the datasheet, e.g. `b"NVMProg "`. Keys enable
+    /// the features of UPDI that halt or reset the target: none is needed to access the
+    /// data space.
+    pub fn send_key(&mut self, key: &[u8; 8]) -> Result<(), UpdiError> {
+        let mut reversed = *key;
+        reversed.reverse();
+        self.send(&[SYNCH, KEY])?;
+        self.send(&reversed)
+    }
+
+    /// Sends `data`, and checks its echo
+    fn send(&mut self, data: &[u8]) -> Result<(), UpdiError> {
+        self.stream.write_all(data)?;
+        self.stream.flush()?;
+        let mut echo = vec![0; data.len()];
+        self.stream.read_exact(&mut echo)?;
+        if echo != data {
+            return Err(UpdiError::Echo);
+        }
+        Ok(())
+    }
+
+    fn receive_byte(&mut self) -> Result<u8, UpdiError> {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn receive_ack(&mut self) -> Result<(), UpdiError> {
+        match self.receive_byte()? {
+            ACK => Ok(()),
+            reply => Err(UpdiError::NoAck(reply)),
+        }
+    }
+
+    /// Returns the data space address of `len` bytes at `address`, if they all are in it
+    fn data_address(address: usize, len: usize) -> Result<usize, UpdiError> {
+        let data = match address {
+            0..=0xffff => address,
+            DATA_SPACE..=0x80_ffff => address - DATA_SPACE,
+            _ => return Err(UpdiError::OutOfRange { address, len }),
+        };
+        if data + len > 0x1_0000 {
+            return Err(UpdiError::OutOfRange { address, len });
+        }
+        Ok(data)
+    }
+
+    /// Points the UPDI pointer at `data`, and repeats the next instruction `len` times
+    fn point(&mut self, data: usize, len: usize) -> Result<(), UpdiError> {
+        let [low, high, ..] = (data as u32).to_le_bytes();
+        if self.wide {
+            self.send(&[SYNCH, ST | PTR_ADDRESS | DATA_24, low, high, 0])?;
+        } else {
+            self.send(&[SYNCH, ST | PTR_ADDRESS | DATA_16, low, high])?;
+        }
+        self.receive_ack()?;
+        if len > 1 {
+            self.send(&[SYNCH, REPEAT | DATA_8, (len - 1) as u8])?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Read + Write> MemoryReader for SerialUpdiReader<S> {
+    type Error = UpdiError;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), UpdiError> {
+        let start = Self::data_address(address, buffer.len())?;
+        for (i, chunk) in buffer.chunks_mut(CHUNK).enumerate() {
+            self.point(start + i * CHUNK, chunk.len())?;
+            self.send(&[SYNCH, LD | PTR_INC | DATA_8])?;
+            self.stream.read_exact(chunk)?;
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), UpdiError> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), UpdiError> {
+        let start = Self::data_address(address, data.len())?;
+        for (i, chunk) in data.chunks(CHUNK).enumerate() {
+            self.point(start + i * CHUNK, chunk.len())?;
+            self.send(&[SYNCH, ST | PTR_INC | DATA_8, chunk[0]])?;
+            self.receive_ack()?;
+            for &byte in &chunk[1..] {
+                self.send(&[byte])?;
+                self.receive_ack()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Reading of a [`ProducerDevice`] from async code.
+
+use alloc::vec::Vec;
+use core::future::Future;
+use core::time::Duration;
+
+use super::{ConsumerError, MemoryReader, PollPolicy, ProducerDevice};
+
+/// A [`ProducerDevice`] whose reads wait for bytes without blocking the executor, obtained
+/// with [`ProducerDevice::into_async`].
+///
+/// It waits by calling `sleep` with the intervals of its [`PollPolicy`] and awaiting the returned future, so
+/// it works with any runtime: `tokio::time::sleep`, `async_std::task::sleep`, or e.g.
+/// `|d| embassy_time::Timer::after(d.try_into().unwrap())` on a microcontroller.
+///
+/// The [`MemoryReader`] is still called synchronously, and each read of the device completes
+/// before the next await. Dropping a read future, e.g. in a `select!`, thus never loses
+/// bytes: they are either consumed and returned, or left in the ring buffer for the next
+/// read.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::AtomicRB;
+/// use std::time::Duration;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+/// # fn block_on<F: core::future::Future>(future: F) -> F::Output {
+/// #     use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+/// #     fn raw() -> RawWaker {
+/// #         RawWaker::new(core::ptr::null(), &VTABLE)
+/// #     }
+/// #     static VTABLE: RawWakerVTable = RawWakerVTable::new(|_| raw(), |_| {}, |_| {}, |_| {});
+/// #     let waker = unsafe { Waker::from_raw(raw()) };
+/// #     let mut future = core::pin::pin!(future);
+/// #     loop {
+/// #         if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+/// #             return output;
+/// #         }
+/// #     }
+/// # }
+///
+/// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let device = ProducerDevice::new(host, address).unwrap();
+/// // With tokio: device.into_async(Duration::from_millis(10), tokio::time::sleep)
+/// let mut device = device.into_async(Duration::from_millis(1), |d| async move {
+///     std::thread::sleep(d)
+/// });
+///
+/// let producer = std::thread::spawn(|| {
+///     std::thread::sleep(Duration::from_millis(10));
+///     RING_BUF.send_bytes_blocking(b"hello");
+/// });
+/// let bytes = block_on(device.read_bytes()).unwrap();
+/// assert_eq!(bytes, b"hello");
+/// producer.join().unwrap();
+/// # }
+/// ```
+pub struct AsyncProducerDevice<M: MemoryReader, S> {
+    device: ProducerDevice<M>,
+    poll: PollPolicy,
+    sleep: S,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns an async version of this device, that polls the ring buffer following
+    /// `poll`, e.g. every [`Duration`], waiting with `sleep`. See [`AsyncProducerDevice`].
+    pub fn into_async<S, F>(
+        self,
+        poll: impl Into<PollPolicy>,
+        sleep: S,
+    ) -> AsyncProducerDevice<M, S>
+    where
+        S: FnMut(Duration) -> F,
+        F: Future<Output = ()>,
+    {
+        AsyncProducerDevice {
+            device: self,
+            poll: poll.into(),
+            sleep,
+        }
+    }
+}
+
+impl<M, S, F> AsyncProducerDevice<M, S>
+where
+    M: MemoryReader,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Waits until some bytes are waiting, and reads as many as fit in `buf`, like
+    /// [`ProducerDevice::read_into`]. Returns the number of bytes read, which is 0 only if
+    /// `buf` is empty.
+    pub async fn read_into(&mut self, buf: &mut [u8]) -> Result<usize, ConsumerError<M::Error>> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut empty_reads = 0;
+        loop {
+            let read = self.device.read_into(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            self.wait(&mut empty_reads).await;
+        }
+    }
+
+    /// Waits until some bytes are waiting, and reads all of them, like
+    /// [`ProducerDevice::read_bytes`]. The returned `Vec` is never empty.
+    pub async fn read_bytes(&mut self) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let mut empty_reads = 0;
+        loop {
+            let bytes = self.device.read_bytes()?;
+            if !bytes.is_empty() {
+                return Ok(bytes);
+            }
+            self.wait(&mut empty_reads).await;
+        }
+    }
+
+    /// Sleeps after an empty read, following the policy
+    async fn wait(&mut self, empty_reads: &mut u32) {
+        self.device.record_sleep(*empty_reads);
+        (self.sleep)(self.poll.interval(*empty_reads)).await;
+        *empty_reads = empty_reads.saturating_add(1);
+    }
+}
+
+impl<M: MemoryReader, S> AsyncProducerDevice<M, S> {
+    /// Returns the device, e.g. to call its other methods
+    pub fn device(&mut self) -> &mut ProducerDevice<M> {
+        &mut self.device
+    }
+
+    /// Returns the device, dropping the sleep function
+    pub fn into_inner(self) -> ProducerDevice<M> {
+        self.device
+    }
+}
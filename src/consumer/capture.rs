@@ -0,0 +1,316 @@
+//! Capture files of the bytes read from a ring buffer, to decode them again offline.
+//!
+//! A capture file starts with [`CAPTURE_MAGIC`], followed by one record per chunk read: its
+//! length as a little-endian `u32`, the time it was read as a little-endian `u64` of
+//! microseconds since the Unix epoch, then its bytes.
+
+use core::fmt;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec;
+use std::vec::Vec;
+
+use super::{ConsumerError, MemoryReader, ProducerDevice};
+use crate::layout;
+
+/// First bytes of a capture file, the last one being the version of the format
+pub const CAPTURE_MAGIC: [u8; 8] = *b"ramlink\x01";
+
+/// Bytes before the chunk of a record: its length and its timestamp
+const RECORD_HEADER_LEN: usize = 12;
+
+/// Size of the ring buffer a [`ReplayReader`] plays the capture in
+const REPLAY_SIZE: usize = 4096;
+
+/// Writes a capture file, each [`CaptureWriter::write_chunk`] being a record stamped with
+/// the time of the host. It is also an [`io::Write`] whose writes are records, so that it
+/// can be one of the sinks of [`ProducerDevice::pipe_to`], next to the terminal.
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::Loopback;
+/// use ramlink::consumer::{CaptureReader, CaptureWriter};
+///
+/// let loopback = Loopback::<16>::new();
+/// let mut device = loopback.device().unwrap();
+/// let mut capture = CaptureWriter::new(Vec::new()).unwrap();
+///
+/// loopback.send_bytes_blocking(b"boot\n");
+/// capture.write_chunk(&device.read_bytes().unwrap()).unwrap();
+/// loopback.send_bytes_blocking(b"ok\n");
+/// capture.write_chunk(&device.read_bytes().unwrap()).unwrap();
+///
+/// let file = capture.into_inner();
+/// let chunks: Vec<_> = CaptureReader::new(&file[..])
+///     .unwrap()
+///     .map(|record| record.unwrap().bytes)
+///     .collect();
+/// assert_eq!(chunks, [b"boot\n".to_vec(), b"ok\n".to_vec()]);
+/// # }
+/// ```
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Writes the start of a capture file to `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        Ok(CaptureWriter { writer })
+    }
+
+    /// Writes a record of `chunk`, read now
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.write_chunk_at(chunk, SystemTime::now())
+    }
+
+    /// Writes a record of `chunk`, read at `timestamp`. Records are written whole at once,
+    /// so that a capture stopped abruptly only loses its last one.
+    pub fn write_chunk_at(&mut self, chunk: &[u8], timestamp: SystemTime) -> io::Result<()> {
+        let len = u32::try_from(chunk.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk too long"))?;
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_micros() as u64);
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + chunk.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&micros.to_le_bytes());
+        record.extend_from_slice(chunk);
+        self.writer.write_all(&record)
+    }
+
+    /// Returns the writer of the capture file
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Write for CaptureWriter<W> {
+    /// Writes a record of the whole of `buf`
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_chunk(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Error of [`CaptureReader`] and [`ReplayReader`]
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The file does not start with [`CAPTURE_MAGIC`]
+    NotACapture,
+    /// Reading the file failed, or it ends within a record
+    Io(io::Error),
+    /// Every record was replayed, and read by the consumer
+    Finished,
+    /// The consumer accessed memory outside of the replayed ring buffer
+    OutOfBounds,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NotACapture => write!(f, "not a ramlink capture file"),
+            ReplayError::Io(e) => write!(f, "failed to read the capture: {e}"),
+            ReplayError::Finished => write!(f, "end of the capture"),
+            ReplayError::OutOfBounds => write!(f, "access outside of the replayed ring buffer"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// A chunk of a capture file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// When the chunk was read
+    pub timestamp: SystemTime,
+    /// Bytes of the chunk
+    pub bytes: Vec<u8>,
+}
+
+/// Reads the records of a capture file written by [`CaptureWriter`]. As an [`Iterator`], it
+/// ends with the file.
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Checks that `reader` starts like a capture file. Fails with
+    /// [`ReplayError::NotACapture`] otherwise.
+    pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+        let mut magic = [0; CAPTURE_MAGIC.len()];
+        match reader.read_exact(&mut magic) {
+            Ok(()) if magic == CAPTURE_MAGIC => Ok(CaptureReader { reader }),
+            Ok(()) => Err(ReplayError::NotACapture),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(ReplayError::NotACapture),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the next record, or `None` at the end of the file
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>, ReplayError> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        // Only the end of the file between two records is its end
+        let mut read = 0;
+        while read < header.len() {
+            match self.reader.read(&mut header[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let mut micros = [0; 8];
+        micros.copy_from_slice(&header[4..]);
+        let mut bytes = vec![0; len as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(CaptureRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(u64::from_le_bytes(micros)),
+            bytes,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// A [`MemoryReader`] that plays a capture file as an `RB16` at address 0, so that the very
+/// code that decodes a device, e.g. a [`LineReader`](super::LineReader) or a
+/// [`FrameReader`](super::FrameReader), decodes the capture instead.
+///
+/// Each time the consumer finds the ring buffer empty, the next record is written to it, so
+/// that the reads return the same chunks as when they were captured, those larger than the
+/// ring buffer being split. Once every record was read, reads fail with
+/// [`ReplayError::Finished`].
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::Loopback;
+/// use ramlink::consumer::{CaptureWriter, ConsumerErrorKind, ReplayError, ReplayReader};
+///
+/// // Captured from the device
+/// let loopback = Loopback::<16>::new();
+/// let mut device = loopback.device().unwrap();
+/// let mut capture = CaptureWriter::new(Vec::new()).unwrap();
+/// for part in ["temp=21\npres", "sure=1013\n", "hum"] {
+///     loopback.send_bytes_blocking(part.as_bytes());
+///     capture.write_chunk(&device.read_bytes().unwrap()).unwrap();
+/// }
+/// let file = capture.into_inner();
+///
+/// // Decoded offline
+/// let mut device = ReplayReader::new(&file[..]).unwrap().device().unwrap();
+/// let mut lines = device.read_lines();
+/// assert_eq!(lines.try_next_line().unwrap().unwrap(), "temp=21");
+/// assert_eq!(lines.try_next_line().unwrap().unwrap(), "pressure=1013");
+/// // The last line was not complete when the capture stopped
+/// assert_eq!(lines.try_next_line().unwrap(), None);
+/// let err = lines.try_next_line().unwrap_err();
+/// assert!(matches!(
+///     err.kind(),
+///     ConsumerErrorKind::ReadMemoryError(ReplayError::Finished)
+/// ));
+/// # }
+/// ```
+pub struct ReplayReader<R: Read> {
+    records: CaptureReader<R>,
+    /// The ring buffer, as the consumer reads it
+    memory: Vec<u8>,
+    /// Bytes of the current record that did not fit in the ring buffer yet
+    pending: Vec<u8>,
+}
+
+impl<R: Read> ReplayReader<R> {
+    /// Returns a reader of the capture file `reader`, its ring buffer empty
+    pub fn new(reader: R) -> Result<Self, ReplayError> {
+        let rb = &layout::RB16;
+        let header = &layout::RB16_HEADER;
+        let mut memory = vec![0; rb.content + REPLAY_SIZE];
+        memory[..3].copy_from_slice(&header.magic(layout::DEFAULT_ID));
+        if let Some(version) = header.version {
+            memory[version.resolve(rb.content, REPLAY_SIZE)] = layout::VERSION;
+        }
+        memory[header.size..header.size + 2].copy_from_slice(&(REPLAY_SIZE as u16).to_le_bytes());
+        Ok(ReplayReader {
+            records: CaptureReader::new(reader)?,
+            memory,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Attaches a [`ProducerDevice`] to the replayed ring buffer
+    pub fn device(self) -> Result<ProducerDevice<Self>, ConsumerError<ReplayError>> {
+        ProducerDevice::new(self, 0)
+    }
+
+    fn index(&self, offset: usize) -> usize {
+        u16::from_le_bytes([self.memory[offset], self.memory[offset + 1]]) as usize
+    }
+
+    /// Writes the next bytes of the capture to the ring buffer if it is empty
+    fn refill(&mut self) -> Result<(), ReplayError> {
+        let rb = &layout::RB16;
+        let prod = self.index(rb.producer);
+        if prod != self.index(rb.consumer) {
+            return Ok(());
+        }
+        while self.pending.is_empty() {
+            match self.records.next_record()? {
+                Some(record) => self.pending = record.bytes,
+                None => return Err(ReplayError::Finished),
+            }
+        }
+        let len = self.pending.len().min(REPLAY_SIZE - 1);
+        for (i, byte) in self.pending.drain(..len).enumerate() {
+            self.memory[rb.content + (prod + i) % REPLAY_SIZE] = byte;
+        }
+        let prod = ((prod + len) % REPLAY_SIZE) as u16;
+        self.memory[rb.producer..rb.producer + 2].copy_from_slice(&prod.to_le_bytes());
+        Ok(())
+    }
+}
+
+impl<R: Read> MemoryReader for ReplayReader<R> {
+    type Error = ReplayError;
+
+    /// Reads the replayed ring buffer, refilling it first if the producer index is read
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), ReplayError> {
+        let end = address
+            .checked_add(buffer.len())
+            .ok_or(ReplayError::OutOfBounds)?;
+        let producer = layout::RB16.producer;
+        if address < producer + 2 && producer < end {
+            self.refill()?;
+        }
+        let bytes = self
+            .memory
+            .get(address..end)
+            .ok_or(ReplayError::OutOfBounds)?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), ReplayError> {
+        let byte = self
+            .memory
+            .get_mut(address)
+            .ok_or(ReplayError::OutOfBounds)?;
+        *byte = value;
+        Ok(())
+    }
+}
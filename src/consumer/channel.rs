@@ -0,0 +1,93 @@
+//! Reading of a [`ProducerDevice`] on a thread of its own.
+
+use super::{ConsumerError, MemoryReader, PollPolicy, ProducerDevice};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::{ControlFlow, Deref};
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::JoinHandle;
+
+/// Thread reading the device, returning the error that stopped it
+type ReaderThread<E> = JoinHandle<Result<(), ConsumerError<E>>>;
+
+/// The receiving end of [`ProducerDevice::spawn_channel`], a [`Receiver`] that also stops
+/// the thread reading the device when dropped
+pub struct ChannelReceiver {
+    receiver: Receiver<Vec<u8>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Deref for ChannelReceiver {
+    type Target = Receiver<Vec<u8>>;
+
+    fn deref(&self) -> &Receiver<Vec<u8>> {
+        &self.receiver
+    }
+}
+
+impl Drop for ChannelReceiver {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<M> ProducerDevice<M>
+where
+    M: MemoryReader + Send + 'static,
+    M::Error: Send + 'static,
+{
+    /// Moves the device to a new thread, which reads it like [`ProducerDevice::run`] and
+    /// sends the bytes of each read on the returned channel.
+    ///
+    /// Dropping the receiver stops the thread within one sleep of `poll`, and the device is then
+    /// dropped, detaching from the producer. The thread also stops on the first error of the
+    /// [`MemoryReader`], which is returned by joining it; the receiver then disconnects once
+    /// the bytes sent before are received.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::time::{Duration, Instant};
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let device = ProducerDevice::new(host, address).unwrap();
+    /// let poll = Duration::from_millis(10);
+    /// let (receiver, reader) = device.spawn_channel(poll);
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hello world");
+    /// let mut received = Vec::new();
+    /// while received.len() < 11 {
+    ///     received.extend(receiver.recv().unwrap());
+    /// }
+    /// assert_eq!(received, b"hello world");
+    ///
+    /// let dropped = Instant::now();
+    /// drop(receiver);
+    /// reader.join().unwrap().unwrap();
+    /// assert!(dropped.elapsed() < 10 * poll);
+    /// assert!(!RING_BUF.host_attached());
+    /// # }
+    /// ```
+    pub fn spawn_channel(
+        mut self,
+        poll: impl Into<PollPolicy>,
+    ) -> (ChannelReceiver, ReaderThread<M::Error>) {
+        let poll = poll.into();
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                self.run_until(poll, &stop, |data| match sender.send(data.to_vec()) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
+                })
+            }
+        });
+        (ChannelReceiver { receiver, stop }, reader)
+    }
+}
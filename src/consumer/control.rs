@@ -0,0 +1,192 @@
+//! Reading the channels of a `ControlBlock` of the producer.
+
+use super::{le_index, ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+use crate::layout::{self, CHANNEL_NAME_LEN};
+
+/// A channel of a control block, as described by the producer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channel {
+    /// Position of the channel in the control block
+    pub index: usize,
+    /// Address of its ring buffer
+    pub address: usize,
+    /// Size of its ring buffer
+    pub size: usize,
+    /// Reserved, 0 for now
+    pub flags: u8,
+    name: [u8; CHANNEL_NAME_LEN],
+}
+
+impl Channel {
+    /// Returns the name the producer gave to the channel, or `""` if it is not UTF-8
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+}
+
+/// The host end of a `ControlBlock`: it lists the channels of the control block, and
+/// attaches a [`ProducerDevice`] to each of them. The devices have their own indices, so
+/// draining one channel leaves the bytes of the others where they are.
+///
+/// Each device needs its own [`MemoryReader`], cloned from that of the `ControlBlockReader`
+/// by [`ControlBlockReader::open`]. A probe can be shared through a `&RefCell`:
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// use core::cell::RefCell;
+/// use ramlink::consumer::ControlBlockReader;
+/// use ramlink::producer::ControlBlock;
+/// # use ramlink::consumer::testing::VolatileReader;
+///
+/// static CHANNELS: ControlBlock<3, 32> = ControlBlock::new(["log", "metrics", "trace"]);
+///
+/// # let start = &CHANNELS as *const _ as *mut u8;
+/// # // SAFETY: the channels are atomics, and the rest is only read
+/// # let host = unsafe { VolatileReader::new(start, core::mem::size_of_val(&CHANNELS)) };
+/// let probe = RefCell::new(host);
+/// let mut block = ControlBlockReader::new(&probe, &CHANNELS as *const _ as usize).unwrap();
+/// let names: Vec<_> = (0..block.channel_count())
+///     .map(|i| block.channel(i).unwrap().name().to_owned())
+///     .collect();
+/// assert_eq!(names, ["log", "metrics", "trace"]);
+///
+/// let log = block.find("log").unwrap();
+/// let mut logs = block.open(&log).unwrap();
+/// let metrics = block.find("metrics").unwrap();
+/// let mut metrics = block.open(&metrics).unwrap();
+///
+/// CHANNELS.channel(0).send_bytes_blocking(b"boot\n");
+/// CHANNELS.channel(1).send_bytes_blocking(&[0x17, 0x2a]);
+/// assert_eq!(logs.read_bytes().unwrap(), b"boot\n");
+/// assert_eq!(CHANNELS.channel(1).len(), 2);
+/// assert_eq!(metrics.read_bytes().unwrap(), [0x17, 0x2a]);
+/// # }
+/// ```
+pub struct ControlBlockReader<M: MemoryReader> {
+    memory_reader: M,
+    address: usize,
+    id: u8,
+    count: usize,
+}
+
+impl<M: MemoryReader> ControlBlockReader<M> {
+    /// Reads the header of the control block at `address`, checking its magic marker and
+    /// version
+    pub fn new(memory_reader: M, address: usize) -> Result<Self, ConsumerError<M::Error>> {
+        Self::new_with_id(memory_reader, address, layout::DEFAULT_ID)
+    }
+
+    /// Same as [`ControlBlockReader::new`], for a control block declared with the id
+    /// `expected_id`, which its channels have too
+    pub fn new_with_id(
+        mut memory_reader: M,
+        address: usize,
+        expected_id: u8,
+    ) -> Result<Self, ConsumerError<M::Error>> {
+        let cb = &layout::CONTROL_BLOCK;
+        let mut header = [0; layout::CONTROL_BLOCK.descriptors];
+        memory_reader
+            .read_memory(address, &mut header)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        if header[..2] != cb.magic_prefix {
+            return Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound));
+        }
+        if header[2] != expected_id {
+            return Err(ConsumerError(ConsumerErrorKind::WrongId(header[2])));
+        }
+        if header[cb.version] != layout::VERSION {
+            return Err(ConsumerError(ConsumerErrorKind::UnsupportedVersion(
+                header[cb.version],
+            )));
+        }
+        Ok(ControlBlockReader {
+            memory_reader,
+            address,
+            id: expected_id,
+            count: header[cb.count] as usize,
+        })
+    }
+
+    /// Returns the address of the control block
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Returns the number of channels of the control block
+    pub fn channel_count(&self) -> usize {
+        self.count
+    }
+
+    /// Reads the descriptor of the channel `index`. Fails with
+    /// [`ConsumerErrorKind::ChannelNotFound`] if there are not that many channels.
+    pub fn channel(&mut self, index: usize) -> Result<Channel, ConsumerError<M::Error>> {
+        if index >= self.count {
+            return Err(ConsumerError(ConsumerErrorKind::ChannelNotFound));
+        }
+        let cb = &layout::CONTROL_BLOCK;
+        let mut descriptor = [0; layout::CONTROL_BLOCK.descriptor_len];
+        self.memory_reader
+            .read_memory(
+                self.address + cb.descriptors + index * cb.descriptor_len,
+                &mut descriptor,
+            )
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        let mut name = [0; CHANNEL_NAME_LEN];
+        name.copy_from_slice(&descriptor[cb.name..cb.name + CHANNEL_NAME_LEN]);
+        Ok(Channel {
+            index,
+            address: self.address + le_index(&descriptor[cb.offset..cb.offset + 4]),
+            size: le_index(&descriptor[cb.size..cb.size + 2]),
+            flags: descriptor[cb.flags],
+            name,
+        })
+    }
+
+    /// Returns the first channel named `name`. Fails with
+    /// [`ConsumerErrorKind::ChannelNotFound`] if there is none.
+    pub fn find(&mut self, name: &str) -> Result<Channel, ConsumerError<M::Error>> {
+        for index in 0..self.count {
+            let channel = self.channel(index)?;
+            if channel.name() == name {
+                return Ok(channel);
+            }
+        }
+        Err(ConsumerError(ConsumerErrorKind::ChannelNotFound))
+    }
+
+    /// Attaches a device to `channel`, with a clone of the memory reader, checking that
+    /// the ring buffer is where the descriptor says and of its size
+    pub fn open(&self, channel: &Channel) -> Result<ProducerDevice<M>, ConsumerError<M::Error>>
+    where
+        M: Clone,
+    {
+        ProducerDevice::new_with_id(self.memory_reader.clone(), channel.address, self.id)
+            .and_then(|device| expect_size(device, channel))
+    }
+
+    /// Returns the memory reader, e.g. to attach a single device to a channel with
+    /// [`ProducerDevice::new_with_id`] when `M` can't be cloned
+    pub fn into_inner(self) -> M {
+        self.memory_reader
+    }
+}
+
+/// Fails with [`ConsumerErrorKind::SizeMismatch`] if the ring buffer `device` is attached
+/// to does not have the size of `channel`
+fn expect_size<M: MemoryReader>(
+    device: ProducerDevice<M>,
+    channel: &Channel,
+) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+    let found = device.capacity() + 1;
+    if found != channel.size {
+        return Err(ConsumerError(ConsumerErrorKind::SizeMismatch {
+            expected: channel.size,
+            found,
+        }));
+    }
+    Ok(device)
+}
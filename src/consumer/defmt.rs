@@ -0,0 +1,165 @@
+//! Decoding of the frames of a defmt logger with `defmt-decoder`.
+
+use core::ops::ControlFlow;
+use std::boxed::Box;
+
+use defmt_decoder::{DecodeError, Frame, StreamDecoder, Table};
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, PollPolicy, ProducerDevice};
+
+/// Reads the frames sent by a defmt logger, such as that of `producer::defmt`, and decodes
+/// them with the table of the ELF file of the firmware, obtained with
+/// [`ProducerDevice::defmt_frames`]. Bytes read from the ring buffer are kept by the decoder
+/// until their frame is complete.
+///
+/// Here, with a canned table and stream, those of `defmt::info!("t={=i16}", 21)` then
+/// `defmt::warn!("stall on motor {=u8}", 3)`:
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::ConsumerErrorKind;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// // Usually `Table::parse(&std::fs::read("firmware.elf")?)?`
+/// let table: defmt_decoder::Table = serde_json::from_str(
+///     r#"{
+///         "timestamp": null,
+///         "entries": {
+///             "0": { "string": { "tag": "Info", "string": "t={=i16}" }, "raw_symbol": "" },
+///             "1": { "string": { "tag": "Warn", "string": "stall on motor {=u8}" }, "raw_symbol": "" }
+///         },
+///         "bitflags": {},
+///         "encoding": "Rzcobs"
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut frames = device.defmt_frames(&table);
+///
+/// RING_BUF.send_bytes_blocking(&[0x00, 0x15, 0x7b, 0x00, 0x00, 0x01]);
+/// let frame = frames.try_next_frame().unwrap().unwrap();
+/// assert_eq!(frame.display_message().to_string(), "t=21");
+/// assert_eq!(frames.try_next_frame().unwrap().map(|f| f.index()), None);
+///
+/// RING_BUF.send_bytes_blocking(&[0x03, 0x7a, 0x00]);
+/// let frame = frames.try_next_frame().unwrap().unwrap();
+/// assert_eq!(frame.level().unwrap().as_str(), "warn");
+/// assert_eq!(frame.display_message().to_string(), "stall on motor 3");
+///
+/// // The index of no format string of the table
+/// RING_BUF.send_bytes_blocking(&[0x05, 0x7e, 0x00]);
+/// let err = frames.try_next_frame().unwrap_err();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::InvalidFrame));
+/// # }
+/// ```
+pub struct DefmtReader<'d, 't, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    decoder: Box<dyn StreamDecoder + Send + Sync + 't>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of defmt frames decoded with `table`, see [`DefmtReader`]
+    pub fn defmt_frames<'t>(&mut self, table: &'t Table) -> DefmtReader<'_, 't, M> {
+        DefmtReader {
+            device: self,
+            decoder: table.new_stream_decoder(),
+        }
+    }
+}
+
+impl<M: MemoryReader> DefmtReader<'_, '_, M> {
+    /// Reads the ring buffer, then returns the next frame, or `None` if it is not complete
+    /// yet. Fails with [`ConsumerErrorKind::InvalidFrame`] if a frame can't be decoded,
+    /// e.g. because it was written by another build of the firmware than that of the table.
+    /// With the rzCOBS encoding of defmt, the default, the frame is then discarded.
+    pub fn try_next_frame(&mut self) -> Result<Option<Frame<'_>>, ConsumerError<M::Error>> {
+        let bytes = self.device.read_bytes()?;
+        self.decoder.received(&bytes);
+        match self.decoder.decode() {
+            Ok(frame) => Ok(Some(frame)),
+            Err(DecodeError::UnexpectedEof) => Ok(None),
+            Err(DecodeError::Malformed) => Err(ConsumerError(ConsumerErrorKind::InvalidFrame)),
+        }
+    }
+
+    /// Reads the ring buffer until `on_frame` returns [`ControlFlow::Break`], calling it
+    /// with each frame decoded, and sleeping between reads like [`ProducerDevice::run`].
+    /// Frames that can't be decoded stop the loop, as errors of the [`MemoryReader`] do; see
+    /// [`DefmtReader::try_next_frame`].
+    /// ```no_run
+    /// # #[cfg(all(feature = "elf", feature = "producer"))] {
+    /// # use core::fmt::Error;
+    /// # use ramlink::consumer::{MemoryReader, ProducerDevice};
+    /// # struct Probe;
+    /// # impl MemoryReader for Probe {
+    /// #     type Error = Error;
+    /// #     fn read_memory(&mut self, _: usize, _: &mut [u8]) -> Result<(), Error> { Ok(()) }
+    /// #     fn write_memory(&mut self, _: usize, _: u8) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// use core::ops::ControlFlow;
+    /// use defmt_decoder::Table;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// let elf = Path::new("target/thumbv7em-none-eabihf/release/firmware");
+    /// let table = Table::parse(&std::fs::read(elf).unwrap()).unwrap().unwrap();
+    /// let mut device = ProducerDevice::new_from_elf(Probe, elf, "DEFMT_RB").unwrap();
+    /// let mut frames = device.defmt_frames(&table);
+    /// frames
+    ///     .run(Duration::from_millis(10), |frame| {
+    ///         println!("{}", frame.display(true));
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn run(
+        &mut self,
+        poll: impl Into<PollPolicy>,
+        mut on_frame: impl FnMut(&Frame) -> ControlFlow<()>,
+    ) -> Result<(), ConsumerError<M::Error>> {
+        // Frames may be left from the last `try_next_frame`
+        if let ControlFlow::Break(result) = decode_all(&mut *self.decoder, &mut on_frame) {
+            return result;
+        }
+        let decoder = &mut self.decoder;
+        let mut result = Ok(());
+        self.device.run(poll, |data| {
+            decoder.received(data);
+            match decode_all(&mut **decoder, &mut on_frame) {
+                ControlFlow::Break(stopped) => {
+                    result = stopped;
+                    ControlFlow::Break(())
+                }
+                ControlFlow::Continue(()) => ControlFlow::Continue(()),
+            }
+        })?;
+        result
+    }
+}
+
+/// Calls `on_frame` with the frames that are complete, until it returns
+/// [`ControlFlow::Break`] or a frame can't be decoded
+fn decode_all<E>(
+    decoder: &mut (dyn StreamDecoder + Send + Sync + '_),
+    on_frame: &mut impl FnMut(&Frame) -> ControlFlow<()>,
+) -> ControlFlow<Result<(), ConsumerError<E>>> {
+    loop {
+        match decoder.decode() {
+            Ok(frame) => {
+                if on_frame(&frame).is_break() {
+                    return ControlFlow::Break(Ok(()));
+                }
+            }
+            Err(DecodeError::UnexpectedEof) => return ControlFlow::Continue(()),
+            Err(DecodeError::Malformed) => {
+                return ControlFlow::Break(Err(ConsumerError(ConsumerErrorKind::InvalidFrame)))
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Raw snapshots of a ring buffer, for post-mortem debugging.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{le_index, read_block, ConsumerError, MemoryReader, ProducerDevice};
+use crate::layout::Offset;
+
+/// Bytes per line of the hexdump of [`RbSnapshot`]
+const LINE: usize = 16;
+
+/// A ring buffer exactly as it was in the RAM of the producer, obtained with
+/// [`ProducerDevice::dump`]. Nothing is checked, so that a corrupted ring buffer can be
+/// looked at: the indices may be out of the content.
+///
+/// Its [`Display`](fmt::Display) is a hexdump of the content, with the consumer and
+/// producer indices marked with `C` and `P` under their slot, or `*` if they are equal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RbSnapshot {
+    /// Magic marker, `None` for an `RBCompact`, which has none
+    pub magic: Option<[u8; 3]>,
+    /// Version of the layout, as read when attaching
+    pub version: u8,
+    /// Size of the content, as read when attaching
+    pub size: usize,
+    /// Slot the producer writes next
+    pub producer: usize,
+    /// Slot the consumer reads next
+    pub consumer: usize,
+    /// Counter of the bytes dropped by the producer, wrapping around
+    pub dropped: u16,
+    /// Whether the host-attached flag is set
+    pub host_attached: bool,
+    /// The whole content, including the bytes already read and those never written
+    pub content: Vec<u8>,
+}
+
+impl RbSnapshot {
+    /// Returns the number of bytes waiting for the consumer, or `None` if an index is out
+    /// of the content
+    pub fn pending(&self) -> Option<usize> {
+        (self.producer < self.size && self.consumer < self.size)
+            .then(|| (self.producer + self.size - self.consumer) % self.size)
+    }
+}
+
+impl fmt::Display for RbSnapshot {
+    /// ```
+    /// use ramlink::consumer::RbSnapshot;
+    ///
+    /// let mut content = b"hello".to_vec();
+    /// content.resize(20, 0);
+    /// let snapshot = RbSnapshot {
+    ///     magic: Some([0x88, 0x88, 0x88]),
+    ///     version: 1,
+    ///     size: 20,
+    ///     producer: 18,
+    ///     consumer: 2,
+    ///     dropped: 0,
+    ///     host_attached: true,
+    ///     content,
+    /// };
+    /// let expected = concat!(
+    ///     "magic 88 88 88, version 1, size 20, consumer 2, producer 18, 16 pending, ",
+    ///     "dropped 0, host attached\n",
+    ///     "0000  68 65 6c 6c 6f 00 00 00 00 00 00 00 00 00 00 00  |hello...........|\n",
+    ///     "            C\n",
+    ///     "0010  00 00 00 00                                      |....|\n",
+    ///     "            P\n",
+    /// );
+    /// assert_eq!(snapshot.to_string(), expected);
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.magic {
+            Some([a, b, c]) => write!(f, "magic {a:02x} {b:02x} {c:02x}, ")?,
+            None => write!(f, "no magic, ")?,
+        }
+        write!(
+            f,
+            "version {}, size {}, consumer {}, producer {}, ",
+            self.version, self.size, self.consumer, self.producer
+        )?;
+        match self.pending() {
+            Some(pending) => write!(f, "{pending} pending, ")?,
+            None => write!(f, "corrupt indices, ")?,
+        }
+        let attached = if self.host_attached { "" } else { "not " };
+        writeln!(f, "dropped {}, host {attached}attached", self.dropped)?;
+
+        for (i, line) in self.content.chunks(LINE).enumerate() {
+            let start = i * LINE;
+            write!(f, "{start:04x} ")?;
+            for byte in line {
+                write!(f, " {byte:02x}")?;
+            }
+            write!(f, "{:width$}  |", "", width = 3 * (LINE - line.len()))?;
+            for &byte in line {
+                let printable = byte.is_ascii_graphic() || byte == b' ';
+                write!(f, "{}", if printable { byte as char } else { '.' })?;
+            }
+            writeln!(f, "|")?;
+
+            let marks = [(self.consumer, 'C'), (self.producer, 'P')];
+            let mut marks = marks
+                .iter()
+                .filter(|(slot, _)| (start..start + line.len()).contains(slot));
+            if let Some(&(first, mark)) = marks.next() {
+                let mut column = 0;
+                let both = self.consumer == self.producer;
+                for (slot, mark) in core::iter::once((first, if both { '*' } else { mark }))
+                    .chain(marks.filter(|_| !both).copied())
+                {
+                    // "0000 " then " xx" per byte: the mark goes under the first digit
+                    let at = 6 + 3 * (slot - start);
+                    write!(f, "{:width$}{mark}", "", width = at - column)?;
+                    column = at + 1;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Reads the whole ring buffer as it is in the RAM of the producer: header, indices,
+    /// and the full content, including the bytes already read. Nothing is written, and
+    /// nothing is checked, so this can be called while the ring buffer is broken, or from
+    /// a debugging tool while another consumer reads it. See
+    /// [`ProducerDevice::set_block_size`] for interfaces that cap their transfers.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// RING_BUF.send_bytes_blocking(b"hello");
+    /// assert_eq!(device.read_bytes_max(2).unwrap(), b"he");
+    ///
+    /// let snapshot = device.dump().unwrap();
+    /// assert_eq!(snapshot.magic, Some([0x88, 0x88, 0x88]));
+    /// assert_eq!((snapshot.consumer, snapshot.producer), (2, 5));
+    /// assert_eq!(snapshot.content, b"hello\x13\x13\x13");
+    /// assert_eq!(
+    ///     snapshot.to_string().lines().skip(1).collect::<Vec<_>>(),
+    ///     [
+    ///         "0000  68 65 6c 6c 6f 13 13 13                          |hello...|",
+    ///         "            C        P",
+    ///     ]
+    /// );
+    ///
+    /// // The bytes behind the consumer index are still there
+    /// assert_eq!(device.read_bytes().unwrap(), b"llo");
+    /// assert_eq!(device.dump().unwrap().content, b"hello\x13\x13\x13");
+    /// # }
+    /// ```
+    pub fn dump(&mut self) -> Result<RbSnapshot, ConsumerError<M::Error>> {
+        let layout = self.layout;
+        let mut bytes = vec![0; layout.content + self.rb_size + layout.after_content];
+        let (start, content, block) = (self.ram_start, self.content, self.block_size);
+        // The content of an `RBIndirect` is elsewhere, and read in place of its pointer
+        let contiguous = if layout.indirect {
+            layout.content
+        } else {
+            bytes.len()
+        };
+        let (header, content_bytes) = bytes.split_at_mut(contiguous);
+        read_block(start, header, block, |address, buf| {
+            self.read_memory(address, buf)
+        })?;
+        if !content_bytes.is_empty() {
+            read_block(content, content_bytes, block, |address, buf| {
+                self.read_memory(address, buf)
+            })?;
+        }
+
+        let width = layout.index_width;
+        let (content, size) = (layout.content, self.rb_size);
+        let at = |offset: Offset| offset.resolve(content, size);
+        Ok(RbSnapshot {
+            magic: layout
+                .header
+                .as_ref()
+                .map(|_| [bytes[0], bytes[1], bytes[2]]),
+            version: self.version,
+            size: self.rb_size,
+            producer: le_index(&bytes[layout.producer..layout.producer + width]),
+            consumer: le_index(&bytes[layout.consumer..layout.consumer + width]),
+            dropped: layout.dropped.map_or(0, |offset| {
+                u16::from_le_bytes([bytes[at(offset)], bytes[at(offset) + 1]])
+            }),
+            host_attached: layout
+                .host_attached
+                .is_some_and(|offset| bytes[at(offset)] != 0),
+            content: bytes[content..content + size].to_vec(),
+        })
+    }
+}
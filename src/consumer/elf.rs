@@ -0,0 +1,115 @@
+//! Lookup of the address of a ring buffer in the ELF file of the firmware.
+
+use core::fmt;
+use std::io;
+use std::path::Path;
+use std::string::{String, ToString};
+
+use object::{Architecture, Object, ObjectSymbol};
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+
+/// Offset of the data address space in AVR ELF files, which share a single address space
+/// between flash, RAM and EEPROM
+const AVR_DATA_OFFSET: u64 = 0x80_0000;
+/// Offset of the EEPROM address space in AVR ELF files, which ends the data one
+const AVR_EEPROM_OFFSET: u64 = 0x81_0000;
+
+/// Error of [`address_from_elf`]
+#[derive(Debug)]
+pub enum ElfError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not a valid ELF file
+    Parse(String),
+    /// The symbol is not defined in the file
+    SymbolNotFound(String),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::Io(e) => write!(f, "failed to read the ELF file: {e}"),
+            ElfError::Parse(e) => write!(f, "invalid ELF file: {e}"),
+            ElfError::SymbolNotFound(symbol) => write!(f, "symbol {symbol} not found"),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ElfError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the address in RAM of `symbol`, as defined in the ELF file at `path`, e.g. the
+/// name of a ring buffer declared with `ramlink_static!`. The address is the one to give to
+/// [`ProducerDevice::new`].
+///
+/// On AVR, the linker places RAM at `0x800000` in the ELF file, to tell it apart from flash:
+/// that offset is removed, as memory readers use data space addresses. Other architectures,
+/// such as Cortex-M, have a single address space, and their addresses are returned as is.
+/// ```
+/// use ramlink::consumer::address_from_elf;
+/// use std::path::Path;
+///
+/// let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+/// // ATmega328P, whose RAM starts at 0x100
+/// assert_eq!(address_from_elf(&fixtures.join("avr.elf"), "RING_BUF").unwrap(), 0x104);
+/// assert_eq!(
+///     address_from_elf(&fixtures.join("cortex-m.elf"), "RING_BUF").unwrap(),
+///     0x2000_0004
+/// );
+///
+/// let err = address_from_elf(&fixtures.join("avr.elf"), "NOPE").unwrap_err();
+/// assert_eq!(err.to_string(), "symbol NOPE not found");
+/// ```
+pub fn address_from_elf(path: &Path, symbol: &str) -> Result<usize, ElfError> {
+    let data = std::fs::read(path).map_err(ElfError::Io)?;
+    let file = object::File::parse(&*data).map_err(|e| ElfError::Parse(e.to_string()))?;
+    let address = file
+        .symbol_by_name(symbol)
+        .filter(|symbol| symbol.is_definition())
+        .ok_or_else(|| ElfError::SymbolNotFound(symbol.to_string()))?
+        .address();
+
+    let address = match file.architecture() {
+        Architecture::Avr if (AVR_DATA_OFFSET..AVR_EEPROM_OFFSET).contains(&address) => {
+            address - AVR_DATA_OFFSET
+        }
+        _ => address,
+    };
+    usize::try_from(address).map_err(|e| ElfError::Parse(e.to_string()))
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Same as [`ProducerDevice::new`], at the address of `symbol` in the ELF file at
+    /// `path`, see [`address_from_elf`]. Fails with [`ConsumerErrorKind::Elf`] if it can't
+    /// be found.
+    /// ```no_run
+    /// # use core::fmt::Error;
+    /// # use ramlink::consumer::{MemoryReader, ProducerDevice};
+    /// # struct Probe;
+    /// # impl MemoryReader for Probe {
+    /// #     type Error = Error;
+    /// #     fn read_memory(&mut self, _: usize, _: &mut [u8]) -> Result<(), Error> { Ok(()) }
+    /// #     fn write_memory(&mut self, _: usize, _: u8) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// use std::path::Path;
+    ///
+    /// let elf = Path::new("target/avr-none/release/firmware.elf");
+    /// let mut device = ProducerDevice::new_from_elf(Probe, elf, "RING_BUF").unwrap();
+    /// ```
+    pub fn new_from_elf(
+        memory_reader: M,
+        path: &Path,
+        symbol: &str,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        let address =
+            address_from_elf(path, symbol).map_err(|e| ConsumerError(ConsumerErrorKind::Elf(e)))?;
+        ProducerDevice::new(memory_reader, address)
+    }
+}
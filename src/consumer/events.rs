@@ -0,0 +1,181 @@
+//! Reading of the stream along with the bytes the producer dropped.
+
+use alloc::vec::Vec;
+
+use super::{ConsumerError, MemoryReader, ProducerDevice};
+use crate::layout;
+
+/// What [`ProducerDevice::read_events`] found in the ring buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadEvent {
+    /// Bytes sent by the producer, in order
+    Data(Vec<u8>),
+    /// Bytes the producer sent, but dropped as the ring buffer was full, or overwrote
+    /// before they were read
+    Gap {
+        /// How many bytes are missing from the stream at this point
+        bytes_lost: u32,
+    },
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Reads the bytes waiting, like [`ProducerDevice::read_bytes`], and tells where the
+    /// producer dropped some with a [`ReadEvent::Gap`], so that a decoder can discard the
+    /// frame it was in the middle of instead of parsing garbage. Returns no events if
+    /// nothing happened since the last call.
+    ///
+    /// The gaps come from the dropped counter of the ring buffer, which is read before the
+    /// bytes. The producer only drops bytes when the ring buffer is full, so they followed
+    /// the bytes waiting, and the gap comes after them. Bytes dropped while the previous
+    /// call was running are reported one call late. The counter is shared with
+    /// [`ProducerDevice::dropped_bytes`], so both should not be used together.
+    ///
+    /// A producer using `send_bytes_overwrite` moves the consumer index itself, so a host
+    /// polling too slowly may miss whole ring buffers of data while the indices look fine.
+    /// If the producer was built with the `wraps` feature, it counts how many times its
+    /// index went back to the start, and the position of the producer in the stream is
+    /// compared with that of the consumer: what is more than a ring buffer ahead was
+    /// overwritten, and is reported as a gap too, the larger of both estimates being kept.
+    /// The cached consumer index, if any, is then dropped. This needs a poll at least every
+    /// 65536 wraps, and only works if the device is read by `read_events` alone.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ReadEvent;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// assert_eq!(device.read_events().unwrap(), []);
+    ///
+    /// // The host was too slow, the third line does not fit
+    /// RING_BUF.send_bytes_lossy(b"t=21\nt=22\n");
+    /// RING_BUF.send_bytes_lossy(b"t=2345\n");
+    /// RING_BUF.send_bytes_lossy(b"t=24\n");
+    /// let events = device.read_events().unwrap();
+    /// assert_eq!(
+    ///     events,
+    ///     [
+    ///         ReadEvent::Data(b"t=21\nt=22\nt=234".to_vec()),
+    ///         ReadEvent::Gap { bytes_lost: 7 }
+    ///     ]
+    /// );
+    ///
+    /// // A line decoder throws away the line the gap cut
+    /// let mut line = Vec::new();
+    /// let mut lines = Vec::new();
+    /// for event in events {
+    ///     match event {
+    ///         ReadEvent::Data(bytes) => {
+    ///             for byte in bytes {
+    ///                 match byte {
+    ///                     b'\n' => lines.push(String::from_utf8(core::mem::take(&mut line)).unwrap()),
+    ///                     _ => line.push(byte),
+    ///                 }
+    ///             }
+    ///         }
+    ///         ReadEvent::Gap { .. } => line.clear(),
+    ///     }
+    /// }
+    /// assert_eq!(lines, ["t=21", "t=22"]);
+    /// assert!(line.is_empty());
+    /// # }
+    /// ```
+    ///
+    /// A producer lapping the consumer more than 65536 bytes ahead, which the dropped
+    /// counter alone does not notice:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "wraps"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ReadEvent;
+    /// use ramlink::producer::RB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// // The consumer accesses the ring buffer meanwhile, so it is only borrowed for a call
+    /// let rb: *mut RB<8> = Box::into_raw(Box::new(RB::new()));
+    /// let send = |data: &[u8]| unsafe { (*rb).send_bytes_overwrite(data) };
+    /// # let host = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RB<8>>()) };
+    /// let mut device = ProducerDevice::new(host, rb as usize).unwrap();
+    /// send(b"abc");
+    /// assert_eq!(device.read_events().unwrap(), [ReadEvent::Data(b"abc".to_vec())]);
+    ///
+    /// // 20 bytes overwrite 13 of them
+    /// let data: Vec<u8> = (0..20).collect();
+    /// send(&data);
+    /// assert_eq!(
+    ///     device.read_events().unwrap(),
+    ///     [ReadEvent::Data(data[13..].to_vec()), ReadEvent::Gap { bytes_lost: 13 }]
+    /// );
+    ///
+    /// // The dropped counter wraps around, but not the position of the producer
+    /// let data: Vec<u8> = (0..65536 + 20).map(|i| i as u8).collect();
+    /// send(&data);
+    /// assert_eq!(unsafe { (*rb).dropped() }, 13 + 13);
+    /// assert_eq!(
+    ///     device.read_events().unwrap(),
+    ///     [
+    ///         ReadEvent::Data(data[65536 + 13..].to_vec()),
+    ///         ReadEvent::Gap { bytes_lost: 65536 + 13 }
+    ///     ]
+    /// );
+    /// # drop(device);
+    /// # drop(unsafe { Box::from_raw(rb) });
+    /// # }
+    /// ```
+    pub fn read_events(&mut self) -> Result<Vec<ReadEvent>, ConsumerError<M::Error>> {
+        let dropped = self.dropped_bytes()?;
+        let lapped = self.lapped_bytes()?;
+        let bytes = self.read_bytes()?;
+        if let Some(position) = &mut self.read_position {
+            let modulus = (u16::MAX as u64 + 1) * self.rb_size as u64;
+            *position = (*position + lapped + bytes.len() as u64) % modulus;
+        }
+        let mut events = Vec::new();
+        if !bytes.is_empty() {
+            events.push(ReadEvent::Data(bytes));
+        }
+        let bytes_lost = u64::from(dropped).max(lapped);
+        if bytes_lost > 0 {
+            events.push(ReadEvent::Gap {
+                bytes_lost: bytes_lost as u32,
+            });
+        }
+        Ok(events)
+    }
+
+    /// Returns how many bytes the producer overwrote since the last call, from its wrap
+    /// count, or 0 if it does not have one. The first call only takes the position of the
+    /// consumer as reference.
+    fn lapped_bytes(&mut self) -> Result<u64, ConsumerError<M::Error>> {
+        if self.features & layout::FEATURE_WRAPS == 0 {
+            return Ok(0);
+        }
+        let modulus = (u16::MAX as u64 + 1) * self.rb_size as u64;
+        // The producer counts a wrap before it publishes the index, so a wrap count read
+        // between two equal producer indices goes with them
+        let (written, pending) = loop {
+            let (prod_v, remote_cons) = self.read_indices()?;
+            let wraps = self.wraps()?;
+            if self.read_indices()?.0 == prod_v {
+                let pending = self.pending(prod_v, self.local_consumer(remote_cons));
+                break (
+                    u64::from(wraps) * self.rb_size as u64 + prod_v as u64,
+                    pending,
+                );
+            }
+        };
+        let Some(read) = self.read_position else {
+            self.read_position = Some((written + modulus - pending as u64) % modulus);
+            return Ok(0);
+        };
+        let unread = (written + modulus - read) % modulus;
+        let lapped = unread.saturating_sub(self.capacity() as u64);
+        if lapped > 0 {
+            self.refresh();
+        }
+        Ok(lapped)
+    }
+}
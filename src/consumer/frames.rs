@@ -0,0 +1,743 @@
+//! Reassembly of length-prefixed and COBS frames.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+use crate::layout;
+
+/// Reads frames made of a length byte followed by that many bytes of payload, obtained with
+/// [`ProducerDevice::frames`]. Bytes read from the ring buffer are kept until their frame
+/// is complete, so frames may arrive over any number of reads.
+///
+/// As an [`Iterator`], it yields the frames that are complete, and ends when there are none
+/// left for now.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::ConsumerErrorKind;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut frames = device.frames();
+///
+/// RING_BUF.send_bytes_blocking(&[3, b'a', b'b']);
+/// assert_eq!(frames.try_next_frame().unwrap(), None);
+/// // Wraps around the end of the ring buffer
+/// RING_BUF.send_bytes_blocking(&[b'c', 2, b'x', b'y']);
+/// assert_eq!(frames.next().unwrap().unwrap(), b"abc");
+/// assert_eq!(frames.next().unwrap().unwrap(), b"xy");
+/// assert!(frames.next().is_none());
+///
+/// // Can't be sent through a ring buffer of 7 bytes
+/// RING_BUF.send_bytes_blocking(&[9]);
+/// let err = frames.try_next_frame().unwrap_err();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::FrameTooLong(9)));
+/// # }
+/// ```
+pub struct FrameReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet, starting with a length byte
+    buffer: Vec<u8>,
+    /// Set by [`FrameReader::with_crc`]
+    crc: bool,
+    /// Set by [`FrameReader::with_varint_lengths`]
+    varint: bool,
+    /// Set by [`FrameReader::with_compression`]
+    #[cfg(feature = "compression")]
+    compressed: bool,
+    /// Set after a CRC mismatch, until a frame is found whose CRC matches
+    resyncing: bool,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of length-prefixed frames, see [`FrameReader`]
+    pub fn frames(&mut self) -> FrameReader<'_, M> {
+        FrameReader {
+            device: self,
+            buffer: Vec::new(),
+            crc: false,
+            varint: false,
+            #[cfg(feature = "compression")]
+            compressed: false,
+            resyncing: false,
+        }
+    }
+}
+
+impl<M: MemoryReader> FrameReader<'_, M> {
+    /// Reads frames sent with `RB::send_frame_crc` instead, followed by a CRC-8 of their
+    /// length and payload. A frame whose CRC does not match fails with
+    /// [`ConsumerErrorKind::BadCrc`], e.g. if the debug link garbled a read. Its length
+    /// byte is discarded, and the reader then looks for the next frame one byte at a time:
+    /// until a frame has the right CRC, the candidates that don't are discarded silently.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<32> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let mut frames = device.frames().with_crc();
+    ///
+    /// RING_BUF.send_frame_crc(b"ok");
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"ok");
+    ///
+    /// // A frame whose last byte flipped, then a good one
+    /// RING_BUF.send_bytes_blocking(&[3, b'a', b'b', b'd', 0x00]);
+    /// RING_BUF.send_frame_crc(b"next");
+    /// let err = frames.next().unwrap().unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::BadCrc { got: 0x00, .. }));
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"next");
+    /// assert!(frames.next().is_none());
+    /// # }
+    /// ```
+    pub fn with_crc(mut self) -> Self {
+        self.crc = true;
+        self
+    }
+
+    /// Reads frames sent with `RB16::send_frame_varint` instead, whose length is a LEB128
+    /// varint, so that they can be larger than 255 bytes. As with a length byte, a length
+    /// larger than the capacity of the ring buffer fails with
+    /// [`ConsumerErrorKind::FrameTooLong`], without waiting for the rest of the varint if
+    /// its first bytes are enough to tell. Combines with [`FrameReader::with_crc`], the CRC
+    /// then covering the whole varint.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::RB16;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// // The consumer accesses the ring buffer meanwhile, so it is only borrowed for a call
+    /// let rb: *mut RB16<1000> = Box::into_raw(Box::new(RB16::new()));
+    /// # let host = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RB16<1000>>()) };
+    /// let mut device = ProducerDevice::new(host, rb as usize).unwrap();
+    /// let mut frames = device.frames().with_varint_lengths();
+    ///
+    /// let payload: Vec<u8> = (0..700).map(|i| i as u8).collect();
+    /// unsafe { (*rb).send_frame_varint(&payload) };
+    /// unsafe { (*rb).send_frame_varint(b"") };
+    /// assert_eq!(frames.next().unwrap().unwrap(), payload);
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"");
+    ///
+    /// // Two bytes with the high bit set are already more than 999 bytes
+    /// unsafe { (*rb).send_bytes_blocking(&[0x80, 0x80]) };
+    /// let err = frames.next().unwrap().unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::FrameTooLong(16384)));
+    /// # }
+    /// ```
+    pub fn with_varint_lengths(mut self) -> Self {
+        self.varint = true;
+        self
+    }
+
+    /// Reads frames sent with `RB::send_frame_compressed` instead, and returns them
+    /// decompressed. A frame that can't be decompressed, e.g. one that refers to bytes
+    /// before its start, fails with [`ConsumerErrorKind::InvalidFrame`]. It is discarded
+    /// as a whole, and as each frame is compressed on its own, the next ones are not
+    /// affected.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let mut frames = device.frames().with_compression();
+    ///
+    /// let trace = b"pwm=50% pwm=50% pwm=51% pwm=50% pwm=50% pwm=49% pwm=50% pwm=50%";
+    /// RING_BUF.send_frame_compressed::<6>(trace);
+    /// RING_BUF.send_frame_compressed::<12>(b"");
+    /// RING_BUF.send_frame_compressed::<4>(b"xyz");
+    /// assert!(RING_BUF.len() < trace.len());
+    /// assert_eq!(frames.next().unwrap().unwrap(), trace);
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"");
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"xyz");
+    ///
+    /// // A back-reference 2 bytes before the start of the frame, then a good frame
+    /// RING_BUF.send_bytes_blocking(&[4, 8, 0b1, 0x01, 0x00]);
+    /// RING_BUF.send_frame_compressed::<8>(b"aaaaaaaa");
+    /// let err = frames.next().unwrap().unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::InvalidFrame));
+    /// assert_eq!(frames.next().unwrap().unwrap(), b"aaaaaaaa");
+    /// # }
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    /// Returns the next frame, or `None` if it is not complete yet. Fails with
+    /// [`ConsumerErrorKind::FrameTooLong`] if the length byte is larger than the capacity of
+    /// the ring buffer, which means that the reader is out of sync with the producer. The
+    /// length byte is then discarded, so that the next call starts from the following byte.
+    pub fn try_next_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        if let Some(frame) = self.pop_frame()? {
+            return Ok(Some(frame));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        self.pop_frame()
+    }
+
+    /// Returns the next frame, polling the ring buffer every `poll` until it is complete.
+    /// Fails with [`ConsumerErrorKind::Timeout`] if that takes more than `timeout`, `got`
+    /// being the number of bytes buffered, length byte included.
+    #[cfg(feature = "std")]
+    pub fn next_frame(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        loop {
+            if let Some(frame) = self.try_next_frame()? {
+                return Ok(frame);
+            }
+            if start.elapsed() >= timeout {
+                let got = self.buffer.len();
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+
+    /// Returns the length of the first frame and the number of bytes of its prefix, or
+    /// `None` if the prefix is not complete yet. Fails with the length, or with what it is
+    /// at least, if it is larger than the capacity of the ring buffer.
+    fn prefix(&self) -> Result<Option<(usize, usize)>, usize> {
+        let capacity = self.device.capacity();
+        if !self.varint {
+            return match self.buffer.first() {
+                Some(&len) if len as usize > capacity => Err(len as usize),
+                Some(&len) => Ok(Some((len as usize, 1))),
+                None => Ok(None),
+            };
+        }
+        let mut len = 0;
+        for (i, &byte) in self.buffer.iter().enumerate() {
+            len |= ((byte & 0x7F) as usize) << (7 * i);
+            if byte & 0x80 == 0 {
+                return if len > capacity {
+                    Err(len)
+                } else {
+                    Ok(Some((len, i + 1)))
+                };
+            }
+            // The next bytes only add higher bits
+            let at_least = len.max(1 << (7 * (i + 1)));
+            if at_least > capacity {
+                return Err(at_least);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Removes the first frame from the buffer if it is complete, and its CRC matches in
+    /// CRC mode
+    fn pop_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        loop {
+            let (len, prefix) = match self.prefix() {
+                Ok(Some(prefix)) => prefix,
+                Ok(None) => return Ok(None),
+                Err(len) => {
+                    self.buffer.remove(0);
+                    if self.resyncing {
+                        continue;
+                    }
+                    return Err(ConsumerError(ConsumerErrorKind::FrameTooLong(len)));
+                }
+            };
+            // Index of the CRC byte, if any, right after the payload
+            let end = prefix + len;
+            if self.buffer.len() < end + self.crc as usize {
+                return Ok(None);
+            }
+            if self.crc {
+                let expected = self.buffer[..end]
+                    .iter()
+                    .fold(0, |crc, &b| layout::crc8_update(crc, b));
+                let got = self.buffer[end];
+                if got != expected {
+                    self.buffer.remove(0);
+                    if core::mem::replace(&mut self.resyncing, true) {
+                        continue;
+                    }
+                    return Err(ConsumerError(ConsumerErrorKind::BadCrc { expected, got }));
+                }
+                self.resyncing = false;
+            }
+            let frame = self.buffer[prefix..end].to_vec();
+            self.buffer.drain(..end + self.crc as usize);
+            #[cfg(feature = "compression")]
+            if self.compressed {
+                return match decompress(&frame) {
+                    Some(frame) => Ok(Some(frame)),
+                    None => Err(ConsumerError(ConsumerErrorKind::InvalidFrame)),
+                };
+            }
+            return Ok(Some(frame));
+        }
+    }
+}
+
+impl<M: MemoryReader> Iterator for FrameReader<'_, M> {
+    type Item = Result<Vec<u8>, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_frame().transpose()
+    }
+}
+
+/// Decompresses a frame sent with `RB::send_frame_compressed`: a window byte, then groups
+/// of a flag byte and up to 8 literal bytes or back-references. Returns `None` if the
+/// frame is not valid.
+#[cfg(feature = "compression")]
+fn decompress(frame: &[u8]) -> Option<Vec<u8>> {
+    let (&window_bits, mut rest) = frame.split_first()?;
+    if window_bits == layout::LZSS_STORED {
+        return Some(rest.to_vec());
+    }
+    if !layout::LZSS_WINDOW_BITS.contains(&window_bits.into()) {
+        return None;
+    }
+    let length_bits = 16 - window_bits;
+    let mut payload = Vec::new();
+    while let Some((&flags, items)) = rest.split_first() {
+        rest = items;
+        for bit in 0..8 {
+            if rest.is_empty() {
+                break;
+            }
+            if flags & 1 << bit == 0 {
+                payload.push(rest[0]);
+                rest = &rest[1..];
+                continue;
+            }
+            let code = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+            rest = &rest[2..];
+            let start = payload.len().checked_sub((code >> length_bits) + 1)?;
+            let len = (code & ((1 << length_bits) - 1)) + layout::LZSS_MIN_MATCH;
+            // The match may run past its start, into the bytes it copies
+            for i in start..start + len {
+                payload.push(payload[i]);
+            }
+        }
+    }
+    Some(payload)
+}
+
+/// A frame sent with `RB::send_frame_timestamped`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampedFrame {
+    /// Value of the clock of the target when the frame was sent. The clock wraps around
+    /// at `u32::MAX`, which the reader counts, so this keeps growing over long sessions
+    pub timestamp: u64,
+    /// The frame, without its timestamp
+    pub payload: Vec<u8>,
+}
+
+impl TimestampedFrame {
+    /// Returns the timestamp in seconds, for a clock of `clock_hz`, e.g. the core clock
+    /// for the cycle counter of a Cortex-M
+    pub fn seconds(&self, clock_hz: u32) -> f64 {
+        self.timestamp as f64 / clock_hz as f64
+    }
+}
+
+/// Reads frames sent with `RB::send_frame_timestamped`, obtained with
+/// [`ProducerDevice::timestamped_frames`]: length-prefixed frames like those of
+/// [`FrameReader`], whose first 4 bytes are the timestamp.
+///
+/// The 32-bit clock of the target wraps around, in about 27 seconds for the cycle counter
+/// of a Cortex-M at 160 MHz. A timestamp lower than the previous one is counted as a wrap,
+/// so frames must be sent at least once per period of the clock for the timestamps to be
+/// right.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::{AtomicRB, TimestampSource};
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// static CYCLES: AtomicU32 = AtomicU32::new(u32::MAX - 99);
+///
+/// struct Cycles;
+///
+/// impl TimestampSource for Cycles {
+///     fn now() -> u32 {
+///         CYCLES.fetch_add(100, Ordering::Relaxed)
+///     }
+/// }
+///
+/// static RING_BUF: AtomicRB<32> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut frames = device.timestamped_frames();
+///
+/// RING_BUF.send_frame_timestamped::<Cycles>(b"a");
+/// RING_BUF.send_frame_timestamped::<Cycles>(b"b");
+/// let a = frames.next().unwrap().unwrap();
+/// assert_eq!((a.timestamp, &a.payload[..]), (u32::MAX as u64 - 99, &b"a"[..]));
+/// // The clock wrapped around between both frames
+/// let b = frames.next().unwrap().unwrap();
+/// assert_eq!(b.timestamp, u32::MAX as u64 + 1);
+/// // 100 cycles of a 1 kHz clock
+/// assert!((b.seconds(1000) - a.seconds(1000) - 0.1).abs() < 1e-6);
+/// # }
+/// ```
+pub struct TimestampedFrameReader<'d, M: MemoryReader> {
+    frames: FrameReader<'d, M>,
+    /// Timestamp of the previous frame, wraps included
+    last: Option<u64>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of timestamped frames, see [`TimestampedFrameReader`]
+    pub fn timestamped_frames(&mut self) -> TimestampedFrameReader<'_, M> {
+        TimestampedFrameReader {
+            frames: self.frames(),
+            last: None,
+        }
+    }
+}
+
+impl<M: MemoryReader> TimestampedFrameReader<'_, M> {
+    /// Returns the next frame, or `None` if it is not complete yet. Fails like
+    /// [`FrameReader::try_next_frame`], or with [`ConsumerErrorKind::InvalidFrame`] if the
+    /// frame is too short to hold a timestamp.
+    pub fn try_next_frame(&mut self) -> Result<Option<TimestampedFrame>, ConsumerError<M::Error>> {
+        let Some(mut payload) = self.frames.try_next_frame()? else {
+            return Ok(None);
+        };
+        let Some(&[b0, b1, b2, b3]) = payload.get(..4) else {
+            return Err(ConsumerError(ConsumerErrorKind::InvalidFrame));
+        };
+        let low = u32::from_le_bytes([b0, b1, b2, b3]) as u64;
+        let timestamp = match self.last {
+            None => low,
+            Some(last) => {
+                let timestamp = last & !0xFFFF_FFFF | low;
+                if timestamp < last {
+                    timestamp + (1 << 32)
+                } else {
+                    timestamp
+                }
+            }
+        };
+        self.last = Some(timestamp);
+        payload.drain(..4);
+        Ok(Some(TimestampedFrame { timestamp, payload }))
+    }
+}
+
+impl<M: MemoryReader> Iterator for TimestampedFrameReader<'_, M> {
+    type Item = Result<TimestampedFrame, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_frame().transpose()
+    }
+}
+
+/// Appends the bytes waiting in the ring buffer to `buffer`
+pub(super) fn read_more<M: MemoryReader>(
+    device: &mut ProducerDevice<M>,
+    buffer: &mut Vec<u8>,
+) -> Result<(), ConsumerError<M::Error>> {
+    let buffered = buffer.len();
+    buffer.resize(buffered + device.capacity(), 0);
+    let read = device.read_into(&mut buffer[buffered..]);
+    buffer.truncate(buffered + read.as_ref().map_or(0, |read| *read));
+    read.map(|_| ())
+}
+
+/// Reads frames sent with `RB::send_frame_cobs`, obtained with
+/// [`ProducerDevice::cobs_frames`]. Each frame ends with a zero byte, so the reader can
+/// start in the middle of one: the bytes before the first zero are discarded. A producer
+/// that wants its first frame read can send a zero byte before it.
+///
+/// As an [`Iterator`], it yields the frames that are complete, and ends when there are none
+/// left for now.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::AtomicRB;
+/// use std::time::Duration;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// let payloads = [
+///     vec![],
+///     vec![0],
+///     vec![1, 0, 0, 2, 0],
+///     (0..300).map(|i| i as u8).collect(), // zeros and runs of 255 non-zero bytes
+///     vec![0x42; 254],
+/// ];
+///
+/// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let sent = payloads.clone();
+/// let producer = std::thread::spawn(move || {
+///     RING_BUF.send_bytes_blocking(b"garbage\0");
+///     for payload in &sent {
+///         RING_BUF.send_frame_cobs(payload);
+///     }
+/// });
+///
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut frames = device.cobs_frames();
+/// for payload in &payloads {
+///     let frame = frames
+///         .next_frame(Duration::from_millis(1), Duration::from_secs(5))
+///         .unwrap();
+///     assert_eq!(&frame, payload);
+/// }
+/// producer.join().unwrap();
+/// # }
+/// ```
+pub struct CobsFrameReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet
+    buffer: Vec<u8>,
+    /// Set once a zero byte was read, before which bytes are discarded
+    synced: bool,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of COBS frames, see [`CobsFrameReader`]
+    pub fn cobs_frames(&mut self) -> CobsFrameReader<'_, M> {
+        CobsFrameReader {
+            device: self,
+            buffer: Vec::new(),
+            synced: false,
+        }
+    }
+}
+
+impl<M: MemoryReader> CobsFrameReader<'_, M> {
+    /// Returns the next frame, or `None` if it is not complete yet. Fails with
+    /// [`ConsumerErrorKind::InvalidFrame`] if a frame can't be decoded, e.g. because some of
+    /// its bytes were dropped by the producer. The frame is then discarded.
+    pub fn try_next_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        if let Some(frame) = self.pop_frame()? {
+            return Ok(Some(frame));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        self.pop_frame()
+    }
+
+    /// Returns the next frame, polling the ring buffer every `poll` until it is complete.
+    /// See [`FrameReader::next_frame`].
+    #[cfg(feature = "std")]
+    pub fn next_frame(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        loop {
+            if let Some(frame) = self.try_next_frame()? {
+                return Ok(frame);
+            }
+            if start.elapsed() >= timeout {
+                let got = self.buffer.len();
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+
+    /// Removes the first frame from the buffer if it is complete, and decodes it
+    fn pop_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        while let Some(end) = self.buffer.iter().position(|&b| b == 0) {
+            let frame = cobs_decode(&self.buffer[..end]);
+            let synced = core::mem::replace(&mut self.synced, true);
+            self.buffer.drain(..=end);
+            if synced {
+                return frame
+                    .map(Some)
+                    .ok_or(ConsumerError(ConsumerErrorKind::InvalidFrame));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<M: MemoryReader> Iterator for CobsFrameReader<'_, M> {
+    type Item = Result<Vec<u8>, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_frame().transpose()
+    }
+}
+
+/// Reads frames sent with `RB::send_frame_slip`, obtained with
+/// [`ProducerDevice::slip_frames`]. Frames end with an END byte, so the reader can start in
+/// the middle of one: the bytes before the first END are discarded. As the producer sends
+/// an END before each frame too, none is lost this way, and the empty frames between
+/// back-to-back END bytes are skipped.
+///
+/// As an [`Iterator`], it yields the frames that are complete, and ends when there are none
+/// left for now.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::AtomicRB;
+/// use std::time::Duration;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// let payloads = [
+///     vec![0xC0],
+///     vec![0xDB],
+///     vec![0xDB, 0xDC, 0xC0, 0xDD, 0xC0, 0xC0],
+///     (0..300).map(|i| i as u8).collect(), // several times around the ring buffer
+///     vec![0x42; 20],
+/// ];
+///
+/// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let sent = payloads.clone();
+/// let producer = std::thread::spawn(move || {
+///     RING_BUF.send_bytes_blocking(&[0xDB, 0x01, 0xC0, 0xC0]);
+///     for payload in &sent {
+///         RING_BUF.send_frame_slip(payload);
+///     }
+/// });
+///
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut frames = device.slip_frames();
+/// for payload in &payloads {
+///     let frame = frames
+///         .next_frame(Duration::from_millis(1), Duration::from_secs(5))
+///         .unwrap();
+///     assert_eq!(&frame, payload);
+/// }
+/// producer.join().unwrap();
+/// # }
+/// ```
+pub struct SlipFrameReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet
+    buffer: Vec<u8>,
+    /// Set once an END byte was read, before which bytes are discarded
+    synced: bool,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of SLIP frames, see [`SlipFrameReader`]
+    pub fn slip_frames(&mut self) -> SlipFrameReader<'_, M> {
+        SlipFrameReader {
+            device: self,
+            buffer: Vec::new(),
+            synced: false,
+        }
+    }
+}
+
+impl<M: MemoryReader> SlipFrameReader<'_, M> {
+    /// Returns the next frame, or `None` if it is not complete yet. Fails with
+    /// [`ConsumerErrorKind::InvalidFrame`] if an ESC byte is followed by another byte than
+    /// those it escapes, e.g. because some bytes were dropped by the producer. The frame is
+    /// then discarded.
+    pub fn try_next_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        if let Some(frame) = self.pop_frame()? {
+            return Ok(Some(frame));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        self.pop_frame()
+    }
+
+    /// Returns the next frame, polling the ring buffer every `poll` until it is complete.
+    /// See [`FrameReader::next_frame`].
+    #[cfg(feature = "std")]
+    pub fn next_frame(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        loop {
+            if let Some(frame) = self.try_next_frame()? {
+                return Ok(frame);
+            }
+            if start.elapsed() >= timeout {
+                let got = self.buffer.len();
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+
+    /// Removes the first frame that is not empty from the buffer if it is complete, and
+    /// unescapes it
+    fn pop_frame(&mut self) -> Result<Option<Vec<u8>>, ConsumerError<M::Error>> {
+        while let Some(end) = self.buffer.iter().position(|&b| b == layout::SLIP_END) {
+            let frame = slip_decode(&self.buffer[..end]);
+            let synced = core::mem::replace(&mut self.synced, true);
+            self.buffer.drain(..=end);
+            if synced && end > 0 {
+                return frame
+                    .map(Some)
+                    .ok_or(ConsumerError(ConsumerErrorKind::InvalidFrame));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<M: MemoryReader> Iterator for SlipFrameReader<'_, M> {
+    type Item = Result<Vec<u8>, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_frame().transpose()
+    }
+}
+
+/// Unescapes a SLIP frame, without its END byte
+fn slip_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut bytes = encoded.iter();
+    while let Some(&b) = bytes.next() {
+        decoded.push(match b {
+            layout::SLIP_ESC => match *bytes.next()? {
+                layout::SLIP_ESC_END => layout::SLIP_END,
+                layout::SLIP_ESC_ESC => layout::SLIP_ESC,
+                _ => return None,
+            },
+            b => b,
+        });
+    }
+    Some(decoded)
+}
+
+/// Decodes a COBS frame, without its trailing zero
+fn cobs_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut rest = encoded;
+    while let Some((&code, block)) = rest.split_first() {
+        let len = code as usize - 1;
+        decoded.extend_from_slice(block.get(..len)?);
+        rest = &block[len..];
+        if code != 0xFF && !rest.is_empty() {
+            decoded.push(0);
+        }
+    }
+    Some(decoded)
+}
@@ -0,0 +1,386 @@
+//! [`std::io::Read`] adapters of [`ProducerDevice`].
+
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+use std::string::ToString;
+use std::time::{Duration, Instant};
+use std::vec;
+use std::vec::Vec;
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, PollPolicy, ProducerDevice};
+
+impl<E: Debug> From<ConsumerError<E>> for io::Error {
+    fn from(err: ConsumerError<E>) -> Self {
+        let kind = match err.kind() {
+            ConsumerErrorKind::MagicMarkerNotFound
+            | ConsumerErrorKind::WrongId(_)
+            | ConsumerErrorKind::CorruptIndex { .. }
+            | ConsumerErrorKind::MultipleRingBuffers(_)
+            | ConsumerErrorKind::SizeMismatch { .. }
+            | ConsumerErrorKind::FeaturesMismatch { .. }
+            | ConsumerErrorKind::Desynchronized
+            | ConsumerErrorKind::RingBufferTooSmall
+            | ConsumerErrorKind::FrameTooLong(_)
+            | ConsumerErrorKind::InvalidFrame
+            | ConsumerErrorKind::BadCrc { .. }
+            | ConsumerErrorKind::UnknownChannel(_) => io::ErrorKind::InvalidData,
+            #[cfg(feature = "postcard")]
+            ConsumerErrorKind::Deserialize(_) => io::ErrorKind::InvalidData,
+            ConsumerErrorKind::UnsupportedVersion(_)
+            | ConsumerErrorKind::UnsupportedPointerWidth(_)
+            | ConsumerErrorKind::FeatureNotEnabled => io::ErrorKind::Unsupported,
+            ConsumerErrorKind::ReadMemoryError(_) | ConsumerErrorKind::WriteMemoryError(_) => {
+                io::ErrorKind::Other
+            }
+            ConsumerErrorKind::ChannelNotFound => io::ErrorKind::NotFound,
+            ConsumerErrorKind::Timeout { .. } => io::ErrorKind::TimedOut,
+            ConsumerErrorKind::Sink { error, .. } => error.kind(),
+            #[cfg(feature = "elf")]
+            ConsumerErrorKind::Elf(_) => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err.to_string())
+    }
+}
+
+/// Reads the bytes waiting in the ring buffer, like [`ProducerDevice::read_into`]. `read`
+/// returns `Ok(0)` whenever the ring buffer is empty, which most readers take for the end of
+/// the stream: see [`ProducerDevice::blocking_reader`] to wait for bytes instead.
+impl<M: MemoryReader> io::Read for ProducerDevice<M> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.read_into(buf)?)
+    }
+}
+
+/// A [`ProducerDevice`] whose [`io::Read::read`] waits for at least one byte, obtained with
+/// [`ProducerDevice::blocking_reader`]
+pub struct BlockingReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    poll: Duration,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader that polls the ring buffer every `poll` until at least one byte is
+    /// waiting, so that the stream never ends.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::io::{BufRead, BufReader};
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// let producer = std::thread::spawn(|| {
+    ///     RING_BUF.send_bytes_blocking(b"first line\n");
+    ///     RING_BUF.send_bytes_blocking(b"second line\n");
+    /// });
+    ///
+    /// let mut lines = BufReader::new(device.blocking_reader(Duration::from_millis(1))).lines();
+    /// assert_eq!(lines.next().unwrap().unwrap(), "first line");
+    /// assert_eq!(lines.next().unwrap().unwrap(), "second line");
+    /// producer.join().unwrap();
+    /// # }
+    /// ```
+    pub fn blocking_reader(&mut self, poll: Duration) -> BlockingReader<'_, M> {
+        BlockingReader { device: self, poll }
+    }
+}
+
+impl<M: MemoryReader> io::Read for BlockingReader<'_, M> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let read = self.device.read_into(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            std::thread::sleep(self.poll);
+        }
+    }
+}
+
+/// What [`ProducerDevice::pipe_to_with`] does when a sink fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkErrorPolicy {
+    /// Returns the error, once the other sinks are flushed
+    Stop,
+    /// Stops writing to the failing sink, and goes on with the others
+    Skip,
+}
+
+/// Options of [`ProducerDevice::pipe_to_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipeOptions {
+    /// What to do when a sink fails. [`SinkErrorPolicy::Stop`] by default
+    pub on_error: SinkErrorPolicy,
+    /// Longest time bytes stay in the buffers of the sinks while more keep arriving. They
+    /// are also flushed as soon as the ring buffer is found empty. 100 ms by default
+    pub flush_interval: Duration,
+    /// Whether the rest of a chunk that a sink only partly wrote is written again, as
+    /// `write_all` does. Otherwise, a partial write fails the sink with
+    /// [`io::ErrorKind::WriteZero`]. `true` by default
+    pub retry_partial: bool,
+}
+
+impl Default for PipeOptions {
+    fn default() -> Self {
+        PipeOptions {
+            on_error: SinkErrorPolicy::Stop,
+            flush_interval: Duration::from_millis(100),
+            retry_partial: true,
+        }
+    }
+}
+
+/// Throughput of a [`ProducerDevice::pipe_to`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipeStats {
+    /// Number of bytes read, and written to every sink that did not fail
+    pub bytes: u64,
+    /// Number of reads that returned bytes
+    pub chunks: u64,
+    /// How long the pipe ran
+    pub elapsed: Duration,
+    /// Bytes per second over the whole run
+    pub bytes_per_second: f64,
+    /// Number of sinks skipped after failing, see [`SinkErrorPolicy::Skip`]
+    pub failed_sinks: usize,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Reads the ring buffer until `stop` is set, writing every chunk to all of `sinks`,
+    /// e.g. to the terminal and to a capture file to decode later. Sleeps between reads
+    /// that find it empty as [`ProducerDevice::run`] does, and returns the throughput once
+    /// `stop` is set, checked before each read.
+    ///
+    /// The sinks are flushed when the ring buffer is found empty, every 100 ms while bytes
+    /// keep arriving, and before returning. A sink that fails ends the pipe with
+    /// [`ConsumerErrorKind::Sink`]: see [`ProducerDevice::pipe_to_with`] to skip it instead.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::io::Write;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// static STOP: AtomicBool = AtomicBool::new(false);
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// let producer = std::thread::spawn(|| {
+    ///     RING_BUF.send_bytes_blocking(b"boot\nsensor ok\n");
+    ///     RING_BUF.send_bytes_blocking(b"idle\n");
+    ///     while !RING_BUF.is_empty() {}
+    ///     STOP.store(true, Ordering::Relaxed);
+    /// });
+    ///
+    /// let (mut terminal, mut capture) = (Vec::new(), Vec::new());
+    /// let sinks: &mut [&mut dyn Write] = &mut [&mut terminal, &mut capture];
+    /// let stats = device.pipe_to(sinks, Duration::from_millis(1), &STOP).unwrap();
+    /// producer.join().unwrap();
+    /// assert_eq!(stats.bytes, 20);
+    /// assert_eq!(terminal, b"boot\nsensor ok\nidle\n");
+    /// assert_eq!(capture, terminal);
+    /// # }
+    /// ```
+    pub fn pipe_to(
+        &mut self,
+        sinks: &mut [&mut dyn io::Write],
+        poll: impl Into<PollPolicy>,
+        stop: &AtomicBool,
+    ) -> Result<PipeStats, ConsumerError<M::Error>> {
+        self.pipe_to_with(sinks, poll, stop, PipeOptions::default())
+    }
+
+    /// Same as [`ProducerDevice::pipe_to`], with other [`PipeOptions`]. With
+    /// [`SinkErrorPolicy::Skip`], a sink that fails is no longer written to, while the
+    /// others still get every byte:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::{PipeOptions, SinkErrorPolicy};
+    /// use ramlink::producer::AtomicRB;
+    /// use std::io::{self, Write};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// /// A capture file on a full disk
+    /// struct Full;
+    ///
+    /// impl Write for Full {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+    ///         Err(io::ErrorKind::StorageFull.into())
+    ///     }
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let poll = Duration::from_millis(1);
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hello");
+    /// let mut terminal = Vec::new();
+    /// let sinks: &mut [&mut dyn Write] = &mut [&mut Full, &mut terminal];
+    /// let err = device.pipe_to(sinks, poll, &AtomicBool::new(false)).unwrap_err();
+    /// assert_eq!(io::Error::from(err).kind(), io::ErrorKind::StorageFull);
+    /// assert_eq!(terminal, b"");
+    ///
+    /// let stop = AtomicBool::new(false);
+    /// let sinks: &mut [&mut dyn Write] = &mut [&mut Full, &mut terminal];
+    /// let options = PipeOptions {
+    ///     on_error: SinkErrorPolicy::Skip,
+    ///     ..PipeOptions::default()
+    /// };
+    /// RING_BUF.send_bytes_blocking(b"world");
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         while !RING_BUF.is_empty() {}
+    ///         stop.store(true, Ordering::Relaxed);
+    ///     });
+    ///     let stats = device.pipe_to_with(sinks, poll, &stop, options).unwrap();
+    ///     assert_eq!(stats.failed_sinks, 1);
+    /// });
+    /// assert_eq!(terminal, b"world");
+    /// # }
+    /// ```
+    pub fn pipe_to_with(
+        &mut self,
+        sinks: &mut [&mut dyn io::Write],
+        poll: impl Into<PollPolicy>,
+        stop: &AtomicBool,
+        options: PipeOptions,
+    ) -> Result<PipeStats, ConsumerError<M::Error>> {
+        let poll = poll.into();
+        let start = Instant::now();
+        let mut stats = PipeStats {
+            bytes: 0,
+            chunks: 0,
+            elapsed: Duration::ZERO,
+            bytes_per_second: 0.0,
+            failed_sinks: 0,
+        };
+        let mut pipe = Pipe {
+            failed: vec![false; sinks.len()],
+            sinks,
+            options,
+            unflushed: None,
+        };
+        let mut buf = vec![0; self.capacity()];
+        let mut empty_reads = 0;
+        let result = loop {
+            if stop.load(Ordering::Relaxed) {
+                break Ok(());
+            }
+            let read = match self.read_into(&mut buf) {
+                Ok(read) => read,
+                Err(e) => break Err(e),
+            };
+            if read == 0 {
+                if let Err(e) = pipe.flush() {
+                    break Err(e);
+                }
+                self.record_sleep(empty_reads);
+                std::thread::sleep(poll.interval(empty_reads));
+                empty_reads = empty_reads.saturating_add(1);
+                continue;
+            }
+            empty_reads = 0;
+            stats.bytes += read as u64;
+            stats.chunks += 1;
+            if let Err(e) = pipe.write(&buf[..read]) {
+                break Err(e);
+            }
+        };
+        // The bytes written so far are flushed even if the pipe failed
+        let flushed = pipe.flush();
+        result.and(flushed)?;
+        stats.elapsed = start.elapsed();
+        stats.bytes_per_second = stats.bytes as f64 / stats.elapsed.as_secs_f64();
+        stats.failed_sinks = pipe.failed.iter().filter(|&&failed| failed).count();
+        Ok(stats)
+    }
+}
+
+/// The sinks of [`ProducerDevice::pipe_to_with`]
+struct Pipe<'s, 'w> {
+    sinks: &'s mut [&'w mut dyn io::Write],
+    /// Sinks skipped after failing
+    failed: Vec<bool>,
+    options: PipeOptions,
+    /// When the oldest byte not flushed yet was written
+    unflushed: Option<Instant>,
+}
+
+impl Pipe<'_, '_> {
+    /// Writes `chunk` to every sink, and flushes them if the oldest byte not flushed yet is
+    /// older than the flush interval
+    fn write<E>(&mut self, chunk: &[u8]) -> Result<(), ConsumerError<E>> {
+        for index in 0..self.sinks.len() {
+            if self.failed[index] {
+                continue;
+            }
+            let written = if self.options.retry_partial {
+                self.sinks[index].write_all(chunk)
+            } else {
+                match self.sinks[index].write(chunk) {
+                    Ok(n) if n < chunk.len() => Err(io::ErrorKind::WriteZero.into()),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e),
+                }
+            };
+            if let Err(error) = written {
+                self.fail(index, error)?;
+            }
+        }
+        let unflushed = *self.unflushed.get_or_insert_with(Instant::now);
+        if unflushed.elapsed() >= self.options.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes every sink, if anything was written since the last flush
+    fn flush<E>(&mut self) -> Result<(), ConsumerError<E>> {
+        if self.unflushed.take().is_none() {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for index in 0..self.sinks.len() {
+            if self.failed[index] {
+                continue;
+            }
+            if let Err(error) = self.sinks[index].flush() {
+                // The other sinks are still flushed
+                result = result.and(self.fail(index, error));
+            }
+        }
+        result
+    }
+
+    /// Skips the sink `index`, or returns `error` if the policy is to stop
+    fn fail<E>(&mut self, index: usize, error: io::Error) -> Result<(), ConsumerError<E>> {
+        match self.options.on_error {
+            SinkErrorPolicy::Stop => Err(ConsumerError(ConsumerErrorKind::Sink { index, error })),
+            SinkErrorPolicy::Skip => {
+                self.failed[index] = true;
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Rendering of what is read from a ring buffer as JSON lines, for other tools.
+
+use std::format;
+use std::io::{self, Write};
+use std::string::String;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{LogRecord, ReadEvent, Record};
+use crate::layout;
+
+/// How the `payload` of a [`JsonEvent`] is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEncoding {
+    /// As text, the bytes being valid UTF-8
+    Utf8,
+    /// As lowercase hexadecimal, two digits per byte, without separators
+    Hex,
+}
+
+/// What a line written by [`JsonSink`] is about, in its `type` field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonEvent {
+    /// Bytes read from the ring buffer at once
+    Chunk {
+        /// How `payload` is written
+        encoding: PayloadEncoding,
+        /// The bytes
+        payload: String,
+    },
+    /// A frame, e.g. of [`FrameReader`](super::FrameReader)
+    Frame {
+        /// How `payload` is written
+        encoding: PayloadEncoding,
+        /// The frame
+        payload: String,
+    },
+    /// A record of [`TaggedRecordReader`](super::TaggedRecordReader): `tag` is 1 for
+    /// `send_text`, 2 for `send_binary`, and that of the application otherwise
+    Record {
+        /// Tag of the frame
+        tag: u8,
+        /// How `payload` is written
+        encoding: PayloadEncoding,
+        /// The frame, without its tag
+        payload: String,
+    },
+    /// A log record, of [`RecordReader`](super::RecordReader)
+    Log {
+        /// Name of the level, e.g. `"WARN"`
+        level: String,
+        /// Name of the target of the firmware, or its id
+        target: String,
+        /// The message
+        message: String,
+    },
+    /// Bytes dropped by the producer, see [`ReadEvent::Gap`]
+    Gap {
+        /// How many bytes are missing from the stream at this point
+        bytes_lost: u32,
+    },
+}
+
+impl JsonEvent {
+    /// Returns the payload of `bytes`: as text if it is valid UTF-8, in hexadecimal
+    /// otherwise
+    fn payload(bytes: &[u8]) -> (PayloadEncoding, String) {
+        match core::str::from_utf8(bytes) {
+            Ok(text) => (PayloadEncoding::Utf8, text.into()),
+            Err(_) => {
+                let hex = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                (PayloadEncoding::Hex, hex)
+            }
+        }
+    }
+
+    /// Returns a chunk of `bytes`
+    pub fn chunk(bytes: &[u8]) -> Self {
+        let (encoding, payload) = Self::payload(bytes);
+        JsonEvent::Chunk { encoding, payload }
+    }
+
+    /// Returns a frame of `bytes`
+    pub fn frame(bytes: &[u8]) -> Self {
+        let (encoding, payload) = Self::payload(bytes);
+        JsonEvent::Frame { encoding, payload }
+    }
+
+    /// Returns the bytes of the payload, or `None` if this event has none, or its
+    /// hexadecimal is invalid
+    pub fn payload_bytes(&self) -> Option<Vec<u8>> {
+        let (encoding, payload) = match self {
+            JsonEvent::Chunk { encoding, payload }
+            | JsonEvent::Frame { encoding, payload }
+            | JsonEvent::Record {
+                encoding, payload, ..
+            } => (encoding, payload),
+            JsonEvent::Log { .. } | JsonEvent::Gap { .. } => return None,
+        };
+        match encoding {
+            PayloadEncoding::Utf8 => Some(payload.as_bytes().to_vec()),
+            PayloadEncoding::Hex => (0..payload.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(payload.get(i..i + 2)?, 16).ok())
+                .collect(),
+        }
+    }
+}
+
+impl From<&Record> for JsonEvent {
+    fn from(record: &Record) -> Self {
+        let (tag, bytes) = match record {
+            Record::Text(text) => (layout::TAG_TEXT, text.as_bytes()),
+            Record::Binary(data) => (layout::TAG_BINARY, &data[..]),
+            Record::User(tag, data) => (*tag, &data[..]),
+        };
+        let (encoding, payload) = Self::payload(bytes);
+        JsonEvent::Record {
+            tag,
+            encoding,
+            payload,
+        }
+    }
+}
+
+impl From<&ReadEvent> for JsonEvent {
+    fn from(event: &ReadEvent) -> Self {
+        match event {
+            ReadEvent::Data(data) => JsonEvent::chunk(data),
+            ReadEvent::Gap { bytes_lost } => JsonEvent::Gap {
+                bytes_lost: *bytes_lost,
+            },
+        }
+    }
+}
+
+/// A line written by [`JsonSink`]. Its fields are those of the schema, which only gains
+/// new fields or event types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonLine {
+    /// When it was read, in microseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Channel of the control block it was read from, or `null`
+    pub channel: Option<String>,
+    /// What was read, whose `type` and fields are those of the line
+    #[serde(flatten)]
+    pub event: JsonEvent,
+}
+
+/// Writes what is read from a ring buffer as JSON lines, one object per chunk, frame,
+/// record or gap, for tools such as `jq`, e.g. for `ramlink-dump --format json`. Each line
+/// is a [`JsonLine`]: the time it was read, the channel, the `type` of event, and its
+/// fields. Payloads are written as text where they are valid UTF-8, and in hexadecimal
+/// otherwise, as told by their `encoding`.
+/// ```
+/// use ramlink::consumer::{JsonEvent, JsonLine, JsonSink, LogRecord, ReadEvent, Record};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let mut sink = JsonSink::new(Vec::new()).with_channel("log");
+/// let at = UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_001);
+/// sink.write_event_at(&JsonEvent::chunk(b"boot\n"), at).unwrap();
+/// sink.write_event_at(&JsonEvent::frame(&[0xff, 0x00]), at).unwrap();
+/// sink.write_event_at(&(&ReadEvent::Gap { bytes_lost: 3 }).into(), at).unwrap();
+/// sink.write_event_at(&(&Record::User(0x81, vec![7])).into(), at).unwrap();
+/// let record = LogRecord { level: 2, target: 3, message: "stall".into() };
+/// sink.write_log_at(&record, "motor", at).unwrap();
+///
+/// let output = String::from_utf8(sink.into_inner()).unwrap();
+/// let lines: Vec<&str> = output.lines().collect();
+/// let head = r#"{"timestamp":1700000000000001,"channel":"log","type":"#;
+/// assert_eq!(
+///     lines,
+///     [
+///         format!(r#"{head}"chunk","encoding":"utf8","payload":"boot\n"}}"#),
+///         format!(r#"{head}"frame","encoding":"hex","payload":"ff00"}}"#),
+///         format!(r#"{head}"gap","bytes_lost":3}}"#),
+///         format!(r#"{head}"record","tag":129,"encoding":"utf8","payload":"\u0007"}}"#),
+///         format!(r#"{head}"log","level":"WARN","target":"motor","message":"stall"}}"#),
+///     ]
+/// );
+///
+/// // Lines are read back with serde_json
+/// let line: JsonLine = serde_json::from_str(lines[1]).unwrap();
+/// assert_eq!(line.channel.as_deref(), Some("log"));
+/// assert_eq!(line.event.payload_bytes().unwrap(), [0xff, 0x00]);
+/// ```
+pub struct JsonSink<W: Write> {
+    writer: W,
+    channel: Option<String>,
+}
+
+impl<W: Write> JsonSink<W> {
+    /// Writes lines to `writer`, without a channel
+    pub fn new(writer: W) -> Self {
+        JsonSink {
+            writer,
+            channel: None,
+        }
+    }
+
+    /// Sets the channel of the lines, that of the control block being read
+    pub fn with_channel(mut self, name: impl Into<String>) -> Self {
+        self.channel = Some(name.into());
+        self
+    }
+
+    /// Writes a line of `event`, read now
+    pub fn write_event(&mut self, event: &JsonEvent) -> io::Result<()> {
+        self.write_event_at(event, SystemTime::now())
+    }
+
+    /// Writes a line of `event`, read at `timestamp`, and flushes it, so that a tool
+    /// reading a pipe gets it right away
+    pub fn write_event_at(&mut self, event: &JsonEvent, timestamp: SystemTime) -> io::Result<()> {
+        let line = JsonLine {
+            timestamp: timestamp
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_micros() as u64),
+            channel: self.channel.clone(),
+            event: event.clone(),
+        };
+        let mut json = serde_json::to_vec(&line)?;
+        json.push(b'\n');
+        self.writer.write_all(&json)?;
+        self.writer.flush()
+    }
+
+    /// Writes a line of a chunk of `bytes` read now
+    pub fn write_chunk(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_event(&JsonEvent::chunk(bytes))
+    }
+
+    /// Writes a line of a frame read now
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_event(&JsonEvent::frame(frame))
+    }
+
+    /// Writes a line of a tagged record read now
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        self.write_event(&record.into())
+    }
+
+    /// Writes a line of what [`ProducerDevice::read_events`](super::ProducerDevice::read_events)
+    /// returned now: a chunk or a gap
+    pub fn write_read_event(&mut self, event: &ReadEvent) -> io::Result<()> {
+        self.write_event(&event.into())
+    }
+
+    /// Writes a line of a log record read now, whose target is named `target_name`, e.g.
+    /// by [`RecordReader::target_name`](super::RecordReader::target_name)
+    pub fn write_log(&mut self, record: &LogRecord, target_name: &str) -> io::Result<()> {
+        self.write_log_at(record, target_name, SystemTime::now())
+    }
+
+    /// Writes a line of a log record read at `timestamp`
+    pub fn write_log_at(
+        &mut self,
+        record: &LogRecord,
+        target_name: &str,
+        timestamp: SystemTime,
+    ) -> io::Result<()> {
+        let event = JsonEvent::Log {
+            level: record.level_name().into(),
+            target: target_name.into(),
+            message: record.message.clone(),
+        };
+        self.write_event_at(&event, timestamp)
+    }
+
+    /// Returns the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
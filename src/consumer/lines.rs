@@ -0,0 +1,167 @@
+//! Reassembly of text lines.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use super::frames::read_more;
+#[cfg(feature = "std")]
+use super::ConsumerErrorKind;
+use super::{ConsumerError, MemoryReader, ProducerDevice};
+
+/// Reads lines of text ending with `\n`, obtained with [`ProducerDevice::read_lines`].
+/// Bytes read from the ring buffer are kept until their line is complete, so a line may
+/// arrive over any number of reads. Lines are returned without their `\n` (nor `\r\n`), and
+/// invalid UTF-8 is replaced with `U+FFFD`.
+///
+/// As an [`Iterator`], it yields the lines that are complete, and ends when there are none
+/// left for now.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// static RING_BUF: AtomicRB<32> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut lines = device.read_lines();
+///
+/// RING_BUF.send_bytes_blocking(format!("temp={}\ntemp=", 21).as_bytes());
+/// assert_eq!(lines.next().unwrap().unwrap(), "temp=21");
+/// assert!(lines.next().is_none());
+///
+/// RING_BUF.send_bytes_blocking(format!("{}\r\n", 22).as_bytes());
+/// RING_BUF.send_bytes_blocking(b"bad \xff\n");
+/// assert_eq!(lines.next().unwrap().unwrap(), "temp=22");
+/// assert_eq!(lines.next().unwrap().unwrap(), "bad \u{fffd}");
+/// # }
+/// ```
+pub struct LineReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet, the start of a line
+    buffer: Vec<u8>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of lines of text, see [`LineReader`]
+    pub fn read_lines(&mut self) -> LineReader<'_, M> {
+        LineReader {
+            device: self,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Reads a single line of text, polling the ring buffer every `poll` until its `\n`
+    /// arrives. The line is returned like with [`LineReader`], but the bytes after it are
+    /// left in the ring buffer, so that nothing is lost between two calls.
+    ///
+    /// Fails with [`ConsumerErrorKind::Timeout`] if that takes more than `timeout`, `got`
+    /// being the number of bytes of the line that were read, and are then lost.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let (poll, timeout) = (Duration::from_millis(1), Duration::from_secs(5));
+    ///
+    /// // Longer than the ring buffer
+    /// let producer = std::thread::spawn(|| RING_BUF.send_bytes_blocking(b"hello world\nbye\n"));
+    /// assert_eq!(device.read_line(poll, timeout).unwrap(), "hello world");
+    /// assert_eq!(device.read_line(poll, timeout).unwrap(), "bye");
+    /// producer.join().unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_line(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<String, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        let mut line = Vec::new();
+        let mut peeked = alloc::vec![0; self.capacity()];
+        loop {
+            let waiting = self.peek_into(&mut peeked)?;
+            let len = peeked[..waiting]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(waiting, |end| end + 1);
+            let got = line.len();
+            line.resize(got + len, 0);
+            self.read_into(&mut line[got..])?;
+            if line.last() == Some(&b'\n') {
+                return Ok(decode_line(&line));
+            }
+            if start.elapsed() >= timeout {
+                let got = line.len();
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+}
+
+impl<M: MemoryReader> LineReader<'_, M> {
+    /// Returns the next line, or `None` if it is not complete yet
+    pub fn try_next_line(&mut self) -> Result<Option<String>, ConsumerError<M::Error>> {
+        if let Some(line) = self.pop_line() {
+            return Ok(Some(line));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        Ok(self.pop_line())
+    }
+
+    /// Returns the next line, polling the ring buffer every `poll` until it is complete.
+    /// Fails with [`ConsumerErrorKind::Timeout`] if that takes more than `timeout`, `got`
+    /// being the number of bytes buffered, which are kept for the next call.
+    #[cfg(feature = "std")]
+    pub fn next_line(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<String, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        loop {
+            if let Some(line) = self.try_next_line()? {
+                return Ok(line);
+            }
+            if start.elapsed() >= timeout {
+                let got = self.buffer.len();
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+
+    /// Removes the first line from the buffer if it is complete
+    fn pop_line(&mut self) -> Option<String> {
+        let end = self.buffer.iter().position(|&b| b == b'\n')?;
+        let line = decode_line(&self.buffer[..=end]);
+        self.buffer.drain(..=end);
+        Some(line)
+    }
+}
+
+impl<M: MemoryReader> Iterator for LineReader<'_, M> {
+    type Item = Result<String, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_line().transpose()
+    }
+}
+
+/// Decodes a line, stripping its `\n` or `\r\n`
+fn decode_line(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
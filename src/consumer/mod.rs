@@ -4,160 +4,2122 @@
 //! In order to work, you must:
 //! - Implement the `MemoryReader` trait
 //! - Know the exact memory location of the RB struct defined in your producer
-//! <br>
-//! # Example
-//! Implement the trait for your specific device
-//! ```
-//!  struct mk2<'a> {
-//!      dev: JtagIceMkii<'a>,
-//!  }
+//!   <br>
 //!
-//!  impl<'a> ramlink::consumer::MemoryReader for mk2<'a> {
-//!      fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), String> {
-//!          for i in 0..buffer.len() {
-//!              let byte = self.dev.read_ram_byte((address + i) as u16).unwrap();
-//!              buffer[i] = byte;
-//!          }
-//!          Ok(())
-//!      }
-//!      fn write_memory(&mut self, address: usize, value: u8) -> Result<(), String> {
-//!          self.dev.write_ram_byte(address as u16, value);
-//!          Ok(())
-//!      }
-//!  }
-//!```
-//! Initialize it. In this example, the producer device is an AVR Attiny402, and the RB struct
-//! is stored at address `0x3f0e`
-//! ```
-//!    let mm = mk2 { dev: dgr };
+//! The consumer is `no_std` and does not allocate: [`ProducerDevice::read_into`] reads into
+//! a slice. The `alloc` cargo feature adds `ProducerDevice::read_bytes`, which returns a
+//! `Vec`, and the `std` feature adds `ProducerDevice::is_alive`. The `async` feature adds
+//! `AsyncProducerDevice`, whose reads wait for bytes without blocking an executor.
+//! # Example
+//! Implement the `MemoryReader` trait for your specific device, or, with an AVR and an Atmel
+//! JTAGICE mkII, use the reader of the `avr-jtagice` feature. In this example, the producer
+//! device is an ATmega328P, whose SRAM spans `0x0100..0x0900`, and the RB struct is stored
+//! at address `0x08e0`
+//! ```ignore
+//!    let mut mm = JtagIceMkiiReader::open("/dev/ttyUSB0", 0x0100..0x0900)?;
+//!    mm.set_emulator_mode(EmulatorMode::DebugWire)?;
 //!
-//!    let mut rb = ramlink::consumer::ProducerDevice::new(Box::new(mm), 0x3f0e).unwrap();
+//!    let mut rb = ramlink::consumer::ProducerDevice::new(mm, 0x08e0).unwrap();
 //! ```
 //! and start reading:
+//! ```ignore
+//!    rb.run(Duration::from_millis(10), |data| {
+//!        println!("I READ {:02x?}", data);
+//!        ControlFlow::Continue(())
+//!    })?;
 //! ```
-//!    while true {
-//!        let r = rb.read_bytes();
-//!        if r.len() > 0 {
-//!            println!("I READ {:02x?}", r);
-//!        }
-//!   }
+//! # probe-rs
+//! With the `probe-rs` feature, `adapters::ProbeRsReader` reads the memory through any
+//! probe that [probe-rs](https://probe.rs) drives, e.g. an ST-Link, a J-Link or a CMSIS-DAP
+//! probe, as `examples/probe_rs_dump.rs` does:
+//! ```text
+//! cargo run --example probe_rs_dump -F probe-rs -- STM32F411RETx 0x20000100
 //! ```
+//! # defmt
+//! The bytes of a ring buffer written by a defmt logger, see
+//! [`producer`](crate::producer#defmt), are decoded with the ELF file of the firmware.
+//! `ramlink-dump` can feed them to `defmt-print`:
+//! ```text
+//! ramlink-dump --elf firmware.elf --symbol DEFMT_RB --raw | defmt-print -e firmware.elf
+//! ```
+//! or, with the `defmt-decoder` feature, a host program can decode them itself with
+//! `DefmtReader`, see [`ProducerDevice::defmt_frames`](ProducerDevice#method.defmt_frames).
 
 #![warn(missing_docs)]
 
+#[cfg(feature = "alloc")]
 use alloc::{boxed::Box, vec::Vec};
-use core::fmt::Error;
-use std::println;
-extern crate alloc;
-extern crate std;
+use core::fmt::{self, Debug};
+#[cfg(feature = "std")]
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::layout::{self, Layout, Offset};
+use crate::LayoutDescriptor;
 
-use super::RB_MAGIC;
+#[cfg(feature = "std")]
+pub mod adapters;
+#[cfg(feature = "async")]
+mod async_device;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "async")]
+pub use async_device::AsyncProducerDevice;
+#[cfg(feature = "std")]
+mod channel;
+mod control;
+#[cfg(feature = "std")]
+pub use channel::ChannelReceiver;
+pub use control::{Channel, ControlBlockReader};
+#[cfg(feature = "elf")]
+mod elf;
+#[cfg(feature = "elf")]
+pub use elf::{address_from_elf, ElfError};
+#[cfg(feature = "alloc")]
+mod dump;
+#[cfg(feature = "alloc")]
+pub use dump::RbSnapshot;
+#[cfg(feature = "alloc")]
+mod events;
+#[cfg(feature = "alloc")]
+pub use events::ReadEvent;
+#[cfg(feature = "alloc")]
+mod frames;
+#[cfg(feature = "alloc")]
+pub use frames::{
+    CobsFrameReader, FrameReader, SlipFrameReader, TimestampedFrame, TimestampedFrameReader,
+};
+#[cfg(feature = "alloc")]
+mod mux;
+#[cfg(feature = "alloc")]
+pub use mux::MuxReader;
+#[cfg(feature = "alloc")]
+mod lines;
+#[cfg(feature = "alloc")]
+mod records;
+#[cfg(feature = "alloc")]
+pub use lines::LineReader;
+#[cfg(feature = "alloc")]
+pub use records::{LogRecord, RecordReader};
+#[cfg(feature = "alloc")]
+mod tagged;
+#[cfg(feature = "tracing")]
+pub mod tracing_bridge;
+#[cfg(feature = "alloc")]
+pub use tagged::{Record, TaggedRecordReader};
+#[cfg(feature = "defmt-decoder")]
+mod defmt;
+#[cfg(feature = "defmt-decoder")]
+pub use self::defmt::DefmtReader;
+mod poll;
+#[cfg(all(feature = "postcard", feature = "std"))]
+mod typed;
+pub use poll::PollPolicy;
+mod writer;
+pub use writer::HostWriter;
+#[cfg(feature = "std")]
+mod capture;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "std")]
+mod reconnect;
+#[cfg(feature = "alloc")]
+mod scan;
+#[cfg(feature = "std")]
+pub use capture::{
+    CaptureReader, CaptureRecord, CaptureWriter, ReplayError, ReplayReader, CAPTURE_MAGIC,
+};
+#[cfg(feature = "std")]
+pub use io::{BlockingReader, PipeOptions, PipeStats, SinkErrorPolicy};
+#[cfg(feature = "json")]
+pub use json::{JsonEvent, JsonLine, JsonSink, PayloadEncoding};
+#[cfg(feature = "std")]
+pub use reconnect::{ReconnectPolicy, RunEvent};
+#[cfg(feature = "alloc")]
+pub use scan::{scan_for_control_block, scan_for_rb, scan_for_rb_with_names, FoundRB};
+
+/// Reads `buf` from `address` with `read`, in transfers of at most `block` bytes. A transfer
+/// that fails is retried in halves, and the smaller size is kept for the next ones: only
+/// the failure of a single byte is returned.
+fn read_block<E>(
+    address: usize,
+    buf: &mut [u8],
+    block: usize,
+    mut read: impl FnMut(usize, &mut [u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut block = block.max(1);
+    let mut done = 0;
+    while done < buf.len() {
+        let len = block.min(buf.len() - done);
+        match read(address + done, &mut buf[done..done + len]) {
+            Ok(()) => done += len,
+            Err(e) if len == 1 => return Err(e),
+            Err(_) => block = len / 2,
+        }
+    }
+    Ok(())
+}
+
+/// Forwards to `log::debug!` if the `log` feature is enabled, otherwise does nothing
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+    };
+}
 
 /// Error for consumer
+///
+/// It implements [`core::error::Error`], so it can be returned with `?` from functions
+/// returning e.g. `Box<dyn Error>`. Use [`ConsumerError::kind`] to tell errors apart.
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use ramlink::consumer::testing::InMemoryReader;
+/// use ramlink::consumer::ProducerDevice;
+///
+/// fn attach(ram: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+///     ProducerDevice::new(InMemoryReader::new(ram), 0)?;
+///     Ok(())
+/// }
+///
+/// let err = attach(vec![0; 16]).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "magic marker not found, the address of the ring buffer may be wrong"
+/// );
+/// // The headers of an `RB16`, cut short
+/// let err = attach(vec![0x89, 0x16, 0x42, 1]).unwrap_err();
+/// assert_eq!(err.to_string(), "ring buffer has id 0x42, not the expected one");
+/// let err = attach(vec![0x89, 0x16, 0x88, 7]).unwrap_err();
+/// assert_eq!(err.to_string(), "unsupported ring buffer layout version 7");
+/// let err = attach(vec![0x89, 0x16, 0x88, 1]).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "failed to read memory: OutOfBounds { address: 4, len: 2 }"
+/// );
+///
+/// // An `RBCompact<8>` whose consumer index is out of it
+/// let memory = InMemoryReader::new(vec![3, 9, 0, 0, 0, 0]);
+/// let mut device = ProducerDevice::new_unchecked(memory, 0, 8).unwrap();
+/// let err = device.read_into(&mut [0; 8]).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "corrupt indices: producer 3, consumer 9, ring buffer size 8"
+/// );
+/// # }
+/// ```
 #[derive(Debug)]
-pub struct ConsumerError(ConsumerErrorKind);
+pub struct ConsumerError<E>(ConsumerErrorKind<E>);
+
+impl<E: Debug> fmt::Display for ConsumerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<E: Debug> core::error::Error for ConsumerError<E> {}
 
-/// Error types that the consumer can have
+impl<E> ConsumerError<E> {
+    /// Returns what went wrong
+    pub fn kind(&self) -> &ConsumerErrorKind<E> {
+        &self.0
+    }
+}
+
+/// Error types that the consumer can have. `E` is the error of the [`MemoryReader`]
 #[derive(Debug)]
-pub enum ConsumerErrorKind {
+pub enum ConsumerErrorKind<E> {
     /// The magic marker was not found at the start of the struct. Maybe the RAM address is wrong
     MagicMarkerNotFound,
-    /// The ring buffer reports a size 0. This should not happen
-    RingBufferSizeNull,
+    /// The magic marker was found, but the ring buffer has another id than the expected
+    /// one, see [`ProducerDevice::new_with_id`]
+    WrongId(u8),
+    /// The ring buffer was written by a newer version of this crate, whose layout is unknown
+    UnsupportedVersion(u8),
+    /// The content pointer of an `RBIndirect` is this many bytes wide, which is not a
+    /// valid address on the host
+    UnsupportedPointerWidth(u8),
     /// There was an error reading the memory address
-    ReadMemoryError(Error),
+    ReadMemoryError(E),
     /// There was an error writing to the memory address
-    WriteMemoryError(Error),
+    WriteMemoryError(E),
+    /// The producer was built without the cargo feature providing this field
+    FeatureNotEnabled,
+    /// A producer or consumer index is outside of the ring buffer. The producer may have
+    /// been reset or its RAM corrupted, or the device attached to a wrong address, see
+    /// [`ProducerDevice::resync`]
+    CorruptIndex {
+        /// Producer index read from the ring buffer
+        producer: usize,
+        /// Consumer index read from the ring buffer
+        consumer: usize,
+        /// Size of the ring buffer, which both indices must be less than
+        size: usize,
+    },
+    /// The ring buffer does not have the optional fields of the layout given to
+    /// [`ProducerDevice::new_with_layout`]
+    FeaturesMismatch {
+        /// Bitmask of the optional fields of the layout that was given
+        expected: u8,
+        /// Bitmask read from the header of the ring buffer
+        found: u8,
+    },
+    /// Several ring buffers were found by [`ProducerDevice::discover`], this many
+    MultipleRingBuffers(usize),
+    /// The ring buffer does not have the size given to [`ProducerDevice::new_expect_size`]
+    SizeMismatch {
+        /// Size that was given
+        expected: usize,
+        /// Size read from the header of the ring buffer
+        found: usize,
+    },
+    /// The producer was reset, or its ring buffer overwritten, since the device attached
+    /// to it: see [`ProducerDevice::set_integrity_check`] and [`ProducerDevice::resync`]
+    Desynchronized,
+    /// The ring buffer has a size of 1, or of 0 for one whose indices are wider than a
+    /// byte, so it can never hold a byte, and reading it would wait forever
+    RingBufferTooSmall,
+    /// A length byte is larger than the capacity of the ring buffer, see `FrameReader`
+    FrameTooLong(usize),
+    /// A frame could not be decoded, e.g. a COBS one, see `CobsFrameReader`
+    InvalidFrame,
+    /// The CRC of a frame does not match its bytes, see `FrameReader::with_crc`
+    BadCrc {
+        /// CRC of the length and payload as read
+        expected: u8,
+        /// CRC byte that followed them
+        got: u8,
+    },
+    /// The ring buffer could not be found in the ELF file of the firmware, see
+    /// [`ProducerDevice::new_from_elf`]
+    #[cfg(feature = "elf")]
+    Elf(ElfError),
+    /// The control block has no channel of this index or name, see
+    /// [`ControlBlockReader::channel`]
+    ChannelNotFound,
+    /// A frame is tagged with a channel that the `MuxReader` does not expect, or with a
+    /// reserved tag, see `TaggedRecordReader`
+    UnknownChannel(u8),
+    /// Not all the bytes arrived in time, see `ProducerDevice::read_exact`
+    Timeout {
+        /// Number of bytes that were read
+        got: usize,
+    },
+    /// A message could not be deserialized by postcard, see `ProducerDevice::read_postcard`
+    #[cfg(feature = "postcard")]
+    Deserialize(postcard::Error),
+    /// Writing to a sink of [`ProducerDevice::pipe_to`] failed
+    #[cfg(feature = "std")]
+    Sink {
+        /// Position of the sink in those given to `pipe_to`
+        index: usize,
+        /// Error of the sink
+        error: std::io::Error,
+    },
+}
+
+impl<E: Debug> fmt::Display for ConsumerErrorKind<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsumerErrorKind::MagicMarkerNotFound => write!(
+                f,
+                "magic marker not found, the address of the ring buffer may be wrong"
+            ),
+            ConsumerErrorKind::WrongId(id) => {
+                write!(f, "ring buffer has id {id:#04x}, not the expected one")
+            }
+            ConsumerErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported ring buffer layout version {version}")
+            }
+            ConsumerErrorKind::UnsupportedPointerWidth(width) => {
+                write!(f, "unsupported content pointer of {width} bytes")
+            }
+            ConsumerErrorKind::ReadMemoryError(e) => write!(f, "failed to read memory: {e:?}"),
+            ConsumerErrorKind::WriteMemoryError(e) => write!(f, "failed to write memory: {e:?}"),
+            ConsumerErrorKind::FeatureNotEnabled => write!(
+                f,
+                "the producer was built without the cargo feature providing this field"
+            ),
+            ConsumerErrorKind::CorruptIndex {
+                producer,
+                consumer,
+                size,
+            } => write!(
+                f,
+                "corrupt indices: producer {producer}, consumer {consumer}, ring buffer size {size}"
+            ),
+            ConsumerErrorKind::FeaturesMismatch { expected, found } => write!(
+                f,
+                "ring buffer has optional fields {found:#04x} instead of {expected:#04x}"
+            ),
+            ConsumerErrorKind::MultipleRingBuffers(found) => {
+                write!(f, "{found} ring buffers found instead of one")
+            }
+            ConsumerErrorKind::SizeMismatch { expected, found } => write!(
+                f,
+                "ring buffer has a size of {found} bytes instead of {expected}"
+            ),
+            ConsumerErrorKind::Desynchronized => {
+                write!(f, "the producer was reset since the device attached to it")
+            }
+            ConsumerErrorKind::RingBufferTooSmall => {
+                write!(f, "ring buffer of size 0 or 1 can't hold any byte")
+            }
+            ConsumerErrorKind::FrameTooLong(len) => {
+                write!(f, "frame of {len} bytes does not fit in the ring buffer")
+            }
+            ConsumerErrorKind::InvalidFrame => write!(f, "invalid frame"),
+            ConsumerErrorKind::BadCrc { expected, got } => {
+                write!(f, "frame CRC is {got:#04x} instead of {expected:#04x}")
+            }
+            ConsumerErrorKind::ChannelNotFound => {
+                write!(f, "no such channel in the control block")
+            }
+            ConsumerErrorKind::UnknownChannel(channel) => {
+                write!(f, "frame on unknown channel {channel}")
+            }
+            #[cfg(feature = "elf")]
+            ConsumerErrorKind::Elf(e) => fmt::Display::fmt(e, f),
+            ConsumerErrorKind::Timeout { got } => {
+                write!(f, "timed out, {got} bytes read")
+            }
+            #[cfg(feature = "postcard")]
+            ConsumerErrorKind::Deserialize(e) => write!(f, "failed to deserialize message: {e}"),
+            #[cfg(feature = "std")]
+            ConsumerErrorKind::Sink { index, error } => {
+                write!(f, "failed to write to sink {index}: {error}")
+            }
+        }
+    }
+}
+
+/// Statistics kept by a producer built with the `stats` feature, see [`ProducerDevice::producer_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProducerStats {
+    /// Highest number of bytes that were ever waiting in the ring buffer
+    pub high_water: u8,
+    /// Number of bytes sent since startup, wrapping around at `u32::MAX`
+    pub total_sent: u32,
+}
+
+/// Statistics of the reads of a [`ProducerDevice`], see [`ProducerDevice::stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConsumerStats {
+    /// Number of bytes read
+    pub total_bytes: u64,
+    /// Number of reads, including those that found the ring buffer empty
+    pub polls: u64,
+    /// Number of reads that found the ring buffer empty
+    pub empty_polls: u64,
+    /// Highest number of bytes returned by a single read
+    pub max_read: usize,
+    /// Number of sleeps of the run loops, e.g. [`ProducerDevice::run`], by how many empty
+    /// reads in a row came before: index `i` counts the sleeps of
+    /// [`PollPolicy::interval`]`(i)`, and the last index the longer streaks too
+    pub sleeps: [u64; 8],
+    /// Bytes read per second, measured over the last full second
+    #[cfg(feature = "std")]
+    pub bytes_per_second: f64,
 }
 
+/// Duration over which [`ConsumerStats::bytes_per_second`] is measured
+#[cfg(feature = "std")]
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
 /// Trait that the consumer interface (JTAG, UPDI, ...) must support
 pub trait MemoryReader {
+    /// Error of the interface, returned in [`ConsumerErrorKind::ReadMemoryError`] and
+    /// [`ConsumerErrorKind::WriteMemoryError`]
+    type Error: Debug;
+
     /// Reads [`buffer`] elements starting from `address`.
-    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Error>;
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error>;
     /// Writes `value` at the specified memory address
-    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Error>;
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Self::Error>;
+
+    /// Writes `data` starting from `address`. The default implementation writes one byte
+    /// at a time, in order, with [`MemoryReader::write_memory`]: interfaces that can write
+    /// a block at once should override it, so that wide fields are written in one go.
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), Self::Error> {
+        for (i, byte) in data.iter().enumerate() {
+            self.write_memory(address + i, *byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: MemoryReader + ?Sized> MemoryReader for &mut M {
+    type Error = M::Error;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), M::Error> {
+        (**self).read_memory(address, buffer)
+    }
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), M::Error> {
+        (**self).write_memory(address, value)
+    }
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), M::Error> {
+        (**self).write_memory_slice(address, data)
+    }
+}
+
+/// Shares a reader, e.g. between a [`ProducerDevice`] and a [`HostWriter`] talking to the
+/// same probe. Each transfer borrows it mutably, so it must not be borrowed meanwhile.
+impl<M: MemoryReader + ?Sized> MemoryReader for &core::cell::RefCell<M> {
+    type Error = M::Error;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), M::Error> {
+        self.borrow_mut().read_memory(address, buffer)
+    }
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), M::Error> {
+        self.borrow_mut().write_memory(address, value)
+    }
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), M::Error> {
+        self.borrow_mut().write_memory_slice(address, data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: MemoryReader + ?Sized> MemoryReader for Box<M> {
+    type Error = M::Error;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), M::Error> {
+        (**self).read_memory(address, buffer)
+    }
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), M::Error> {
+        (**self).write_memory(address, value)
+    }
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), M::Error> {
+        (**self).write_memory_slice(address, data)
+    }
 }
 
 /// Represents a producer device, consisting of a reader, a ring buffer RAM address, and a ring buffer size
-pub struct ProducerDevice<'a> {
+///
+/// The reader is stored by value, so it can borrow e.g. a probe session. `&mut` references
+/// to readers are readers too, so that the reader is still usable once the device is gone:
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use core::fmt::Error;
+/// use ramlink::consumer::{MemoryReader, ProducerDevice};
+/// use ramlink::producer::RB;
+///
+/// struct Probe<'a> {
+///     ram: &'a mut [u8],
+/// }
+///
+/// impl MemoryReader for Probe<'_> {
+///     type Error = Error;
+///     fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Error> {
+///         buffer.copy_from_slice(self.ram.get(address..address + buffer.len()).ok_or(Error)?);
+///         Ok(())
+///     }
+///     fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Error> {
+///         *self.ram.get_mut(address).ok_or(Error)? = value;
+///         Ok(())
+///     }
+/// }
+///
+/// let mut rb = RB::<8>::new();
+/// rb.send_bytes_blocking(b"hi");
+/// let mut ram = unsafe {
+///     core::slice::from_raw_parts(&rb as *const RB<8> as *const u8, core::mem::size_of_val(&rb))
+/// }
+/// .to_vec();
+///
+/// let mut probe = Probe { ram: &mut ram };
+/// let mut device = ProducerDevice::new(&mut probe, 0).unwrap();
+/// assert_eq!(device.read_bytes().unwrap(), b"hi");
+/// drop(device);
+///
+/// // The consumer index was written back to the borrowed RAM
+/// let mut index = [0];
+/// probe.read_memory(5, &mut index).unwrap();
+/// assert_eq!(index, [2]);
+/// # }
+/// ```
+pub struct ProducerDevice<M: MemoryReader> {
     /// The location in RAM of the [`RB`] struct
     ram_start: usize,
+    /// Id of the ring buffer, the last byte of its magic marker
+    id: u8,
     /// The memory reader implementation
-    memory_reader: Box<dyn 'a + MemoryReader>,
-    /// The size of the ring buffer. Will be checked against size defined in the [`RB`] struct.
-    rb_size: u8,
+    memory_reader: M,
+    /// The size of the ring buffer, as defined in the [`RB`] struct.
+    rb_size: usize,
+    /// Offsets of the fields, which depend on the width of the indices
+    layout: &'static Layout,
+    /// Given to [`ProducerDevice::new_with_layout`], the only layout then accepted
+    expected_layout: Option<LayoutDescriptor>,
+    /// Address of the content, after the header or wherever an `RBIndirect` points to
+    content: usize,
+    /// Value of the producer's dropped counter at the last [`ProducerDevice::dropped_bytes`]
+    last_dropped: u16,
+    /// Version of the layout, 0 for the ring buffers without a version byte
+    version: u8,
+    /// Optional fields of the producer, see [`ProducerDevice::producer_stats`]
+    features: u8,
+    /// See [`ProducerDevice::name`], all zeros if there is none
+    name: [u8; layout::NAME_LEN],
+    /// Last heartbeat value seen by [`ProducerDevice::is_alive`], and when it changed
+    #[cfg(feature = "std")]
+    last_heartbeat: Option<(u8, Instant)>,
+    /// See [`ProducerDevice::set_ack_threshold`]
+    ack_threshold: usize,
+    /// Consumer index not written back to the producer yet, and how many bytes it is ahead
+    pending_ack: Option<(usize, usize)>,
+    /// See [`ProducerDevice::set_integrity_check`], 0 when disabled
+    integrity_check: usize,
+    /// Reads since the integrity was last checked
+    unchecked_reads: usize,
+    /// See [`ProducerDevice::set_consumer_cache`]
+    cache_consumer: bool,
+    /// Consumer index in the ring buffer, as last read or written, if cached
+    cached_consumer: Option<usize>,
+    /// See [`ProducerDevice::set_block_size`]
+    #[cfg(feature = "alloc")]
+    block_size: usize,
+    /// Position in the stream of the next byte [`ProducerDevice::read_events`] reads, modulo
+    /// the 65536 wraps the producer counts
+    #[cfg(feature = "alloc")]
+    read_position: Option<u64>,
+    /// See [`ProducerDevice::stats`]
+    stats: ConsumerStats,
+    /// Start of the current [`ConsumerStats::bytes_per_second`] window, and bytes read since
+    #[cfg(feature = "std")]
+    rate_window: (Instant, u64),
+    /// See [`ProducerDevice::set_retry`], at least 1
+    attempts: usize,
+    /// Wait before the first retry, doubled for each of the next ones
+    #[cfg(feature = "std")]
+    backoff: Duration,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, E: Debug> ProducerDevice<Box<dyn MemoryReader<Error = E> + 'a>> {
+    /// Same as [`ProducerDevice::new`], with a boxed reader, e.g. when the probe is chosen
+    /// at runtime
+    pub fn new_boxed(
+        memory_reader: Box<dyn MemoryReader<Error = E> + 'a>,
+        ram_start_address: usize,
+    ) -> Result<Self, ConsumerError<E>> {
+        Self::new(memory_reader, ram_start_address)
+    }
 }
 
-const ADDR_SIZE: usize = 3;
-const ADDR_PROD: usize = 4;
-const ADDR_CONS: usize = 5;
-const ADDR_BUFF: usize = 6;
+/// Generates the typed readers of [`ProducerDevice`], matching the producer's typed senders
+macro_rules! le_readers {
+    ($($ty:ty => $read:ident;)*) => {
+        $(
+            #[doc = concat!("Reads a little-endian `", stringify!($ty), "`, or returns `None` if not all ")]
+            #[doc = "of its bytes are available yet."]
+            pub fn $read(&mut self) -> Result<Option<$ty>, ConsumerError<M::Error>> {
+                let mut bytes = [0; core::mem::size_of::<$ty>()];
+                Ok(self.read_value(&mut bytes)?.then(|| <$ty>::from_le_bytes(bytes)))
+            }
+        )*
+    };
+}
 
-impl<'a> ProducerDevice<'a> {
+impl<M: MemoryReader> ProducerDevice<M> {
     /// Initiates a new ProducerDevice. Connects to the [`RB`] struct and checks that the
-    /// magic markers are present, etc.
+    /// magic markers are present, etc. `RB`, `RB16` and `RBIndirect` are all supported, the
+    /// magic marker telling which one is at `ram_start_address`. So are the ring buffers of older
+    /// versions of this crate, see [`ProducerDevice::version`], while those of newer
+    /// versions fail with [`ConsumerErrorKind::UnsupportedVersion`].
+    ///
+    /// The host-attached flag of the ring buffer is set until the ProducerDevice is dropped,
+    /// so that `send_bytes_auto` on the producer blocks instead of dropping data. Here, the
+    /// "device" is a ring buffer in this very process:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// use ramlink::consumer::testing::VolatileReader;
+    /// use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    ///
+    /// static RING_BUF: AtomicRB<4> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    ///
+    /// let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    /// let device = ProducerDevice::new(host, address).unwrap();
+    /// assert!(RING_BUF.host_attached());
+    /// drop(device);
+    /// assert!(!RING_BUF.host_attached());
+    ///
+    /// // Nobody is listening, so what does not fit is dropped instead of blocking
+    /// RING_BUF.send_bytes_auto(b"hello");
+    /// assert_eq!(RING_BUF.dropped(), 2);
+    ///
+    /// let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// assert!(RING_BUF.host_attached());
+    /// assert_eq!(device.read_bytes().unwrap(), b"hel");
+    /// # }
+    /// ```
+    ///
+    /// The content of an `RBIndirect` is read where its pointer says, however far from the
+    /// header it is.
     pub fn new(
-        mut memory_reader: Box<dyn MemoryReader>,
+        memory_reader: M,
         ram_start_address: usize,
-    ) -> Result<ProducerDevice<'a>, ConsumerError> {
-        let mut magic_markers = [0; 3];
-        memory_reader
-            .read_memory(ram_start_address, &mut magic_markers)
-            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        Self::new_with_id(memory_reader, ram_start_address, layout::DEFAULT_ID)
+    }
 
-        if magic_markers != RB_MAGIC {
-            return Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound));
+    /// Same as [`ProducerDevice::new`], for a ring buffer created with an `ID` const generic
+    /// parameter, e.g. `RB<64, 0x42>`. Fails with [`ConsumerErrorKind::WrongId`] if the ring
+    /// buffer at `ram_start_address` has another id, so that two ring buffers of the same
+    /// firmware can't be mixed up.
+    pub fn new_with_id(
+        mut memory_reader: M,
+        ram_start_address: usize,
+        expected_id: u8,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        let header = Header::read(&mut memory_reader, ram_start_address, expected_id)?;
+        Self::from_header(memory_reader, ram_start_address, expected_id, header)
+    }
+
+    /// Same as [`ProducerDevice::new`], but fails with [`ConsumerErrorKind::SizeMismatch`]
+    /// if the ring buffer is not `expected_size` bytes long, e.g. 64 for an `RB<64>`. An
+    /// address that only looks like a ring buffer is then rejected before anything is
+    /// written to it.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    ///
+    /// let err = ProducerDevice::new_expect_size(host, address, 32).err().unwrap();
+    /// assert!(matches!(
+    ///     err.kind(),
+    ///     ConsumerErrorKind::SizeMismatch { expected: 32, found: 64 }
+    /// ));
+    /// assert!(!RING_BUF.host_attached());
+    ///
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    /// let device = ProducerDevice::new_expect_size(host, address, 64).unwrap();
+    /// assert_eq!(device.capacity(), 63);
+    /// # }
+    /// ```
+    pub fn new_expect_size(
+        mut memory_reader: M,
+        ram_start_address: usize,
+        expected_size: usize,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        let id = layout::DEFAULT_ID;
+        let header = Header::read(&mut memory_reader, ram_start_address, id)?;
+        if header.rb_size != expected_size {
+            return Err(ConsumerError(ConsumerErrorKind::SizeMismatch {
+                expected: expected_size,
+                found: header.rb_size,
+            }));
         }
+        Self::from_header(memory_reader, ram_start_address, id, header)
+    }
 
-        let mut buf = [0; 1];
-        memory_reader
-            .read_memory(ram_start_address + ADDR_SIZE, &mut buf)
-            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+    /// Same as [`ProducerDevice::new`], but only accepts a ring buffer of the layout
+    /// `expected`, e.g. the `LAYOUT` of the producer struct, shared with the firmware. The
+    /// magic marker, the version and the id must be those of the layout, or this fails as
+    /// `new` would, and its optional fields those of the features byte, or this fails with
+    /// [`ConsumerErrorKind::FeaturesMismatch`]. [`ProducerDevice::resync`] then only
+    /// accepts this layout as well.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::{AtomicRB, RB16};
+    /// use ramlink::LayoutDescriptor;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let layout = AtomicRB::<64>::LAYOUT;
+    /// let mut device = ProducerDevice::new_with_layout(host, address, layout).unwrap();
+    /// RING_BUF.send_bytes_blocking(b"hi");
+    /// assert_eq!(device.read_bytes().unwrap(), b"hi");
+    ///
+    /// // Not the width of the indices the host was built for
+    /// let rb16 = LayoutDescriptor::v1().with_u16_indices();
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    /// let err = ProducerDevice::new_with_layout(host, address, rb16).err().unwrap();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::MagicMarkerNotFound));
+    ///
+    /// // Nor the optional fields
+    /// let rb = Box::into_raw(Box::new(RB16::<64>::new()));
+    /// # let host = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RB16<64>>()) };
+    /// let heartbeat = rb16.with_heartbeat();
+    /// let err = ProducerDevice::new_with_layout(host, rb as usize, heartbeat)
+    ///     .err()
+    ///     .unwrap();
+    /// assert!(matches!(
+    ///     err.kind(),
+    ///     ConsumerErrorKind::FeaturesMismatch { expected: 2, found: 0 }
+    /// ));
+    /// # drop(unsafe { Box::from_raw(rb) });
+    /// # }
+    /// ```
+    pub fn new_with_layout(
+        mut memory_reader: M,
+        ram_start_address: usize,
+        expected: LayoutDescriptor,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        let header = Header::read_expected(&mut memory_reader, ram_start_address, &expected)?;
+        let mut device =
+            Self::from_header(memory_reader, ram_start_address, expected.id(), header)?;
+        device.expected_layout = Some(expected);
+        Ok(device)
+    }
 
-        let rb_size = buf[0];
-        if rb_size == 0 {
-            return Err(ConsumerError(ConsumerErrorKind::RingBufferSizeNull));
-        }
+    /// Attaches to the ring buffer whose header was just read
+    fn from_header(
+        mut memory_reader: M,
+        ram_start_address: usize,
+        expected_id: u8,
+        header: Header,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        debug!(
+            "Ring buffer at {:#x}: version {}, size {}",
+            ram_start_address, header.version, header.rb_size
+        );
+        let name = header.read_name(&mut memory_reader)?;
 
-        // XXX logging
-        println!("The RB is of size {}", rb_size);
+        let mut device = ProducerDevice {
+            ram_start: ram_start_address,
+            memory_reader,
+            id: expected_id,
+            rb_size: header.rb_size,
+            layout: header.layout,
+            expected_layout: None,
+            content: header.content,
+            last_dropped: 0,
+            version: header.version,
+            features: header.features,
+            name,
+            #[cfg(feature = "std")]
+            last_heartbeat: None,
+            ack_threshold: 1,
+            pending_ack: None,
+            integrity_check: 0,
+            unchecked_reads: 0,
+            cache_consumer: false,
+            cached_consumer: None,
+            #[cfg(feature = "alloc")]
+            block_size: usize::MAX,
+            #[cfg(feature = "alloc")]
+            read_position: None,
+            stats: ConsumerStats::default(),
+            #[cfg(feature = "std")]
+            rate_window: (Instant::now(), 0),
+            attempts: 1,
+            #[cfg(feature = "std")]
+            backoff: Duration::ZERO,
+        };
+        device.attach()?;
+        Ok(device)
+    }
 
-        Ok(ProducerDevice {
+    /// Attaches to an `RBCompact` of size `rb_size` at `ram_start_address`. Such a ring
+    /// buffer has no header, so nothing can be checked: reading from a wrong address or
+    /// with a wrong size returns garbage, or fails with [`ConsumerErrorKind::CorruptIndex`]
+    /// at best.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::RBCompact;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// // The consumer accesses the ring buffer meanwhile, so it is only borrowed for a call
+    /// let rb: *mut RBCompact<16> = Box::into_raw(Box::new(RBCompact::new()));
+    /// # let host = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RBCompact<16>>()) };
+    /// let mut device = ProducerDevice::new_unchecked(host, rb as usize, 16).unwrap();
+    /// assert!(unsafe { (*rb).host_attached() });
+    ///
+    /// unsafe { (*rb).send_bytes_blocking(b"hello") };
+    /// assert_eq!(device.read_bytes().unwrap(), b"hello");
+    /// # }
+    /// ```
+    ///
+    /// Fails with [`ConsumerErrorKind::RingBufferTooSmall`] if `rb_size` is 1.
+    ///
+    /// # Panics
+    /// Panics if `rb_size` is not within `1..=256`.
+    pub fn new_unchecked(
+        memory_reader: M,
+        ram_start_address: usize,
+        rb_size: usize,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        assert!(
+            (1..=256).contains(&rb_size),
+            "RBCompact size must be within 1..=256"
+        );
+        if rb_size == 1 {
+            return Err(ConsumerError(ConsumerErrorKind::RingBufferTooSmall));
+        }
+        let mut device = ProducerDevice {
             ram_start: ram_start_address,
             memory_reader,
+            id: layout::DEFAULT_ID,
             rb_size,
-        })
+            layout: &layout::RB_COMPACT,
+            expected_layout: None,
+            content: ram_start_address + layout::RB_COMPACT.content,
+            last_dropped: 0,
+            version: 0,
+            features: 0,
+            name: [0; layout::NAME_LEN],
+            #[cfg(feature = "std")]
+            last_heartbeat: None,
+            ack_threshold: 1,
+            pending_ack: None,
+            integrity_check: 0,
+            unchecked_reads: 0,
+            cache_consumer: false,
+            cached_consumer: None,
+            #[cfg(feature = "alloc")]
+            block_size: usize::MAX,
+            #[cfg(feature = "alloc")]
+            read_position: None,
+            stats: ConsumerStats::default(),
+            #[cfg(feature = "std")]
+            rate_window: (Instant::now(), 0),
+            attempts: 1,
+            #[cfg(feature = "std")]
+            backoff: Duration::ZERO,
+        };
+        device.attach()?;
+        Ok(device)
+    }
+
+    /// Re-reads the header of the ring buffer and discards everything cached from it, e.g.
+    /// after the producer was reset or reflashed with a different ring buffer size. The
+    /// dropped bytes, wraps and heartbeat tracking start over.
+    ///
+    /// The producer may be reset in the middle of a [`ProducerDevice::read_bytes`]: the
+    /// bytes returned are then stale, and the consumer index written back is that of the
+    /// old stream, so the next read may return up to a full ring buffer of stale bytes.
+    /// Indices are always checked against the size of the ring buffer, so this never reads
+    /// out of it. Indices that are out of it make reads fail with
+    /// [`ConsumerErrorKind::CorruptIndex`], until the producer resets the ring buffer:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::{MemoryReader, ProducerDevice};
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// # let mut host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// // Garbage in the consumer index, e.g. a glitch of the debug link
+    /// host.write_memory(address + 5, 200).unwrap();
+    /// assert!(device.read_bytes().is_err());
+    ///
+    /// RING_BUF.reset();
+    /// device.resync().unwrap();
+    /// RING_BUF.send_bytes_blocking(b"back");
+    /// assert_eq!(device.read_bytes().unwrap(), b"back");
+    /// # }
+    /// ```
+    pub fn resync(&mut self) -> Result<(), ConsumerError<M::Error>> {
+        // Without a header, there is nothing to re-read
+        if self.layout.header.is_some() {
+            let header = self.read_header()?;
+            debug!(
+                "Resynced, version {}, size {}",
+                header.version, header.rb_size
+            );
+            self.rb_size = header.rb_size;
+            self.layout = header.layout;
+            self.content = header.content;
+            self.name = header.read_name(&mut self.memory_reader)?;
+            self.version = header.version;
+            self.features = header.features;
+        }
+        #[cfg(feature = "std")]
+        {
+            self.last_heartbeat = None;
+        }
+        #[cfg(feature = "alloc")]
+        {
+            self.read_position = None;
+        }
+        self.pending_ack = None;
+        self.unchecked_reads = 0;
+        self.cached_consumer = None;
+        self.attach()
+    }
+
+    /// Reads the header of the ring buffer, of the layout given to
+    /// [`ProducerDevice::new_with_layout`] if any
+    fn read_header(&mut self) -> Result<Header, ConsumerError<M::Error>> {
+        match &self.expected_layout {
+            Some(expected) => {
+                Header::read_expected(&mut self.memory_reader, self.ram_start, expected)
+            }
+            None => Header::read(&mut self.memory_reader, self.ram_start, self.id),
+        }
+    }
+
+    /// Checks every `reads` reads that the producer was not reset, and that the header of
+    /// its ring buffer did not change. Reads fail with [`ConsumerErrorKind::Desynchronized`]
+    /// otherwise, instead of returning bytes from a stream that started over, until
+    /// [`ProducerDevice::resync`] is called. 0, the default, disables the check.
+    ///
+    /// A check reads the header again, and the host-attached flag, which a reset of the
+    /// producer clears: this costs a few more transfers with the [`MemoryReader`]. Peeks
+    /// count as reads. Dropping another device reading the same ring buffer also clears the
+    /// flag, and is then taken for a reset.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::RB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// let rb: *mut RB<8> = Box::into_raw(Box::new(RB::new()));
+    /// # let host = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RB<8>>()) };
+    /// let mut device = ProducerDevice::new(host, rb as usize).unwrap();
+    /// device.set_integrity_check(1);
+    ///
+    /// unsafe { (*rb).send_bytes_blocking(b"abc") };
+    /// assert_eq!(device.read_bytes().unwrap(), b"abc");
+    ///
+    /// // The producer reboots and sends again
+    /// unsafe {
+    ///     rb.write(RB::new());
+    ///     (*rb).send_bytes_blocking(b"hello");
+    /// }
+    /// let err = device.read_bytes().unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::Desynchronized));
+    ///
+    /// device.resync().unwrap();
+    /// assert_eq!(device.read_bytes().unwrap(), b"hello");
+    /// # }
+    /// ```
+    pub fn set_integrity_check(&mut self, reads: usize) {
+        self.integrity_check = reads;
+        self.unchecked_reads = 0;
+    }
+
+    /// Checks the ring buffer if it is due, see [`ProducerDevice::set_integrity_check`]
+    fn check_integrity(&mut self) -> Result<(), ConsumerError<M::Error>> {
+        if self.integrity_check == 0 {
+            return Ok(());
+        }
+        self.unchecked_reads += 1;
+        if self.unchecked_reads < self.integrity_check {
+            return Ok(());
+        }
+
+        // The producer may have moved the consumer index when resetting
+        self.cached_consumer = None;
+        let desynchronized = ConsumerError(ConsumerErrorKind::Desynchronized);
+        if self.layout.header.is_some() {
+            let header = match self.read_header() {
+                Ok(header) => header,
+                Err(ConsumerError(ConsumerErrorKind::ReadMemoryError(e))) => {
+                    return Err(ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))
+                }
+                Err(_) => return Err(desynchronized),
+            };
+            if !core::ptr::eq(header.layout, self.layout)
+                || header.rb_size != self.rb_size
+                || header.content != self.content
+                || header.version != self.version
+                || header.features != self.features
+            {
+                return Err(desynchronized);
+            }
+        }
+        if let Some(host_attached) = self.layout.host_attached {
+            if self.read_one_byte(self.field_address(host_attached))? == 0 {
+                return Err(desynchronized);
+            }
+        }
+        // Only once the check passed, so that every read fails until the device is resynced
+        self.unchecked_reads = 0;
+        Ok(())
+    }
+
+    /// Returns the address right after the ring buffer, its optional fields included. That
+    /// is where the `RxRB` of a `DuplexRB` is, to attach a [`HostWriter`] to.
+    pub fn end_address(&self) -> usize {
+        self.content + self.rb_size + self.layout.after_content + layout::trailer_len(self.features)
+    }
+
+    /// Returns the address of the field at `offset` of the ring buffer
+    fn field_address(&self, offset: Offset) -> usize {
+        offset.address(self.ram_start, self.content, self.rb_size)
+    }
+
+    /// Returns the version of the ring buffer layout. Ring buffers written before the
+    /// layout had a version byte are version 0, as are the headerless `RBCompact`.
+    ///
+    /// A version 0 `RB<4>` holding `"hi"`, as dumped from a firmware built with the first
+    /// releases of this crate, whose `RB` has nothing but the magic marker, the size and
+    /// the indices before its content, and nothing after it:
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use ramlink::consumer::testing::InMemoryReader;
+    /// use ramlink::consumer::ProducerDevice;
+    ///
+    /// let dump = vec![
+    ///     0x88, 0x88, 0x88, // magic marker
+    ///     4,    // size
+    ///     2, 0, // producer, consumer
+    ///     b'h', b'i', 0x13, 0x13,
+    /// ];
+    /// let memory = InMemoryReader::new(dump.clone());
+    /// let mut device = ProducerDevice::new(memory.clone(), 0).unwrap();
+    /// assert_eq!(device.version(), 0);
+    /// assert_eq!(device.capacity(), 3);
+    /// assert_eq!(device.read_bytes().unwrap(), b"hi");
+    /// assert_eq!(device.dropped_bytes().unwrap(), 0);
+    /// drop(device);
+    ///
+    /// // Only the consumer index was written, there is no host-attached flag to clear
+    /// let mut read = dump;
+    /// read[5] = 2;
+    /// assert_eq!(*memory.memory(), read);
+    /// # }
+    /// ```
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the name the producer gave to the ring buffer with `RB::new_named`, or
+    /// `None` if it has none, or if it is not UTF-8. Only the ring buffers built with the
+    /// `names` feature can have one.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "names"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// static TELEMETRY: AtomicRB<32> = AtomicRB::new_named("telem");
+    /// static LOGS: AtomicRB<32> = AtomicRB::new();
+    ///
+    /// # let host = VolatileReader::from_atomic_rb(&TELEMETRY);
+    /// let device = ProducerDevice::new(host, &TELEMETRY as *const _ as usize).unwrap();
+    /// assert_eq!(device.name(), Some("telem"));
+    /// # let host = VolatileReader::from_atomic_rb(&LOGS);
+    /// let device = ProducerDevice::new(host, &LOGS as *const _ as usize).unwrap();
+    /// assert_eq!(device.name(), None);
+    /// # }
+    /// ```
+    pub fn name(&self) -> Option<&str> {
+        name_str(&self.name)
+    }
+
+    /// Takes the current dropped counter as reference, and sets the host-attached flag
+    fn attach(&mut self) -> Result<(), ConsumerError<M::Error>> {
+        self.last_dropped = self.read_dropped()?;
+        match self.layout.host_attached {
+            Some(host_attached) => self.write_memory_slice(self.field_address(host_attached), &[1]),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the dropped counter, always 0 for the layouts without one
+    fn read_dropped(&mut self) -> Result<u16, ConsumerError<M::Error>> {
+        let Some(dropped) = self.layout.dropped else {
+            return Ok(0);
+        };
+        let mut buf = [0u8; 2];
+        self.read_memory(self.field_address(dropped), &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Returns the number of bytes the producer discarded since the last call, or since
+    /// the device was attached for the first call. The producer counter wraps around at
+    /// `u16::MAX`, so this must be called before 65536 more bytes are dropped.
+    pub fn dropped_bytes(&mut self) -> Result<u16, ConsumerError<M::Error>> {
+        let dropped = self.read_dropped()?;
+        let delta = dropped.wrapping_sub(self.last_dropped);
+        self.last_dropped = dropped;
+        Ok(delta)
     }
 
     /// Reads one byte at the specified memory address. A wragger against [`read_memory`].
-    fn read_one_byte(&mut self, address: usize) -> Result<u8, ConsumerError> {
+    fn read_one_byte(&mut self, address: usize) -> Result<u8, ConsumerError<M::Error>> {
         let mut buf = [0u8; 1];
-        self.memory_reader
-            .read_memory(address, &mut buf)
-            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        self.read_memory(address, &mut buf)?;
         Ok(buf[0])
     }
 
+    /// Wraps `index` around the ring buffer. Most ring buffers have a power of two size, so
+    /// the modulo can be avoided.
+    fn wrap_index(&self, index: usize) -> usize {
+        if self.rb_size.is_power_of_two() {
+            index & (self.rb_size - 1)
+        } else {
+            index % self.rb_size
+        }
+    }
+
+    /// Reads the producer and consumer indices at once, checking that they are within the
+    /// ring buffer. In all layouts, the consumer index follows the producer one. Only the
+    /// producer index is read if the consumer one is cached.
+    fn read_indices(&mut self) -> Result<(usize, usize), ConsumerError<M::Error>> {
+        let width = self.layout.index_width;
+        let mut buf = [0u8; 4];
+        let buf = match self.cached_consumer {
+            Some(_) => &mut buf[..width],
+            None => &mut buf[..2 * width],
+        };
+        self.read_memory(self.ram_start + self.layout.producer, buf)?;
+        let prod_v = le_index(&buf[..width]);
+        let cons_v = self
+            .cached_consumer
+            .unwrap_or_else(|| le_index(&buf[width..]));
+        if prod_v >= self.rb_size || cons_v >= self.rb_size {
+            self.cached_consumer = None;
+            return Err(ConsumerError(ConsumerErrorKind::CorruptIndex {
+                producer: prod_v,
+                consumer: cons_v,
+                size: self.rb_size,
+            }));
+        }
+        if self.cache_consumer {
+            self.cached_consumer = Some(cons_v);
+        }
+        Ok((prod_v, cons_v))
+    }
+
+    /// Reads `buf.len()` bytes of content starting at slot `from`, with one read, or two if
+    /// they wrap around the end of the ring buffer
+    fn read_content(&mut self, from: usize, buf: &mut [u8]) -> Result<(), ConsumerError<M::Error>> {
+        let content = self.content;
+        let (first, wrapped) = buf.split_at_mut(buf.len().min(self.rb_size - from));
+        for (address, part) in [(content + from, first), (content, wrapped)] {
+            if !part.is_empty() {
+                self.read_memory(address, part)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the largest transfer with the [`MemoryReader`] when reading whole spans of
+    /// memory, as [`ProducerDevice::dump`] does, for interfaces that cap their transfers.
+    /// Unlimited by default.
+    ///
+    /// A transfer that fails is retried in halves, down to single bytes, and the smaller
+    /// size is kept for the rest of the span, so this is only needed to avoid the failed
+    /// attempts.
+    #[cfg(feature = "alloc")]
+    pub fn set_block_size(&mut self, bytes: usize) {
+        self.block_size = bytes.max(1);
+    }
+
+    /// Writes the consumer index. With the default [`MemoryReader::write_memory_slice`], a
+    /// 16 bits index is written low byte first.
+    fn write_consumer_index(&mut self, index: usize) -> Result<(), ConsumerError<M::Error>> {
+        let bytes = index.to_le_bytes();
+        // A failed write may have written part of a 16 bits index
+        self.cached_consumer = None;
+        self.write_memory_slice(
+            self.ram_start + self.layout.consumer,
+            &bytes[..self.layout.index_width],
+        )?;
+        if self.cache_consumer {
+            self.cached_consumer = Some(index);
+        }
+        Ok(())
+    }
+
+    /// Keeps the consumer index of the ring buffer in the device instead of reading it
+    /// along with the producer index on each read, which saves a byte, or two with `RB16`,
+    /// per read. Disabled by default.
+    ///
+    /// Only the consumer moves that index, except when the producer resets the ring buffer
+    /// or overwrites old bytes with `send_bytes_overwrite`: the cache is then stale, and
+    /// reads return garbage until [`ProducerDevice::refresh`] is called. The cache is
+    /// dropped when [`ProducerDevice::set_integrity_check`] checks the ring buffer, or when
+    /// a read fails with [`ConsumerErrorKind::CorruptIndex`], so enabling the integrity
+    /// check makes up for resets.
+    pub fn set_consumer_cache(&mut self, enabled: bool) {
+        self.cache_consumer = enabled;
+        self.cached_consumer = None;
+    }
+
+    /// Drops the consumer index cached with [`ProducerDevice::set_consumer_cache`], so that
+    /// the next read reads it from the ring buffer again
+    pub fn refresh(&mut self) {
+        self.cached_consumer = None;
+    }
+
+    /// Sets how many bytes may be read before the consumer index is written back to the
+    /// producer, 1 by default. Writes are much slower than reads on some interfaces, e.g.
+    /// UPDI, so this saves time at the cost of free space on the producer side, until
+    /// [`ProducerDevice::ack`] is called or the threshold is reached.
+    ///
+    /// The index is always written back when the producer sees a full ring buffer, so that
+    /// `send_bytes_blocking` can't wait forever. A producer waiting for more than one free
+    /// byte, e.g. with `send_u32_le`, can still wait until the next `ack`:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// device.set_ack_threshold(64);
+    ///
+    /// RING_BUF.send_bytes_blocking(b"abc");
+    /// assert_eq!(device.read_bytes().unwrap(), b"abc");
+    /// // Not acknowledged yet, the producer still sees 3 bytes in the ring buffer
+    /// assert_eq!(RING_BUF.free_space(), 4);
+    ///
+    /// // The producer now sees a full ring buffer, so the next read acknowledges
+    /// RING_BUF.send_bytes_blocking(b"defg");
+    /// assert!(RING_BUF.is_full());
+    /// assert_eq!(device.read_bytes().unwrap(), b"defg");
+    /// assert!(RING_BUF.is_empty());
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hi");
+    /// assert_eq!(device.read_bytes().unwrap(), b"hi");
+    /// device.ack().unwrap();
+    /// assert!(RING_BUF.is_empty());
+    /// # }
+    /// ```
+    pub fn set_ack_threshold(&mut self, bytes: usize) {
+        self.ack_threshold = bytes;
+    }
+
+    /// Writes the consumer index back to the producer, if bytes were read since it last
+    /// was. See [`ProducerDevice::set_ack_threshold`].
+    pub fn ack(&mut self) -> Result<(), ConsumerError<M::Error>> {
+        if let Some((index, _)) = self.pending_ack {
+            self.write_consumer_index(index)?;
+            self.pending_ack = None;
+        }
+        Ok(())
+    }
+
+    /// Makes each transfer with the [`MemoryReader`] up to `attempts` times before failing,
+    /// instead of once, for interfaces with transient errors. With the `std` feature, the
+    /// device sleeps `backoff` before the first retry, and twice as long before each of the
+    /// next ones.
+    ///
+    /// Whatever the errors, no byte is ever returned twice or lost: a read that fails
+    /// returns no byte and consumes none, except when the consumer index can't be written
+    /// back. The read then returns the bytes it consumed, and the write is retried before
+    /// the next read.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub fn set_retry(&mut self, attempts: usize, backoff: Duration) {
+        self.attempts = attempts.max(1);
+        #[cfg(feature = "std")]
+        {
+            self.backoff = backoff;
+        }
+    }
+
+    /// Calls `transfer` with the memory reader until it succeeds, at most as many times as
+    /// set by [`ProducerDevice::set_retry`]
+    fn with_retry(
+        &mut self,
+        mut transfer: impl FnMut(&mut M) -> Result<(), M::Error>,
+    ) -> Result<(), M::Error> {
+        #[cfg(feature = "std")]
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match transfer(&mut self.memory_reader) {
+                Err(_) if attempt < self.attempts => {
+                    attempt += 1;
+                    #[cfg(feature = "std")]
+                    {
+                        std::thread::sleep(backoff);
+                        backoff *= 2;
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reads `buf.len()` bytes at `address`, see [`ProducerDevice::with_retry`]
+    fn read_memory(
+        &mut self,
+        address: usize,
+        buf: &mut [u8],
+    ) -> Result<(), ConsumerError<M::Error>> {
+        self.with_retry(|reader| reader.read_memory(address, buf))
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))
+    }
+
+    /// Writes `data` at `address`, see [`ProducerDevice::with_retry`]
+    fn write_memory_slice(
+        &mut self,
+        address: usize,
+        data: &[u8],
+    ) -> Result<(), ConsumerError<M::Error>> {
+        self.with_retry(|reader| reader.write_memory_slice(address, data))
+            .map_err(|e| ConsumerError(ConsumerErrorKind::WriteMemoryError(e)))
+    }
+
+    /// Returns the consumer index as seen by this device, given the one in the ring buffer
+    fn local_consumer(&self, remote: usize) -> usize {
+        self.pending_ack.map_or(remote, |(index, _)| index)
+    }
+
+    /// Moves the consumer index past `n` more bytes, and writes it back to the producer if
+    /// enough bytes are unacknowledged, or if the producer was waiting for space
+    fn consume(&mut self, n: usize, prod_v: usize, remote_cons: usize) {
+        let (cons_v, unacked) = self.pending_ack.unwrap_or((remote_cons, 0));
+        self.pending_ack = Some((self.wrap_index(cons_v + n), unacked + n));
+        let producer_full = self.wrap_index(prod_v + 1) == remote_cons;
+        if unacked + n >= self.ack_threshold || producer_full {
+            // The bytes were read either way, so they must be returned: if the write fails,
+            // it is retried before the next read, which fails instead
+            let _ = self.ack();
+        }
+    }
+
+    /// Returns the address of the optional field `feature`, or an error if the producer
+    /// was built without it
+    fn trailer_address(&self, feature: u8) -> Result<usize, ConsumerError<M::Error>> {
+        let offset = layout::trailer_offset(self.features, feature)
+            .ok_or(ConsumerError(ConsumerErrorKind::FeatureNotEnabled))?;
+        Ok(self.content + self.rb_size + self.layout.after_content + offset)
+    }
+
+    /// Reads the statistics kept by the producer. They only exist if the producer is an
+    /// [`RB`] built with the `stats` feature, otherwise [`ConsumerErrorKind::FeatureNotEnabled`]
+    /// is returned.
+    pub fn producer_stats(&mut self) -> Result<ProducerStats, ConsumerError<M::Error>> {
+        let address = self.trailer_address(layout::FEATURE_STATS)?;
+        let mut buf = [0u8; layout::STATS_LEN];
+        self.read_memory(address, &mut buf)?;
+        Ok(ProducerStats {
+            high_water: buf[0],
+            total_sent: u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]),
+        })
+    }
+
+    /// Reads the heartbeat counter, incremented by `RB::tick` on the producer. It only
+    /// exists if the producer was built with the `heartbeat` feature.
+    pub fn heartbeat(&mut self) -> Result<u8, ConsumerError<M::Error>> {
+        let address = self.trailer_address(layout::FEATURE_HEARTBEAT)?;
+        self.read_one_byte(address)
+    }
+
+    /// Reads the wrap count of the producer, incremented each time its index goes back to
+    /// the start of the content. It only exists if the producer was built with the `wraps`
+    /// feature, see [`ProducerDevice::read_events`].
+    pub fn wraps(&mut self) -> Result<u16, ConsumerError<M::Error>> {
+        let address = self.trailer_address(layout::FEATURE_WRAPS)?;
+        let mut buf = [0; layout::WRAPS_LEN];
+        self.read_memory(address, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Returns `false` if the heartbeat did not change for `window`, i.e. if the producer
+    /// seems hung rather than just quiet. The window must be longer than the tick period,
+    /// and this must be polled more often than the counter wraps around. The first call
+    /// always returns `true`.
+    #[cfg(feature = "std")]
+    pub fn is_alive(&mut self, window: Duration) -> Result<bool, ConsumerError<M::Error>> {
+        let heartbeat = self.heartbeat()?;
+        let now = Instant::now();
+        match self.last_heartbeat {
+            Some((last, changed_at)) if last == heartbeat => Ok(now - changed_at < window),
+            _ => {
+                self.last_heartbeat = Some((heartbeat, now));
+                Ok(true)
+            }
+        }
+    }
+
+    /// Reads exactly `buf.len()` bytes if that many are waiting in the RB struct. Otherwise,
+    /// nothing is consumed and `false` is returned. Like [`ProducerDevice::read_into`], the
+    /// ring buffer is checked if it is due, and a failed write of the consumer index retried.
+    fn read_value(&mut self, buf: &mut [u8]) -> Result<bool, ConsumerError<M::Error>> {
+        self.prepare_read()?;
+        let (copied, prod_v, remote_cons) = self.copy_pending(buf)?;
+        if copied < buf.len() {
+            self.record_read(0);
+            return Ok(false);
+        }
+        self.consume(copied, prod_v, remote_cons);
+        self.record_read(copied);
+        Ok(true)
+    }
+
+    le_readers! {
+        u16 => read_u16_le;
+        i16 => read_i16_le;
+        u32 => read_u32_le;
+        i32 => read_i32_le;
+        f32 => read_f32_le;
+    }
+
+    /// Reads the bytes waiting in the RB struct into `buf`, as many as fit. By doing so it
+    /// consumes them from the producer struct and frees some space in the process. Returns
+    /// the number of bytes read.
+    ///
+    /// This takes at most four transfers with the [`MemoryReader`]: one read of the indices,
+    /// one or two reads of the content, depending on whether the bytes wrap around the end
+    /// of the ring buffer, and one write of the consumer index, unless it is deferred by
+    /// [`ProducerDevice::set_ack_threshold`].
+    ///
+    /// The consumer index is only written once the bytes were copied, so the producer never
+    /// reuses slots that were not delivered. If a transfer fails:
+    /// - reading the indices or the content: nothing is consumed, and the error is returned;
+    /// - reading the second part of bytes that wrap around: the first part is returned, and
+    ///   only it is consumed;
+    /// - writing the consumer index: the bytes are returned anyway, and the write is retried
+    ///   by the next read.
+    ///
+    /// Whatever the memory holds, a read returns at most [`ProducerDevice::capacity`] bytes,
+    /// or fails, e.g. with [`ConsumerErrorKind::CorruptIndex`], but never reads outside of
+    /// the ring buffer.
+    pub fn read_into(&mut self, buf: &mut [u8]) -> Result<usize, ConsumerError<M::Error>> {
+        self.prepare_read()?;
+        let (read, prod_v, remote_cons) = self.copy_pending(buf)?;
+        if read > 0 {
+            self.consume(read, prod_v, remote_cons);
+        }
+        self.record_read(read);
+        Ok(read)
+    }
+
+    /// Returns statistics of the reads since the device attached, or since the last call to
+    /// [`ProducerDevice::reset_stats`], e.g. to tune the poll interval. Every read counts,
+    /// whatever the method, but peeks don't.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hello");
+    /// device.read_bytes().unwrap();
+    /// device.read_bytes().unwrap();
+    /// RING_BUF.send_bytes_blocking(&7u16.to_le_bytes());
+    /// device.read_u16_le().unwrap();
+    ///
+    /// let stats = device.stats();
+    /// assert_eq!(stats.total_bytes, 7);
+    /// assert_eq!((stats.polls, stats.empty_polls), (3, 1));
+    /// assert_eq!(stats.max_read, 5);
+    ///
+    /// device.reset_stats();
+    /// assert_eq!(device.stats().polls, 0);
+    /// # }
+    /// ```
+    pub fn stats(&self) -> ConsumerStats {
+        self.stats
+    }
+
+    /// Sets all the statistics of [`ProducerDevice::stats`] back to 0
+    pub fn reset_stats(&mut self) {
+        self.stats = ConsumerStats::default();
+        #[cfg(feature = "std")]
+        {
+            self.rate_window = (Instant::now(), 0);
+        }
+    }
+
+    /// Counts a sleep of a run loop, after `empty_reads` empty reads in a row, in the
+    /// statistics
+    #[cfg(any(feature = "std", feature = "async"))]
+    fn record_sleep(&mut self, empty_reads: u32) {
+        let sleeps = &mut self.stats.sleeps;
+        sleeps[(empty_reads as usize).min(sleeps.len() - 1)] += 1;
+    }
+
+    /// Counts a read of `read` bytes in the statistics
+    fn record_read(&mut self, read: usize) {
+        let stats = &mut self.stats;
+        stats.polls += 1;
+        if read == 0 {
+            stats.empty_polls += 1;
+        }
+        stats.total_bytes += read as u64;
+        stats.max_read = stats.max_read.max(read);
+
+        #[cfg(feature = "std")]
+        {
+            let (start, bytes) = &mut self.rate_window;
+            *bytes += read as u64;
+            let elapsed = start.elapsed();
+            if elapsed >= RATE_WINDOW {
+                stats.bytes_per_second = *bytes as f64 / elapsed.as_secs_f64();
+                self.rate_window = (Instant::now(), 0);
+            }
+        }
+    }
+
+    /// Copies the bytes waiting in the RB struct into `buf`, as many as fit, without
+    /// consuming them: the producer does not see it, and the bytes are still there for the
+    /// next read. Returns the number of bytes copied.
+    ///
+    /// The producer may be sending meanwhile, so a later peek may return more bytes, but
+    /// always starting with the ones returned by this one.
+    ///
+    /// A peek only reads the indices and the content. It never writes to the target, not
+    /// even a consumer index whose write failed, nor checks the ring buffer, which are left
+    /// to the next read.
+    pub fn peek_into(&mut self, buf: &mut [u8]) -> Result<usize, ConsumerError<M::Error>> {
+        Ok(self.copy_pending(buf)?.0)
+    }
+
+    /// Same as [`ProducerDevice::peek_into`], returning all the bytes waiting in a `Vec`.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hel");
+    /// assert_eq!(device.peek_bytes().unwrap(), b"hel");
+    /// RING_BUF.send_bytes_blocking(b"lo");
+    /// assert_eq!(device.peek_available().unwrap(), 5);
+    /// assert_eq!(device.capacity(), 15);
+    /// assert_eq!(device.peek_bytes().unwrap(), b"hello");
+    /// assert_eq!(RING_BUF.len(), 5);
+    ///
+    /// assert_eq!(device.read_bytes().unwrap(), b"hello");
+    /// assert!(device.peek_bytes().unwrap().is_empty());
+    /// # }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn peek_bytes(&mut self) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let mut bytes = alloc::vec![0; self.rb_size];
+        let peeked = self.peek_into(&mut bytes)?;
+        bytes.truncate(peeked);
+        Ok(bytes)
+    }
+
+    /// Returns the number of bytes waiting in the RB struct, without consuming them. Same
+    /// as [`ProducerDevice::available`].
+    pub fn peek_available(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        self.available()
+    }
+
+    /// Returns the number of bytes waiting in the RB struct, i.e. that the next read would
+    /// return. This only reads the indices.
+    pub fn available(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        let (prod_v, remote_cons) = self.read_indices()?;
+        Ok(self.pending(prod_v, self.local_consumer(remote_cons)))
+    }
+
+    /// Returns the number of bytes the RB struct can hold, which is one less than its size
+    pub fn capacity(&self) -> usize {
+        self.rb_size - 1
+    }
+
+    /// Returns the number of bytes between the consumer index `cons_v` and the producer
+    /// index `prod_v`
+    fn pending(&self, prod_v: usize, cons_v: usize) -> usize {
+        // Checked indices never give more, but a read must never go past the ring buffer
+        self.wrap_index(prod_v + self.rb_size - cons_v)
+            .min(self.capacity())
+    }
+
+    /// Before a read that consumes bytes, checks the ring buffer if it is due, and writes
+    /// the consumer index back if the last write failed
+    fn prepare_read(&mut self) -> Result<(), ConsumerError<M::Error>> {
+        self.check_integrity()?;
+        if self
+            .pending_ack
+            .is_some_and(|(_, unacked)| unacked >= self.ack_threshold)
+        {
+            // The last write of the consumer index failed
+            self.ack()?;
+        }
+        Ok(())
+    }
+
+    /// Copies the bytes waiting into `buf`, as many as fit. Returns their number, and the
+    /// producer and consumer indices read from the ring buffer. This only reads the indices
+    /// and the content, so that peeks never write to the target.
+    fn copy_pending(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, usize, usize), ConsumerError<M::Error>> {
+        let (prod_v, remote_cons) = self.read_indices()?;
+        debug!("Producer index {}, consumer index {}", prod_v, remote_cons);
+        let cons_v = self.local_consumer(remote_cons);
+
+        let mut copied = self.pending(prod_v, cons_v).min(buf.len());
+        if copied > 0 {
+            // Bytes that wrap around are read in two parts: if the second read fails, the
+            // first part is still returned, and the next read starts with the second one
+            let first = copied.min(self.rb_size - cons_v);
+            self.read_content(cons_v, &mut buf[..first])?;
+            if first < copied && self.read_content(0, &mut buf[first..copied]).is_err() {
+                copied = first;
+            }
+        }
+        Ok((copied, prod_v, remote_cons))
+    }
+
+    /// Fills `buf`, polling the ring buffer every `poll` until enough bytes arrived. Fails
+    /// with [`ConsumerErrorKind::Timeout`] if that takes more than `timeout`, in which case
+    /// the bytes that did arrive are at the start of `buf`.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let (poll, timeout) = (Duration::from_millis(1), Duration::from_millis(10));
+    ///
+    /// let mut record = [0; 4];
+    /// RING_BUF.send_bytes_blocking(b"abcdef");
+    /// device.read_exact(&mut record, poll, timeout).unwrap();
+    /// assert_eq!(&record, b"abcd");
+    ///
+    /// let err = device.read_exact(&mut record, poll, timeout).unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::Timeout { got: 2 }));
+    /// assert_eq!(&record[..2], b"ef");
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<(), ConsumerError<M::Error>> {
+        let start = Instant::now();
+        let mut got = 0;
+        loop {
+            got += self.read_into(&mut buf[got..])?;
+            if got == buf.len() {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+        }
+    }
+
+    /// Reads the ring buffer until `on_data` returns [`ControlFlow::Break`], sleeping
+    /// between reads while it is empty, every `poll` if it is a [`Duration`], or backing off
+    /// if it is an adaptive [`PollPolicy`]. `on_data` is called with the bytes of each read, never
+    /// with an empty slice. Errors of the [`MemoryReader`] stop the loop and are returned, so
+    /// that the caller can reconnect and run it again.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use core::ops::ControlFlow;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// let producer = std::thread::spawn(|| RING_BUF.send_bytes_blocking(b"hello world!"));
+    /// let mut received = Vec::new();
+    /// device
+    ///     .run(Duration::from_millis(1), |data| {
+    ///         received.extend_from_slice(data);
+    ///         if received.ends_with(b"!") {
+    ///             ControlFlow::Break(())
+    ///         } else {
+    ///             ControlFlow::Continue(())
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(received, b"hello world!");
+    /// producer.join().unwrap();
+    /// # }
+    /// ```
+    ///
+    /// How long the loop slept is in [`ProducerDevice::stats`], to tune the policy.
+    #[cfg(feature = "std")]
+    pub fn run(
+        &mut self,
+        poll: impl Into<PollPolicy>,
+        on_data: impl FnMut(&[u8]) -> ControlFlow<()>,
+    ) -> Result<(), ConsumerError<M::Error>> {
+        self.run_until(poll, &AtomicBool::new(false), on_data)
+    }
+
+    /// Same as [`ProducerDevice::run`], but also returns once `stop` is set, e.g. by another
+    /// thread through an `Arc<AtomicBool>`. `stop` is checked before each read.
+    ///
+    /// A ring buffer of size 1 can't hold any byte, so the device refuses to attach to it
+    /// with [`ConsumerErrorKind::RingBufferTooSmall`] rather than poll it forever:
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use ramlink::consumer::testing::InMemoryReader;
+    /// use ramlink::consumer::{ConsumerErrorKind, ProducerDevice};
+    ///
+    /// let rb = vec![0x88, 0x88, 0x88, 1, 0, 0, 0x13];
+    /// let err = ProducerDevice::new(InMemoryReader::new(rb), 0).err().unwrap();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::RingBufferTooSmall));
+    /// let err = ProducerDevice::new_unchecked(InMemoryReader::new(vec![0; 6]), 0, 1).err();
+    /// assert!(matches!(err.unwrap().kind(), ConsumerErrorKind::RingBufferTooSmall));
+    /// # }
+    /// ```
+    ///
+    /// Otherwise, it runs until `stop` is set, whether bytes arrive or not:
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use core::ops::ControlFlow;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<8> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// let stop = Arc::new(AtomicBool::new(false));
+    /// let stopper = std::thread::spawn({
+    ///     let stop = stop.clone();
+    ///     move || {
+    ///         std::thread::sleep(Duration::from_millis(20));
+    ///         stop.store(true, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// // Nothing is ever sent
+    /// device
+    ///     .run_until(Duration::from_millis(1), &stop, |_| ControlFlow::Continue(()))
+    ///     .unwrap();
+    /// stopper.join().unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn run_until(
+        &mut self,
+        poll: impl Into<PollPolicy>,
+        stop: &AtomicBool,
+        mut on_data: impl FnMut(&[u8]) -> ControlFlow<()>,
+    ) -> Result<(), ConsumerError<M::Error>> {
+        let poll = poll.into();
+        let mut buf = alloc::vec![0; self.capacity()];
+        let mut empty_reads = 0;
+        while !stop.load(Ordering::Relaxed) {
+            let read = self.read_into(&mut buf)?;
+            if read == 0 {
+                self.record_sleep(empty_reads);
+                std::thread::sleep(poll.interval(empty_reads));
+                empty_reads = empty_reads.saturating_add(1);
+            } else if on_data(&buf[..read]).is_break() {
+                break;
+            } else {
+                empty_reads = 0;
+            }
+        }
+        Ok(())
+    }
+
     /// Reads the maximum number of bytes from the RB struct. By doing so it
     /// consumes the bytes from the producer struct an frees some space in the process.
-    pub fn read_bytes(&mut self) -> Result<Vec<u8>, ConsumerError> {
-        let mut bytes: Vec<u8> = Vec::new();
+    ///
+    /// Like [`ProducerDevice::read_into`], this takes at most four transfers, however many
+    /// bytes are waiting, and a single read when none are.
+    #[cfg(feature = "alloc")]
+    pub fn read_bytes(&mut self) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        self.read_bytes_max(self.rb_size)
+    }
 
-        let prod_a = self.ram_start + ADDR_PROD;
-        let cons_a = self.ram_start + ADDR_CONS;
-        let buff_a = self.ram_start + ADDR_BUFF;
+    /// Same as [`ProducerDevice::read_bytes`], but reads at most `max` bytes, leaving the
+    /// others for the next call. `read_bytes` never returns more than the bytes waiting when
+    /// it is called, at most a full ring buffer, but this bounds the time spent on slow
+    /// interfaces.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    ///
+    /// RING_BUF.send_bytes_blocking(b"hello");
+    /// assert_eq!(device.read_bytes_max(3).unwrap(), b"hel");
+    /// assert_eq!(device.read_bytes_max(3).unwrap(), b"lo");
+    /// # }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn read_bytes_max(&mut self, max: usize) -> Result<Vec<u8>, ConsumerError<M::Error>> {
+        let mut bytes = alloc::vec![0; max.min(self.rb_size)];
+        let read = self.read_into(&mut bytes)?;
+        bytes.truncate(read);
+        Ok(bytes)
+    }
+}
 
-        let prod_v = self.read_one_byte(prod_a)?;
-        let mut cons_v = self.read_one_byte(cons_a)?;
+/// Decodes a little-endian index of one or two bytes
+fn le_index(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |index, &byte| index << 8 | byte as usize)
+}
+
+/// Returns the name padded with zeros in `name`, or `None` if it is empty or not UTF-8
+fn name_str(name: &[u8]) -> Option<&str> {
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    core::str::from_utf8(&name[..len])
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+/// The part of the ring buffer header that the consumer caches
+struct Header {
+    layout: &'static Layout,
+    rb_size: usize,
+    /// Address of the content
+    content: usize,
+    version: u8,
+    features: u8,
+}
+
+impl Header {
+    /// Reads the header of the ring buffer at `ram_start`, checking its magic marker, id and
+    /// version
+    fn read<M: MemoryReader + ?Sized>(
+        memory_reader: &mut M,
+        ram_start: usize,
+        id: u8,
+    ) -> Result<Header, ConsumerError<M::Error>> {
+        let layouts = [
+            &layout::RB,
+            &layout::RB16,
+            &layout::RB_INDIRECT,
+            &layout::RB_V0,
+            &layout::RB16_V0,
+        ];
+        Self::read_any(memory_reader, ram_start, id, &layouts)
+    }
 
-        while prod_v != cons_v {
-            let buff_v = self.read_one_byte(buff_a + cons_v as usize)?;
-            cons_v = (cons_v + 1) % self.rb_size;
-            bytes.push(buff_v);
-            self.memory_reader
-                .write_memory(cons_a, cons_v)
-                .map_err(|e| ConsumerError(ConsumerErrorKind::WriteMemoryError(e)))?;
+    /// Same as [`Header::read`], for a ring buffer of the layout `expected` only, whose
+    /// optional fields must be those of the features byte
+    fn read_expected<M: MemoryReader + ?Sized>(
+        memory_reader: &mut M,
+        ram_start: usize,
+        expected: &LayoutDescriptor,
+    ) -> Result<Header, ConsumerError<M::Error>> {
+        let header = Self::read_any(
+            memory_reader,
+            ram_start,
+            expected.id(),
+            &[expected.layout()],
+        )?;
+        if header.features != expected.features() {
+            return Err(ConsumerError(ConsumerErrorKind::FeaturesMismatch {
+                expected: expected.features(),
+                found: header.features,
+            }));
+        }
+        Ok(header)
+    }
+
+    /// Same as [`Header::read`], for a ring buffer of one of `layouts`
+    fn read_any<M: MemoryReader + ?Sized>(
+        memory_reader: &mut M,
+        ram_start: usize,
+        id: u8,
+        layouts: &[&'static Layout],
+    ) -> Result<Header, ConsumerError<M::Error>> {
+        let mut magic_markers = [0; 3];
+        memory_reader
+            .read_memory(ram_start, &mut magic_markers)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+
+        let mut candidates = layouts
+            .iter()
+            .copied()
+            .filter(|layout| {
+                layout
+                    .header
+                    .as_ref()
+                    .is_some_and(|header| header.magic_prefix == magic_markers[..2])
+            })
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound));
+        }
+        if magic_markers[2] != id {
+            return Err(ConsumerError(ConsumerErrorKind::WrongId(magic_markers[2])));
+        }
+        // `RB` and the `RB` of version 0 share their magic marker, and only the former has
+        // the marker after its content
+        for layout in candidates {
+            if let Some(header) = Self::read_layout(memory_reader, ram_start, layout)? {
+                return Ok(header);
+            }
+        }
+        Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound))
+    }
+
+    /// Reads the header of the ring buffer at `ram_start`, whose magic marker is that of
+    /// `layout`. Returns `None` if `layout` has a [`layout::CONTENT_END_MARKER`] and it is
+    /// not there.
+    fn read_layout<M: MemoryReader + ?Sized>(
+        memory_reader: &mut M,
+        ram_start: usize,
+        layout: &'static Layout,
+    ) -> Result<Option<Header>, ConsumerError<M::Error>> {
+        let Some(header) = &layout.header else {
+            return Ok(None);
+        };
+        let read = |memory_reader: &mut M, address, buf: &mut [u8]| {
+            memory_reader
+                .read_memory(address, buf)
+                .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))
+        };
+
+        let mut version = [0; 1];
+        if let Some(Offset::Start(offset)) = header.version {
+            read(memory_reader, ram_start + offset, &mut version)?;
+            if version[0] != layout::VERSION {
+                return Err(ConsumerError(ConsumerErrorKind::UnsupportedVersion(
+                    version[0],
+                )));
+            }
+        }
+
+        let mut buf = [0; 2];
+        let buf = &mut buf[..layout.index_width];
+        read(memory_reader, ram_start + header.size, buf)?;
+        // For `RB`, indices are single bytes, so a size of 0 stands for a 256 bytes ring buffer.
+        // Wider indices have room for the actual size, so 0 is a zeroed or corrupt header.
+        let rb_size = match le_index(buf) {
+            0 if layout.index_width == 1 => 256,
+            size => size,
+        };
+
+        let content = if layout.indirect {
+            Self::read_content_pointer(memory_reader, ram_start + layout.content)?
+        } else {
+            ram_start + layout.content
+        };
+
+        if let Some(marker) = header.end_marker {
+            // The version follows the marker, checked in the same read. What follows the
+            // content of an `RB` of version 0 may not be readable, e.g. at the end of the RAM
+            let mut end = [0; 2];
+            let address = content + rb_size + marker;
+            if memory_reader.read_memory(address, &mut end).is_err()
+                || end[0] != layout::CONTENT_END_MARKER
+            {
+                return Ok(None);
+            }
+            if end[1] != layout::VERSION {
+                return Err(ConsumerError(ConsumerErrorKind::UnsupportedVersion(end[1])));
+            }
+            version[0] = end[1];
+        }
+
+        if rb_size <= 1 {
+            return Err(ConsumerError(ConsumerErrorKind::RingBufferTooSmall));
+        }
+
+        let mut features = [0; 1];
+        if let Some(offset) = header.features {
+            let address = offset.address(ram_start, content, rb_size);
+            read(memory_reader, address, &mut features)?;
+        }
+
+        Ok(Some(Header {
+            layout,
+            rb_size,
+            content,
+            version: version[0],
+            features: features[0],
+        }))
+    }
+
+    /// Reads the name of the ring buffer, all zeros if it has none
+    fn read_name<M: MemoryReader + ?Sized>(
+        &self,
+        memory_reader: &mut M,
+    ) -> Result<[u8; layout::NAME_LEN], ConsumerError<M::Error>> {
+        let mut name = [0; layout::NAME_LEN];
+        if let Some(offset) = layout::trailer_offset(self.features, layout::FEATURE_NAME) {
+            // Like the header, it must be read by interfaces that cap their transfers
+            let address = self.content + self.rb_size + self.layout.after_content + offset;
+            read_block(address, &mut name, layout::NAME_LEN, |address, buf| {
+                memory_reader.read_memory(address, buf)
+            })
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        }
+        Ok(name)
+    }
+
+    /// Reads the content pointer of an `RBIndirect` at `address`: its width, a reserved
+    /// byte, then the pointer, little-endian. Fails with
+    /// [`ConsumerErrorKind::UnsupportedPointerWidth`] if it can't be an address of the host.
+    fn read_content_pointer<M: MemoryReader + ?Sized>(
+        memory_reader: &mut M,
+        address: usize,
+    ) -> Result<usize, ConsumerError<M::Error>> {
+        let mut width = [0; 1];
+        memory_reader
+            .read_memory(address, &mut width)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        let width = width[0];
+        if width == 0 || width as usize > core::mem::size_of::<usize>() {
+            return Err(ConsumerError(ConsumerErrorKind::UnsupportedPointerWidth(
+                width,
+            )));
+        }
+        let mut pointer = [0; layout::MAX_POINTER_WIDTH];
+        let pointer = &mut pointer[..width as usize];
+        memory_reader
+            .read_memory(address + 2, pointer)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        Ok(le_index(pointer))
+    }
+}
+
+impl<M: MemoryReader> Drop for ProducerDevice<M> {
+    /// Acknowledges the bytes read, and clears the host-attached flag so that the producer
+    /// stops blocking. Errors are ignored, as the link to the device may already be gone.
+    fn drop(&mut self) {
+        let _ = self.ack();
+        if let Some(host_attached) = self.layout.host_attached {
+            let _ = self.write_memory_slice(self.field_address(host_attached), &[0]);
         }
-        Ok(bytes)
     }
 }
@@ -0,0 +1,144 @@
+//! Demultiplexing of frames tagged with a channel byte.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+use super::frames::read_more;
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+
+/// Callback of a channel, see [`MuxReader::on_frame`]
+type Handler<'d> = Box<dyn FnMut(&[u8]) + 'd>;
+
+/// What to do with the frames of a channel
+enum Sink<'d> {
+    /// Keep them until [`MuxReader::recv`] takes them
+    Queue(VecDeque<Vec<u8>>),
+    /// Pass them to a callback as they arrive
+    Handler(Handler<'d>),
+}
+
+/// Splits the frames sent with `RB::send_frame_on` by channel, obtained with
+/// [`ProducerDevice::mux`]. Each channel is either queued, its frames then being taken with
+/// [`MuxReader::recv`], or handled by a callback called by [`MuxReader::poll`].
+///
+/// A frame may arrive over any number of polls: its first bytes are kept until the rest
+/// is read. A frame of a channel that was neither queued nor handled makes `poll` fail
+/// with [`ConsumerErrorKind::UnknownChannel`], so that a firmware with more channels than
+/// the host expects does not go unnoticed. The frame is discarded, and the next poll goes
+/// on with the frames after it.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::ConsumerErrorKind;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// const LOG: u8 = 0;
+/// const TRACE: u8 = 1;
+///
+/// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut traced = Vec::new();
+/// let mut mux = device.mux();
+/// mux.queue(LOG);
+/// mux.on_frame(TRACE, |frame| traced.extend_from_slice(frame));
+///
+/// RING_BUF.send_frame_on(LOG, b"boot");
+/// RING_BUF.send_frame_on(TRACE, &[0x01, 0x02]);
+/// RING_BUF.send_bytes_blocking(&[LOG, 3, b'o']);
+/// assert_eq!(mux.poll().unwrap(), 2);
+/// assert_eq!(mux.pending(), 3);
+/// RING_BUF.send_bytes_blocking(b"k\n");
+/// assert_eq!(mux.poll().unwrap(), 1);
+/// assert_eq!(mux.recv(LOG).unwrap(), b"boot");
+/// assert_eq!(mux.recv(LOG).unwrap(), b"ok\n");
+/// assert_eq!(mux.recv(LOG), None);
+///
+/// RING_BUF.send_frame_on(7, b"?");
+/// RING_BUF.send_frame_on(TRACE, &[0x03]);
+/// let err = mux.poll().unwrap_err();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::UnknownChannel(7)));
+/// assert_eq!(mux.poll().unwrap(), 1);
+/// drop(mux);
+/// assert_eq!(traced, [0x01, 0x02, 0x03]);
+/// # }
+/// ```
+pub struct MuxReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not dispatched yet, starting with a channel byte
+    buffer: Vec<u8>,
+    sinks: BTreeMap<u8, Sink<'d>>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of frames tagged with a channel, see [`MuxReader`]
+    pub fn mux(&mut self) -> MuxReader<'_, M> {
+        MuxReader {
+            device: self,
+            buffer: Vec::new(),
+            sinks: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'d, M: MemoryReader> MuxReader<'d, M> {
+    /// Keeps the frames of `channel` until they are taken with [`MuxReader::recv`]. Replaces
+    /// the callback of the channel, if any.
+    pub fn queue(&mut self, channel: u8) {
+        if !matches!(self.sinks.get(&channel), Some(Sink::Queue(_))) {
+            self.sinks.insert(channel, Sink::Queue(VecDeque::new()));
+        }
+    }
+
+    /// Calls `handler` with each frame of `channel`, from [`MuxReader::poll`]. Frames of the
+    /// channel still queued are dropped.
+    pub fn on_frame(&mut self, channel: u8, handler: impl FnMut(&[u8]) + 'd) {
+        self.sinks.insert(channel, Sink::Handler(Box::new(handler)));
+    }
+
+    /// Reads the ring buffer once, and queues or handles the frames that are complete.
+    /// Returns how many there were. Fails with [`ConsumerErrorKind::UnknownChannel`] on the
+    /// first frame of a channel that is neither queued nor handled, after dispatching the
+    /// frames before it.
+    pub fn poll(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        read_more(self.device, &mut self.buffer)?;
+        let mut dispatched = 0;
+        let mut start = 0;
+        let result = loop {
+            let Some(&[channel, len]) = self.buffer.get(start..start + 2) else {
+                break Ok(dispatched);
+            };
+            let end = start + 2 + len as usize;
+            let Some(frame) = self.buffer.get(start + 2..end) else {
+                break Ok(dispatched);
+            };
+            start = end;
+            match self.sinks.get_mut(&channel) {
+                Some(Sink::Queue(queue)) => queue.push_back(frame.to_vec()),
+                Some(Sink::Handler(handler)) => handler(frame),
+                None => break Err(ConsumerError(ConsumerErrorKind::UnknownChannel(channel))),
+            }
+            dispatched += 1;
+        };
+        self.buffer.drain(..start);
+        result
+    }
+
+    /// Takes the oldest frame queued for `channel`, if any. Call [`MuxReader::poll`] first
+    /// to read the ring buffer.
+    pub fn recv(&mut self, channel: u8) -> Option<Vec<u8>> {
+        match self.sinks.get_mut(&channel) {
+            Some(Sink::Queue(queue)) => queue.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes read but not dispatched yet, e.g. the start of a frame
+    /// whose end is not written yet
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+}
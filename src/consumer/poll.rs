@@ -0,0 +1,74 @@
+//! How often the run loops read the ring buffer.
+
+use core::time::Duration;
+
+/// How long the run loops, e.g. [`ProducerDevice::run`](super::ProducerDevice::run), sleep
+/// between reads that find the ring buffer empty.
+///
+/// After an empty read, a loop sleeps `min`, then `factor` times longer after each empty
+/// read in a row, up to `max`. As soon as a read returns bytes, it goes back to `min`. An
+/// idle target is thus polled rarely, while bursts are read with the latency of `min`.
+///
+/// A [`Duration`] converts to a fixed interval, which is what the loops take as well:
+/// ```
+/// use ramlink::consumer::PollPolicy;
+/// use std::time::Duration;
+///
+/// let ms = Duration::from_millis;
+/// let policy = PollPolicy::adaptive(ms(1), ms(50));
+/// let intervals: Vec<_> = (0..8).map(|empty_reads| policy.interval(empty_reads)).collect();
+/// assert_eq!(intervals, [ms(1), ms(2), ms(4), ms(8), ms(16), ms(32), ms(50), ms(50)]);
+///
+/// let fixed = PollPolicy::from(ms(10));
+/// assert_eq!((fixed.interval(0), fixed.interval(100)), (ms(10), ms(10)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollPolicy {
+    /// Sleep after the first empty read
+    pub min: Duration,
+    /// Longest sleep
+    pub max: Duration,
+    /// How much longer each sleep is than the previous one, while the ring buffer stays
+    /// empty. 1 keeps sleeping `min`.
+    pub factor: u32,
+}
+
+impl PollPolicy {
+    /// Returns a policy that always sleeps `interval`
+    pub const fn fixed(interval: Duration) -> Self {
+        PollPolicy {
+            min: interval,
+            max: interval,
+            factor: 1,
+        }
+    }
+
+    /// Returns a policy that doubles the sleep from `min` up to `max`
+    pub const fn adaptive(min: Duration, max: Duration) -> Self {
+        PollPolicy {
+            min,
+            max,
+            factor: 2,
+        }
+    }
+
+    /// Returns how long to sleep after an empty read, the `empty_reads` reads right before
+    /// it having been empty too
+    pub fn interval(&self, empty_reads: u32) -> Duration {
+        let mut interval = self.min;
+        // From a nanosecond, a factor of 2 overflows any Duration within 64 steps
+        for _ in 0..empty_reads.min(64) {
+            if interval >= self.max {
+                break;
+            }
+            interval = interval.saturating_mul(self.factor);
+        }
+        interval.min(self.max)
+    }
+}
+
+impl From<Duration> for PollPolicy {
+    fn from(interval: Duration) -> Self {
+        PollPolicy::fixed(interval)
+    }
+}
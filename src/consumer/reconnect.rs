@@ -0,0 +1,214 @@
+//! Run loop that attaches again to a producer that was reset or reflashed.
+
+use core::ops::ControlFlow;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, PollPolicy, ProducerDevice};
+
+/// How [`ProducerDevice::run_reconnecting`] tries to attach again to a producer it lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// How many times to re-read the header before giving up, or `None` to keep trying
+    pub attempts: Option<u32>,
+    /// How long to sleep after each failed attempt, the `n`th one sleeping
+    /// `backoff.interval(n - 1)`
+    pub backoff: PollPolicy,
+}
+
+impl ReconnectPolicy {
+    /// Returns a policy that gives up after `attempts` failed attempts
+    pub const fn new(attempts: u32, backoff: PollPolicy) -> Self {
+        ReconnectPolicy {
+            attempts: Some(attempts),
+            backoff,
+        }
+    }
+
+    /// Returns a policy that keeps trying until the producer is back, or the loop stopped
+    pub const fn forever(backoff: PollPolicy) -> Self {
+        ReconnectPolicy {
+            attempts: None,
+            backoff,
+        }
+    }
+}
+
+/// Keeps trying, from every 10 ms up to every second, which is about what reflashing a
+/// target takes
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy::forever(PollPolicy::adaptive(
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        ))
+    }
+}
+
+/// What [`ProducerDevice::run_reconnecting`] passes to its callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunEvent<'a> {
+    /// Bytes sent by the producer, in order
+    Data(&'a [u8]),
+    /// The producer was lost, and the header is about to be read again, for the
+    /// `attempt`th time since. A decoder should drop what it kept of the old stream.
+    Reconnecting {
+        /// 1 for the first attempt
+        attempt: u32,
+    },
+    /// The producer is back: the bytes that follow come from its new stream
+    Reconnected,
+}
+
+/// Returns `true` if `err` means that the producer was reset or erased, rather than that
+/// the memory reader failed
+fn is_lost<E>(err: &ConsumerError<E>) -> bool {
+    matches!(
+        err.kind(),
+        ConsumerErrorKind::Desynchronized
+            | ConsumerErrorKind::MagicMarkerNotFound
+            | ConsumerErrorKind::CorruptIndex { .. }
+    )
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Same as [`ProducerDevice::run_until`], but attaches again to the producer when it
+    /// is lost, e.g. because the target was reflashed while the loop was running.
+    ///
+    /// A read failing with [`ConsumerErrorKind::Desynchronized`],
+    /// [`ConsumerErrorKind::MagicMarkerNotFound`] or [`ConsumerErrorKind::CorruptIndex`]
+    /// passes [`RunEvent::Reconnecting`] to `on_data`, then calls
+    /// [`ProducerDevice::resync`], which validates the header as [`ProducerDevice::new`]
+    /// does. This is repeated as set by `reconnect` until the magic marker is back, then
+    /// [`RunEvent::Reconnected`] is passed, and reading goes on from the indices of the new
+    /// stream. The ring buffer is expected at the same address: a firmware that moved it
+    /// needs a new device. If all attempts fail, the loop returns the error of the last one.
+    /// Errors of the memory reader end the loop as they do in `run_until`.
+    ///
+    /// Only [`ProducerDevice::set_integrity_check`] notices a producer that was reset to a
+    /// valid state, and it does so before the bytes of the read are returned: with a check
+    /// on every read, the bytes before a reset and those after it are always separated by
+    /// `Reconnecting`. Without it, only indices out of the ring buffer are noticed.
+    /// ```
+    /// # #[cfg(feature = "producer")] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use core::ops::ControlFlow;
+    /// use ramlink::consumer::testing::VolatileReader;
+    /// use ramlink::consumer::{ConsumerErrorKind, PollPolicy, ReconnectPolicy, RunEvent};
+    /// use ramlink::producer::RB;
+    /// use std::sync::atomic::AtomicBool;
+    /// use std::time::Duration;
+    ///
+    /// let rb: *mut RB<16> = Box::into_raw(Box::new(RB::new()));
+    /// let reader = unsafe { VolatileReader::new(rb as *mut u8, core::mem::size_of::<RB<16>>()) };
+    /// let mut device = ProducerDevice::new(reader, rb as usize).unwrap();
+    /// device.set_integrity_check(1);
+    /// unsafe { (*rb).send_bytes_blocking(b"before") };
+    ///
+    /// let backoff = PollPolicy::fixed(Duration::from_millis(1));
+    /// let mut events = Vec::new();
+    /// let stop = AtomicBool::new(false);
+    /// device
+    ///     .run_reconnecting(backoff, ReconnectPolicy::new(5, backoff), &stop, |event| {
+    ///         events.push(format!("{event:?}"));
+    ///         match event {
+    ///             // Reflashing erases the RAM, the new firmware boots a few attempts later
+    ///             RunEvent::Data(b"before") => unsafe { rb.write_bytes(0, 1) },
+    ///             RunEvent::Reconnecting { attempt: 3 } => unsafe {
+    ///                 rb.write(RB::new());
+    ///                 (*rb).send_bytes_blocking(b"after");
+    ///             },
+    ///             RunEvent::Data(_) => return ControlFlow::Break(()),
+    ///             _ => {}
+    ///         }
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     events,
+    ///     [
+    ///         "Data([98, 101, 102, 111, 114, 101])",
+    ///         "Reconnecting { attempt: 1 }",
+    ///         "Reconnecting { attempt: 2 }",
+    ///         "Reconnecting { attempt: 3 }",
+    ///         "Reconnected",
+    ///         "Data([97, 102, 116, 101, 114])",
+    ///     ]
+    /// );
+    ///
+    /// // A target that never comes back
+    /// unsafe { rb.write_bytes(0, 1) };
+    /// let err = device
+    ///     .run_reconnecting(backoff, ReconnectPolicy::new(2, backoff), &stop, |_| {
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::MagicMarkerNotFound));
+    /// # drop(device);
+    /// # drop(unsafe { Box::from_raw(rb) });
+    /// # }
+    /// ```
+    pub fn run_reconnecting(
+        &mut self,
+        poll: impl Into<PollPolicy>,
+        reconnect: ReconnectPolicy,
+        stop: &AtomicBool,
+        mut on_data: impl FnMut(RunEvent<'_>) -> ControlFlow<()>,
+    ) -> Result<(), ConsumerError<M::Error>> {
+        let poll = poll.into();
+        let mut buf = alloc::vec![0; self.capacity()];
+        let mut empty_reads = 0;
+        while !stop.load(Ordering::Relaxed) {
+            match self.read_into(&mut buf) {
+                Ok(0) => {
+                    self.record_sleep(empty_reads);
+                    std::thread::sleep(poll.interval(empty_reads));
+                    empty_reads = empty_reads.saturating_add(1);
+                }
+                Ok(read) => {
+                    if on_data(RunEvent::Data(&buf[..read])).is_break() {
+                        break;
+                    }
+                    empty_reads = 0;
+                }
+                Err(err) if is_lost(&err) => {
+                    if self.reconnect(reconnect, stop, &mut on_data)?.is_break() {
+                        break;
+                    }
+                    // The new firmware may have a ring buffer of another size
+                    buf.resize(self.capacity(), 0);
+                    empty_reads = 0;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls [`ProducerDevice::resync`] until it succeeds, as set by `policy`. Breaks if
+    /// `on_data` or `stop` ended the loop meanwhile.
+    fn reconnect(
+        &mut self,
+        policy: ReconnectPolicy,
+        stop: &AtomicBool,
+        on_data: &mut impl FnMut(RunEvent<'_>) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>, ConsumerError<M::Error>> {
+        let mut attempt = 1;
+        while !stop.load(Ordering::Relaxed) {
+            if on_data(RunEvent::Reconnecting { attempt }).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+            match self.resync() {
+                Ok(()) => return Ok(on_data(RunEvent::Reconnected)),
+                Err(err) if policy.attempts.is_some_and(|attempts| attempt >= attempts) => {
+                    return Err(err)
+                }
+                Err(_) => {
+                    std::thread::sleep(policy.backoff.interval(attempt - 1));
+                    attempt = attempt.saturating_add(1);
+                }
+            }
+        }
+        Ok(ControlFlow::Break(()))
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoding of the log records sent with the `ram_info!` family of macros.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::frames::read_more;
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+
+/// Name of the levels, 1 for errors to 5 for traces, like `log::Level`
+const LEVELS: [&str; 5] = ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"];
+
+/// A log record sent with `ram_info!` and the like
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Severity, 1 for errors to 5 for traces
+    pub level: u8,
+    /// Id of the target, as given by the firmware
+    pub target: u8,
+    /// Message, with invalid UTF-8 replaced
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Returns the name of the level, e.g. `"WARN"`
+    pub fn level_name(&self) -> &'static str {
+        LEVELS[self.level as usize - 1]
+    }
+
+    /// Returns the level as a `log::Level`
+    #[cfg(feature = "log")]
+    pub fn log_level(&self) -> log::Level {
+        [
+            log::Level::Error,
+            log::Level::Warn,
+            log::Level::Info,
+            log::Level::Debug,
+            log::Level::Trace,
+        ][self.level as usize - 1]
+    }
+}
+
+/// Reads the log records of a ring buffer, obtained with [`ProducerDevice::records`]. The
+/// target ids of the firmware are mapped back to names with [`RecordReader::name_target`];
+/// those without a name are shown as their number.
+///
+/// As an [`Iterator`], it yields the records that are complete, and ends when there are
+/// none left for now. With the `log` feature, [`RecordReader::forward`] emits them through
+/// the `log` facade of the host instead, so that they are filtered and shown like the
+/// host's own, e.g. by `env_logger`.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::LogRecord;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut records = device.records();
+/// records.name_target(3, "motor");
+///
+/// // What `ram_warn!(target: 3, "stall")` and `ram_info!("up")` send
+/// RING_BUF.send_bytes_blocking(&[2, 3, 5, b's', b't', b'a', b'l', b'l']);
+/// RING_BUF.send_bytes_blocking(&[3, 9, 2, b'u']);
+/// let record = records.next().unwrap().unwrap();
+/// assert_eq!(
+///     record,
+///     LogRecord { level: 2, target: 3, message: "stall".into() }
+/// );
+/// assert_eq!(record.level_name(), "WARN");
+/// assert_eq!(records.target_name(record.target), "motor");
+/// assert!(records.next().is_none());
+///
+/// RING_BUF.send_bytes_blocking(b"p");
+/// let record = records.next().unwrap().unwrap();
+/// assert_eq!(record.message, "up");
+/// assert_eq!(records.target_name(record.target), "9");
+/// # }
+/// ```
+pub struct RecordReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet, starting with a level byte
+    buffer: Vec<u8>,
+    targets: BTreeMap<u8, String>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of log records, see [`RecordReader`]
+    pub fn records(&mut self) -> RecordReader<'_, M> {
+        RecordReader {
+            device: self,
+            buffer: Vec::new(),
+            targets: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: MemoryReader> RecordReader<'_, M> {
+    /// Names the target `id`, e.g. after the module of the firmware that uses it
+    pub fn name_target(&mut self, id: u8, name: impl Into<String>) {
+        self.targets.insert(id, name.into());
+    }
+
+    /// Returns the name of the target `id`, or its number if it has none
+    pub fn target_name(&self, id: u8) -> String {
+        match self.targets.get(&id) {
+            Some(name) => name.clone(),
+            None => id.to_string(),
+        }
+    }
+
+    /// Returns the next record, or `None` if it is not complete yet. Fails with
+    /// [`ConsumerErrorKind::InvalidFrame`] if the level byte is not within 1..=5, which
+    /// means that the reader is out of sync with the producer. The level byte is then
+    /// discarded, so that the next call starts from the following byte.
+    pub fn try_next_record(&mut self) -> Result<Option<LogRecord>, ConsumerError<M::Error>> {
+        if let Some(record) = self.pop_record()? {
+            return Ok(Some(record));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        self.pop_record()
+    }
+
+    /// Emits the records that are complete through the `log` facade, with the name of
+    /// their target, and returns how many were read. Like those of the host, records above
+    /// `log::max_level` are discarded, and the others filtered by the logger.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::sync::Mutex;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// struct Collect(Mutex<Vec<String>>);
+    ///
+    /// impl log::Log for Collect {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &log::Record) {
+    ///         let line = format!("{} {}: {}", record.level(), record.target(), record.args());
+    ///         self.0.lock().unwrap().push(line);
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: Collect = Collect(Mutex::new(Vec::new()));
+    /// log::set_logger(&LOGGER).unwrap();
+    /// log::set_max_level(log::LevelFilter::Info);
+    ///
+    /// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let mut records = device.records();
+    /// records.name_target(1, "adc");
+    ///
+    /// RING_BUF.send_bytes_blocking(&[1, 1, 2, b'h', b'i']);
+    /// RING_BUF.send_bytes_blocking(&[4, 1, 2, b'l', b'o']);
+    /// RING_BUF.send_bytes_blocking(&[3, 7, 2, b'o', b'k']);
+    /// assert_eq!(records.forward().unwrap(), 3);
+    /// assert_eq!(*LOGGER.0.lock().unwrap(), ["ERROR adc: hi", "INFO 7: ok"]);
+    /// # }
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn forward(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        let mut forwarded = 0;
+        while let Some(record) = self.try_next_record()? {
+            forwarded += 1;
+            if record.log_level() > log::max_level() {
+                continue;
+            }
+            let target = self.target_name(record.target);
+            log::logger().log(
+                &log::Record::builder()
+                    .level(record.log_level())
+                    .target(&target)
+                    .args(format_args!("{}", record.message))
+                    .build(),
+            );
+        }
+        Ok(forwarded)
+    }
+
+    /// Removes the first record from the buffer if it is complete
+    fn pop_record(&mut self) -> Result<Option<LogRecord>, ConsumerError<M::Error>> {
+        let Some(&level) = self.buffer.first() else {
+            return Ok(None);
+        };
+        if !(1..=LEVELS.len() as u8).contains(&level) {
+            self.buffer.remove(0);
+            return Err(ConsumerError(ConsumerErrorKind::InvalidFrame));
+        }
+        let Some(&[target, len]) = self.buffer.get(1..3) else {
+            return Ok(None);
+        };
+        let Some(message) = self.buffer.get(3..3 + len as usize) else {
+            return Ok(None);
+        };
+        let record = LogRecord {
+            level,
+            target,
+            message: String::from_utf8_lossy(message).into_owned(),
+        };
+        self.buffer.drain(..3 + len as usize);
+        Ok(Some(record))
+    }
+}
+
+impl<M: MemoryReader> Iterator for RecordReader<'_, M> {
+    type Item = Result<LogRecord, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_record().transpose()
+    }
+}
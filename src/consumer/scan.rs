@@ -0,0 +1,304 @@
+//! Discovery of ring buffers in the memory of the producer.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::{
+    le_index, name_str, read_block, ConsumerError, ConsumerErrorKind, ControlBlockReader, Header,
+    MemoryReader, ProducerDevice,
+};
+use crate::layout;
+
+/// Bytes read at once while scanning
+const BLOCK: usize = 256;
+/// Bytes after a candidate address that are needed to check it
+const HEADER_LEN: usize = layout::MAX_HEADER_LEN;
+
+/// Bytes of the scanned memory, as a reader for [`Header::read`]
+struct Block<'a> {
+    start: usize,
+    bytes: &'a [u8],
+}
+
+impl MemoryReader for Block<'_> {
+    type Error = ();
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), ()> {
+        let offset = address.checked_sub(self.start).ok_or(())?;
+        let bytes = self.bytes.get(offset..offset + buffer.len()).ok_or(())?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_memory(&mut self, _address: usize, _value: u8) -> Result<(), ()> {
+        Err(())
+    }
+}
+
+/// Returns `true` if a ring buffer with the default id starts at `address`: its magic
+/// marker and version are known, and its indices are within its size
+fn is_ring_buffer(block: &mut Block<'_>, address: usize) -> bool {
+    let Ok(header) = Header::read(block, address, layout::DEFAULT_ID) else {
+        return false;
+    };
+    let width = header.layout.index_width;
+    let mut indices = [0; 4];
+    let indices = &mut indices[..2 * width];
+    block
+        .read_memory(address + header.layout.producer, indices)
+        .is_ok_and(|()| {
+            le_index(&indices[..width]) < header.rb_size
+                && le_index(&indices[width..]) < header.rb_size
+        })
+}
+
+/// Returns `true` if a control block with the default id starts at `address`: its magic
+/// marker and version are known, and it has at least one channel
+fn is_control_block(block: &mut Block<'_>, address: usize) -> bool {
+    let cb = &layout::CONTROL_BLOCK;
+    let mut header = [0; layout::CONTROL_BLOCK.descriptors];
+    block.read_memory(address, &mut header).is_ok()
+        && header[..3] == cb.magic(layout::DEFAULT_ID)
+        && header[cb.version] == layout::VERSION
+        && header[cb.count] > 0
+}
+
+/// Returns the addresses within `range` at which a ring buffer starts, trying every
+/// `stride` bytes from `range.start`, e.g. 4 to only find ring buffers aligned like an
+/// `AtomicRB` on Cortex-M. The whole header must be within `range`.
+///
+/// The range is read in blocks of 256 bytes, so scanning all the RAM of a small part only
+/// takes a few dozen transfers with the [`MemoryReader`]. Interfaces that fail on such
+/// transfers are read in smaller ones. Only ring buffers with the default
+/// id are found, and `RBCompact` can't be, as it has no magic marker.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// use ramlink::consumer::scan_for_rb;
+/// use ramlink::consumer::testing::InMemoryReader;
+/// use ramlink::producer::RB;
+///
+/// // 2 KB of RAM, with a ring buffer across the first 256 bytes block
+/// let rb = RB::<16>::new();
+/// let rb = unsafe {
+///     core::slice::from_raw_parts(&rb as *const RB<16> as *const u8, core::mem::size_of_val(&rb))
+/// };
+/// let mut ram: Vec<u8> = (0..2048).map(|i| (i * 7) as u8).collect();
+/// ram[250..250 + rb.len()].copy_from_slice(rb);
+///
+/// let mut reader = InMemoryReader::new(ram);
+/// assert_eq!(scan_for_rb(&mut reader, 0..2048, 1).unwrap(), [250]);
+/// assert!(scan_for_rb(&mut reader, 0..2048, 4).unwrap().is_empty());
+/// # }
+/// ```
+pub fn scan_for_rb<M: MemoryReader + ?Sized>(
+    reader: &mut M,
+    range: Range<usize>,
+    stride: usize,
+) -> Result<Vec<usize>, ConsumerError<M::Error>> {
+    scan(reader, range, stride, is_ring_buffer)
+}
+
+/// A ring buffer found by [`scan_for_rb_with_names`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoundRB {
+    /// Address of the ring buffer, to give to [`ProducerDevice::new`]
+    pub address: usize,
+    name: [u8; layout::NAME_LEN],
+}
+
+impl FoundRB {
+    /// Returns the name of the ring buffer, see [`ProducerDevice::name`]
+    pub fn name(&self) -> Option<&str> {
+        name_str(&self.name)
+    }
+}
+
+/// Same as [`scan_for_rb`], along with the name of each ring buffer, so that the one to
+/// attach to can be picked by name when a firmware has several. The header of each is read
+/// again, to find the name after its content.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "names"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::scan_for_rb_with_names;
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+///
+/// #[repr(C)]
+/// struct Ram {
+///     logs: AtomicRB<16>,
+///     telemetry: AtomicRB<16>,
+///     anonymous: AtomicRB<16>,
+/// }
+///
+/// static RAM: Ram = Ram {
+///     logs: AtomicRB::new_named("logs"),
+///     telemetry: AtomicRB::new_named("telem"),
+///     anonymous: AtomicRB::new(),
+/// };
+/// let start = &RAM as *const _ as usize;
+/// let range = start..start + core::mem::size_of::<Ram>();
+/// # // SAFETY: the ring buffers are atomics, and the rest is only read
+/// # let mut host = unsafe { VolatileReader::new(start as *mut u8, range.len()) };
+///
+/// let found = scan_for_rb_with_names(&mut host, range, 1).unwrap();
+/// let names: Vec<_> = found.iter().map(|rb| rb.name()).collect();
+/// assert_eq!(names, [Some("logs"), Some("telem"), None]);
+///
+/// let telemetry = found.iter().find(|rb| rb.name() == Some("telem")).unwrap();
+/// let mut device = ProducerDevice::new(host, telemetry.address).unwrap();
+/// RAM.telemetry.send_bytes_blocking(&[0x17]);
+/// assert_eq!(device.read_bytes().unwrap(), [0x17]);
+/// # }
+/// ```
+pub fn scan_for_rb_with_names<M: MemoryReader + ?Sized>(
+    reader: &mut M,
+    range: Range<usize>,
+    stride: usize,
+) -> Result<Vec<FoundRB>, ConsumerError<M::Error>> {
+    scan_for_rb(reader, range, stride)?
+        .into_iter()
+        .map(|address| {
+            let header = Header::read(reader, address, layout::DEFAULT_ID)?;
+            let name = header.read_name(reader)?;
+            Ok(FoundRB { address, name })
+        })
+        .collect()
+}
+
+/// Same as [`scan_for_rb`], for the control blocks with the default id. The ring buffers
+/// of their channels are not returned, see [`ControlBlockReader::discover`].
+pub fn scan_for_control_block<M: MemoryReader + ?Sized>(
+    reader: &mut M,
+    range: Range<usize>,
+    stride: usize,
+) -> Result<Vec<usize>, ConsumerError<M::Error>> {
+    scan(reader, range, stride, is_control_block)
+}
+
+/// Returns the addresses within `range`, every `stride` bytes, at which `is_match` is true
+fn scan<M: MemoryReader + ?Sized>(
+    reader: &mut M,
+    range: Range<usize>,
+    stride: usize,
+    is_match: fn(&mut Block<'_>, usize) -> bool,
+) -> Result<Vec<usize>, ConsumerError<M::Error>> {
+    assert!(stride > 0, "stride must not be 0");
+    let mut found = Vec::new();
+    let mut bytes = vec![0; BLOCK + HEADER_LEN];
+    let mut start = range.start;
+    while start < range.end {
+        let end = range.end.min(start + BLOCK + HEADER_LEN);
+        let bytes = &mut bytes[..end - start];
+        read_block(start, bytes, bytes.len(), |address, buf| {
+            reader.read_memory(address, buf)
+        })
+        .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+
+        let mut block = Block { start, bytes };
+        // Candidates of this block, aligned on `stride` from the start of the range
+        let first = start + (stride - (start - range.start) % stride) % stride;
+        let last = range.end.min(start + BLOCK);
+        for address in (first..last).step_by(stride) {
+            if is_match(&mut block, address) {
+                found.push(address);
+            }
+        }
+        start += BLOCK;
+    }
+    Ok(found)
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Attaches to the ring buffer within `range`, found with [`scan_for_rb`]. Fails with
+    /// [`ConsumerErrorKind::MagicMarkerNotFound`] if there is none, or with
+    /// [`ConsumerErrorKind::MultipleRingBuffers`] if there are several, in which case the
+    /// right address must be given to [`ProducerDevice::new`].
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// #[repr(C)]
+    /// struct Ram {
+    ///     before: [u8; 100],
+    ///     ring_buf: AtomicRB<8>,
+    ///     after: [u8; 100],
+    /// }
+    ///
+    /// static RAM: Ram = Ram {
+    ///     before: [0; 100],
+    ///     ring_buf: AtomicRB::new(),
+    ///     after: [0; 100],
+    /// };
+    /// let start = &RAM as *const _ as usize;
+    /// let range = start..start + core::mem::size_of::<Ram>();
+    /// # // SAFETY: the ring buffers are atomics, and the rest is only read
+    /// # let host = unsafe { VolatileReader::new(start as *mut u8, range.len()) };
+    ///
+    /// let mut device = ProducerDevice::discover(host, range).unwrap();
+    /// RAM.ring_buf.send_bytes_blocking(b"found");
+    /// assert_eq!(device.read_bytes().unwrap(), b"found");
+    /// # }
+    /// ```
+    pub fn discover(
+        mut memory_reader: M,
+        range: Range<usize>,
+    ) -> Result<ProducerDevice<M>, ConsumerError<M::Error>> {
+        match scan_for_rb(&mut memory_reader, range, 1)?[..] {
+            [address] => ProducerDevice::new(memory_reader, address),
+            [] => Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound)),
+            ref found => Err(ConsumerError(ConsumerErrorKind::MultipleRingBuffers(
+                found.len(),
+            ))),
+        }
+    }
+}
+
+impl<M: MemoryReader> ControlBlockReader<M> {
+    /// Reads the control block within `range`, found with [`scan_for_control_block`].
+    /// Fails like [`ProducerDevice::discover`] if there is none, or several.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// use ramlink::consumer::{scan_for_rb, ControlBlockReader};
+    /// use ramlink::producer::ControlBlock;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    ///
+    /// #[repr(C)]
+    /// struct Ram {
+    ///     before: [u8; 100],
+    ///     channels: ControlBlock<2, 16>,
+    ///     after: [u8; 100],
+    /// }
+    ///
+    /// static RAM: Ram = Ram {
+    ///     before: [0; 100],
+    ///     channels: ControlBlock::new(["log", "trace"]),
+    ///     after: [0; 100],
+    /// };
+    /// let start = &RAM as *const _ as usize;
+    /// let range = start..start + core::mem::size_of::<Ram>();
+    /// # // SAFETY: the ring buffers are atomics, and the rest is only read
+    /// # let mut host = unsafe { VolatileReader::new(start as *mut u8, range.len()) };
+    ///
+    /// // Each channel is a ring buffer, but there is a single control block
+    /// assert_eq!(scan_for_rb(&mut host, range.clone(), 1).unwrap().len(), 2);
+    /// let block = ControlBlockReader::discover(host, range).unwrap();
+    /// assert_eq!(block.address(), &RAM.channels as *const _ as usize);
+    /// assert_eq!(block.channel_count(), 2);
+    /// # }
+    /// ```
+    pub fn discover(
+        mut memory_reader: M,
+        range: Range<usize>,
+    ) -> Result<ControlBlockReader<M>, ConsumerError<M::Error>> {
+        match scan_for_control_block(&mut memory_reader, range, 1)?[..] {
+            [address] => ControlBlockReader::new(memory_reader, address),
+            [] => Err(ConsumerError(ConsumerErrorKind::MagicMarkerNotFound)),
+            ref found => Err(ConsumerError(ConsumerErrorKind::MultipleRingBuffers(
+                found.len(),
+            ))),
+        }
+    }
+}
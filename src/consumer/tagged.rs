@@ -0,0 +1,112 @@
+//! Decoding of the frames tagged as text or binary, sent with `RB::send_text` and
+//! `RB::send_binary`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::frames::read_more;
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+use crate::layout;
+
+/// A frame of a ring buffer that mixes text and binary data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    /// Sent with `send_text`, with invalid UTF-8 replaced
+    Text(String),
+    /// Sent with `send_binary`
+    Binary(Vec<u8>),
+    /// Sent with `send_frame_on` and a tag of the application, from 0x80 to 0xFF
+    User(u8, Vec<u8>),
+}
+
+/// Reads the tagged records of a ring buffer, obtained with
+/// [`ProducerDevice::tagged_records`], so that text can be shown as such and binary data
+/// as a hexdump, e.g. by `ramlink-dump --tagged`.
+///
+/// As an [`Iterator`], it yields the records that are complete, and ends when there are
+/// none left for now. A frame with a reserved tag, neither text, binary nor one of the
+/// application, fails with [`ConsumerErrorKind::UnknownChannel`] once it is complete; it is
+/// then discarded, and reading goes on with the frames after it.
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// # use ramlink::consumer::ProducerDevice;
+/// use ramlink::consumer::{ConsumerErrorKind, Record};
+/// use ramlink::producer::AtomicRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+///
+/// static RING_BUF: AtomicRB<32> = AtomicRB::new();
+/// let address = &RING_BUF as *const _ as usize;
+/// let mut device = ProducerDevice::new(host, address).unwrap();
+/// let mut records = device.tagged_records();
+///
+/// RING_BUF.send_text("boot");
+/// RING_BUF.send_binary(&[0xde, 0xad]);
+/// RING_BUF.send_frame_on(0x81, &[7]);
+/// assert_eq!(records.next().unwrap().unwrap(), Record::Text("boot".into()));
+/// assert_eq!(records.next().unwrap().unwrap(), Record::Binary(vec![0xde, 0xad]));
+/// assert_eq!(records.next().unwrap().unwrap(), Record::User(0x81, vec![7]));
+/// assert!(records.next().is_none());
+///
+/// RING_BUF.send_frame_on(0x10, b"?");
+/// RING_BUF.send_text("ok");
+/// let err = records.next().unwrap().unwrap_err();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::UnknownChannel(0x10)));
+/// assert_eq!(records.next().unwrap().unwrap(), Record::Text("ok".into()));
+/// # }
+/// ```
+pub struct TaggedRecordReader<'d, M: MemoryReader> {
+    device: &'d mut ProducerDevice<M>,
+    /// Bytes read but not returned yet, starting with a tag byte
+    buffer: Vec<u8>,
+}
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns a reader of frames tagged as text or binary, see [`TaggedRecordReader`]
+    pub fn tagged_records(&mut self) -> TaggedRecordReader<'_, M> {
+        TaggedRecordReader {
+            device: self,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<M: MemoryReader> TaggedRecordReader<'_, M> {
+    /// Returns the next record, or `None` if it is not complete yet. Fails with
+    /// [`ConsumerErrorKind::UnknownChannel`] on a frame with a reserved tag, which is
+    /// discarded.
+    pub fn try_next_record(&mut self) -> Result<Option<Record>, ConsumerError<M::Error>> {
+        if let Some(record) = self.pop_record()? {
+            return Ok(Some(record));
+        }
+        read_more(self.device, &mut self.buffer)?;
+        self.pop_record()
+    }
+
+    /// Removes the first record from the buffer if it is complete
+    fn pop_record(&mut self) -> Result<Option<Record>, ConsumerError<M::Error>> {
+        let Some(&[tag, len]) = self.buffer.get(..2) else {
+            return Ok(None);
+        };
+        let end = 2 + len as usize;
+        let Some(payload) = self.buffer.get(2..end) else {
+            return Ok(None);
+        };
+        let record = match tag {
+            layout::TAG_TEXT => Ok(Record::Text(String::from_utf8_lossy(payload).into_owned())),
+            layout::TAG_BINARY => Ok(Record::Binary(payload.to_vec())),
+            layout::TAG_USER.. => Ok(Record::User(tag, payload.to_vec())),
+            _ => Err(ConsumerError(ConsumerErrorKind::UnknownChannel(tag))),
+        };
+        self.buffer.drain(..end);
+        record.map(Some)
+    }
+}
+
+impl<M: MemoryReader> Iterator for TaggedRecordReader<'_, M> {
+    type Item = Result<Record, ConsumerError<M::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next_record().transpose()
+    }
+}
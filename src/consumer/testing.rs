@@ -0,0 +1,546 @@
+//! Memory readers to test consumer code without hardware.
+//!
+//! [`InMemoryReader`] reads and writes a `Vec<u8>`, such as a snapshot of the RAM of a
+//! device. With the `producer` feature, [`Loopback`] puts a real `RB` in such a memory, so
+//! that a producer and a [`ProducerDevice`] talk to each other within a test, e.g. to check
+//! a decoder built on top of this crate.
+//!
+//! [`VolatileReader`] reads the memory of this very process, the way a debug probe reads the
+//! RAM of a running device, so that a producer and a consumer really run concurrently, e.g.
+//! on a `static` [`AtomicRB`](crate::producer::AtomicRB).
+//!
+//! [`FaultyReader`] wraps any of them, to make transfers fail or to count them.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+use std::boxed::Box;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::vec::Vec;
+
+use super::MemoryReader;
+#[cfg(feature = "producer")]
+use super::{ConsumerError, ProducerDevice};
+#[cfg(feature = "producer")]
+use crate::producer::{AtomicRB, RB};
+
+/// Error of [`InMemoryReader`]: the access is past the end of the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+    /// Start of the access
+    pub address: usize,
+    /// Number of bytes accessed
+    pub len: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes at {:#x} are out of the memory",
+            self.len, self.address
+        )
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+/// A [`MemoryReader`] over a `Vec<u8>`, address 0 being its first byte. Clones share the
+/// same memory, so that a test can look at it, or change it, while a [`ProducerDevice`]
+/// owns another clone.
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::InMemoryReader;
+/// use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::RB;
+///
+/// // A snapshot of the RAM of a device, with an `RB` at 0x10
+/// let mut rb = RB::<8>::new();
+/// rb.send_bytes_blocking(b"hi");
+/// let rb = unsafe {
+///     core::slice::from_raw_parts(&rb as *const RB<8> as *const u8, core::mem::size_of_val(&rb))
+/// };
+/// let mut ram = vec![0; 0x10];
+/// ram.extend_from_slice(rb);
+///
+/// let memory = InMemoryReader::new(ram);
+/// let mut device = ProducerDevice::new(memory.clone(), 0x10).unwrap();
+/// assert_eq!(device.read_bytes().unwrap(), b"hi");
+/// // The consumer index was written back
+/// assert_eq!(memory.memory()[0x10 + 5], 2);
+///
+/// let err = ProducerDevice::new(memory, 0x100).err().unwrap();
+/// assert_eq!(
+///     err.to_string(),
+///     "failed to read memory: OutOfBounds { address: 256, len: 3 }"
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryReader {
+    memory: Arc<Mutex<Vec<u8>>>,
+}
+
+impl InMemoryReader {
+    /// Returns a reader over `memory`
+    pub fn new(memory: Vec<u8>) -> Self {
+        InMemoryReader {
+            memory: Arc::new(Mutex::new(memory)),
+        }
+    }
+
+    /// Returns a reader over memory shared with other code
+    pub fn from_shared(memory: Arc<Mutex<Vec<u8>>>) -> Self {
+        InMemoryReader { memory }
+    }
+
+    /// Locks the memory, e.g. to look at it, or to change it
+    pub fn memory(&self) -> MutexGuard<'_, Vec<u8>> {
+        // A panicking test does not make the bytes any less valid
+        self.memory.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MemoryReader for InMemoryReader {
+    type Error = OutOfBounds;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), OutOfBounds> {
+        let memory = self.memory();
+        let bytes = address
+            .checked_add(buffer.len())
+            .and_then(|end| memory.get(address..end))
+            .ok_or(OutOfBounds {
+                address,
+                len: buffer.len(),
+            })?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), OutOfBounds> {
+        self.write_memory_slice(address, &[value])
+    }
+
+    fn write_memory_slice(&mut self, address: usize, data: &[u8]) -> Result<(), OutOfBounds> {
+        let mut memory = self.memory();
+        let bytes = address
+            .checked_add(data.len())
+            .and_then(|end| memory.get_mut(address..end))
+            .ok_or(OutOfBounds {
+                address,
+                len: data.len(),
+            })?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// A real [`RB`] at address 0 of an [`InMemoryReader`], to run a producer against a
+/// [`ProducerDevice`] in one process. Clones share the same ring buffer, so the producer
+/// may run in another thread.
+///
+/// The producer side is driven through [`Loopback::with_producer`], or
+/// [`Loopback::send_bytes_blocking`], which lets the consumer run while it waits.
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::Loopback;
+///
+/// let loopback = Loopback::<8>::new();
+/// let mut device = loopback.device().unwrap();
+/// loopback.send_bytes_blocking(b"hello");
+/// assert_eq!(device.read_bytes().unwrap(), b"hello");
+/// assert!(loopback.with_producer(|rb| rb.is_empty()));
+/// # }
+/// ```
+#[cfg(feature = "producer")]
+#[derive(Debug, Clone)]
+pub struct Loopback<const SIZE: usize> {
+    memory: InMemoryReader,
+}
+
+#[cfg(feature = "producer")]
+impl<const SIZE: usize> Default for Loopback<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "producer")]
+impl<const SIZE: usize> Loopback<SIZE> {
+    /// Returns an empty ring buffer, that no consumer is attached to yet
+    pub fn new() -> Self {
+        let rb = RB::<SIZE>::new();
+        // SAFETY: all the fields of `RB` are bytes, without padding
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                &rb as *const RB<SIZE> as *const u8,
+                core::mem::size_of_val(&rb),
+            )
+        };
+        Loopback {
+            memory: InMemoryReader::new(bytes.to_vec()),
+        }
+    }
+
+    /// Returns the memory the ring buffer is in
+    pub fn memory(&self) -> InMemoryReader {
+        self.memory.clone()
+    }
+
+    /// Attaches a consumer to the ring buffer, with [`ProducerDevice::new`]
+    pub fn device(&self) -> Result<ProducerDevice<InMemoryReader>, ConsumerError<OutOfBounds>> {
+        ProducerDevice::new(self.memory(), 0)
+    }
+
+    /// Calls `f` with the ring buffer, the memory being locked meanwhile. `f` must not
+    /// wait for the consumer, e.g. with `send_bytes_blocking`, as it would wait forever:
+    /// use [`Loopback::send_bytes_blocking`] instead.
+    pub fn with_producer<R>(&self, f: impl FnOnce(&mut RB<SIZE>) -> R) -> R {
+        let mut memory = self.memory.memory();
+        assert!(
+            memory.len() >= core::mem::size_of::<RB<SIZE>>(),
+            "the memory of the loopback was truncated"
+        );
+        // SAFETY: the memory was initialized from an `RB<SIZE>`, whose fields are all bytes,
+        // so it is aligned and valid whatever the consumer wrote
+        let rb = unsafe { &mut *(memory.as_mut_ptr() as *mut RB<SIZE>) };
+        f(rb)
+    }
+
+    /// Same as [`RB::send_bytes_blocking`], but the memory is unlocked while the ring buffer
+    /// is full, so that a consumer in another thread can make room.
+    /// ```
+    /// # #[cfg(feature = "producer")] {
+    /// use ramlink::consumer::testing::Loopback;
+    ///
+    /// let loopback = Loopback::<4>::new();
+    /// let mut device = loopback.device().unwrap();
+    ///
+    /// let producer = std::thread::spawn({
+    ///     let loopback = loopback.clone();
+    ///     move || loopback.send_bytes_blocking(b"hello")
+    /// });
+    /// let mut received = Vec::new();
+    /// while received.len() < 5 {
+    ///     received.extend(device.read_bytes().unwrap());
+    /// }
+    /// producer.join().unwrap();
+    /// assert_eq!(received, b"hello");
+    /// # }
+    /// ```
+    pub fn send_bytes_blocking(&self, data: &[u8]) {
+        let mut data = data;
+        loop {
+            let sent = self.with_producer(|rb| rb.try_send_bytes(data));
+            data = &data[sent..];
+            if data.is_empty() {
+                return;
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// A [`MemoryReader`] over memory of this process, at the addresses of its pointers, read
+/// and written one atomic byte at a time, like a debug probe does while the device runs.
+///
+/// Here, a consumer reads a `static` ring buffer that another thread sends on:
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::VolatileReader;
+/// use ramlink::consumer::ProducerDevice;
+/// use ramlink::producer::AtomicRB;
+///
+/// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+/// let reader = VolatileReader::from_atomic_rb(&RING_BUF);
+/// let mut device = ProducerDevice::new(reader, &RING_BUF as *const _ as usize).unwrap();
+///
+/// let producer = std::thread::spawn(|| RING_BUF.send_bytes_blocking(b"hello"));
+/// producer.join().unwrap();
+/// assert_eq!(device.read_bytes().unwrap(), b"hello");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct VolatileReader {
+    start: *mut u8,
+    len: usize,
+}
+
+// SAFETY: the memory is only accessed with atomic reads and writes, which the caller of
+// `VolatileReader::new` allows from any thread
+unsafe impl Send for VolatileReader {}
+
+impl VolatileReader {
+    /// Returns a reader over the `len` bytes at `start`, which it reads and writes at their
+    /// addresses, e.g. `start as usize` for the first one.
+    ///
+    /// # Safety
+    ///
+    /// These bytes must stay valid for reads and writes while the reader exists, from any
+    /// thread. While the reader may access them, whatever else accesses them must do so
+    /// through raw pointers or atomics, and those the reader writes, or that change while
+    /// it reads them, only through atomics: a `&mut` to them is undefined behavior.
+    pub unsafe fn new(start: *mut u8, len: usize) -> Self {
+        VolatileReader { start, len }
+    }
+
+    /// Returns a reader over `rb`, which a producer may use from any thread meanwhile, as
+    /// all its fields are atomics. It stops before the lock of [`AtomicRB::writer`].
+    #[cfg(feature = "producer")]
+    pub fn from_atomic_rb<const SIZE: usize, const ID: u8>(
+        rb: &'static AtomicRB<SIZE, ID>,
+    ) -> Self {
+        // SAFETY: `rb` lives forever, and the fields of the ring buffer are `AtomicU8`s,
+        // which may change under a shared reference
+        unsafe {
+            VolatileReader::new(
+                rb as *const AtomicRB<SIZE, ID> as *mut u8,
+                core::mem::size_of::<RB<SIZE, ID>>(),
+            )
+        }
+    }
+
+    /// Checks that the `len` bytes at `address` are within the memory, and returns the
+    /// offset of the first one
+    fn check(&self, address: usize, len: usize) -> Result<usize, OutOfBounds> {
+        let offset = address.wrapping_sub(self.start as usize);
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(offset),
+            _ => Err(OutOfBounds { address, len }),
+        }
+    }
+
+    /// Returns the byte at `offset`, which must be within the memory
+    fn byte(&self, offset: usize) -> &AtomicU8 {
+        // SAFETY: within the memory given to `new`, whose other accesses are atomic
+        unsafe { AtomicU8::from_ptr(self.start.add(offset)) }
+    }
+}
+
+impl MemoryReader for VolatileReader {
+    type Error = OutOfBounds;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), OutOfBounds> {
+        let offset = self.check(address, buffer.len())?;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.byte(offset + i).load(Ordering::Acquire);
+        }
+        Ok(())
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), OutOfBounds> {
+        let offset = self.check(address, 1)?;
+        self.byte(offset).store(value, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// Error of [`FaultyReader`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault<E> {
+    /// The transfer failed as the [`Faults`] of the reader said
+    Injected,
+    /// The wrapped reader failed
+    Reader(E),
+}
+
+impl<E: fmt::Display> fmt::Display for Fault<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Injected => write!(f, "injected fault"),
+            Fault::Reader(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Fault<E> {}
+
+/// A [`MemoryReader`] wrapping another, whose transfers fail, or are counted, as its
+/// [`Faults`] say, to check how consumer code copes with a debug probe that caps, drops or
+/// delays its transfers.
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::consumer::testing::{FaultyReader, Loopback};
+/// use ramlink::consumer::ProducerDevice;
+///
+/// let loopback = Loopback::<8>::new();
+/// let reader = FaultyReader::new(loopback.memory());
+/// let faults = reader.faults();
+/// let mut device = ProducerDevice::new(reader, 0).unwrap();
+///
+/// loopback.send_bytes_blocking(b"hi");
+/// faults.fail_transfer(1);
+/// assert!(device.read_bytes().is_err());
+/// assert_eq!(device.read_bytes().unwrap(), b"hi");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FaultyReader<M> {
+    inner: M,
+    faults: Faults,
+}
+
+impl<M: MemoryReader> FaultyReader<M> {
+    /// Returns a reader over `inner`, without faults yet
+    pub fn new(inner: M) -> Self {
+        FaultyReader {
+            inner,
+            faults: Faults::default(),
+        }
+    }
+
+    /// Returns the faults of the reader, to change them or to read its counters while a
+    /// [`ProducerDevice`](super::ProducerDevice) owns it
+    pub fn faults(&self) -> Faults {
+        self.faults.clone()
+    }
+}
+
+impl<M: MemoryReader> MemoryReader for FaultyReader<M> {
+    type Error = Fault<M::Error>;
+
+    fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let (hook, fails) = {
+            let mut state = self.faults.state();
+            state.reads += 1;
+            state.bytes_read += buffer.len();
+            let hook = match &mut state.on_read {
+                Some((1, _)) => state.on_read.take().map(|(_, hook)| hook),
+                Some((reads, _)) => {
+                    *reads -= 1;
+                    None
+                }
+                None => None,
+            };
+            let capped = state.max_read.is_some_and(|max| buffer.len() > max);
+            (hook, state.transfer() || capped)
+        };
+        if let Some(hook) = hook {
+            hook();
+        }
+        if fails {
+            return Err(Fault::Injected);
+        }
+        self.inner
+            .read_memory(address, buffer)
+            .map_err(Fault::Reader)
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Self::Error> {
+        let fails = {
+            let mut state = self.faults.state();
+            state.writes += 1;
+            state.transfer() || state.fail_writes
+        };
+        if fails {
+            return Err(Fault::Injected);
+        }
+        self.inner
+            .write_memory(address, value)
+            .map_err(Fault::Reader)
+    }
+}
+
+/// The faults of a [`FaultyReader`], and its counters. Clones share them, so that a test
+/// can change them while the reader is in use. A transfer is a call to
+/// [`MemoryReader::read_memory`] or [`MemoryReader::write_memory`], whether it fails or not.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    state: Arc<Mutex<FaultState>>,
+}
+
+#[derive(Default)]
+struct FaultState {
+    reads: usize,
+    writes: usize,
+    bytes_read: usize,
+    max_read: Option<usize>,
+    /// Number of the transfer that fails, counting from 1 for the next one, or 0 for none
+    fail_in: usize,
+    fail_every: usize,
+    /// Transfers since `fail_every` was set
+    transfers: usize,
+    fail_writes: bool,
+    on_read: Option<(usize, Box<dyn FnOnce() + Send>)>,
+}
+
+impl FaultState {
+    /// Counts a transfer, and returns whether it fails
+    fn transfer(&mut self) -> bool {
+        let mut fails = false;
+        if self.fail_in > 0 {
+            self.fail_in -= 1;
+            fails |= self.fail_in == 0;
+        }
+        if self.fail_every > 0 {
+            self.transfers += 1;
+            fails |= self.transfers % self.fail_every == 0;
+        }
+        fails
+    }
+}
+
+impl fmt::Debug for FaultState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultState")
+            .field("reads", &self.reads)
+            .field("writes", &self.writes)
+            .field("bytes_read", &self.bytes_read)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Faults {
+    fn state(&self) -> MutexGuard<'_, FaultState> {
+        // A panicking test does not make the counters any less valid
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the number of reads since the reader was created, or the counters reset
+    pub fn reads(&self) -> usize {
+        self.state().reads
+    }
+
+    /// Returns the number of writes since the reader was created, or the counters reset
+    pub fn writes(&self) -> usize {
+        self.state().writes
+    }
+
+    /// Returns the number of bytes the reads asked for since the reader was created, or the
+    /// counters reset
+    pub fn bytes_read(&self) -> usize {
+        self.state().bytes_read
+    }
+
+    /// Sets the counters back to 0
+    pub fn reset_counts(&self) {
+        let mut state = self.state();
+        (state.reads, state.writes, state.bytes_read) = (0, 0, 0);
+    }
+
+    /// Makes the reads of more than `bytes` fail, or none with `None`
+    pub fn set_max_read(&self, bytes: Option<usize>) {
+        self.state().max_read = bytes;
+    }
+
+    /// Makes the `n`th next transfer fail, 1 being the next one, or none with 0
+    pub fn fail_transfer(&self, n: usize) {
+        self.state().fail_in = n;
+    }
+
+    /// Makes every `n`th transfer fail from now on, or none with 0
+    pub fn fail_every(&self, n: usize) {
+        let mut state = self.state();
+        (state.fail_every, state.transfers) = (n, 0);
+    }
+
+    /// Makes the writes fail, or not
+    pub fn set_fail_writes(&self, fail: bool) {
+        self.state().fail_writes = fail;
+    }
+
+    /// Calls `f` before the `n`th next read, 1 being the next one, e.g. to have a producer
+    /// send while the consumer polls
+    pub fn on_read(&self, n: usize, f: impl FnOnce() + Send + 'static) {
+        self.state().on_read = Some((n.max(1), Box::new(f)));
+    }
+}
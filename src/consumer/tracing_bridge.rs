@@ -0,0 +1,122 @@
+//! Emission of the log records of the target as `tracing` events of the host.
+//!
+//! With the `tracing` feature, [`RecordReader::forward_tracing`] is to `tracing` what
+//! `RecordReader::forward` is to `log`: each record becomes an event, whose target is
+//! `"firmware"`, so that a subscriber filters and shows them apart from those of the host,
+//! e.g. with `RUST_LOG=firmware=debug`. The events carry the id of the target of the
+//! firmware, its name, and a timestamp as fields. The producer sends no time: the
+//! timestamp is when the record was decoded, in microseconds since the Unix epoch.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::Level;
+
+use super::{ConsumerError, LogRecord, MemoryReader, RecordReader};
+
+impl LogRecord {
+    /// Returns the level as a `tracing::Level`
+    pub fn tracing_level(&self) -> Level {
+        [
+            Level::ERROR,
+            Level::WARN,
+            Level::INFO,
+            Level::DEBUG,
+            Level::TRACE,
+        ][self.level as usize - 1]
+    }
+}
+
+/// Emits `record` to the current subscriber, as an event of target `"firmware"` with the
+/// fields `target_id`, `target_name`, `timestamp`, and the message
+pub fn emit(record: &LogRecord, target_name: &str, timestamp: SystemTime) {
+    let timestamp = timestamp
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_micros() as u64);
+    // The level of an event must be known at compile time
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!(
+                target: "firmware",
+                $level,
+                target_id = record.target,
+                target_name,
+                timestamp,
+                "{}",
+                record.message
+            )
+        };
+    }
+    match record.tracing_level() {
+        Level::ERROR => event!(Level::ERROR),
+        Level::WARN => event!(Level::WARN),
+        Level::INFO => event!(Level::INFO),
+        Level::DEBUG => event!(Level::DEBUG),
+        Level::TRACE => event!(Level::TRACE),
+    }
+}
+
+impl<M: MemoryReader> RecordReader<'_, M> {
+    /// Emits the records that are complete as `tracing` events, see [`emit`], and returns
+    /// how many were read. The subscriber filters them as it does those of the host.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::producer::AtomicRB;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// // Keeps what the subscriber writes, which `fmt::TestWriter` would print instead
+    /// #[derive(Clone, Default)]
+    /// struct Output(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Output {
+    ///     fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(data)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let output = Output::default();
+    /// let writer = output.clone();
+    /// let subscriber = tracing_subscriber::fmt()
+    ///     .with_writer(move || writer.clone())
+    ///     .with_max_level(tracing::Level::INFO)
+    ///     .with_ansi(false)
+    ///     .without_time()
+    ///     .finish();
+    ///
+    /// static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let mut records = device.records();
+    /// records.name_target(1, "adc");
+    ///
+    /// RING_BUF.send_bytes_blocking(&[1, 1, 2, b'h', b'i']);
+    /// RING_BUF.send_bytes_blocking(&[4, 1, 2, b'l', b'o']);
+    /// RING_BUF.send_bytes_blocking(&[3, 7, 2, b'o', b'k']);
+    /// let forwarded = tracing::subscriber::with_default(subscriber, || records.forward_tracing());
+    /// assert_eq!(forwarded.unwrap(), 3);
+    ///
+    /// let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    /// let lines: Vec<&str> = output.lines().collect();
+    /// assert_eq!(lines.len(), 2);
+    /// assert!(lines[0].starts_with("ERROR firmware: hi target_id=1 target_name=\"adc\" timestamp="));
+    /// assert!(lines[1].starts_with(" INFO firmware: ok target_id=7 target_name=\"7\" timestamp="));
+    /// # }
+    /// ```
+    pub fn forward_tracing(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        let mut forwarded = 0;
+        while let Some(record) = self.try_next_record()? {
+            forwarded += 1;
+            if record.tracing_level() > tracing::level_filters::LevelFilter::current() {
+                continue;
+            }
+            emit(&record, &self.target_name(record.target), SystemTime::now());
+        }
+        Ok(forwarded)
+    }
+}
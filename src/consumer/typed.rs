@@ -0,0 +1,118 @@
+//! Reading of the values sent with `RB::send_postcard`.
+
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use serde::de::DeserializeOwned;
+
+use super::{ConsumerError, ConsumerErrorKind, MemoryReader, ProducerDevice};
+
+/// Number of bytes of the longest LEB128 varint that fits in a `usize`
+const MAX_PREFIX: usize = (usize::BITS as usize).div_ceil(7);
+
+impl<M: MemoryReader> ProducerDevice<M> {
+    /// Returns the next value sent with `RB::send_postcard`, deserialized with postcard,
+    /// polling the ring buffer every `poll` until its frame is complete. Only the bytes of
+    /// that frame are consumed, and the frame may be larger than the ring buffer.
+    ///
+    /// Fails with [`ConsumerErrorKind::Timeout`] if that takes more than `timeout`, `got`
+    /// being the number of bytes of the frame read, length prefix included, and with
+    /// [`ConsumerErrorKind::Deserialize`] if the frame is not a `T`. The bytes of the frame
+    /// are consumed either way, so after a timeout the next call starts in the middle of it.
+    /// ```
+    /// # #[cfg(all(feature = "producer", feature = "std"))] {
+    /// # use ramlink::consumer::ProducerDevice;
+    /// use ramlink::consumer::ConsumerErrorKind;
+    /// use ramlink::producer::AtomicRB;
+    /// use serde::{Deserialize, Serialize};
+    /// use std::time::Duration;
+    /// # use ramlink::consumer::testing::VolatileReader;
+    /// # let host = VolatileReader::from_atomic_rb(&RING_BUF);
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// enum Event {
+    ///     Boot { version: (u8, u8) },
+    ///     Samples { channel: u8, values: [i16; 16] },
+    ///     Fault(u32),
+    /// }
+    ///
+    /// let events = [
+    ///     Event::Boot { version: (1, 4) },
+    ///     // Larger than the ring buffer
+    ///     Event::Samples { channel: 2, values: core::array::from_fn(|i| i as i16 * -700) },
+    ///     Event::Fault(0xdead_beef),
+    /// ];
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::new();
+    /// let address = &RING_BUF as *const _ as usize;
+    /// let producer = std::thread::spawn(move || {
+    ///     for event in &events {
+    ///         RING_BUF.send_postcard(event).unwrap();
+    ///     }
+    ///     RING_BUF.send_postcard(&0x1234_u16).unwrap();
+    /// });
+    ///
+    /// let mut device = ProducerDevice::new(host, address).unwrap();
+    /// let (poll, timeout) = (Duration::from_millis(1), Duration::from_secs(5));
+    /// let read: Vec<Event> = (0..3)
+    ///     .map(|_| device.read_postcard(poll, timeout).unwrap())
+    ///     .collect();
+    /// producer.join().unwrap();
+    /// assert_eq!(read[0], Event::Boot { version: (1, 4) });
+    /// assert!(matches!(read[1], Event::Samples { channel: 2, values } if values[15] == -10500));
+    /// assert_eq!(read[2], Event::Fault(0xdead_beef));
+    ///
+    /// // 0x1234 is not the index of a variant of `Event`
+    /// let err = device.read_postcard::<Event>(poll, timeout).unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::Deserialize(_)));
+    /// let err = device.read_postcard::<Event>(poll, timeout).unwrap_err();
+    /// assert!(matches!(err.kind(), ConsumerErrorKind::Timeout { got: 0 }));
+    /// # }
+    /// ```
+    pub fn read_postcard<T: DeserializeOwned>(
+        &mut self,
+        poll: Duration,
+        timeout: Duration,
+    ) -> Result<T, ConsumerError<M::Error>> {
+        let start = Instant::now();
+        let wait = |got| {
+            if start.elapsed() >= timeout {
+                return Err(ConsumerError(ConsumerErrorKind::Timeout { got }));
+            }
+            std::thread::sleep(poll);
+            Ok(())
+        };
+
+        // The prefix is read a byte at a time, so that no byte after it is consumed
+        let mut len = 0;
+        let mut prefix_len = 0;
+        loop {
+            let mut byte = [0];
+            if self.read_into(&mut byte)? == 0 {
+                wait(prefix_len)?;
+                continue;
+            }
+            len |= ((byte[0] & 0x7F) as usize) << (7 * prefix_len);
+            prefix_len += 1;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            if prefix_len == MAX_PREFIX {
+                return Err(ConsumerError(ConsumerErrorKind::InvalidFrame));
+            }
+        }
+
+        // Grows as bytes arrive, rather than trusting the prefix with an allocation
+        let mut payload = Vec::new();
+        while payload.len() < len {
+            let at = payload.len();
+            payload.resize(at + (len - at).min(self.capacity()), 0);
+            let read = self.read_into(&mut payload[at..])?;
+            payload.truncate(at + read);
+            if read == 0 {
+                wait(prefix_len + at)?;
+            }
+        }
+        postcard::from_bytes(&payload).map_err(|e| ConsumerError(ConsumerErrorKind::Deserialize(e)))
+    }
+}
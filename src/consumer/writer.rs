@@ -0,0 +1,175 @@
+//! Writing to the target, through the `RxRB` of the producer.
+
+use super::{le_index, ConsumerError, ConsumerErrorKind, Header, MemoryReader};
+use crate::layout::{self, Offset};
+
+/// Offset of the host-attached flag of an `RxRB`, which always has one
+const HOST_ATTACHED: usize = match layout::RX.host_attached {
+    Some(Offset::Start(offset)) => offset,
+    _ => panic!("RxRB has a host-attached flag before its content"),
+};
+
+/// The host end of an `RxRB`, the ring buffer the target reads commands from. It writes
+/// the content and the producer index, and only as many bytes as the consumer index of
+/// the target leaves room for, so it never overwrites bytes not read yet.
+///
+/// The host-attached flag of the `RxRB` is set until the writer is dropped. A
+/// [`ProducerDevice`](super::ProducerDevice) and a writer can share a single probe through a
+/// `&RefCell`:
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// use core::cell::RefCell;
+/// use ramlink::consumer::{HostWriter, ProducerDevice};
+/// use ramlink::producer::DuplexRB;
+/// # use ramlink::consumer::testing::VolatileReader;
+///
+/// // The host accesses the link meanwhile, so it is only borrowed for a call
+/// let link: *mut DuplexRB<16, 8> = Box::into_raw(Box::new(DuplexRB::new()));
+/// # let len = core::mem::size_of::<DuplexRB<16, 8>>();
+/// # let host = unsafe { VolatileReader::new(link as *mut u8, len) };
+/// let probe = RefCell::new(host);
+/// let mut device = ProducerDevice::new(&probe, link as usize).unwrap();
+/// let mut writer = HostWriter::new(&probe, device.end_address()).unwrap();
+/// assert!(unsafe { (*link).rx.host_attached() });
+///
+/// // Only 7 bytes fit until the target reads some
+/// assert_eq!(writer.write_bytes(b"level=debug").unwrap(), 7);
+/// assert_eq!(writer.free_space().unwrap(), 0);
+///
+/// let mut command = [0; 16];
+/// let read = unsafe { (*link).rx.read_into(&mut command) };
+/// assert_eq!(&command[..read], b"level=d");
+/// assert_eq!(writer.write_bytes(b"ebug\n").unwrap(), 5);
+/// let read = unsafe { (*link).rx.read_into(&mut command) };
+/// assert_eq!(&command[..read], b"ebug\n");
+///
+/// unsafe { (*link).tx.send_bytes_blocking(b"ok\n") };
+/// assert_eq!(device.read_bytes().unwrap(), b"ok\n");
+///
+/// drop(writer);
+/// assert!(!unsafe { (*link).rx.host_attached() });
+/// # }
+/// ```
+///
+/// A `RB` is not an `RxRB`, and the other way around:
+/// ```
+/// # #[cfg(all(feature = "producer", feature = "std"))] {
+/// use ramlink::consumer::{ConsumerErrorKind, HostWriter, ProducerDevice};
+/// use ramlink::producer::{RxRB, RB};
+/// # use ramlink::consumer::testing::VolatileReader;
+/// # // SAFETY: nothing is written, as neither is attached to
+/// # let reader = |start: *const u8, len| unsafe { VolatileReader::new(start as *mut u8, len) };
+///
+/// let rb = RB::<8>::new();
+/// # let host = reader(&rb as *const _ as *const u8, core::mem::size_of_val(&rb));
+/// let err = HostWriter::new(host, &rb as *const _ as usize).err().unwrap();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::MagicMarkerNotFound));
+///
+/// let rx = RxRB::<8>::new();
+/// # let host = reader(&rx as *const _ as *const u8, core::mem::size_of_val(&rx));
+/// let err = ProducerDevice::new(host, &rx as *const _ as usize).err().unwrap();
+/// assert!(matches!(err.kind(), ConsumerErrorKind::MagicMarkerNotFound));
+/// # }
+/// ```
+pub struct HostWriter<M: MemoryReader> {
+    memory_reader: M,
+    ram_start: usize,
+    rb_size: usize,
+}
+
+impl<M: MemoryReader> HostWriter<M> {
+    /// Attaches to the `RxRB` at `ram_start_address`, checking its magic marker, and sets
+    /// its host-attached flag
+    pub fn new(
+        memory_reader: M,
+        ram_start_address: usize,
+    ) -> Result<Self, ConsumerError<M::Error>> {
+        Self::new_with_id(memory_reader, ram_start_address, layout::DEFAULT_ID)
+    }
+
+    /// Same as [`HostWriter::new`], for an `RxRB` declared with the id `expected_id`
+    pub fn new_with_id(
+        mut memory_reader: M,
+        ram_start_address: usize,
+        expected_id: u8,
+    ) -> Result<Self, ConsumerError<M::Error>> {
+        let header = Header::read_any(
+            &mut memory_reader,
+            ram_start_address,
+            expected_id,
+            &[&layout::RX],
+        )?;
+        let mut writer = HostWriter {
+            memory_reader,
+            ram_start: ram_start_address,
+            rb_size: header.rb_size,
+        };
+        writer.write(HOST_ATTACHED, &[1])?;
+        Ok(writer)
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is its size minus one.
+    pub fn capacity(&self) -> usize {
+        self.rb_size - 1
+    }
+
+    /// Returns how many bytes can be written before the target reads some
+    pub fn free_space(&mut self) -> Result<usize, ConsumerError<M::Error>> {
+        let (prod, cons) = self.read_indices()?;
+        Ok(self.capacity() - (prod + self.rb_size - cons) % self.rb_size)
+    }
+
+    /// Writes as many bytes of `data` as currently fit, without waiting for the target,
+    /// and returns how many were written. The bytes are written before the producer index
+    /// that publishes them, so the target never reads a slot before it is written.
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<usize, ConsumerError<M::Error>> {
+        let (prod, cons) = self.read_indices()?;
+        let free = self.capacity() - (prod + self.rb_size - cons) % self.rb_size;
+        let written = free.min(data.len());
+        if written == 0 {
+            return Ok(0);
+        }
+
+        // Bytes that wrap around are written in two parts
+        let (first, wrapped) = data[..written].split_at(written.min(self.rb_size - prod));
+        self.write(layout::RX.content + prod, first)?;
+        if !wrapped.is_empty() {
+            self.write(layout::RX.content, wrapped)?;
+        }
+        let prod = (prod + written) % self.rb_size;
+        self.write(layout::RX.producer, &[prod as u8])?;
+        Ok(written)
+    }
+
+    /// Reads both indices at once, checking that they are within the ring buffer
+    fn read_indices(&mut self) -> Result<(usize, usize), ConsumerError<M::Error>> {
+        let mut buf = [0; 2];
+        self.memory_reader
+            .read_memory(self.ram_start + layout::RX.producer, &mut buf)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::ReadMemoryError(e)))?;
+        let (prod, cons) = (le_index(&buf[..1]), le_index(&buf[1..]));
+        if prod >= self.rb_size || cons >= self.rb_size {
+            return Err(ConsumerError(ConsumerErrorKind::CorruptIndex {
+                producer: prod,
+                consumer: cons,
+                size: self.rb_size,
+            }));
+        }
+        Ok((prod, cons))
+    }
+
+    /// Writes `data` at `offset` from the start of the ring buffer
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), ConsumerError<M::Error>> {
+        self.memory_reader
+            .write_memory_slice(self.ram_start + offset, data)
+            .map_err(|e| ConsumerError(ConsumerErrorKind::WriteMemoryError(e)))
+    }
+}
+
+impl<M: MemoryReader> Drop for HostWriter<M> {
+    /// Clears the host-attached flag. Errors are ignored, as the link to the device may
+    /// already be gone.
+    fn drop(&mut self) {
+        let _ = self.write(HOST_ATTACHED, &[0]);
+    }
+}
@@ -0,0 +1,894 @@
+//! Memory layout of the ring buffers, shared by the producer and the consumer.
+//!
+//! The consumer only knows the ring buffer through these offsets, so the producer structs
+//! assert at compile time that their fields are where this module says they are.
+//!
+//! [`RB`] starts like the ring buffers of the first releases, so that their consumers still
+//! read it: the magic marker, the size and the indices, then the content. Its other fields
+//! follow the content, starting with [`CONTENT_END_MARKER`].
+//!
+//! Optional fields, enabled by cargo features on the producer side, follow the content in
+//! the order of the [`FEATURE_STATS`], [`FEATURE_HEARTBEAT`], [`FEATURE_WRAPS`], [`FEATURE_NAME`]
+//! bits. The features byte of the header tells which ones are present.
+//!
+//! A snapshot of a producer ring buffer, as a debugger would read it, must be understood
+//! by the consumer:
+//! ```
+//! # #[cfg(all(feature = "producer", feature = "std"))] {
+//! use ramlink::consumer::testing::InMemoryReader;
+//! use ramlink::consumer::ProducerDevice;
+//! use ramlink::producer::{RB, RB16};
+//!
+//! fn snapshot<T>(rb: &T) -> InMemoryReader {
+//!     let bytes = unsafe {
+//!         core::slice::from_raw_parts(rb as *const T as *const u8, core::mem::size_of_val(rb))
+//!     };
+//!     InMemoryReader::new(bytes.to_vec())
+//! }
+//!
+//! let mut rb = RB::<16>::new();
+//! rb.send_bytes_blocking(&[0x42; 15]);
+//! let mut device = ProducerDevice::new(snapshot(&rb), 0).unwrap();
+//! assert_eq!(device.read_bytes().unwrap(), [0x42; 15]);
+//!
+//! // The same consumer code reads `RB16`, whose indices go past 255
+//! let mut rb = RB16::<1000>::new();
+//! let data: Vec<u8> = (0..900).map(|i| i as u8).collect();
+//! rb.send_bytes_blocking(&data);
+//! let mut device = ProducerDevice::new(snapshot(&rb), 0).unwrap();
+//! assert_eq!(device.capacity(), 999);
+//! assert_eq!(device.available().unwrap(), 900);
+//! assert_eq!(device.read_bytes().unwrap(), data);
+//! # }
+//! ```
+
+// Some items are only used by the producer, others only by the consumer
+#![cfg_attr(not(all(feature = "producer", feature = "consumer")), allow(dead_code))]
+
+/// Where a field of a ring buffer is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Offset {
+    /// Relative to the start of the ring buffer
+    Start(usize),
+    /// Relative to the end of the content, for the fields that [`RB`] keeps after it
+    End(usize),
+}
+
+impl Offset {
+    /// Returns the address of the field in the ring buffer at `start`, whose content of
+    /// `size` bytes is at `content`
+    pub const fn address(self, start: usize, content: usize, size: usize) -> usize {
+        match self {
+            Offset::Start(offset) => start + offset,
+            Offset::End(offset) => content + size + offset,
+        }
+    }
+
+    /// Returns the offset of the field from the start of a ring buffer of `size` bytes,
+    /// whose content is `content` bytes after its start
+    pub const fn resolve(self, content: usize, size: usize) -> usize {
+        self.address(0, content, size)
+    }
+}
+
+/// Where the fields describing a ring buffer are. The consumer reads them when it attaches,
+/// see [`Layout::header`].
+pub(crate) struct HeaderLayout {
+    /// Start of the marker at the start of the ring buffer, which also tells which layout
+    /// it uses. Its third byte is the id of the ring buffer, see [`HeaderLayout::magic`]
+    pub magic_prefix: [u8; 2],
+    /// Offset from the end of the content of [`CONTENT_END_MARKER`], for the layouts whose
+    /// magic marker is that of version 0
+    pub end_marker: Option<usize>,
+    /// Version byte. `None` for the version 0 layouts, which predate it
+    pub version: Option<Offset>,
+    /// Size of the ring buffer, as wide as the indices
+    pub size: usize,
+    /// Bitmask of the optional fields following the content. `None` for the `RB` of
+    /// version 0, which has none
+    pub features: Option<Offset>,
+}
+
+/// Id of the ring buffers that were not given one, and of those from before ids existed
+pub(crate) const DEFAULT_ID: u8 = 0x88;
+
+/// Version of the layouts written by the producer. The consumer also reads version 0, the
+/// layouts without a version byte, which it tells apart by their magic marker, or for
+/// [`RB`] by the [`CONTENT_END_MARKER`]
+pub(crate) const VERSION: u8 = 1;
+
+/// First byte after the content of an [`RB`], followed by the version byte. The `RB` of
+/// version 0 has the same magic marker, and whatever follows it in memory after its content,
+/// so the consumer tells them apart by this byte.
+pub(crate) const CONTENT_END_MARKER: u8 = 0x89;
+
+impl HeaderLayout {
+    /// Returns the magic marker of a ring buffer of this layout with the given `id`
+    pub const fn magic(&self, id: u8) -> [u8; 3] {
+        [self.magic_prefix[0], self.magic_prefix[1], id]
+    }
+}
+
+/// Where the fields of a ring buffer are, relative to its start unless told otherwise
+pub(crate) struct Layout {
+    /// `None` if the ring buffer does not describe itself, in which case the consumer must
+    /// be told its size, and it has no optional fields
+    pub header: Option<HeaderLayout>,
+    /// Width in bytes of the size and of the producer/consumer indices, all little-endian
+    pub index_width: usize,
+    pub producer: usize,
+    pub consumer: usize,
+    /// Number of bytes discarded by the producer, little-endian `u16`. `None` for the `RB`
+    /// of version 0, whose producer never drops any
+    pub dropped: Option<Offset>,
+    /// Set to 1 by the consumer while it is attached. `None` for the `RB` of version 0
+    pub host_attached: Option<Offset>,
+    /// Offset of the content, or, if `indirect`, of where it is: the width in bytes of a
+    /// pointer to it, a reserved byte, then the pointer, little-endian
+    pub content: usize,
+    pub indirect: bool,
+    /// Number of bytes of the fields between the content and the optional fields, those
+    /// at an [`Offset::End`]
+    pub after_content: usize,
+}
+
+/// The `stats` feature: a `u8` high water mark and a little-endian `u32` total of bytes sent
+pub(crate) const FEATURE_STATS: u8 = 1 << 0;
+pub(crate) const STATS_LEN: usize = 5;
+/// The `heartbeat` feature: a `u8` counter incremented by the producer
+pub(crate) const FEATURE_HEARTBEAT: u8 = 1 << 1;
+pub(crate) const HEARTBEAT_LEN: usize = 1;
+/// The `wraps` feature: a little-endian `u16` counting how many times the producer index
+/// went back to the start of the content
+pub(crate) const FEATURE_WRAPS: u8 = 1 << 2;
+pub(crate) const WRAPS_LEN: usize = 2;
+/// The `names` feature: the name given by `RB::new_named`, padded with zeros, all zeros if
+/// there is none
+pub(crate) const FEATURE_NAME: u8 = 1 << 3;
+pub(crate) const NAME_LEN: usize = CHANNEL_NAME_LEN;
+
+/// Optional fields of [`RB`] and [`AtomicRB`](crate::producer::AtomicRB), as enabled by
+/// the cargo features
+#[cfg(feature = "producer")]
+pub(crate) const RB_FEATURES: u8 = (if cfg!(feature = "stats") {
+    FEATURE_STATS
+} else {
+    0
+}) | (if cfg!(feature = "heartbeat") {
+    FEATURE_HEARTBEAT
+} else {
+    0
+}) | (if cfg!(feature = "wraps") {
+    FEATURE_WRAPS
+} else {
+    0
+}) | (if cfg!(feature = "names") {
+    FEATURE_NAME
+} else {
+    0
+});
+
+/// Returns the number of bytes of the optional fields enabled in `features`
+pub(crate) const fn trailer_len(features: u8) -> usize {
+    (if features & FEATURE_STATS != 0 {
+        STATS_LEN
+    } else {
+        0
+    }) + (if features & FEATURE_HEARTBEAT != 0 {
+        HEARTBEAT_LEN
+    } else {
+        0
+    }) + (if features & FEATURE_WRAPS != 0 {
+        WRAPS_LEN
+    } else {
+        0
+    }) + (if features & FEATURE_NAME != 0 {
+        NAME_LEN
+    } else {
+        0
+    })
+}
+
+/// Returns the offset of the optional field `feature`, counted from the end of the
+/// [`Layout::after_content`] fields, if it is enabled in `features`
+pub(crate) const fn trailer_offset(features: u8, feature: u8) -> Option<usize> {
+    if features & feature == 0 {
+        return None;
+    }
+    let fields = [
+        (FEATURE_STATS, STATS_LEN),
+        (FEATURE_HEARTBEAT, HEARTBEAT_LEN),
+        (FEATURE_WRAPS, WRAPS_LEN),
+        (FEATURE_NAME, NAME_LEN),
+    ];
+    let mut offset = 0;
+    let mut i = 0;
+    while fields[i].0 != feature {
+        if features & fields[i].0 != 0 {
+            offset += fields[i].1;
+        }
+        i += 1;
+    }
+    Some(offset)
+}
+
+/// Polynomial of the CRC-8 of the frames sent with `send_frame_crc`, that of CRC-8/SMBUS:
+/// x^8 + x^2 + x + 1, with an initial value of 0 and no final XOR
+pub(crate) const CRC8_POLY: u8 = 0x07;
+
+/// Returns the CRC-8 of `crc` followed by `byte`, starting from 0 for the first byte
+pub(crate) const fn crc8_update(crc: u8, byte: u8) -> u8 {
+    let mut crc = crc ^ byte;
+    let mut bit = 0;
+    while bit < 8 {
+        crc = if crc & 0x80 != 0 {
+            crc << 1 ^ CRC8_POLY
+        } else {
+            crc << 1
+        };
+        bit += 1;
+    }
+    crc
+}
+
+// Check value of CRC-8/SMBUS
+const _: () = {
+    let mut crc = 0;
+    let mut i = 0;
+    while i < 9 {
+        crc = crc8_update(crc, b"123456789"[i]);
+        i += 1;
+    }
+    assert!(crc == 0xF4);
+};
+
+/// Window byte of a frame sent with `send_frame_compressed` whose payload is stored as it
+/// is, because compressing it would not make it shorter
+#[cfg(feature = "compression")]
+pub(crate) const LZSS_STORED: u8 = 0;
+/// Window sizes of the LZSS back-references, in bits: the offset takes that many bits of
+/// the big-endian `u16` of a back-reference, and its length the others
+#[cfg(feature = "compression")]
+pub(crate) const LZSS_WINDOW_BITS: core::ops::RangeInclusive<u32> = 4..=12;
+/// Shortest back-reference, as a shorter one would not save anything
+#[cfg(feature = "compression")]
+pub(crate) const LZSS_MIN_MATCH: usize = 3;
+
+/// Tag of the frames sent with `send_text`, in place of the channel of `send_frame_on`
+pub(crate) const TAG_TEXT: u8 = 0x01;
+/// Tag of the frames sent with `send_binary`
+pub(crate) const TAG_BINARY: u8 = 0x02;
+/// First of the tags left to the application, up to 0xFF. The ones below are reserved
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) const TAG_USER: u8 = 0x80;
+
+/// Byte ending a SLIP frame (RFC 1055), sent before it as well by `send_frame_slip`
+pub(crate) const SLIP_END: u8 = 0xC0;
+/// Byte escaping [`SLIP_END`] and itself in a SLIP frame
+pub(crate) const SLIP_ESC: u8 = 0xDB;
+/// Follows [`SLIP_ESC`] in place of a [`SLIP_END`] of the payload
+pub(crate) const SLIP_ESC_END: u8 = 0xDC;
+/// Follows [`SLIP_ESC`] in place of a [`SLIP_ESC`] of the payload
+pub(crate) const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// Header of [`RB`](crate::producer::RB) and [`AtomicRB`](crate::producer::AtomicRB). Up to
+/// the content, they are the [`RB_V0`] of the first releases, whose consumers still read
+/// them: the version and features bytes follow the content.
+pub(crate) const RB_HEADER: HeaderLayout = HeaderLayout {
+    magic_prefix: [0x88, 0x88],
+    end_marker: Some(0),
+    version: Some(Offset::End(1)),
+    size: 3,
+    features: Some(Offset::End(4)),
+};
+
+/// Layout of [`RB`](crate::producer::RB) and [`AtomicRB`](crate::producer::AtomicRB): that
+/// of [`RB_V0`], then after the content [`CONTENT_END_MARKER`], the version, the dropped
+/// count, the features and the host-attached flag
+pub(crate) const RB: Layout = Layout {
+    header: Some(RB_HEADER),
+    index_width: 1,
+    producer: 4,
+    consumer: 5,
+    dropped: Some(Offset::End(2)),
+    host_attached: Some(Offset::End(5)),
+    content: 6,
+    indirect: false,
+    after_content: 6,
+};
+
+/// Header of [`RB16`](crate::producer::RB16), all of it before the content
+pub(crate) const RB16_HEADER: HeaderLayout = HeaderLayout {
+    magic_prefix: [0x89, 0x16],
+    end_marker: None,
+    version: Some(Offset::Start(3)),
+    size: 4,
+    features: Some(Offset::Start(12)),
+};
+
+/// Layout of [`RB16`](crate::producer::RB16)
+pub(crate) const RB16: Layout = Layout {
+    header: Some(RB16_HEADER),
+    index_width: 2,
+    producer: 6,
+    consumer: 8,
+    dropped: Some(Offset::Start(10)),
+    host_attached: Some(Offset::Start(13)),
+    content: 14,
+    indirect: false,
+    after_content: 0,
+};
+
+/// Layout of [`RB`] before the version byte, that of the first releases, still read by
+/// the consumer for the sake of older firmwares: the magic marker `88 88 88`, the size,
+/// both indices, then the content. It has none of the later fields, and no
+/// [`CONTENT_END_MARKER`] after its content.
+pub(crate) const RB_V0: Layout = Layout {
+    header: Some(HeaderLayout {
+        magic_prefix: [0x88, 0x88],
+        end_marker: None,
+        version: None,
+        size: 3,
+        features: None,
+    }),
+    index_width: 1,
+    producer: 4,
+    consumer: 5,
+    dropped: None,
+    host_attached: None,
+    content: 6,
+    indirect: false,
+    after_content: 0,
+};
+
+/// Layout of [`RB16`] before the version byte
+pub(crate) const RB16_V0: Layout = Layout {
+    header: Some(HeaderLayout {
+        magic_prefix: [0x88, 0x16],
+        end_marker: None,
+        version: None,
+        features: Some(Offset::Start(3)),
+        size: 4,
+    }),
+    index_width: 2,
+    producer: 6,
+    consumer: 8,
+    dropped: Some(Offset::Start(10)),
+    host_attached: Some(Offset::Start(12)),
+    content: 13,
+    indirect: false,
+    after_content: 0,
+};
+
+/// Layout of [`RBCompact`](crate::producer::RBCompact), which has no header at all
+pub(crate) const RB_COMPACT: Layout = Layout {
+    header: None,
+    index_width: 1,
+    producer: 0,
+    consumer: 1,
+    dropped: Some(Offset::Start(2)),
+    host_attached: Some(Offset::Start(4)),
+    content: 5,
+    indirect: false,
+    after_content: 0,
+};
+
+/// Header of [`RBIndirect`](crate::producer::RBIndirect), that of [`RB16_HEADER`] with
+/// another second magic byte
+pub(crate) const RB_INDIRECT_HEADER: HeaderLayout = HeaderLayout {
+    magic_prefix: [0x89, 0x49],
+    ..RB16_HEADER
+};
+
+/// Layout of [`RBIndirect`](crate::producer::RBIndirect): that of [`RB16`], with a pointer
+/// to the content in place of the content
+pub(crate) const RB_INDIRECT: Layout = Layout {
+    header: Some(RB_INDIRECT_HEADER),
+    content: 14,
+    indirect: true,
+    ..RB16
+};
+
+/// Widest content pointer the layout has room for, that of 64 bits targets
+pub(crate) const MAX_POINTER_WIDTH: usize = 8;
+
+/// Header of [`RxRB`](crate::producer::RxRB), whose second magic byte tells the host that
+/// it writes to it instead of reading. No consumer of the first releases reads it, so all
+/// of it is before the content.
+pub(crate) const RX_HEADER: HeaderLayout = HeaderLayout {
+    magic_prefix: [0x89, 0x52],
+    end_marker: None,
+    version: Some(Offset::Start(3)),
+    size: 4,
+    features: Some(Offset::Start(9)),
+};
+
+/// Layout of [`RxRB`](crate::producer::RxRB): the host writes the content and the producer
+/// index, and the target the consumer index
+pub(crate) const RX: Layout = Layout {
+    header: Some(RX_HEADER),
+    index_width: 1,
+    producer: 5,
+    consumer: 6,
+    dropped: Some(Offset::Start(7)),
+    host_attached: Some(Offset::Start(10)),
+    content: 11,
+    indirect: false,
+    after_content: 0,
+};
+
+/// What a ring buffer with a header looks like, for a host that knows what the firmware was
+/// built with instead of guessing it from the magic marker: the layout version, the width
+/// of the indices, the optional fields following the content, and the id. Built from
+/// [`LayoutDescriptor::v1`] or [`LayoutDescriptor::v0`], it computes the offset of every
+/// field, and is given to `ProducerDevice::new_with_layout`.
+///
+/// The producer ring buffers have theirs as a `LAYOUT` const, so that a crate shared by the
+/// firmware and the host can hold it:
+/// ```
+/// # #[cfg(feature = "producer")] {
+/// use ramlink::producer::RB16;
+/// use ramlink::LayoutDescriptor;
+///
+/// const TRACE: LayoutDescriptor = LayoutDescriptor::v1().with_u16_indices().with_id(0x42);
+/// assert_eq!(RB16::<512, 0x42>::LAYOUT, TRACE);
+/// assert_eq!(TRACE.magic(), [0x89, 0x16, 0x42]);
+/// assert_eq!((TRACE.producer_offset(), TRACE.consumer_offset()), (6, 8));
+/// assert_eq!(TRACE.content_offset(), 14);
+/// assert_eq!(TRACE.len(512), core::mem::size_of::<RB16<512, 0x42>>());
+///
+/// // An `RB` keeps the fields added since the first releases after its content
+/// let rb = LayoutDescriptor::v1();
+/// assert_eq!(rb.magic(), [0x88, 0x88, 0x88]);
+/// assert_eq!((rb.size_offset(), rb.producer_offset(), rb.consumer_offset()), (3, 4, 5));
+/// assert_eq!(rb.content_offset(), 6);
+/// assert_eq!((rb.version_offset(64), rb.dropped_offset(64)), (Some(71), Some(72)));
+///
+/// // One of a firmware built before the version byte only has its indices
+/// let old = LayoutDescriptor::v0();
+/// assert_eq!(old.magic(), [0x88, 0x88, 0x88]);
+/// assert_eq!((old.size_offset(), old.producer_offset(), old.consumer_offset()), (3, 4, 5));
+/// assert_eq!((old.dropped_offset(64), old.host_attached_offset(64)), (None, None));
+/// assert_eq!(old.content_offset(), 6);
+/// assert_eq!(old.with_heartbeat().heartbeat_offset(64), None);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutDescriptor {
+    /// 0 for the layouts without a version byte
+    version: u8,
+    /// Width in bytes of the size and of the indices
+    index_width: u8,
+    /// Optional fields following the content, as in the features byte of the header
+    features: u8,
+    /// Last byte of the magic marker
+    id: u8,
+}
+
+impl LayoutDescriptor {
+    /// Returns the layout of an `RB` with no optional field, as written by this version
+    /// of the crate
+    pub const fn v1() -> Self {
+        LayoutDescriptor {
+            version: VERSION,
+            index_width: 1,
+            features: 0,
+            id: DEFAULT_ID,
+        }
+    }
+
+    /// Returns the layout of an `RB` as written before the header had a version byte. It
+    /// has no optional fields, dropped count or host-attached flag: those of the `with_*`
+    /// have no offset, and `ProducerDevice::new_with_layout` refuses them.
+    pub const fn v0() -> Self {
+        LayoutDescriptor {
+            version: 0,
+            ..Self::v1()
+        }
+    }
+
+    /// Same layout, with the 16 bits indices of `RB16`
+    pub const fn with_u16_indices(self) -> Self {
+        LayoutDescriptor {
+            index_width: 2,
+            ..self
+        }
+    }
+
+    /// Same layout, for a ring buffer created with the id `id`, e.g. `RB<64, 0x42>`
+    pub const fn with_id(self, id: u8) -> Self {
+        LayoutDescriptor { id, ..self }
+    }
+
+    /// Same layout, with the high water mark and total of the `stats` feature
+    pub const fn with_stats(self) -> Self {
+        self.with_features(FEATURE_STATS)
+    }
+
+    /// Same layout, with the counter of the `heartbeat` feature
+    pub const fn with_heartbeat(self) -> Self {
+        self.with_features(FEATURE_HEARTBEAT)
+    }
+
+    /// Same layout, with the wrap count of the `wraps` feature
+    pub const fn with_wraps(self) -> Self {
+        self.with_features(FEATURE_WRAPS)
+    }
+
+    /// Same layout, with the name of the `names` feature
+    pub const fn with_name(self) -> Self {
+        self.with_features(FEATURE_NAME)
+    }
+
+    /// Same layout, with the optional fields of the `features` bitmask as well
+    pub(crate) const fn with_features(self, features: u8) -> Self {
+        LayoutDescriptor {
+            features: self.features | features,
+            ..self
+        }
+    }
+
+    /// Returns the version of the layout, 0 if the header has no version byte
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the id of the ring buffer
+    pub const fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the width in bytes of the size and of the indices
+    pub const fn index_width(&self) -> usize {
+        self.index_width as usize
+    }
+
+    /// Returns the bitmask of the optional fields, as the features byte of the header
+    pub(crate) const fn features(&self) -> u8 {
+        self.features
+    }
+
+    /// Returns the magic marker at the start of the ring buffer
+    pub const fn magic(&self) -> [u8; 3] {
+        match &self.layout().header {
+            Some(header) => header.magic(self.id),
+            None => unreachable!(),
+        }
+    }
+
+    /// Returns the offset of the version byte of a ring buffer of `size` bytes, `None` for
+    /// version 0. That of `RB` follows the content.
+    pub const fn version_offset(&self, size: usize) -> Option<usize> {
+        if self.version == 0 {
+            None
+        } else if self.fields_after_content() {
+            Some(self.content_offset() + size + 1)
+        } else {
+            Some(3)
+        }
+    }
+
+    /// Returns the offset of the features byte of a ring buffer of `size` bytes, `None`
+    /// for the `RB` of version 0, which has none. The 16 bits layout of version 0 has it
+    /// right after the magic marker, so that the wider fields are aligned.
+    pub const fn features_offset(&self, size: usize) -> Option<usize> {
+        if self.is_baseline() {
+            None
+        } else if self.fields_after_content() {
+            Some(self.content_offset() + size + 4)
+        } else if self.features_first() {
+            Some(3)
+        } else {
+            Some(self.consumer_offset() + self.index_width() + 2)
+        }
+    }
+
+    /// Returns the offset of the size, as wide as the indices
+    pub const fn size_offset(&self) -> usize {
+        let mut offset = 3;
+        if self.version != 0 && !self.fields_after_content() {
+            offset += 1;
+        }
+        if self.features_first() {
+            offset += 1;
+        }
+        offset
+    }
+
+    /// Returns the offset of the producer index
+    pub const fn producer_offset(&self) -> usize {
+        self.size_offset() + self.index_width()
+    }
+
+    /// Returns the offset of the consumer index, written by the host
+    pub const fn consumer_offset(&self) -> usize {
+        self.producer_offset() + self.index_width()
+    }
+
+    /// Returns the offset of the little-endian `u16` count of dropped bytes of a ring
+    /// buffer of `size` bytes, `None` for the `RB` of version 0
+    pub const fn dropped_offset(&self, size: usize) -> Option<usize> {
+        if self.is_baseline() {
+            None
+        } else if self.fields_after_content() {
+            Some(self.content_offset() + size + 2)
+        } else {
+            Some(self.consumer_offset() + self.index_width())
+        }
+    }
+
+    /// Returns the offset of the host-attached flag of a ring buffer of `size` bytes,
+    /// `None` for the `RB` of version 0
+    pub const fn host_attached_offset(&self, size: usize) -> Option<usize> {
+        if self.is_baseline() {
+            None
+        } else if self.fields_after_content() {
+            Some(self.content_offset() + size + 5)
+        } else {
+            // After the dropped count, then the features byte unless it comes first
+            let mut offset = self.consumer_offset() + self.index_width() + 2;
+            if !self.features_first() {
+                offset += 1;
+            }
+            Some(offset)
+        }
+    }
+
+    /// Returns the offset of the content
+    pub const fn content_offset(&self) -> usize {
+        if self.is_baseline() || self.fields_after_content() {
+            self.consumer_offset() + self.index_width()
+        } else {
+            match self.host_attached_offset(0) {
+                Some(offset) => offset + 1,
+                None => unreachable!(),
+            }
+        }
+    }
+
+    /// Returns the offset of the stats of a ring buffer of `size` bytes, if it has them
+    pub const fn stats_offset(&self, size: usize) -> Option<usize> {
+        self.trailer_field(size, FEATURE_STATS)
+    }
+
+    /// Returns the offset of the heartbeat of a ring buffer of `size` bytes, if it has one
+    pub const fn heartbeat_offset(&self, size: usize) -> Option<usize> {
+        self.trailer_field(size, FEATURE_HEARTBEAT)
+    }
+
+    /// Returns the offset of the wrap count of a ring buffer of `size` bytes, if it has one
+    pub const fn wraps_offset(&self, size: usize) -> Option<usize> {
+        self.trailer_field(size, FEATURE_WRAPS)
+    }
+
+    /// Returns the offset of the name of a ring buffer of `size` bytes, if it has one
+    pub const fn name_offset(&self, size: usize) -> Option<usize> {
+        self.trailer_field(size, FEATURE_NAME)
+    }
+
+    /// Returns the number of bytes of the fields of a ring buffer of `size` bytes, up to
+    /// the end of its last optional field. The struct of the producer may be padded after.
+    pub const fn len(&self, size: usize) -> usize {
+        if self.is_baseline() {
+            self.content_offset() + size
+        } else {
+            self.content_offset() + size + self.layout().after_content + trailer_len(self.features)
+        }
+    }
+
+    /// Returns the layout the consumer reads, whose offsets are those computed here
+    pub(crate) const fn layout(&self) -> &'static Layout {
+        match (self.version, self.index_width) {
+            (0, 1) => &RB_V0,
+            (0, _) => &RB16_V0,
+            (_, 1) => &RB,
+            _ => &RB16,
+        }
+    }
+
+    /// Whether this is the `RB` of version 0, which has no other field than the magic
+    /// marker, the size and the indices before its content
+    const fn is_baseline(&self) -> bool {
+        self.version == 0 && self.index_width == 1
+    }
+
+    /// Whether the features byte comes before the size, as in `RB16` version 0
+    const fn features_first(&self) -> bool {
+        self.version == 0 && self.index_width == 2
+    }
+
+    /// Whether the fields other than the size and the indices follow the content, as in
+    /// `RB` version 1, which starts like version 0
+    const fn fields_after_content(&self) -> bool {
+        self.version != 0 && self.index_width == 1
+    }
+
+    const fn trailer_field(&self, size: usize, feature: u8) -> Option<usize> {
+        if self.is_baseline() {
+            return None;
+        }
+        match trailer_offset(self.features, feature) {
+            Some(offset) => {
+                Some(self.content_offset() + size + self.layout().after_content + offset)
+            }
+            None => None,
+        }
+    }
+}
+
+/// Whether `a`, resolved for a ring buffer of `size` bytes whose content is `content` bytes
+/// after its start, and `b` are both the same offset, or both absent, as `==` can't
+/// compare them in a const
+pub(crate) const fn same_offset(
+    a: Option<Offset>,
+    b: Option<usize>,
+    content: usize,
+    size: usize,
+) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.resolve(content, size) == b,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// The offsets computed by the descriptors are those the consumer reads
+const _: () = {
+    let descriptors = [
+        LayoutDescriptor::v1(),
+        LayoutDescriptor::v1().with_u16_indices(),
+        LayoutDescriptor::v0(),
+        LayoutDescriptor::v0().with_u16_indices(),
+    ];
+    let mut i = 0;
+    while i < descriptors.len() {
+        let descriptor = &descriptors[i];
+        let layout = descriptor.layout();
+        let Some(header) = &layout.header else {
+            panic!("descriptors are of layouts with a header");
+        };
+        // A size the offsets after the content depend on
+        let size = 7;
+        let content = layout.content;
+        assert!(layout.index_width == descriptor.index_width());
+        assert!(same_offset(
+            header.version,
+            descriptor.version_offset(size),
+            content,
+            size
+        ));
+        assert!(header.size == descriptor.size_offset());
+        assert!(same_offset(
+            header.features,
+            descriptor.features_offset(size),
+            content,
+            size
+        ));
+        assert!(layout.producer == descriptor.producer_offset());
+        assert!(layout.consumer == descriptor.consumer_offset());
+        assert!(same_offset(
+            layout.dropped,
+            descriptor.dropped_offset(size),
+            content,
+            size
+        ));
+        assert!(same_offset(
+            layout.host_attached,
+            descriptor.host_attached_offset(size),
+            content,
+            size
+        ));
+        assert!(layout.content == content);
+        assert!(content == descriptor.content_offset());
+        assert!(!layout.indirect);
+        i += 1;
+    }
+};
+
+/// Where the fields of a [`ControlBlock`](crate::producer::ControlBlock) are: a header,
+/// then one descriptor per channel, each telling where its ring buffer is
+pub(crate) struct ControlBlockLayout {
+    /// Same as [`HeaderLayout::magic_prefix`], the third byte being the id
+    pub magic_prefix: [u8; 2],
+    pub version: usize,
+    /// Number of channels
+    pub count: usize,
+    /// Start of the first descriptor
+    pub descriptors: usize,
+    pub descriptor_len: usize,
+    /// Within a descriptor, offset of the ring buffer from the start of the control block,
+    /// little-endian `u32`
+    pub offset: usize,
+    /// Within a descriptor, size of the ring buffer, little-endian `u16`
+    pub size: usize,
+    /// Within a descriptor, reserved for channel options, 0 for now
+    pub flags: usize,
+    /// Within a descriptor, name of the channel, padded with zeros
+    pub name: usize,
+}
+
+impl ControlBlockLayout {
+    /// Returns the magic marker of a control block with the given `id`
+    pub const fn magic(&self, id: u8) -> [u8; 3] {
+        [self.magic_prefix[0], self.magic_prefix[1], id]
+    }
+}
+
+/// Longest name of a channel of a control block
+pub(crate) const CHANNEL_NAME_LEN: usize = 8;
+
+/// Layout of [`ControlBlock`](crate::producer::ControlBlock)
+pub(crate) const CONTROL_BLOCK: ControlBlockLayout = ControlBlockLayout {
+    magic_prefix: [0x89, 0x43],
+    version: 3,
+    count: 4,
+    descriptors: 5,
+    descriptor_len: 7 + CHANNEL_NAME_LEN,
+    offset: 0,
+    size: 4,
+    flags: 6,
+    name: 7,
+};
+
+/// Every layout, newest first
+const LAYOUTS: [Layout; 7] = [RB, RB16, RB_INDIRECT, RX, RB_V0, RB16_V0, RB_COMPACT];
+
+/// Bytes before the content in the widest layout, or before the end of the widest content
+/// pointer, which hold every field the consumer needs to recognize a ring buffer
+#[cfg(feature = "alloc")]
+pub(crate) const MAX_HEADER_LEN: usize = {
+    let mut len = 0;
+    let mut i = 0;
+    while i < LAYOUTS.len() {
+        let layout = &LAYOUTS[i];
+        let header_len = if layout.indirect {
+            layout.content + 2 + MAX_POINTER_WIDTH
+        } else {
+            layout.content
+        };
+        if header_len > len {
+            len = header_len;
+        }
+        i += 1;
+    }
+    len
+};
+
+/// Whether the field at `offset`, if the layout has it, is before the content, or within
+/// the [`Layout::after_content`] fields
+const fn outside_content(offset: Option<Offset>, layout: &Layout) -> bool {
+    match offset {
+        Some(Offset::Start(offset)) => offset < layout.content,
+        Some(Offset::End(offset)) => offset < layout.after_content,
+        None => true,
+    }
+}
+
+// The consumer reads both indices at once, and the header is not within the content
+const _: () = {
+    let mut i = 0;
+    while i < LAYOUTS.len() {
+        let layout = &LAYOUTS[i];
+        assert!(layout.consumer == layout.producer + layout.index_width);
+        if let Some(header) = &layout.header {
+            assert!(header.size < layout.content);
+            assert!(outside_content(header.version, layout));
+            assert!(outside_content(header.features, layout));
+            // The marker tells layouts of the same magic marker apart, and the layouts
+            // after the content can't be indirect, as the content would not follow them
+            match (header.end_marker, header.version) {
+                (Some(marker), Some(Offset::End(version))) => {
+                    assert!(version == marker + 1 && version < layout.after_content);
+                    assert!(!layout.indirect);
+                }
+                (Some(_), _) | (None, Some(Offset::End(_))) => {
+                    panic!("the version follows the marker after the content")
+                }
+                _ => {}
+            }
+        }
+        assert!(outside_content(layout.dropped, layout));
+        assert!(outside_content(layout.host_attached, layout));
+        i += 1;
+    }
+};
@@ -17,7 +17,7 @@
 //! Then create an ring buffer of size 5.
 //!
 //! In order to access it safely, we wrap it around a Mutex and a RefCell:
-//! ```
+//! ```ignore
 //!   use avr_device::interrupt::{self, Mutex};
 //!   use core::cell::{Cell, RefCell};
 //!   use ramlink::producer::RB;
@@ -25,7 +25,7 @@
 //!   static RING_BUF: Mutex<RefCell<RB<5>>> = Mutex::new(RefCell::new(RB::<5>::new()));
 //! ```
 //! you can then send data to your consumer:
-//! ```
+//! ```ignore
 //!   interrupt::free(|cs| {
 //!     RING_BUF
 //!     .borrow(cs)
@@ -36,51 +36,134 @@
 //! ## Consumer (laptop with JTAG/UPDI/… interface)
 //! Add this crate to you project, don't forget to enable the `consumer` feature:
 //!
-//! `cargo add ramlink -F consumer`
+//! `cargo add ramlink -F consumer,std`
 //!
-//! Implement the trait for your specific device
+//! Implement the `MemoryReader` trait for your specific device, or, with an AVR and an Atmel
+//! JTAGICE mkII, use the reader of the `avr-jtagice` feature. In this example, the producer
+//! device is an ATmega328P, whose SRAM spans `0x0100..0x0900`, and the RB struct is stored
+//! at address `0x08e0`
+//! ```ignore
+//!    let mut mm = JtagIceMkiiReader::open("/dev/ttyUSB0", 0x0100..0x0900)?;
+//!    mm.set_emulator_mode(EmulatorMode::DebugWire)?;
+//!
+//!    let mut rb = ramlink::consumer::ProducerDevice::new(mm, 0x08e0).unwrap();
 //! ```
-//!  struct mk2<'a> {
-//!      dev: JtagIceMkii<'a>,
-//!  }
-//!
-//!  impl<'a> ramlink::consumer::MemoryReader for mk2<'a> {
-//!      fn read_memory(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), String> {
-//!          for i in 0..buffer.len() {
-//!              let byte = self.dev.read_ram_byte((address + i) as u16).unwrap();
-//!              buffer[i] = byte;
-//!          }
-//!          Ok(())
-//!      }
-//!      fn write_memory(&mut self, address: usize, value: u8) -> Result<(), String> {
-//!          self.dev.write_ram_byte(address as u16, value);
-//!          Ok(())
-//!      }
-//!  }
-//!```
-//! Initialize it. In this example, the producer device is an AVR Attiny402, and the RB struct
-//! is stored at address `0x3f0e`
+//! and start reading:
+//! ```ignore
+//!    rb.run(Duration::from_millis(10), |data| {
+//!        println!("I READ {:02x?}", data);
+//!        ControlFlow::Continue(())
+//!    })?;
 //! ```
-//!    let mm = mk2 { dev: dgr };
 //!
-//!    let mut rb = ramlink::consumer::ProducerDevice::new(Box::new(mm), 0x3f0e).unwrap();
+//! ### Without writing a host program
+//! The `ramlink-dump` binary, built with the `cli` feature, streams a ring buffer to stdout
+//! or to a file through OpenOCD or a GDB server, until Ctrl-C:
+//! ```text
+//! cargo install ramlink -F cli
+//! ramlink-dump --elf firmware.elf --symbol RING_BUF --backend openocd --lines
+//! ramlink-dump --address 0x3f0e --backend gdb --target localhost:3333 --hex
 //! ```
-//! and start reading:
+//!
+//! Built with the `probe-rs` feature as well, it also drives the debug probe itself:
+//! ```text
+//! cargo install ramlink -F cli,probe-rs
+//! ramlink-dump --elf firmware.elf --backend probe-rs --chip STM32F411RETx --lines
+//! ```
+//!
+//! With `--tagged`, the text sent with `send_text` is written as lines, and the data sent
+//! with `send_binary` as hexdumps.
+//!
+//! With `--capture FILE`, the bytes read are recorded along with the time they were read,
+//! and `--replay FILE` streams them again, in any format, without the device:
+//! ```text
+//! ramlink-dump --elf firmware.elf --raw --capture session.cap
+//! ramlink-dump --replay session.cap --lines
+//! ```
+//!
+//! With `--format json`, each chunk read and each gap of bytes dropped by the producer is
+//! written as a JSON object per line, for other tools:
+//! ```text
+//! ramlink-dump --elf firmware.elf --format json | jq -c 'select(.type == "gap")'
+//! ```
+//!
+//! ### Typed messages
+//! With the `postcard` feature, structs are sent with [postcard](https://docs.rs/postcard),
+//! which serializes them with serde, as frames prefixed with their length. On the producer,
+//! with `serde` (with its `derive` feature) as a dependency:
+//! ```ignore
+//! #[derive(serde::Serialize)]
+//! struct Telemetry {
+//!     temperature: i16,
+//!     current: u16,
+//! }
+//!
+//! rb.send_postcard(&telemetry).unwrap();
 //! ```
-//!    while true {
-//!        let r = rb.read_bytes();
-//!        if r.len() > 0 {
-//!            println!("I READ {:02x?}", r);
-//!        }
+//! and on the consumer, with the same struct deriving `serde::Deserialize`:
+//! ```ignore
+//!    loop {
+//!        let telemetry: Telemetry = rb.read_postcard(poll, timeout).unwrap();
+//!    }
+//! ```
+//!
+//! ### Commands to the target
+//! The host can write back, e.g. to change the log level: a `DuplexRB` puts an `RxRB`
+//! right after the `RB`, and the target polls it without ever blocking:
+//! ```ignore
+//!   static mut LINK: DuplexRB<64, 16> = DuplexRB::new();
+//!
+//!   if let Some(command) = link.rx.read_byte() {
+//!       handle(command);
 //!   }
 //! ```
+//! The host finds it after the `RB`, and writes as much as the target has room for:
+//! ```ignore
+//!    let probe = RefCell::new(probe);
+//!    let mut rb = ProducerDevice::new(&probe, 0x3f0e)?;
+//!    let mut commands = HostWriter::new(&probe, rb.end_address())?;
+//!    commands.write_bytes(b"level=debug\n")?;
+//! ```
+//! ### Several streams
+//! A `ControlBlock` holds named channels, each its own ring buffer, so that logs and
+//! binary data are not interleaved:
+//! ```ignore
+//!   static CHANNELS: ControlBlock<2, 128> = ControlBlock::new(["log", "trace"]);
+//!
+//!   CHANNELS.channel(1).send_bytes_blocking(&sample);
+//! ```
+//! The host only needs the address of the control block, and attaches to each channel:
+//! ```ignore
+//!    let probe = RefCell::new(probe);
+//!    let mut block = ControlBlockReader::new(&probe, 0x3f0e)?;
+//!    let mut trace = block.open(&block.find("trace")?)?;
+//! ```
+//! ### Firmware built with the first releases
+//! The default `RB` starts as in the first releases: the magic marker `88 88 88`, the size,
+//! the indices, then the content, so that their consumers still read it. The fields added
+//! since, the layout version among them, follow the content. The consumer reads the ring
+//! buffers of the first releases as well, as version 0, which have none of these fields.
+//! ```
+//! # #[cfg(all(feature = "producer", feature = "consumer"))] {
+//! use ramlink::{producer::RB, LayoutDescriptor};
+//!
+//! assert_eq!(RB::<64>::LAYOUT.magic(), [0x88, 0x88, 0x88]);
+//! assert_eq!(RB::<64>::LAYOUT.content_offset(), LayoutDescriptor::v0().content_offset());
+//! assert_eq!(RB::<64>::LAYOUT.version_offset(64), Some(6 + 64 + 1));
+//! # }
+//! ```
 
 #![no_std]
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
 
-#[allow(dead_code)]
-const RB_MAGIC: [u8; 3] = [0x88, 0x88, 0x88]; // XXX to share amongst prod/cons
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(any(feature = "producer", feature = "consumer"))]
+mod layout;
+#[cfg(any(feature = "producer", feature = "consumer"))]
+pub use layout::LayoutDescriptor;
 
 #[cfg(feature = "consumer")]
 pub mod consumer;
@@ -0,0 +1,604 @@
+//! Ring buffer whose indices are atomics, so that it can be written through a shared reference.
+
+use core::fmt;
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[cfg(feature = "names")]
+use super::padded_name;
+use super::{next_index, wrap, ByteSink, TimestampSource, RB};
+use crate::layout::{self, LayoutDescriptor};
+
+/// A ring buffer with the exact same memory layout as [`RB`], but whose producer/consumer
+/// indices and content are atomics. All the send methods take `&self`, so it can live in a
+/// plain `static` without a Mutex.
+///
+/// There must still be a single producer: the send methods must not be called from two
+/// contexts at the same time (e.g. from `main` and from an interrupt handler). Doing so is
+/// memory safe, but the data sent would be garbled. [`AtomicRB::writer`] hands out a
+/// [`RamlinkWriter`] that enforces this rule.
+#[repr(C)]
+pub struct AtomicRB<const SIZE: usize, const ID: u8 = 0x88> {
+    /// Same as [`RB`]'s magic marker. Atomic so that [`AtomicRB::init`] can write it
+    _magic_marker: [AtomicU8; 3],
+    /// Size of the ring buffer, 0 meaning 256
+    size: AtomicU8,
+    /// Producer slot, only written by the producer
+    producer: AtomicU8,
+    /// Consumer slot, only written by the consumer. If producer = consumer, ring buffer is empty
+    consumer: AtomicU8,
+    /// The actual buffer
+    content: [AtomicU8; SIZE],
+    /// Same as [`RB`]'s marker of the fields after the content
+    end_marker: AtomicU8,
+    /// Same as [`RB`]'s layout version
+    version: AtomicU8,
+    /// Same as [`RB`]'s count of discarded bytes
+    dropped: [AtomicU8; 2],
+    /// Same as [`RB`]'s optional fields
+    features: AtomicU8,
+    /// Written by the consumer
+    host_attached: AtomicU8,
+    /// Same as [`RB`]'s high water mark
+    #[cfg(feature = "stats")]
+    high_water: AtomicU8,
+    /// Same as [`RB`]'s total of bytes sent
+    #[cfg(feature = "stats")]
+    total_sent: [AtomicU8; 4],
+    /// Same as [`RB`]'s heartbeat
+    #[cfg(feature = "heartbeat")]
+    heartbeat: AtomicU8,
+    /// Same as [`RB`]'s wrap count
+    #[cfg(feature = "wraps")]
+    wraps: [AtomicU8; 2],
+    /// Same as [`RB`]'s name
+    #[cfg(feature = "names")]
+    name: [AtomicU8; layout::NAME_LEN],
+    /// Set while a [`RamlinkWriter`] exists. Not part of the layout read by the consumer
+    writer_taken: AtomicBool,
+}
+
+// The consumer reads raw offsets, which must be the same whichever struct the producer uses
+const _: () = {
+    assert!(offset_of!(AtomicRB<7>, writer_taken) == size_of::<RB<7>>());
+    assert!(offset_of!(AtomicRB<7>, _magic_marker) == offset_of!(RB<7>, _magic_marker));
+    assert!(offset_of!(AtomicRB<7>, size) == offset_of!(RB<7>, size));
+    assert!(offset_of!(AtomicRB<7>, producer) == offset_of!(RB<7>, producer));
+    assert!(offset_of!(AtomicRB<7>, consumer) == offset_of!(RB<7>, consumer));
+    assert!(offset_of!(AtomicRB<7>, content) == offset_of!(RB<7>, content));
+    assert!(offset_of!(AtomicRB<7>, end_marker) == offset_of!(RB<7>, end_marker));
+    assert!(offset_of!(AtomicRB<7>, version) == offset_of!(RB<7>, version));
+    assert!(offset_of!(AtomicRB<7>, dropped) == offset_of!(RB<7>, dropped));
+    assert!(offset_of!(AtomicRB<7>, features) == offset_of!(RB<7>, features));
+    assert!(offset_of!(AtomicRB<7>, host_attached) == offset_of!(RB<7>, host_attached));
+    #[cfg(feature = "stats")]
+    assert!(offset_of!(AtomicRB<7>, high_water) == offset_of!(RB<7>, high_water));
+    #[cfg(feature = "stats")]
+    assert!(offset_of!(AtomicRB<7>, total_sent) == offset_of!(RB<7>, total_sent));
+    #[cfg(feature = "heartbeat")]
+    assert!(offset_of!(AtomicRB<7>, heartbeat) == offset_of!(RB<7>, heartbeat));
+    #[cfg(feature = "wraps")]
+    assert!(offset_of!(AtomicRB<7>, wraps) == offset_of!(RB<7>, wraps));
+    #[cfg(feature = "names")]
+    assert!(offset_of!(AtomicRB<7>, name) == offset_of!(RB<7>, name));
+};
+
+impl<const SIZE: usize, const ID: u8> AtomicRB<SIZE, ID> {
+    /// Same as [`RB`]'s size check
+    const CHECK: () = assert!(
+        SIZE > 0 && SIZE <= 256,
+        "AtomicRB size must be within 1..=256"
+    );
+
+    /// Same as [`RB`]'s layout
+    pub const LAYOUT: LayoutDescriptor = RB::<SIZE, ID>::LAYOUT;
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `0x13`
+    pub const fn new() -> Self {
+        Self::new_with_fill(0x13)
+    }
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `fill`
+    pub const fn new_with_fill(fill: u8) -> Self {
+        let () = Self::CHECK;
+        let magic = layout::RB_HEADER.magic(ID);
+        let mut content = [const { AtomicU8::new(0) }; SIZE];
+        let mut i = 0;
+        while i < SIZE {
+            content[i] = AtomicU8::new(fill);
+            i += 1;
+        }
+        AtomicRB {
+            _magic_marker: [
+                AtomicU8::new(magic[0]),
+                AtomicU8::new(magic[1]),
+                AtomicU8::new(magic[2]),
+            ],
+            size: AtomicU8::new(SIZE as u8),
+            producer: AtomicU8::new(0),
+            consumer: AtomicU8::new(0),
+            content,
+            end_marker: AtomicU8::new(layout::CONTENT_END_MARKER),
+            version: AtomicU8::new(layout::VERSION),
+            dropped: [const { AtomicU8::new(0) }; 2],
+            features: AtomicU8::new(layout::RB_FEATURES),
+            host_attached: AtomicU8::new(0),
+            #[cfg(feature = "stats")]
+            high_water: AtomicU8::new(0),
+            #[cfg(feature = "stats")]
+            total_sent: [const { AtomicU8::new(0) }; 4],
+            #[cfg(feature = "heartbeat")]
+            heartbeat: AtomicU8::new(0),
+            #[cfg(feature = "wraps")]
+            wraps: [const { AtomicU8::new(0) }; 2],
+            #[cfg(feature = "names")]
+            name: [const { AtomicU8::new(0) }; layout::NAME_LEN],
+            writer_taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns a new ring buffer of size `SIZE` named `name`. See [`RB::new_named`].
+    /// ```
+    /// use ramlink::producer::AtomicRB;
+    ///
+    /// static TELEMETRY: AtomicRB<64> = AtomicRB::new_named("telem");
+    ///
+    /// TELEMETRY.send_bytes_blocking(&[0x17, 0x2a]);
+    /// ```
+    #[cfg(feature = "names")]
+    pub const fn new_named(name: &str) -> Self {
+        let padded = padded_name(name);
+        let mut name = [const { AtomicU8::new(0) }; layout::NAME_LEN];
+        let mut i = 0;
+        while i < layout::NAME_LEN {
+            name[i] = AtomicU8::new(padded[i]);
+            i += 1;
+        }
+        AtomicRB {
+            name,
+            ..Self::new()
+        }
+    }
+
+    /// Returns a new ring buffer of size `SIZE` whose bytes are all zero, so that it lands in
+    /// `.bss`. [`AtomicRB::init`] must be called before the consumer attaches, see
+    /// [`RB::new_zeroed`].
+    /// ```
+    /// use ramlink::producer::AtomicRB;
+    ///
+    /// static RING_BUF: AtomicRB<256> = AtomicRB::new_zeroed();
+    ///
+    /// RING_BUF.init();
+    /// RING_BUF.send_bytes_blocking(b"hello");
+    /// ```
+    pub const fn new_zeroed() -> Self {
+        let () = Self::CHECK;
+        AtomicRB {
+            _magic_marker: [const { AtomicU8::new(0) }; 3],
+            size: AtomicU8::new(0),
+            producer: AtomicU8::new(0),
+            consumer: AtomicU8::new(0),
+            content: [const { AtomicU8::new(0) }; SIZE],
+            end_marker: AtomicU8::new(0),
+            version: AtomicU8::new(0),
+            dropped: [const { AtomicU8::new(0) }; 2],
+            features: AtomicU8::new(0),
+            host_attached: AtomicU8::new(0),
+            #[cfg(feature = "stats")]
+            high_water: AtomicU8::new(0),
+            #[cfg(feature = "stats")]
+            total_sent: [const { AtomicU8::new(0) }; 4],
+            #[cfg(feature = "heartbeat")]
+            heartbeat: AtomicU8::new(0),
+            #[cfg(feature = "wraps")]
+            wraps: [const { AtomicU8::new(0) }; 2],
+            #[cfg(feature = "names")]
+            name: [const { AtomicU8::new(0) }; layout::NAME_LEN],
+            writer_taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Writes the header of a ring buffer created with [`AtomicRB::new_zeroed`]. See [`RB::init`].
+    pub fn init(&self) {
+        self.end_marker
+            .store(layout::CONTENT_END_MARKER, Ordering::Relaxed);
+        self.version.store(layout::VERSION, Ordering::Relaxed);
+        self.size.store(SIZE as u8, Ordering::Relaxed);
+        self.features.store(layout::RB_FEATURES, Ordering::Relaxed);
+        for (byte, value) in self._magic_marker.iter().zip(layout::RB_HEADER.magic(ID)) {
+            byte.store(value, Ordering::Release);
+        }
+    }
+
+    /// Returns the single [`RamlinkWriter`] of this ring buffer, or an error if one already
+    /// exists. The writer is released when dropped.
+    /// ```
+    /// use ramlink::producer::AtomicRB;
+    ///
+    /// static RING_BUF: AtomicRB<16> = AtomicRB::<16>::new();
+    ///
+    /// let mut writer = RING_BUF.writer().unwrap();
+    /// assert!(RING_BUF.writer().is_err());
+    /// writer.send_bytes_blocking(b"hello");
+    /// ```
+    pub fn writer(&'static self) -> Result<RamlinkWriter<SIZE, ID>, WriterAlreadyTaken> {
+        if take_flag(&self.writer_taken) {
+            Ok(RamlinkWriter { rb: self })
+        } else {
+            Err(WriterAlreadyTaken)
+        }
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    /// See [`RB::send_bytes_blocking`].
+    pub fn send_bytes_blocking(&self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`AtomicRB::send_bytes_blocking`], but calls `idle` each time the wait loop
+    /// finds the ring buffer full. See [`RB::send_bytes_blocking_with`].
+    pub fn send_bytes_blocking_with(&self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(&mut &*self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the consumer. Returns the number of bytes that were accepted.
+    pub fn try_send_bytes(&self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(&mut &*self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends as many bytes of `data` as currently fit, and counts the rest as dropped.
+    /// See [`RB::send_bytes_lossy`].
+    pub fn send_bytes_lossy(&self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(&mut &*self, data)
+    }
+
+    /// Sends bytes, blocking only while a consumer is attached. See [`RB::send_bytes_auto`].
+    pub fn send_bytes_auto(&self, data: &[u8]) {
+        ByteSink::send_bytes_auto(&mut &*self, data)
+    }
+
+    /// Sends `payload` as a frame of the channel `channel`. See [`RB::send_frame_on`].
+    pub fn send_frame_on(&self, channel: u8, payload: &[u8]) {
+        ByteSink::send_frame_on(&mut &*self, channel, payload)
+    }
+
+    /// Sends `text` as a frame tagged as text. See [`RB::send_text`].
+    pub fn send_text(&self, text: &str) {
+        ByteSink::send_text(&mut &*self, text)
+    }
+
+    /// Sends `data` as a frame tagged as binary. See [`RB::send_binary`].
+    pub fn send_binary(&self, data: &[u8]) {
+        ByteSink::send_binary(&mut &*self, data)
+    }
+
+    /// Sends a frame of the channel `channel` if it fits as a whole. See
+    /// [`RB::try_send_frame_on`].
+    pub fn try_send_frame_on(&self, channel: u8, payload: &[u8]) -> bool {
+        ByteSink::try_send_frame_on(&mut &*self, channel, payload)
+    }
+
+    /// Sends `payload` as a frame followed by its CRC-8. See [`RB::send_frame_crc`].
+    pub fn send_frame_crc(&self, payload: &[u8]) {
+        ByteSink::send_frame_crc(&mut &*self, payload)
+    }
+
+    /// Sends `payload` as a COBS frame. See [`RB::send_frame_cobs`].
+    pub fn send_frame_cobs(&self, payload: &[u8]) {
+        ByteSink::send_frame_cobs(&mut &*self, payload)
+    }
+
+    /// Sends `payload` as a SLIP frame. See [`RB::send_frame_slip`].
+    pub fn send_frame_slip(&self, payload: &[u8]) {
+        ByteSink::send_frame_slip(&mut &*self, payload)
+    }
+
+    /// Sends `value` serialized with postcard. See [`RB::send_postcard`].
+    #[cfg(feature = "postcard")]
+    pub fn send_postcard<T: serde::Serialize + ?Sized>(
+        &self,
+        value: &T,
+    ) -> Result<(), postcard::Error> {
+        ByteSink::send_postcard(&mut &*self, value)
+    }
+
+    /// Sends `payload` compressed with LZSS. See [`RB::send_frame_compressed`].
+    #[cfg(feature = "compression")]
+    pub fn send_frame_compressed<const WINDOW_BITS: u32>(&self, payload: &[u8]) {
+        ByteSink::send_frame_compressed::<WINDOW_BITS>(&mut &*self, payload)
+    }
+
+    /// Sends `payload` as a frame stamped with the clock `T`. See
+    /// [`RB::send_frame_timestamped`].
+    pub fn send_frame_timestamped<T: TimestampSource>(&self, payload: &[u8]) {
+        ByteSink::send_frame_timestamped::<T>(&mut &*self, payload)
+    }
+
+    /// Returns `true` while a consumer is attached
+    pub fn host_attached(&self) -> bool {
+        self.host_attached.load(Ordering::Relaxed) != 0
+    }
+
+    /// Counts `n` more discarded bytes
+    fn record_dropped(&self, n: usize) {
+        if n > 0 {
+            let dropped = self.dropped().wrapping_add(n as u16);
+            for (byte, value) in self.dropped.iter().zip(dropped.to_le_bytes()) {
+                byte.store(value, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the number of bytes discarded since startup, wrapping around at `u16::MAX`.
+    pub fn dropped(&self) -> u16 {
+        u16::from_le_bytes(self.dropped.each_ref().map(|b| b.load(Ordering::Relaxed)))
+    }
+
+    /// Updates the statistics once `n` more bytes were published to the consumer
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn record_sent(&self, n: usize) {
+        #[cfg(feature = "stats")]
+        {
+            let total = self.total_sent().wrapping_add(n as u32);
+            for (byte, value) in self.total_sent.iter().zip(total.to_le_bytes()) {
+                byte.store(value, Ordering::Relaxed);
+            }
+            let len = self.len() as u8;
+            if len > self.high_water.load(Ordering::Relaxed) {
+                self.high_water.store(len, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the highest number of bytes that were ever waiting in the ring buffer.
+    #[cfg(feature = "stats")]
+    pub fn high_water(&self) -> usize {
+        self.high_water.load(Ordering::Relaxed) as usize
+    }
+
+    /// Returns the number of bytes sent since startup, wrapping around at `u32::MAX`.
+    #[cfg(feature = "stats")]
+    pub fn total_sent(&self) -> u32 {
+        u32::from_le_bytes(
+            self.total_sent
+                .each_ref()
+                .map(|b| b.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Returns the number of times the producer index went back to the start of the
+    /// content. See [`RB::wraps`].
+    #[cfg(feature = "wraps")]
+    pub fn wraps(&self) -> u16 {
+        u16::from_le_bytes(self.wraps.each_ref().map(|b| b.load(Ordering::Relaxed)))
+    }
+
+    #[cfg(feature = "wraps")]
+    fn set_wraps(&self, wraps: u16) {
+        for (byte, value) in self.wraps.iter().zip(wraps.to_le_bytes()) {
+            byte.store(value, Ordering::Relaxed);
+        }
+    }
+
+    /// Increments the heartbeat counter. See [`RB::tick`].
+    #[cfg(feature = "heartbeat")]
+    pub fn tick(&self) {
+        let heartbeat = self.heartbeat.load(Ordering::Relaxed);
+        self.heartbeat
+            .store(heartbeat.wrapping_add(1), Ordering::Relaxed);
+    }
+
+    /// Empties the ring buffer by zeroing both indices. See [`RB::reset`].
+    pub fn reset(&self) {
+        self.consumer.store(0, Ordering::Release);
+        self.producer.store(0, Ordering::Release);
+        #[cfg(feature = "wraps")]
+        self.set_wraps(0);
+    }
+
+    /// Returns the number of bytes the ring buffer can hold. See [`RB::capacity`].
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the consumer.
+    pub fn len(&self) -> usize {
+        let prod = self.producer.load(Ordering::Relaxed) as usize;
+        let cons = self.consumer.load(Ordering::Acquire) as usize;
+        wrap::<SIZE>(prod + SIZE - cons)
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the consumer reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> Default for AtomicRB<SIZE, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implemented on a shared reference, as the send methods of [`AtomicRB`] take `&self`
+impl<const SIZE: usize, const ID: u8> ByteSink for &AtomicRB<SIZE, ID> {
+    type Index = u8;
+
+    fn producer(&self) -> u8 {
+        self.producer.load(Ordering::Relaxed)
+    }
+
+    fn consumer(&self) -> u8 {
+        self.consumer.load(Ordering::Acquire)
+    }
+
+    fn next(index: u8) -> u8 {
+        next_index::<SIZE>(index)
+    }
+
+    fn write_slot(&mut self, index: u8, byte: u8) {
+        self.content[index as usize].store(byte, Ordering::Relaxed);
+    }
+
+    fn publish(&mut self, index: u8) {
+        #[cfg(feature = "wraps")]
+        if index < self.producer.load(Ordering::Relaxed) {
+            self.set_wraps(self.wraps().wrapping_add(1));
+        }
+        self.producer.store(index, Ordering::Release);
+    }
+
+    fn capacity(&self) -> usize {
+        AtomicRB::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        AtomicRB::len(self)
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        AtomicRB::record_dropped(self, n)
+    }
+
+    fn host_attached(&self) -> bool {
+        AtomicRB::host_attached(self)
+    }
+
+    fn record_sent(&mut self, n: usize) {
+        AtomicRB::record_sent(self, n)
+    }
+}
+
+/// Object safe view of an [`AtomicRB`], so that it can be stored whatever its size
+#[cfg(any(feature = "log", feature = "global", feature = "defmt"))]
+pub(crate) trait Sink: Sync {
+    #[cfg(any(feature = "log", feature = "global"))]
+    fn send_bytes_blocking(&self, data: &[u8]);
+    #[cfg(any(feature = "log", feature = "global"))]
+    fn free_space(&self) -> usize;
+    #[cfg(feature = "log")]
+    fn record_dropped(&self, n: usize);
+    #[cfg(feature = "defmt")]
+    fn send_bytes_auto(&self, data: &[u8]);
+}
+
+#[cfg(any(feature = "log", feature = "global", feature = "defmt"))]
+impl<const SIZE: usize, const ID: u8> Sink for AtomicRB<SIZE, ID> {
+    #[cfg(any(feature = "log", feature = "global"))]
+    fn send_bytes_blocking(&self, data: &[u8]) {
+        AtomicRB::send_bytes_blocking(self, data)
+    }
+
+    #[cfg(any(feature = "log", feature = "global"))]
+    fn free_space(&self) -> usize {
+        AtomicRB::free_space(self)
+    }
+
+    #[cfg(feature = "log")]
+    fn record_dropped(&self, n: usize) {
+        AtomicRB::record_dropped(self, n)
+    }
+
+    #[cfg(feature = "defmt")]
+    fn send_bytes_auto(&self, data: &[u8]) {
+        AtomicRB::send_bytes_auto(self, data)
+    }
+}
+
+/// Atomically sets `flag`, returning `true` if it was previously clear
+#[cfg(target_has_atomic = "8")]
+fn take_flag(flag: &AtomicBool) -> bool {
+    !flag.swap(true, Ordering::Acquire)
+}
+
+/// Atomically sets `flag`, returning `true` if it was previously clear. Targets such as AVR
+/// have no compare-and-swap, so a critical section is used instead
+#[cfg(not(target_has_atomic = "8"))]
+fn take_flag(flag: &AtomicBool) -> bool {
+    critical_section::with(|_| {
+        let taken = flag.load(Ordering::Relaxed);
+        flag.store(true, Ordering::Relaxed);
+        !taken
+    })
+}
+
+/// Error returned by [`AtomicRB::writer`] when a [`RamlinkWriter`] already exists
+#[derive(Debug)]
+pub struct WriterAlreadyTaken;
+
+/// The single producer handle of an [`AtomicRB`]. As only one can exist at a time, owning it
+/// guarantees that nobody else is sending on the ring buffer.
+pub struct RamlinkWriter<const SIZE: usize, const ID: u8 = 0x88> {
+    rb: &'static AtomicRB<SIZE, ID>,
+}
+
+impl<const SIZE: usize, const ID: u8> RamlinkWriter<SIZE, ID> {
+    /// Returns the ring buffer this writer sends to, e.g. to query its occupancy
+    pub fn rb(&self) -> &'static AtomicRB<SIZE, ID> {
+        self.rb
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.rb.send_bytes_blocking(data)
+    }
+
+    /// Same as [`RamlinkWriter::send_bytes_blocking`], but calls `idle` each time the wait
+    /// loop finds the ring buffer full.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        self.rb.send_bytes_blocking_with(data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without blocking.
+    /// Returns the number of bytes that were accepted.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        self.rb.try_send_bytes(data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false` if it is full.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.rb.try_send_byte(b)
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> Drop for RamlinkWriter<SIZE, ID> {
+    fn drop(&mut self) {
+        self.rb.writer_taken.store(false, Ordering::Release);
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> fmt::Write for RamlinkWriter<SIZE, ID> {
+    /// Implements write_str so we can use the write! macro
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize, const ID: u8> ufmt::uWrite for RamlinkWriter<SIZE, ID> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Ring buffer without a header, for parts where every byte of RAM counts.
+
+use core::fmt;
+use core::mem::offset_of;
+
+use super::{next_index, wrap, ByteSink};
+use crate::layout::{self, Offset};
+
+/// Same as [`RB`](super::RB), minus the magic marker, the version, the size and the
+/// features byte, which saves 6 bytes of RAM. The consumer can't check that it reads a ring
+/// buffer at all, so it must be given the right address and size with
+/// `ProducerDevice::new_unchecked`.
+///
+/// There is no room for the optional fields of the `stats` and `heartbeat` features either.
+/// ```
+/// use ramlink::producer::RBCompact;
+///
+/// let mut rb = RBCompact::<16>::new();
+/// assert_eq!(core::mem::size_of_val(&rb), 16 + 5);
+/// assert_eq!(rb.try_send_bytes(b"hello"), 5);
+/// ```
+#[repr(C)]
+pub struct RBCompact<const SIZE: usize> {
+    /// Producer slot
+    producer: u8,
+    /// Consumer slot. If producer = consumer, ring buffer is empty
+    consumer: u8,
+    /// Number of bytes discarded by the producer, little-endian, wrapping around
+    dropped: [u8; 2],
+    /// Set to 1 by the consumer while it is attached
+    host_attached: u8,
+    /// The actual buffer
+    content: [u8; SIZE],
+}
+
+// The consumer reads raw offsets
+const _: () = {
+    assert!(offset_of!(RBCompact<7>, producer) == layout::RB_COMPACT.producer);
+    assert!(offset_of!(RBCompact<7>, consumer) == layout::RB_COMPACT.consumer);
+    assert!(
+        matches!(layout::RB_COMPACT.dropped, Some(Offset::Start(v)) if v == offset_of!(RBCompact<7>, dropped))
+    );
+    assert!(
+        matches!(layout::RB_COMPACT.host_attached, Some(Offset::Start(v)) if v == offset_of!(RBCompact<7>, host_attached))
+    );
+    assert!(offset_of!(RBCompact<7>, content) == layout::RB_COMPACT.content);
+};
+
+impl<const SIZE: usize> RBCompact<SIZE> {
+    /// Indices are `u8`, evaluated by [`RBCompact::new`]
+    const CHECK: () = assert!(
+        SIZE > 0 && SIZE <= 256,
+        "RBCompact size must be within 1..=256"
+    );
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `0x13`
+    pub const fn new() -> Self {
+        Self::new_with_fill(0x13)
+    }
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `fill`. There is
+    /// no header to initialize, so with a `fill` of 0 a `static` one lands in `.bss`.
+    pub const fn new_with_fill(fill: u8) -> Self {
+        let () = Self::CHECK;
+        RBCompact {
+            producer: 0,
+            consumer: 0,
+            dropped: [0; 2],
+            host_attached: 0,
+            content: [fill; SIZE],
+        }
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    /// See [`RB::send_bytes_blocking`](super::RB::send_bytes_blocking).
+    pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`RBCompact::send_bytes_blocking`], but calls `idle` each time the wait loop
+    /// finds the ring buffer full.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the consumer. Returns the number of bytes that were accepted.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends as many bytes of `data` as currently fit, and counts the rest as dropped.
+    /// See [`RB::send_bytes_lossy`](super::RB::send_bytes_lossy).
+    pub fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(self, data)
+    }
+
+    /// Sends bytes, blocking only while a consumer is attached.
+    /// See [`RB::send_bytes_auto`](super::RB::send_bytes_auto).
+    pub fn send_bytes_auto(&mut self, data: &[u8]) {
+        ByteSink::send_bytes_auto(self, data)
+    }
+
+    /// Returns `true` while a consumer is attached
+    pub fn host_attached(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.host_attached) != 0 }
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        if n > 0 {
+            let dropped = self.dropped().wrapping_add(n as u16);
+            unsafe { core::ptr::write_volatile(&mut self.dropped, dropped.to_le_bytes()) };
+        }
+    }
+
+    /// Returns the number of bytes discarded since startup, wrapping around at `u16::MAX`.
+    pub fn dropped(&self) -> u16 {
+        u16::from_le_bytes(unsafe { core::ptr::read_volatile(&self.dropped) })
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is `SIZE - 1`.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the consumer.
+    pub fn len(&self) -> usize {
+        let prod = unsafe { core::ptr::read_volatile(&self.producer) } as usize;
+        let cons = unsafe { core::ptr::read_volatile(&self.consumer) } as usize;
+        wrap::<SIZE>(prod + SIZE - cons)
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the consumer reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+
+    /// Empties the ring buffer by zeroing both indices. See [`RB::reset`](super::RB::reset).
+    pub fn reset(&mut self) {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        unsafe {
+            core::ptr::write_volatile(&mut self.consumer, 0);
+            core::ptr::write_volatile(&mut self.producer, 0);
+        }
+    }
+
+    /// Waits until the consumer has read every byte sent so far. Never returns if no
+    /// consumer is attached.
+    pub fn flush(&self) {
+        while !self.is_empty() {}
+    }
+}
+
+impl<const SIZE: usize> Default for RBCompact<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> ByteSink for RBCompact<SIZE> {
+    type Index = u8;
+
+    fn producer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.producer) }
+    }
+
+    fn consumer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.consumer) }
+    }
+
+    fn next(index: u8) -> u8 {
+        next_index::<SIZE>(index)
+    }
+
+    fn write_slot(&mut self, index: u8, byte: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.content[index as usize], byte) };
+    }
+
+    fn publish(&mut self, index: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.producer, index) };
+    }
+
+    fn capacity(&self) -> usize {
+        RBCompact::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RBCompact::len(self)
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        RBCompact::record_dropped(self, n)
+    }
+
+    fn host_attached(&self) -> bool {
+        RBCompact::host_attached(self)
+    }
+}
+
+impl<const SIZE: usize> fmt::Write for RBCompact<SIZE> {
+    /// Implements write_str so we can use the write! macro
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize> ufmt::uWrite for RBCompact<SIZE> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! LZSS compression of the frames sent with `RB::send_frame_compressed`.
+//!
+//! The encoding is a sequence of groups: a flag byte, then up to 8 items, from its least
+//! significant bit. An item is a literal byte for a 0 bit, and a back-reference for a 1
+//! bit: a big-endian `u16` holding the distance to the start of the match minus 1 in its
+//! `window_bits` high bits, and the length of the match minus 3 in the others. Matches
+//! are only looked for in the payload, so compressing needs no memory besides a group.
+
+use crate::layout::LZSS_MIN_MATCH;
+
+/// Calls `emit` with the encoding of `payload`, one group at a time
+pub(crate) fn compress(payload: &[u8], window_bits: u32, mut emit: impl FnMut(&[u8])) {
+    let length_bits = 16 - window_bits;
+    let max_len = LZSS_MIN_MATCH + (1 << length_bits) - 1;
+    let mut group = [0; 1 + 8 * 2];
+    let mut len = 1;
+    let mut items = 0;
+    let mut i = 0;
+    while i < payload.len() {
+        let (distance, matched) = longest_match(payload, i, 1 << window_bits, max_len);
+        if matched >= LZSS_MIN_MATCH {
+            let code = ((distance - 1) << length_bits | (matched - LZSS_MIN_MATCH)) as u16;
+            group[0] |= 1 << items;
+            group[len..len + 2].copy_from_slice(&code.to_be_bytes());
+            len += 2;
+            i += matched;
+        } else {
+            group[len] = payload[i];
+            len += 1;
+            i += 1;
+        }
+        items += 1;
+        if items == 8 {
+            emit(&group[..len]);
+            group[0] = 0;
+            len = 1;
+            items = 0;
+        }
+    }
+    if items > 0 {
+        emit(&group[..len]);
+    }
+}
+
+/// Returns the distance and length of the longest match of the bytes at `i` starting at
+/// most `window` bytes before, which may run past `i`
+fn longest_match(payload: &[u8], i: usize, window: usize, max_len: usize) -> (usize, usize) {
+    let mut best = (0, 0);
+    for start in i.saturating_sub(window)..i {
+        let len = payload[i..]
+            .iter()
+            .take(max_len)
+            .zip(&payload[start..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if len > best.1 {
+            best = (i - start, len);
+        }
+    }
+    best
+}
@@ -0,0 +1,124 @@
+//! Several named ring buffers behind a single address, one per stream.
+
+use core::mem::{offset_of, size_of};
+
+use super::{padded_name, AtomicRB};
+use crate::layout::{self, CHANNEL_NAME_LEN};
+
+/// Where a channel is, read by the consumer
+#[repr(C)]
+struct Descriptor {
+    /// Offset of the ring buffer from the start of the control block, little-endian
+    offset: [u8; 4],
+    /// Size of the ring buffer, little-endian
+    size: [u8; 2],
+    /// Reserved, 0 for now
+    flags: u8,
+    /// Name of the channel, padded with zeros
+    name: [u8; CHANNEL_NAME_LEN],
+}
+
+/// `N` channels, each an [`AtomicRB`] of `SIZE` bytes with a name of up to 8 bytes, e.g.
+/// one for the logs, one for telemetry and one for a binary trace, so that the streams
+/// are not interleaved. The consumer only needs the address of the control block, and
+/// finds the channels by name with `consumer::ControlBlockReader`; reading one channel
+/// leaves the others as they are.
+///
+/// Like `AtomicRB`, it lives in a plain `static`, and each channel can be written from
+/// its own context:
+/// ```
+/// use ramlink::producer::ControlBlock;
+///
+/// static CHANNELS: ControlBlock<3, 64> = ControlBlock::new(["log", "metrics", "trace"]);
+///
+/// CHANNELS.channel(0).send_bytes_blocking(b"boot\n");
+/// CHANNELS.channel(2).send_bytes_blocking(&[0x01, 0x42]);
+/// assert_eq!(CHANNELS.name(1), "metrics");
+/// assert_eq!(CHANNELS.channel(1).len(), 0);
+/// ```
+#[repr(C)]
+pub struct ControlBlock<const N: usize, const SIZE: usize, const ID: u8 = 0x88> {
+    /// Tells the consumer that this is a control block, and its id
+    _magic_marker: [u8; 3],
+    /// Version of the layout, the same as [`RB`](super::RB)'s
+    version: u8,
+    /// Number of channels
+    count: u8,
+    descriptors: [Descriptor; N],
+    channels: [AtomicRB<SIZE, ID>; N],
+}
+
+// The consumer reads raw offsets
+const _: () = {
+    type Block = ControlBlock<2, 7>;
+    assert!(offset_of!(Block, _magic_marker) == 0);
+    assert!(offset_of!(Block, version) == layout::CONTROL_BLOCK.version);
+    assert!(offset_of!(Block, count) == layout::CONTROL_BLOCK.count);
+    assert!(offset_of!(Block, descriptors) == layout::CONTROL_BLOCK.descriptors);
+    assert!(size_of::<Descriptor>() == layout::CONTROL_BLOCK.descriptor_len);
+    assert!(offset_of!(Descriptor, offset) == layout::CONTROL_BLOCK.offset);
+    assert!(offset_of!(Descriptor, size) == layout::CONTROL_BLOCK.size);
+    assert!(offset_of!(Descriptor, flags) == layout::CONTROL_BLOCK.flags);
+    assert!(offset_of!(Descriptor, name) == layout::CONTROL_BLOCK.name);
+};
+
+impl<const N: usize, const SIZE: usize, const ID: u8> ControlBlock<N, SIZE, ID> {
+    /// The channel count is a `u8`, evaluated by [`ControlBlock::new`]
+    const CHECK: () = assert!(
+        N > 0 && N <= 255,
+        "ControlBlock must have within 1..=255 channels"
+    );
+
+    /// Returns a control block whose channels are named `names`, in that order, their ring
+    /// buffers empty. Names longer than 8 bytes fail to compile.
+    pub const fn new(names: [&str; N]) -> Self {
+        let () = Self::CHECK;
+        let mut descriptors = [const {
+            Descriptor {
+                offset: [0; 4],
+                size: [0; 2],
+                flags: 0,
+                name: [0; CHANNEL_NAME_LEN],
+            }
+        }; N];
+        let mut i = 0;
+        while i < N {
+            let offset = offset_of!(Self, channels) + i * size_of::<AtomicRB<SIZE, ID>>();
+            descriptors[i] = Descriptor {
+                offset: (offset as u32).to_le_bytes(),
+                size: (SIZE as u16).to_le_bytes(),
+                flags: 0,
+                name: padded_name(names[i]),
+            };
+            i += 1;
+        }
+        ControlBlock {
+            _magic_marker: layout::CONTROL_BLOCK.magic(ID),
+            version: layout::VERSION,
+            count: N as u8,
+            descriptors,
+            channels: [const { AtomicRB::new() }; N],
+        }
+    }
+
+    /// Returns the ring buffer of the channel `index`. Panics if there are not that many
+    /// channels.
+    pub fn channel(&self, index: usize) -> &AtomicRB<SIZE, ID> {
+        &self.channels[index]
+    }
+
+    /// Returns the name of the channel `index`. Panics if there are not that many channels.
+    pub fn name(&self, index: usize) -> &str {
+        let name = &self.descriptors[index].name;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        // Copied whole from a `&str` by `new`
+        core::str::from_utf8(&name[..len]).unwrap_or_default()
+    }
+
+    /// Returns the ring buffer of the channel named `name`, if there is one
+    pub fn find(&self, name: &str) -> Option<&AtomicRB<SIZE, ID>> {
+        (0..N)
+            .find(|&i| self.name(i) == name)
+            .map(|i| self.channel(i))
+    }
+}
@@ -0,0 +1,129 @@
+//! A [defmt](https://defmt.ferrous-systems.com) global logger writing into an
+//! [`AtomicRB`], in place of RTT.
+//!
+//! With the `defmt` feature, this crate provides the global logger of the firmware, so it
+//! must not be linked along with another one, such as `defmt-rtt`. Frames are serialized
+//! with a critical section held from the start to the end of each, so that `main` and
+//! interrupt handlers can log concurrently. A
+//! [`critical-section`](https://docs.rs/critical-section) implementation must be provided,
+//! usually by your HAL or by `cortex-m`.
+//!
+//! Frames are sent with [`AtomicRB::send_bytes_auto`]: they wait for room only while the
+//! host is attached, so that logging never hangs a board left alone, and are dropped
+//! before [`init`] is called.
+//! ```ignore
+//! use ramlink::producer::AtomicRB;
+//!
+//! #[no_mangle]
+//! static DEFMT_RB: AtomicRB<256> = AtomicRB::new();
+//!
+//! ramlink::producer::defmt::init(&DEFMT_RB);
+//! defmt::info!("t={=i16}", 21);
+//! ```
+//! The host decodes the stream with the ELF file of the firmware, see
+//! [`consumer`](crate::consumer#defmt) and `examples/defmt_cortex_m.rs`.
+//!
+//! Here on the host, calling the logger as `defmt::info!("t={=i16}", 21)` does, its format
+//! string being the first of the table:
+//! ```
+//! # #[cfg(all(feature = "consumer", feature = "std"))] {
+//! # use ramlink::consumer::ProducerDevice;
+//! use defmt::Logger as _;
+//! use ramlink::producer::defmt::Logger;
+//! use ramlink::producer::AtomicRB;
+//! # use ramlink::consumer::testing::VolatileReader;
+//! # let host = VolatileReader::from_atomic_rb(&DEFMT_RB);
+//!
+//! static DEFMT_RB: AtomicRB<32> = AtomicRB::new();
+//! ramlink::producer::defmt::init(&DEFMT_RB);
+//!
+//! Logger::acquire();
+//! unsafe {
+//!     Logger::write(&0_u16.to_le_bytes());
+//!     Logger::write(&21_i16.to_le_bytes());
+//!     Logger::release();
+//! }
+//!
+//! // The frame encoded with rzCOBS, between zeros
+//! let address = &DEFMT_RB as *const _ as usize;
+//! let mut device = ProducerDevice::new(host, address).unwrap();
+//! assert_eq!(device.read_bytes().unwrap(), [0x00, 0x15, 0x7b, 0x00]);
+//! # }
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::{CriticalSection, Mutex, RestoreState};
+
+use super::atomic::Sink;
+use super::AtomicRB;
+
+static SINK: Mutex<Cell<Option<&'static dyn Sink>>> = Mutex::new(Cell::new(None));
+
+/// Set from `acquire` to `release`, to catch a frame started within another
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// What a frame needs from `acquire` to `release`, accessed only within its critical section
+struct Frame {
+    restore: UnsafeCell<RestoreState>,
+    encoder: UnsafeCell<defmt::Encoder>,
+    sink: Cell<Option<&'static dyn Sink>>,
+}
+
+// Only accessed from within the critical section held by a frame
+unsafe impl Sync for Frame {}
+
+static FRAME: Frame = Frame {
+    restore: UnsafeCell::new(RestoreState::invalid()),
+    encoder: UnsafeCell::new(defmt::Encoder::new()),
+    sink: Cell::new(None),
+};
+
+/// Makes `rb` the ring buffer of the defmt logger. Until this is called, frames are dropped.
+pub fn init<const SIZE: usize, const ID: u8>(rb: &'static AtomicRB<SIZE, ID>) {
+    critical_section::with(|cs| SINK.borrow(cs).set(Some(rb)));
+}
+
+/// The defmt global logger, see the [module documentation](self)
+#[defmt::global_logger]
+pub struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // SAFETY: released by `release`, which defmt calls once the frame is complete
+        let restore = unsafe { critical_section::acquire() };
+        if TAKEN.swap(true, Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        // SAFETY: within the critical section, which no other frame can hold
+        unsafe {
+            let cs = CriticalSection::new();
+            *FRAME.restore.get() = restore;
+            FRAME.sink.set(SINK.borrow(cs).get());
+            (*FRAME.encoder.get()).start_frame(send);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        // SAFETY: defmt calls this after `acquire`, within its critical section
+        unsafe {
+            (*FRAME.encoder.get()).end_frame(send);
+            TAKEN.store(false, Ordering::Relaxed);
+            critical_section::release(*FRAME.restore.get());
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        // SAFETY: defmt calls this after `acquire`, within its critical section
+        unsafe { (*FRAME.encoder.get()).write(bytes, send) }
+    }
+}
+
+/// Sends encoded bytes of the current frame, if the ring buffer was given to [`init`]
+fn send(bytes: &[u8]) {
+    if let Some(sink) = FRAME.sink.get() {
+        sink.send_bytes_auto(bytes);
+    }
+}
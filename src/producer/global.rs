@@ -0,0 +1,195 @@
+//! A global ring buffer, written to with the [`print!`](crate::print) and
+//! [`println!`](crate::println) macros from anywhere in the firmware.
+//!
+//! Each call is done in a critical section, so `main` and interrupt handlers can print
+//! concurrently without interleaving their output. A
+//! [`critical-section`](https://docs.rs/critical-section) implementation must be provided,
+//! usually by your HAL or by `cortex-m`/`avr-device`.
+//!
+//! ```
+//! use ramlink::producer::{global, AtomicRB};
+//!
+//! static RING_BUF: AtomicRB<32> = AtomicRB::<32>::new();
+//!
+//! ramlink::println!("dropped, init wasn't called yet");
+//! global::init(&RING_BUF);
+//! ramlink::println!("t={}", 21);
+//! assert_eq!(RING_BUF.len(), "t=21\n".len());
+//! ```
+//!
+//! # Log records
+//! [`ram_error!`](crate::ram_error), [`ram_warn!`](crate::ram_warn),
+//! [`ram_info!`](crate::ram_info), [`ram_debug!`](crate::ram_debug) and
+//! [`ram_trace!`](crate::ram_trace) send records that keep their severity, in fewer
+//! bytes than text: a level byte, from 1 for errors to 5 for traces like `log::Level`, a
+//! target id byte, a length byte, then the message, cut at 255 bytes. The target is a
+//! number given by the firmware, 0 by default, that the host can map back to a name;
+//! `ProducerDevice::records` decodes them.
+//! ```
+//! use ramlink::producer::{global, AtomicRB};
+//!
+//! static RING_BUF: AtomicRB<32> = AtomicRB::<32>::new();
+//! const MOTOR: u8 = 3;
+//!
+//! global::init(&RING_BUF);
+//! ramlink::ram_warn!(target: MOTOR, "stall at {}", 21);
+//! assert_eq!(RING_BUF.len(), 3 + "stall at 21".len());
+//! ```
+
+use core::cell::Cell;
+use core::fmt::{self, Write};
+use critical_section::Mutex;
+
+use super::atomic::Sink;
+use super::AtomicRB;
+
+static SINK: Mutex<Cell<Option<&'static dyn Sink>>> = Mutex::new(Cell::new(None));
+
+/// Makes `rb` the global ring buffer. Until this is called, the macros do nothing.
+pub fn init<const SIZE: usize, const ID: u8>(rb: &'static AtomicRB<SIZE, ID>) {
+    critical_section::with(|cs| SINK.borrow(cs).set(Some(rb)));
+}
+
+/// Implementation of the [`print!`](crate::print) macro
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    critical_section::with(|cs| {
+        if let Some(sink) = SINK.borrow(cs).get() {
+            let _ = SinkWriter(sink).write_fmt(args);
+        }
+    });
+}
+
+/// Implementation of the [`ram_log!`](crate::ram_log) macro
+#[doc(hidden)]
+pub fn _record(level: u8, target: u8, args: fmt::Arguments) {
+    critical_section::with(|cs| {
+        if let Some(sink) = SINK.borrow(cs).get() {
+            // The length goes first, so the message is formatted once to count its bytes
+            let mut counter = Truncated::new(None, 255);
+            let _ = counter.write_fmt(args);
+            sink.send_bytes_blocking(&[level, target, counter.len as u8]);
+            let _ = Truncated::new(Some(sink), counter.len).write_fmt(args);
+        }
+    });
+}
+
+/// Calls `f` with the global ring buffer from a critical section, if [`init`] was called
+#[cfg(all(feature = "panic-handler", target_os = "none"))]
+pub(crate) fn with_sink(f: impl FnOnce(&'static dyn Sink)) {
+    critical_section::with(|cs| {
+        if let Some(sink) = SINK.borrow(cs).get() {
+            f(sink);
+        }
+    });
+}
+
+struct SinkWriter(&'static dyn Sink);
+
+impl fmt::Write for SinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Sends the first `remaining` bytes to `sink`, or only counts them if there is no sink
+struct Truncated {
+    sink: Option<&'static dyn Sink>,
+    remaining: usize,
+    len: usize,
+}
+
+impl Truncated {
+    fn new(sink: Option<&'static dyn Sink>, max_len: usize) -> Truncated {
+        Truncated {
+            sink,
+            remaining: max_len,
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Write for Truncated {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.remaining);
+        if let Some(sink) = self.sink {
+            sink.send_bytes_blocking(&s.as_bytes()[..n]);
+        }
+        self.remaining -= n;
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// Prints to the global ring buffer, see [`producer::global`](crate::producer::global)
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::producer::global::_print(::core::format_args!($($arg)*))
+    };
+}
+
+/// Prints to the global ring buffer, with a newline, see [`producer::global`](crate::producer::global)
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::producer::global::_print(::core::format_args!("{}\n", ::core::format_args!($($arg)*)))
+    };
+}
+
+/// Sends a log record of `level`, 1 for errors to 5 for traces, to the global ring buffer,
+/// see [log records](crate::producer::global#log-records). The target id is 0 unless given
+/// as `target: ID`.
+#[macro_export]
+macro_rules! ram_log {
+    ($level:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::producer::global::_record($level, $target, ::core::format_args!($($arg)+))
+    };
+    ($level:expr, $($arg:tt)+) => {
+        $crate::ram_log!($level, target: 0, $($arg)+)
+    };
+}
+
+/// Sends an error record to the global ring buffer, see [`ram_log!`](crate::ram_log)
+#[macro_export]
+macro_rules! ram_error {
+    ($($arg:tt)+) => {
+        $crate::ram_log!(1, $($arg)+)
+    };
+}
+
+/// Sends a warning record to the global ring buffer, see [`ram_log!`](crate::ram_log)
+#[macro_export]
+macro_rules! ram_warn {
+    ($($arg:tt)+) => {
+        $crate::ram_log!(2, $($arg)+)
+    };
+}
+
+/// Sends an info record to the global ring buffer, see [`ram_log!`](crate::ram_log)
+#[macro_export]
+macro_rules! ram_info {
+    ($($arg:tt)+) => {
+        $crate::ram_log!(3, $($arg)+)
+    };
+}
+
+/// Sends a debug record to the global ring buffer, see [`ram_log!`](crate::ram_log)
+#[macro_export]
+macro_rules! ram_debug {
+    ($($arg:tt)+) => {
+        $crate::ram_log!(4, $($arg)+)
+    };
+}
+
+/// Sends a trace record to the global ring buffer, see [`ram_log!`](crate::ram_log)
+#[macro_export]
+macro_rules! ram_trace {
+    ($($arg:tt)+) => {
+        $crate::ram_log!(5, $($arg)+)
+    };
+}
@@ -0,0 +1,279 @@
+//! Ring buffer whose content is in a separate array, for when it must be placed apart from
+//! the header.
+
+use core::fmt;
+use core::mem::{offset_of, size_of};
+
+use super::{wrap, ByteSink};
+use crate::layout::{self, Offset};
+
+/// Same as [`RB16`](super::RB16), but its content is an external `[u8; SIZE]` array that
+/// the header points to, so that the linker can place each of them on its own: the small
+/// header at an address that is easy to find, and the content wherever there is room,
+/// e.g. in a section that is not zeroed at startup. The consumer follows the pointer by
+/// itself.
+///
+/// The pointer is stored as the target has it, after a byte telling its width: 2 bytes
+/// on AVR, 4 on Cortex-M. It is always at the same offset, so the consumer reads any of
+/// them. Only little-endian targets are supported.
+/// ```
+/// use core::ptr::addr_of_mut;
+/// use ramlink::producer::RBIndirect;
+///
+/// // E.g. with `#[link_section = ".noinit"]`, given a linker script that has one
+/// static mut CONTENT: [u8; 2048] = [0; 2048];
+/// static mut RING_BUF: RBIndirect<2048> = unsafe { RBIndirect::new(addr_of_mut!(CONTENT)) };
+///
+/// let rb = unsafe { &mut *addr_of_mut!(RING_BUF) };
+/// assert_eq!(rb.try_send_bytes(&[0x42; 300]), 300);
+/// assert_eq!(rb.len(), 300);
+/// ```
+#[repr(C)]
+pub struct RBIndirect<const SIZE: usize, const ID: u8 = 0x88> {
+    /// Same as [`RB`](super::RB)'s magic marker, with a different second byte
+    _magic_marker: [u8; 3],
+    /// Same as [`RB`](super::RB)'s layout version
+    version: u8,
+    /// Size of the ring buffer
+    size: u16,
+    /// Producer slot
+    producer: u16,
+    /// Consumer slot. If producer = consumer, ring buffer is empty
+    consumer: u16,
+    /// Number of bytes discarded by the producer, wrapping around
+    dropped: u16,
+    /// Which optional fields follow the content. There are none
+    features: u8,
+    /// Set to 1 by the consumer while it is attached
+    host_attached: u8,
+    /// Width of the content pointer, in bytes
+    pointer_width: u8,
+    /// Keeps the pointer at the same offset on all targets, aligned for those that need it
+    _reserved: u8,
+    /// The actual buffer, `SIZE` bytes long
+    content: *mut u8,
+}
+
+// The consumer reads raw offsets
+const _: () = {
+    type Indirect = RBIndirect<7>;
+    let header = &layout::RB_INDIRECT_HEADER;
+    assert!(offset_of!(Indirect, _magic_marker) == 0);
+    assert!(matches!(header.version, Some(Offset::Start(v)) if v == offset_of!(Indirect, version)));
+    assert!(offset_of!(Indirect, size) == header.size);
+    assert!(offset_of!(Indirect, producer) == layout::RB_INDIRECT.producer);
+    assert!(offset_of!(Indirect, consumer) == layout::RB_INDIRECT.consumer);
+    assert!(
+        matches!(layout::RB_INDIRECT.dropped, Some(Offset::Start(v)) if v == offset_of!(Indirect, dropped))
+    );
+    assert!(
+        matches!(header.features, Some(Offset::Start(v)) if v == offset_of!(Indirect, features))
+    );
+    assert!(
+        matches!(layout::RB_INDIRECT.host_attached, Some(Offset::Start(v)) if v == offset_of!(Indirect, host_attached))
+    );
+    assert!(offset_of!(Indirect, pointer_width) == layout::RB_INDIRECT.content);
+    assert!(offset_of!(Indirect, content) == layout::RB_INDIRECT.content + 2);
+    assert!(size_of::<*mut u8>() <= layout::MAX_POINTER_WIDTH);
+};
+
+impl<const SIZE: usize, const ID: u8> RBIndirect<SIZE, ID> {
+    /// Indices are `u16`, and the consumer reads the pointer as little-endian, evaluated
+    /// by [`RBIndirect::new`]
+    const CHECK: () = {
+        assert!(
+            SIZE > 0 && SIZE <= 65535,
+            "RBIndirect size must be within 1..=65535"
+        );
+        assert!(
+            cfg!(target_endian = "little"),
+            "RBIndirect needs a little-endian target"
+        );
+    };
+
+    /// Returns a new, empty ring buffer whose content is `content`, which can be the address
+    /// of a `static`, see the example above.
+    ///
+    /// # Safety
+    ///
+    /// `content` must be valid for writes for as long as the ring buffer is used, and must
+    /// not be accessed otherwise meanwhile.
+    pub const unsafe fn new(content: *mut [u8; SIZE]) -> Self {
+        let () = Self::CHECK;
+        RBIndirect {
+            _magic_marker: layout::RB_INDIRECT_HEADER.magic(ID),
+            version: layout::VERSION,
+            size: (SIZE as u16).to_le(),
+            producer: 0,
+            consumer: 0,
+            dropped: 0,
+            features: 0,
+            host_attached: 0,
+            pointer_width: size_of::<*mut u8>() as u8,
+            _reserved: 0,
+            content: content.cast(),
+        }
+    }
+
+    fn producer(&self) -> usize {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.producer) }) as usize
+    }
+
+    fn set_producer(&mut self, index: usize) {
+        unsafe { core::ptr::write_volatile(&mut self.producer, (index as u16).to_le()) };
+    }
+
+    fn consumer(&self) -> usize {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.consumer) }) as usize
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    /// See [`RB::send_bytes_blocking`](super::RB::send_bytes_blocking).
+    pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`RBIndirect::send_bytes_blocking`], but calls `idle` each time the wait
+    /// loop finds the ring buffer full.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the consumer. Returns the number of bytes that were accepted.
+    /// The producer index is only published once, after the accepted bytes were written.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends as many bytes of `data` as currently fit, and counts the rest as dropped.
+    /// See [`RB::send_bytes_lossy`](super::RB::send_bytes_lossy).
+    pub fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(self, data)
+    }
+
+    /// Sends bytes, blocking only while a consumer is attached.
+    /// See [`RB::send_bytes_auto`](super::RB::send_bytes_auto).
+    pub fn send_bytes_auto(&mut self, data: &[u8]) {
+        ByteSink::send_bytes_auto(self, data)
+    }
+
+    /// Returns `true` while a consumer is attached
+    pub fn host_attached(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.host_attached) != 0 }
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        let dropped = self.dropped().wrapping_add(n as u16);
+        unsafe { core::ptr::write_volatile(&mut self.dropped, dropped.to_le()) };
+    }
+
+    /// Returns the number of bytes discarded since startup, wrapping around at `u16::MAX`.
+    pub fn dropped(&self) -> u16 {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.dropped) })
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is `SIZE - 1`.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the consumer.
+    pub fn len(&self) -> usize {
+        wrap::<SIZE>(self.producer() + SIZE - self.consumer())
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the consumer reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+
+    /// Empties the ring buffer by zeroing both indices. See [`RB::reset`](super::RB::reset).
+    pub fn reset(&mut self) {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        unsafe { core::ptr::write_volatile(&mut self.consumer, 0) };
+        self.set_producer(0);
+    }
+
+    /// Waits until the consumer has read every byte sent so far. Never returns if no
+    /// consumer is attached.
+    pub fn flush(&self) {
+        while !self.is_empty() {}
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> ByteSink for RBIndirect<SIZE, ID> {
+    type Index = usize;
+
+    fn producer(&self) -> usize {
+        RBIndirect::producer(self)
+    }
+
+    fn consumer(&self) -> usize {
+        RBIndirect::consumer(self)
+    }
+
+    fn next(index: usize) -> usize {
+        wrap::<SIZE>(index + 1)
+    }
+
+    fn write_slot(&mut self, index: usize, byte: u8) {
+        // Within the `SIZE` bytes that `new` was given
+        unsafe { core::ptr::write_volatile(self.content.add(index), byte) };
+    }
+
+    fn publish(&mut self, index: usize) {
+        self.set_producer(index)
+    }
+
+    fn capacity(&self) -> usize {
+        RBIndirect::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RBIndirect::len(self)
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        RBIndirect::record_dropped(self, n)
+    }
+
+    fn host_attached(&self) -> bool {
+        RBIndirect::host_attached(self)
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> fmt::Write for RBIndirect<SIZE, ID> {
+    /// Implements write_str so we can use the write! macro
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize, const ID: u8> ufmt::uWrite for RBIndirect<SIZE, ID> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! A [`log`] backend writing records into an [`AtomicRB`].
+//!
+//! Each record is written as `[LEVEL target] message`, followed by a delimiter
+//! (`\n` by default). Records are serialized with a critical section, so it is fine to log
+//! from both `main` and interrupt handlers. A [`critical-section`](https://docs.rs/critical-section)
+//! implementation must be provided, usually by your HAL or by `cortex-m`/`avr-device`.
+//!
+//! ```
+//! use ramlink::producer::{logger, AtomicRB};
+//!
+//! static RING_BUF: AtomicRB<64> = AtomicRB::<64>::new();
+//!
+//! logger::init(&RING_BUF, logger::Config::default()).unwrap();
+//! log::info!(target: "app", "t={}", 21);
+//! assert_eq!(RING_BUF.len(), "[INFO app] t=21\n".len());
+//! ```
+//!
+//! On the consumer side, the stream is split back into records on the delimiter. A record
+//! may arrive in several chunks, so keep the trailing incomplete part for the next read:
+//! ```ignore
+//!    let mut pending = Vec::new();
+//!    loop {
+//!        pending.extend(rb.read_bytes()?);
+//!        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+//!            let record: Vec<u8> = pending.drain(..=end).collect();
+//!            println!("{}", String::from_utf8_lossy(&record[..end]));
+//!        }
+//!    }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use super::atomic::Sink;
+use super::AtomicRB;
+
+/// What to do with a record that does not fit in the ring buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullPolicy {
+    /// Wait for the consumer to make room. Never returns if no consumer is attached
+    Block,
+    /// Discard the whole record, counting its bytes in [`AtomicRB::dropped`]
+    Drop,
+}
+
+/// Configuration of the logger, given to [`init`]
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Records above this level are discarded
+    pub level: LevelFilter,
+    /// Records are truncated to this many bytes, delimiter excluded
+    pub max_line: usize,
+    /// What to do when a record does not fit in the ring buffer
+    pub on_full: FullPolicy,
+    /// Byte appended after each record, so that the consumer can split them
+    pub delimiter: u8,
+}
+
+impl Config {
+    const DEFAULT: Config = Config {
+        level: LevelFilter::Info,
+        max_line: 80,
+        on_full: FullPolicy::Block,
+        delimiter: b'\n',
+    };
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::DEFAULT
+    }
+}
+
+struct RamLogger {
+    /// Written once by [`init`], before the logger is installed
+    sink: UnsafeCell<Option<&'static dyn Sink>>,
+    /// Written once by [`init`], before the logger is installed
+    config: UnsafeCell<Config>,
+}
+
+// The cells are only written by `init`, before `log` can hand the logger out
+unsafe impl Sync for RamLogger {}
+
+static LOGGER: RamLogger = RamLogger {
+    sink: UnsafeCell::new(None),
+    config: UnsafeCell::new(Config::DEFAULT),
+};
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the ramlink logger, sending records to `rb`. Fails if a logger is already installed.
+pub fn init<const SIZE: usize, const ID: u8>(
+    rb: &'static AtomicRB<SIZE, ID>,
+    config: Config,
+) -> Result<(), SetLoggerError> {
+    critical_section::with(|_| {
+        // On a second call, leave the installed logger alone and let `log` report the error
+        if !INITIALIZED.load(Ordering::Relaxed) {
+            INITIALIZED.store(true, Ordering::Relaxed);
+            unsafe {
+                *LOGGER.sink.get() = Some(rb);
+                *LOGGER.config.get() = config;
+            }
+        }
+        install(config.level)
+    })
+}
+
+#[cfg(target_has_atomic = "ptr")]
+fn install(level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Targets such as AVR have no compare-and-swap. This is called from a critical section,
+/// so there can't be any concurrent call.
+#[cfg(not(target_has_atomic = "ptr"))]
+fn install(level: LevelFilter) -> Result<(), SetLoggerError> {
+    unsafe {
+        log::set_logger_racy(&LOGGER)?;
+        log::set_max_level_racy(level);
+    }
+    Ok(())
+}
+
+impl RamLogger {
+    fn config(&self) -> &Config {
+        unsafe { &*self.config.get() }
+    }
+
+    fn sink(&self) -> Option<&'static dyn Sink> {
+        unsafe { *self.sink.get() }
+    }
+}
+
+impl Log for RamLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.config().level
+    }
+
+    fn log(&self, record: &Record) {
+        let config = self.config();
+        let Some(sink) = self.sink() else {
+            return;
+        };
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        critical_section::with(|_| {
+            if config.on_full == FullPolicy::Drop {
+                let mut counter = LineWriter::new(None, config.max_line);
+                write_record(&mut counter, record);
+                if sink.free_space() < counter.len + 1 {
+                    sink.record_dropped(counter.len + 1);
+                    return;
+                }
+            }
+
+            let mut writer = LineWriter::new(Some(sink), config.max_line);
+            write_record(&mut writer, record);
+            sink.send_bytes_blocking(&[config.delimiter]);
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+fn write_record(w: &mut LineWriter, record: &Record) {
+    let _ = write!(
+        w,
+        "[{} {}] {}",
+        record.level(),
+        record.target(),
+        record.args()
+    );
+}
+
+/// Sends at most `remaining` bytes to `sink`, or only counts them if there is no sink
+struct LineWriter {
+    sink: Option<&'static dyn Sink>,
+    remaining: usize,
+    len: usize,
+}
+
+impl LineWriter {
+    fn new(sink: Option<&'static dyn Sink>, max_line: usize) -> LineWriter {
+        LineWriter {
+            sink,
+            remaining: max_line,
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Write for LineWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.remaining);
+        if let Some(sink) = self.sink {
+            sink.send_bytes_blocking(&s.as_bytes()[..n]);
+        }
+        self.remaining -= n;
+        self.len += n;
+        Ok(())
+    }
+}
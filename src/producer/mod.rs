@@ -8,7 +8,7 @@
 //! # Examples
 //! The following creates the [`RB`] struct of size **5** as a static variable. In order to
 //! access it safely, we wrap it around a Mutex and a RefCell:
-//! ```
+//! ```ignore
 //!   use avr_device::interrupt::{self, Mutex};
 //!   use core::cell::{Cell, RefCell};
 //!   use ramlink::producer::RB;
@@ -16,7 +16,7 @@
 //!   static RING_BUF: Mutex<RefCell<RB<5>>> = Mutex::new(RefCell::new(RB::<5>::new()));
 //! ```
 //! data can then be sent to it:
-//! ```
+//! ```ignore
 //!   interrupt::free(|cs| {
 //!     RING_BUF
 //!     .borrow(cs)
@@ -24,19 +24,171 @@
 //!     .send_bytes_blocking(&[temperature, current]);
 //!   });
 //!```
+//! Alternatively, [`AtomicRB`] can be written through a shared reference, so it can
+//! live in a plain `static`:
+//! ```
+//!   use ramlink::producer::AtomicRB;
+//!
+//!   static RING_BUF: AtomicRB<5> = AtomicRB::<5>::new();
+//!
+//!   RING_BUF.send_bytes_blocking(&[0x42, 0x43]);
+//! ```
+//! # The consumer is not in the program
+//! The consumer reads and writes the ring buffer through a debug probe, behind the back of
+//! the compiler, which thus sees stores that nothing reads, and loads of memory that
+//! nothing writes: it could merge, defer or drop them. So the producer only accesses the
+//! indices, the content and the counters it keeps for the consumer, the dropped bytes,
+//! the statistics and the heartbeat, with volatile reads and writes, which are emitted as
+//! written, and compiler fences order the content against the index that publishes it.
+//! The producer index is written last, once the bytes it covers are in place; the
+//! consumer index is read first, before the slots it freed are overwritten.
+//!
+//! [`AtomicRB`] gets the same guarantees from atomics, and [`GrantW`] from its
+//! [`commit`](GrantW::commit).
+//! # defmt
+//! [defmt](https://defmt.ferrous-systems.com) only needs a byte transport, which an
+//! [`AtomicRB`] can be in place of RTT: with the `defmt` feature, [`defmt`](self::defmt)
+//! provides a global logger writing into one. The host decodes the stream with the ELF
+//! file of the firmware, see [`consumer`](crate::consumer#defmt).
 
 #![warn(missing_docs)]
-use const_assert::{Assert, IsTrue};
 use core::fmt;
+use core::mem::offset_of;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::layout::{self, LayoutDescriptor, Offset};
+
+mod atomic;
+pub use atomic::{AtomicRB, RamlinkWriter, WriterAlreadyTaken};
+mod compact;
+pub use compact::RBCompact;
+#[cfg(feature = "compression")]
+mod compress;
+mod control;
+pub use control::ControlBlock;
+mod indirect;
+pub use indirect::RBIndirect;
+mod rb16;
+pub use rb16::RB16;
+#[cfg(feature = "rtt")]
+mod rtt;
+#[cfg(feature = "rtt")]
+pub use rtt::RttRB;
+mod rx;
+pub use rx::{DuplexRB, RxRB};
+mod section;
+mod sink;
+use sink::ByteSink;
+
+#[cfg(feature = "defmt")]
+pub mod defmt;
+#[cfg(feature = "global")]
+pub mod global;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "panic")]
+pub mod panic;
+
+/// Error returned by [`RB::send_bytes_timeout`] when the consumer stopped reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendTimeout {
+    /// Number of bytes that were sent before giving up
+    pub written: usize,
+}
+
+/// A clock of the target, read by [`RB::send_frame_timestamped`], e.g. the cycle counter of
+/// a Cortex-M (`DWT::cycle_count`) or a free-running timer. It may wrap around at
+/// `u32::MAX`, the consumer counting the wraps.
+pub trait TimestampSource {
+    /// Returns the current value of the clock
+    fn now() -> u32;
+}
+
+/// Wraps `index` around a ring buffer of `SIZE` slots. When `SIZE` is a power of two, this
+/// is a mask instead of a modulo, which is much cheaper on parts without a divider: built
+/// for an ATmega328P with `opt-level = "s"`, `try_send_bytes` of an `RB<64>` is 65
+/// instructions, against 81 for an `RB<60>`.
+pub(crate) const fn wrap<const SIZE: usize>(index: usize) -> usize {
+    if SIZE.is_power_of_two() {
+        index & (SIZE - 1)
+    } else {
+        index % SIZE
+    }
+}
+
+/// Returns the slot following `index` in a ring buffer of `SIZE` slots. This stays on `u8`,
+/// as a modulo on `usize` is twice as wide on AVR.
+pub(crate) const fn next_index<const SIZE: usize>(index: u8) -> u8 {
+    if SIZE.is_power_of_two() {
+        index.wrapping_add(1) & (SIZE - 1) as u8
+    } else {
+        (index + 1) % SIZE as u8
+    }
+}
 
-use super::RB_MAGIC;
+/// Returns `name` padded with zeros, evaluated at compile time for a `static`, e.g. the name
+/// of a channel of a [`ControlBlock`]
+pub(crate) const fn padded_name(name: &str) -> [u8; layout::CHANNEL_NAME_LEN] {
+    let bytes = name.as_bytes();
+    assert!(
+        bytes.len() <= layout::CHANNEL_NAME_LEN,
+        "names are at most 8 bytes long"
+    );
+    let mut padded = [0; layout::CHANNEL_NAME_LEN];
+    let mut i = 0;
+    while i < bytes.len() {
+        padded[i] = bytes[i];
+        i += 1;
+    }
+    padded
+}
+
+/// Called between reading the consumer index and writing the slots it freed, so that the
+/// compiler can't move these writes before the read, while the consumer may still be
+/// reading the slots.
+#[inline(always)]
+pub(crate) fn acquire_fence() {
+    compiler_fence(Ordering::Acquire);
+}
+
+/// Called between writing content and publishing the producer index that covers it, so
+/// that the compiler can't move the index write first: the consumer reads a slot as soon
+/// as the index says it is written.
+#[inline(always)]
+pub(crate) fn release_fence() {
+    compiler_fence(Ordering::Release);
+}
+
+/// Generates the typed senders of [`RB`]: each value is sent little-endian, all of its
+/// bytes at once, so that the consumer never sees half of it
+macro_rules! le_senders {
+    ($($ty:ty => $send:ident, $try_send:ident;)*) => {
+        $(
+            #[doc = concat!("Sends a `", stringify!($ty), "` in little-endian, blocking until all of its bytes fit. ")]
+            #[doc = "Fails the build if the ring buffer is too small to ever hold them."]
+            pub fn $send(&mut self, value: $ty) {
+                self.send_value_blocking(value.to_le_bytes())
+            }
+
+            #[doc = concat!("Sends a `", stringify!($ty), "` in little-endian without blocking. Returns `false`, ")]
+            #[doc = "having sent nothing, if not all of its bytes fit."]
+            pub fn $try_send(&mut self, value: $ty) -> bool {
+                self.try_send_value(&value.to_le_bytes())
+            }
+        )*
+    };
+}
 
 /// The RingBuffer struct that will contain our message to be sent.
 /// Some fields are read only while others are written by the consumer (host, JTAG, ...)
-pub struct RB<const SIZE: usize> {
+///
+/// Up to the end of the content, it is laid out as in the first releases, so that their
+/// consumers still read it. The fields added since follow the content.
+#[repr(C)]
+pub struct RB<const SIZE: usize, const ID: u8 = 0x88> {
     /// This eats 3 bytes for "nothing" but is useful for debuging purposes to ensure that the RAM address is correct
     _magic_marker: [u8; 3],
-    /// Size of the ring buffer. Could be removed if both parties agree on a defined size
+    /// Size of the ring buffer, 0 meaning 256. Could be removed if both parties agree on a defined size
     size: u8,
     /// Producer slot
     producer: u8,
@@ -44,52 +196,861 @@ pub struct RB<const SIZE: usize> {
     consumer: u8,
     /// The actual buffer
     content: [u8; SIZE],
+    /// Tells the consumer that the fields below follow, unlike in the first releases
+    end_marker: u8,
+    /// Version of the layout, so that the consumer knows where the other fields are
+    version: u8,
+    /// Number of bytes discarded by the producer, little-endian, wrapping around
+    dropped: [u8; 2],
+    /// Which optional fields follow
+    features: u8,
+    /// Set to 1 by the consumer while it is attached
+    host_attached: u8,
+    /// Highest number of bytes that were ever waiting in the buffer
+    #[cfg(feature = "stats")]
+    high_water: u8,
+    /// Number of bytes sent since startup, little-endian. Stored as bytes to keep the
+    /// struct unaligned, so that its offset is the same on all targets
+    #[cfg(feature = "stats")]
+    total_sent: [u8; 4],
+    /// Incremented by [`RB::tick`], so that the consumer can tell if the producer is alive
+    #[cfg(feature = "heartbeat")]
+    heartbeat: u8,
+    /// Number of times the producer index went back to 0, little-endian, wrapping around,
+    /// so that the consumer can tell how far it was lapped
+    #[cfg(feature = "wraps")]
+    wraps: [u8; 2],
+    /// Given by [`RB::new_named`], padded with zeros, so that the consumer can tell the
+    /// ring buffers of a firmware apart
+    #[cfg(feature = "names")]
+    name: [u8; layout::NAME_LEN],
+}
+
+/// Offset from the end of the content of the field at `offset` in an `RB<7>`
+const fn rb7_end_offset(offset: usize) -> usize {
+    offset - layout::RB.content - 7
 }
 
-impl<const SIZE: usize> RB<SIZE>
-where
-    Assert<{ SIZE <= 255 }>: IsTrue,
-{
-    /// Returns a new ring buffer of size `SIZE`
-    pub const fn new() -> RB<SIZE> {
+/// Offset of an optional field of an `RB<7>`
+#[cfg(any(
+    feature = "stats",
+    feature = "heartbeat",
+    feature = "wraps",
+    feature = "names"
+))]
+const fn rb7_trailer_offset(feature: u8) -> usize {
+    match layout::trailer_offset(layout::RB_FEATURES, feature) {
+        Some(offset) => layout::RB.content + 7 + layout::RB.after_content + offset,
+        None => panic!("feature not enabled"),
+    }
+}
+
+// The consumer reads raw offsets
+const _: () = {
+    assert!(offset_of!(RB<7>, _magic_marker) == 0);
+    assert!(offset_of!(RB<7>, size) == layout::RB_HEADER.size);
+    assert!(offset_of!(RB<7>, producer) == layout::RB.producer);
+    assert!(offset_of!(RB<7>, consumer) == layout::RB.consumer);
+    assert!(offset_of!(RB<7>, content) == layout::RB.content);
+    assert!(
+        matches!(layout::RB_HEADER.end_marker, Some(v) if v == rb7_end_offset(offset_of!(RB<7>, end_marker)))
+    );
+    assert!(
+        matches!(layout::RB_HEADER.version, Some(Offset::End(v)) if v == rb7_end_offset(offset_of!(RB<7>, version)))
+    );
+    assert!(
+        matches!(layout::RB.dropped, Some(Offset::End(v)) if v == rb7_end_offset(offset_of!(RB<7>, dropped)))
+    );
+    assert!(
+        matches!(layout::RB_HEADER.features, Some(Offset::End(v)) if v == rb7_end_offset(offset_of!(RB<7>, features)))
+    );
+    assert!(
+        matches!(layout::RB.host_attached, Some(Offset::End(v)) if v == rb7_end_offset(offset_of!(RB<7>, host_attached)))
+    );
+    assert!(core::mem::size_of::<RB<7>>() == RB::<7>::LAYOUT.len(7));
+    #[cfg(feature = "stats")]
+    assert!(offset_of!(RB<7>, high_water) == rb7_trailer_offset(layout::FEATURE_STATS));
+    #[cfg(feature = "heartbeat")]
+    assert!(offset_of!(RB<7>, heartbeat) == rb7_trailer_offset(layout::FEATURE_HEARTBEAT));
+    #[cfg(feature = "wraps")]
+    assert!(offset_of!(RB<7>, wraps) == rb7_trailer_offset(layout::FEATURE_WRAPS));
+    #[cfg(feature = "names")]
+    assert!(offset_of!(RB<7>, name) == rb7_trailer_offset(layout::FEATURE_NAME));
+};
+
+/// Checks that a value of `N` bytes fits in a ring buffer of `SIZE` bytes, which holds
+/// `SIZE - 1` at most
+struct ValueCheck<const SIZE: usize, const N: usize>;
+
+impl<const SIZE: usize, const N: usize> ValueCheck<SIZE, N> {
+    const FITS: () = assert!(N < SIZE, "RB is too small to ever hold the value");
+}
+
+impl<const SIZE: usize, const ID: u8> RB<SIZE, ID> {
+    /// Indices are `u8`, so `SIZE` must be at most 256. Evaluated by [`RB::new`], so that
+    /// a bad size fails the build
+    const CHECK: () = assert!(SIZE > 0 && SIZE <= 256, "RB size must be within 1..=256");
+
+    /// Layout of this ring buffer, with the optional fields of the enabled cargo features,
+    /// for `ProducerDevice::new_with_layout`
+    pub const LAYOUT: LayoutDescriptor = LayoutDescriptor::v1()
+        .with_id(ID)
+        .with_features(layout::RB_FEATURES);
+
+    /// Returns a new ring buffer of size `SIZE`. Its content is filled with `0x13`, which
+    /// is easy to spot in a memory dump.
+    pub const fn new() -> Self {
+        Self::new_with_fill(0x13)
+    }
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `fill`
+    pub const fn new_with_fill(fill: u8) -> Self {
+        let () = Self::CHECK;
         RB {
-            _magic_marker: RB_MAGIC,
+            _magic_marker: layout::RB_HEADER.magic(ID),
             size: SIZE as u8,
             producer: 0,
             consumer: 0,
-            content: [0x13; SIZE],
+            content: [fill; SIZE],
+            end_marker: layout::CONTENT_END_MARKER,
+            version: layout::VERSION,
+            dropped: [0; 2],
+            features: layout::RB_FEATURES,
+            host_attached: 0,
+            #[cfg(feature = "stats")]
+            high_water: 0,
+            #[cfg(feature = "stats")]
+            total_sent: [0; 4],
+            #[cfg(feature = "heartbeat")]
+            heartbeat: 0,
+            #[cfg(feature = "wraps")]
+            wraps: [0; 2],
+            #[cfg(feature = "names")]
+            name: [0; layout::NAME_LEN],
+        }
+    }
+
+    /// Returns a new ring buffer of size `SIZE` named `name`, which the consumer reads with
+    /// `ProducerDevice::name`, e.g. to tell the ring buffers found by a scan apart. Names
+    /// longer than 8 bytes fail to compile.
+    ///
+    /// The name is an optional field, only there with the `names` feature: without it, the
+    /// ring buffers made by [`RB::new`] don't have it, and are the same as ever.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// static mut TELEMETRY: RB<64> = RB::new_named("telem");
+    ///
+    /// let rb = unsafe { &mut *core::ptr::addr_of_mut!(TELEMETRY) };
+    /// rb.send_bytes_blocking(&[0x17, 0x2a]);
+    /// ```
+    #[cfg(feature = "names")]
+    pub const fn new_named(name: &str) -> Self {
+        RB {
+            name: padded_name(name),
+            ..Self::new()
         }
     }
 
+    /// Returns a new ring buffer of size `SIZE` whose bytes are all zero, header included,
+    /// so that a `static` one lands in `.bss` instead of `.data`. This saves its whole size
+    /// in flash, and the copy at startup: with a `static` `RB<256>` built for a Cortex-M4,
+    /// `.data` goes from 268 bytes with [`RB::new`] to 0.
+    ///
+    /// [`RB::init`] must then be called at startup, before the consumer attaches, as it
+    /// won't find the magic marker until then.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// static mut RING_BUF: RB<256> = RB::new_zeroed();
+    ///
+    /// let rb = unsafe { &mut *core::ptr::addr_of_mut!(RING_BUF) };
+    /// rb.init();
+    /// rb.send_bytes_blocking(b"hello");
+    /// ```
+    pub const fn new_zeroed() -> Self {
+        let () = Self::CHECK;
+        RB {
+            _magic_marker: [0; 3],
+            size: 0,
+            producer: 0,
+            consumer: 0,
+            content: [0; SIZE],
+            end_marker: 0,
+            version: 0,
+            dropped: [0; 2],
+            features: 0,
+            host_attached: 0,
+            #[cfg(feature = "stats")]
+            high_water: 0,
+            #[cfg(feature = "stats")]
+            total_sent: [0; 4],
+            #[cfg(feature = "heartbeat")]
+            heartbeat: 0,
+            #[cfg(feature = "wraps")]
+            wraps: [0; 2],
+            #[cfg(feature = "names")]
+            name: [0; layout::NAME_LEN],
+        }
+    }
+
+    /// Writes the header of a ring buffer created with [`RB::new_zeroed`]. This does not
+    /// touch the indices, so it is harmless to call it on any ring buffer.
+    pub fn init(&mut self) {
+        self.end_marker = layout::CONTENT_END_MARKER;
+        self.version = layout::VERSION;
+        self.size = SIZE as u8;
+        self.features = layout::RB_FEATURES;
+        // Written last, so that the consumer can't attach to a half-written header
+        compiler_fence(Ordering::SeqCst);
+        unsafe { core::ptr::write_volatile(&mut self._magic_marker, layout::RB_HEADER.magic(ID)) };
+    }
+
+    /// Reads the producer index, see the module documentation for why it is volatile
+    fn producer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.producer) }
+    }
+
+    fn set_producer(&mut self, index: u8) {
+        // Counted before the index is published, see `ProducerDevice::read_events`
+        #[cfg(feature = "wraps")]
+        if index < self.producer() {
+            let wraps = u16::from_le_bytes(self.wraps).wrapping_add(1);
+            unsafe { core::ptr::write_volatile(&mut self.wraps, wraps.to_le_bytes()) };
+        }
+        unsafe { core::ptr::write_volatile(&mut self.producer, index) };
+    }
+
+    fn consumer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.consumer) }
+    }
+
+    fn set_consumer(&mut self, index: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.consumer, index) };
+    }
+
+    /// Writes `byte` in the content slot `index`
+    fn write_slot(&mut self, index: usize, byte: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.content[index], byte) };
+    }
+
     /// Sends bytes on the ring buffer. This is blocking. If the
     /// ring buffer is full, it will wait for more space before moving on.
-    /// This busy-waits for now.
-    /// TODO: Add a non-blocking (lossy) method + interrupt based one ?
+    /// This busy-waits for now, see [`RB::try_send_bytes`] for a non-blocking variant.
+    /// TODO: Add an interrupt based one ?
     pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`RB::send_bytes_blocking`], but calls `idle` each time the wait loop finds
+    /// the ring buffer full. This is where to sleep until the next interrupt, pet a watchdog
+    /// or blink a LED. No critical section is held while `idle` runs.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the consumer. Returns the number of bytes that were accepted.
+    /// The producer index is only published once, after the accepted bytes were written.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends bytes on the ring buffer, waiting for space like [`RB::send_bytes_blocking`], but
+    /// gives up once the ring buffer stayed full for `max_spins` polls of the consumer index.
+    /// The error tells how many bytes were written, so only the rest needs to be retried.
+    /// ```
+    /// use ramlink::producer::{SendTimeout, RB};
+    ///
+    /// // Nobody consumes this ring buffer
+    /// let mut rb = RB::<4>::new();
+    /// let data = [1, 2, 3, 4, 5];
+    /// assert_eq!(rb.send_bytes_timeout(&data, 100), Err(SendTimeout { written: 3 }));
+    /// ```
+    pub fn send_bytes_timeout(&mut self, data: &[u8], max_spins: u32) -> Result<(), SendTimeout> {
+        ByteSink::send_bytes_timeout(self, data, max_spins)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, and discards the
+    /// rest, counting them in [`RB::dropped`] so that the consumer knows data was lost.
+    /// Returns the number of bytes that were sent.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<4>::new();
+    /// assert_eq!(rb.send_bytes_lossy(&[1, 2, 3, 4, 5]), 3);
+    /// assert_eq!(rb.dropped(), 2);
+    /// ```
+    pub fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(self, data)
+    }
+
+    /// Sends bytes like [`RB::send_bytes_blocking`] while a consumer is attached, and like
+    /// [`RB::send_bytes_lossy`] otherwise, so that firmware running without a host never
+    /// hangs on a full ring buffer. If the consumer detaches while this waits, the rest of
+    /// `data` is dropped.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// // Nobody ever attaches to this ring buffer
+    /// let mut rb = RB::<4>::new();
+    /// rb.send_bytes_auto(&[1, 2, 3, 4, 5]);
+    /// assert_eq!(rb.len(), 3);
+    /// assert_eq!(rb.dropped(), 2);
+    /// ```
+    pub fn send_bytes_auto(&mut self, data: &[u8]) {
+        ByteSink::send_bytes_auto(self, data)
+    }
+
+    /// Returns `true` while a consumer is attached, see [`RB::send_bytes_auto`]
+    pub fn host_attached(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.host_attached) != 0 }
+    }
+
+    /// Sends bytes on the ring buffer without ever blocking. If the ring buffer is
+    /// full, the oldest byte is discarded to make room for the new one, and counted in
+    /// [`RB::dropped`].
+    ///
+    /// To discard a byte, the producer advances the consumer index itself, which is
+    /// otherwise only written by the consumer. The consumer index is read and written
+    /// back with volatile accesses, but the consumer may move it at the same time:
+    /// - if both sides advance it past the same byte, they write the same value and
+    ///   nothing is lost twice;
+    /// - if the consumer read the index before the producer discarded bytes, its
+    ///   write-back can move the index backwards. The consumer will then receive some
+    ///   stale bytes again, but the index always stays within the ring buffer.
+    ///
+    /// In other words, a drain racing with an overwrite may see duplicated or stale
+    /// bytes, never out of bounds data. Use framing if the stream must be checked.
+    pub fn send_bytes_overwrite(&mut self, data: &[u8]) {
         for elem in data.iter() {
-            loop {
-                let prod = unsafe { core::ptr::read_volatile(&self.producer) };
-                let cons = unsafe { core::ptr::read_volatile(&self.consumer) };
-                if (prod + 1) % self.size != cons {
-                    break;
-                }
+            let prod = self.producer();
+            let next_p = next_index::<SIZE>(prod);
+            let cons = self.consumer();
+            if next_p == cons {
+                self.set_consumer(next_index::<SIZE>(cons));
+                self.record_dropped(1);
             }
+            acquire_fence();
+
+            self.write_slot(prod as usize, *elem);
+            release_fence();
+            self.set_producer(next_p);
+            self.record_sent(1);
+        }
+    }
+
+    /// Sends `bytes` as a whole, waiting until they all fit in the ring buffer. A ring
+    /// buffer whose capacity is less than `N` would wait forever, so it fails the build:
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<5>::new();
+    /// rb.send_u32_le(0xdead_beef);
+    /// assert!(rb.is_full());
+    /// ```
+    /// ```compile_fail
+    /// use ramlink::producer::RB;
+    ///
+    /// // Holds 3 bytes at most
+    /// RB::<4>::new().send_u32_le(0xdead_beef);
+    /// ```
+    fn send_value_blocking<const N: usize>(&mut self, bytes: [u8; N]) {
+        let () = ValueCheck::<SIZE, N>::FITS;
+        while self.free_space() < N {}
+        self.try_send_bytes(&bytes);
+    }
+
+    /// Sends `bytes` as a whole, or nothing at all if they don't fit in the ring buffer
+    fn try_send_value(&mut self, bytes: &[u8]) -> bool {
+        if self.free_space() < bytes.len() {
+            return false;
+        }
+        self.try_send_bytes(bytes);
+        true
+    }
+
+    /// Sends `payload` as a COBS frame, blocking like [`RB::send_bytes_blocking`]: it is
+    /// encoded on the fly so that it contains no zero byte, and followed by a zero. The
+    /// consumer can then find the start of the next frame wherever it starts reading, see
+    /// `ProducerDevice::cobs_frames`. The frame may be larger than the ring buffer, and is
+    /// at most `payload.len() / 254 + 2` bytes longer than `payload`.
+    pub fn send_frame_cobs(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_cobs(self, payload)
+    }
+
+    /// Sends `value` serialized with postcard, blocking like [`RB::send_bytes_blocking`].
+    /// It is sent as a frame prefixed with its length as a LEB128 varint, which may be larger
+    /// than the ring buffer, and is read with `ProducerDevice::read_postcard`. The value is
+    /// serialized twice, to count its bytes and then to send them, so that no buffer is
+    /// needed. Fails if it can't be serialized by postcard, in which case nothing is sent.
+    /// ```
+    /// # #[cfg(feature = "postcard")] {
+    /// use ramlink::producer::RB;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Telemetry {
+    ///     temperature: i16,
+    ///     current: u16,
+    /// }
+    ///
+    /// let mut rb = RB::<16>::new();
+    /// rb.send_postcard(&Telemetry { temperature: -40, current: 300 }).unwrap();
+    /// // The length, -40 zigzag-encoded as 79, then 300 as the varint 0xAC 0x02
+    /// assert_eq!(rb.len(), 1 + 3);
+    /// # }
+    /// ```
+    #[cfg(feature = "postcard")]
+    pub fn send_postcard<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), postcard::Error> {
+        ByteSink::send_postcard(self, value)
+    }
+
+    /// Sends `payload` as a frame of the channel `channel`: the channel byte, a length byte,
+    /// then the payload, blocking like [`RB::send_bytes_blocking`]. Several streams then
+    /// share a single ring buffer, and the consumer splits them apart again with
+    /// `ProducerDevice::mux`. Panics if `payload` is longer than 255 bytes.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<16>::new();
+    /// rb.send_frame_on(2, b"abc");
+    /// assert_eq!(rb.len(), 5);
+    /// assert!(!rb.try_send_frame_on(1, &[0; 9]));
+    /// assert_eq!(rb.len(), 5);
+    /// ```
+    pub fn send_frame_on(&mut self, channel: u8, payload: &[u8]) {
+        ByteSink::send_frame_on(self, channel, payload)
+    }
+
+    /// Sends `text` as a frame tagged as text, so that the consumer can tell it from binary
+    /// data sent on the same ring buffer, see `ProducerDevice::tagged_records`. Panics if
+    /// `text` is longer than 255 bytes.
+    ///
+    /// Tags from 0x80 to 0xFF are left to the application, for frames sent with
+    /// [`RB::send_frame_on`].
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<32>::new();
+    /// rb.send_text("t=21");
+    /// rb.send_binary(&[0x15, 0x00]);
+    /// assert_eq!(rb.len(), 2 + 4 + 2 + 2);
+    /// ```
+    pub fn send_text(&mut self, text: &str) {
+        ByteSink::send_text(self, text)
+    }
+
+    /// Sends `data` as a frame tagged as binary, see [`RB::send_text`]. Panics if `data` is
+    /// longer than 255 bytes.
+    pub fn send_binary(&mut self, data: &[u8]) {
+        ByteSink::send_binary(self, data)
+    }
 
-            self.content[self.producer as usize] = *elem;
+    /// Same as [`RB::send_frame_on`], but returns `false` without sending anything if the
+    /// whole frame does not fit in the ring buffer right now, or if `payload` is longer
+    /// than 255 bytes. The consumer thus never gets part of a frame.
+    pub fn try_send_frame_on(&mut self, channel: u8, payload: &[u8]) -> bool {
+        ByteSink::try_send_frame_on(self, channel, payload)
+    }
+
+    /// Sends `payload` as a SLIP frame (RFC 1055), blocking like
+    /// [`RB::send_bytes_blocking`]: its `0xC0` and `0xDB` bytes are escaped on the fly, and
+    /// it is put between two `0xC0` END bytes, so that the consumer can start reading
+    /// anywhere, see `ProducerDevice::slip_frames`. SLIP has no empty frames: an empty
+    /// `payload` is only sent as END bytes, which the consumer skips.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<16>::new();
+    /// rb.send_frame_slip(&[0x01, 0xC0, 0xDB]);
+    /// assert_eq!(rb.len(), 1 + 5 + 1);
+    /// ```
+    pub fn send_frame_slip(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_slip(self, payload)
+    }
+
+    /// Sends `payload` as a length-prefixed frame followed by the CRC-8 of the length and
+    /// the payload, blocking like [`RB::send_bytes_blocking`]. The consumer reads it with
+    /// `ProducerDevice::frames` in CRC mode, which rejects frames corrupted on the way and
+    /// finds the next one. Panics if `payload` is longer than 255 bytes.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<16>::new();
+    /// rb.send_frame_crc(b"123456789");
+    /// assert_eq!(rb.len(), 1 + 9 + 1);
+    /// ```
+    pub fn send_frame_crc(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_crc(self, payload)
+    }
+
+    /// Sends `payload` compressed with LZSS, as a length-prefixed frame, blocking like
+    /// [`RB::send_bytes_blocking`]. Its first byte is `WINDOW_BITS`, within 4..=12: repeated
+    /// runs of bytes are sent as references to the same bytes at most `2^WINDOW_BITS` bytes
+    /// before in the frame, which are looked up in `payload` itself, so that compressing
+    /// takes no RAM. Larger windows find more repetitions, at the cost of CPU time.
+    ///
+    /// Each frame is compressed on its own, so that losing one does not prevent the consumer
+    /// from decompressing the next ones with `ProducerDevice::frames` in compression mode.
+    /// A payload that does not shrink is sent as it is, with a first byte of 0. Panics if
+    /// the frame is still longer than 255 bytes.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<64>::new();
+    /// let trace = b"adc=1023 adc=1022 adc=1023 adc=1023 adc=1021 adc=1023 adc=1023 adc=1022";
+    /// rb.send_frame_compressed::<8>(trace);
+    /// assert!(rb.len() < trace.len() / 2);
+    ///
+    /// // Sent as it is, plus the length and window bytes
+    /// rb.reset();
+    /// rb.send_frame_compressed::<8>(b"abcdef");
+    /// assert_eq!(rb.len(), 1 + 1 + 6);
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn send_frame_compressed<const WINDOW_BITS: u32>(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_compressed::<WINDOW_BITS>(self, payload)
+    }
+
+    /// Sends `payload` as a length-prefixed frame whose first 4 bytes are the current value
+    /// of the clock `T`, little-endian, blocking like [`RB::send_bytes_blocking`]. The
+    /// consumer reads them with `ProducerDevice::timestamped_frames`, which needs the
+    /// whole frame to fit in the ring buffer: `payload` must be at most `SIZE - 5` bytes.
+    /// ```
+    /// use ramlink::producer::{TimestampSource, RB};
+    ///
+    /// struct Ticks;
+    ///
+    /// impl TimestampSource for Ticks {
+    ///     fn now() -> u32 {
+    ///         0x0102_0304
+    ///     }
+    /// }
+    ///
+    /// let mut rb = RB::<16>::new();
+    /// rb.send_frame_timestamped::<Ticks>(b"adc");
+    /// assert_eq!(rb.len(), 1 + 4 + 3);
+    /// ```
+    pub fn send_frame_timestamped<T: TimestampSource>(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_timestamped::<T>(self, payload)
+    }
+
+    le_senders! {
+        u16 => send_u16_le, try_send_u16_le;
+        i16 => send_i16_le, try_send_i16_le;
+        u32 => send_u32_le, try_send_u32_le;
+        i32 => send_i32_le, try_send_i32_le;
+        f32 => send_f32_le, try_send_f32_le;
+    }
+
+    /// Grants write access to at most `len` bytes of free space, without copying: the bytes
+    /// are written in place and published with [`GrantW::commit`]. A grant never spans the
+    /// end of the ring buffer, so it may be shorter than `len` even if there is more free
+    /// space after the wrap. Returns `None` if the ring buffer is full.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<8>::new();
+    /// let mut grant = rb.grant(3).unwrap();
+    /// grant.copy_from_slice(b"abc");
+    /// grant.commit(3);
+    /// assert_eq!(rb.len(), 3);
+    ///
+    /// // Grants are capped to the free space, and publish nothing unless committed
+    /// let mut grant = rb.grant(10).unwrap();
+    /// assert_eq!(grant.len(), 4);
+    /// grant[0] = b'd';
+    /// drop(grant);
+    /// assert_eq!(rb.len(), 3);
+    /// ```
+    pub fn grant(&mut self, len: usize) -> Option<GrantW<'_, SIZE, ID>> {
+        let prod = self.producer() as usize;
+        let cons = self.consumer() as usize;
+        acquire_fence();
+
+        let free = wrap::<SIZE>(cons + SIZE - prod - 1);
+        let len = len.min(free).min(SIZE - prod);
+        if len == 0 {
+            return None;
+        }
+        Some(GrantW {
+            rb: self,
+            start: prod,
+            len,
+        })
+    }
 
-            let next_p = (self.producer + 1) % self.size;
-            self.producer = next_p;
+    /// Updates the statistics once `n` more bytes were published to the consumer. Like the
+    /// dropped counter, they are written with volatile writes.
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn record_sent(&mut self, n: usize) {
+        #[cfg(feature = "stats")]
+        {
+            let total = self.total_sent().wrapping_add(n as u32);
+            unsafe { core::ptr::write_volatile(&mut self.total_sent, total.to_le_bytes()) };
+            let len = self.len();
+            if len > self.high_water() {
+                unsafe { core::ptr::write_volatile(&mut self.high_water, len as u8) };
+            }
         }
     }
+
+    /// Counts `n` more discarded bytes. The counter is only read by the consumer, so it is
+    /// written with a volatile write, see the module documentation.
+    fn record_dropped(&mut self, n: usize) {
+        if n > 0 {
+            let dropped = self.dropped().wrapping_add(n as u16);
+            unsafe { core::ptr::write_volatile(&mut self.dropped, dropped.to_le_bytes()) };
+        }
+    }
+
+    /// Returns the number of bytes discarded by the producer since startup, wrapping around
+    /// at `u16::MAX`. The consumer reads it with `ProducerDevice::dropped_bytes`.
+    pub fn dropped(&self) -> u16 {
+        u16::from_le_bytes(unsafe { core::ptr::read_volatile(&self.dropped) })
+    }
+
+    /// Returns the highest number of bytes that were ever waiting in the ring buffer. If
+    /// it reaches [`RB::capacity`], the producer had to wait or drop data at some point.
+    #[cfg(feature = "stats")]
+    pub fn high_water(&self) -> usize {
+        unsafe { core::ptr::read_volatile(&self.high_water) as usize }
+    }
+
+    /// Returns the number of bytes sent since startup, wrapping around at `u32::MAX`.
+    #[cfg(feature = "stats")]
+    pub fn total_sent(&self) -> u32 {
+        u32::from_le_bytes(unsafe { core::ptr::read_volatile(&self.total_sent) })
+    }
+
+    /// Returns the number of times the producer index went back to the start of the
+    /// content, wrapping around at `u16::MAX`. The consumer compares it with what it read
+    /// to tell if [`RB::send_bytes_overwrite`] lapped it, see `ProducerDevice::read_events`.
+    #[cfg(feature = "wraps")]
+    pub fn wraps(&self) -> u16 {
+        u16::from_le_bytes(self.wraps)
+    }
+
+    /// Increments the heartbeat counter. This is cheap enough to be called from a timer
+    /// interrupt: as long as it changes, the consumer knows the producer is running, even
+    /// if it has nothing to send.
+    #[cfg(feature = "heartbeat")]
+    pub fn tick(&mut self) {
+        let heartbeat = unsafe { core::ptr::read_volatile(&self.heartbeat) };
+        unsafe { core::ptr::write_volatile(&mut self.heartbeat, heartbeat.wrapping_add(1)) };
+    }
+
+    /// Returns the number of bytes the ring buffer can hold. This is `SIZE - 1`, as one
+    /// slot is always left free to tell a full ring buffer from an empty one.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the consumer.
+    /// ```
+    /// use ramlink::producer::RB;
+    ///
+    /// let mut rb = RB::<4>::new();
+    /// assert!(rb.is_empty());
+    /// assert_eq!(rb.try_send_bytes(&[1, 2, 3, 4]), 3);
+    /// assert!(rb.is_full());
+    /// assert_eq!(rb.len(), 3);
+    ///
+    /// // Wraps the producer index around, past the consumer one
+    /// rb.send_bytes_overwrite(&[5, 6]);
+    /// assert_eq!(rb.len(), 3);
+    /// assert_eq!(rb.free_space(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        let prod = self.producer() as usize;
+        let cons = self.consumer() as usize;
+        wrap::<SIZE>(prod + SIZE - cons)
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the consumer reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+
+    /// Waits until the consumer has read every byte sent so far. This busy-waits, and
+    /// never returns if no consumer is attached: see [`RB::flush_timeout`] for a bounded variant.
+    pub fn flush(&self) {
+        while !self.try_flush() {}
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far, without waiting.
+    pub fn try_flush(&self) -> bool {
+        self.is_empty()
+    }
+
+    /// Waits until the consumer has read every byte sent so far, giving up after polling
+    /// the indices `spins` times. Returns `true` if the ring buffer was drained.
+    pub fn flush_timeout(&self, spins: u32) -> bool {
+        (0..=spins).any(|_| self.try_flush())
+    }
+
+    /// Empties the ring buffer by zeroing both indices, e.g. after a soft reset of the
+    /// firmware. The consumer index is zeroed first: a consumer reading in between sees
+    /// stale bytes, but never indices outside of the ring buffer. The consumer should then
+    /// call `ProducerDevice::resync`.
+    pub fn reset(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+        self.set_consumer(0);
+        self.set_producer(0);
+        #[cfg(feature = "wraps")]
+        unsafe {
+            core::ptr::write_volatile(&mut self.wraps, [0; 2])
+        };
+    }
+}
+
+/// A contiguous region of free space in an [`RB`], obtained with [`RB::grant`]. It derefs
+/// to the granted bytes, which are published by [`GrantW::commit`]. Dropping it without
+/// committing publishes nothing.
+pub struct GrantW<'a, const SIZE: usize, const ID: u8 = 0x88> {
+    rb: &'a mut RB<SIZE, ID>,
+    start: usize,
+    len: usize,
 }
 
-impl<const SIZE: usize> fmt::Write for RB<SIZE>
-where
-    Assert<{ SIZE <= 255 }>: IsTrue,
-{
+impl<const SIZE: usize, const ID: u8> GrantW<'_, SIZE, ID> {
+    /// Publishes the first `n` granted bytes to the consumer (at most the grant length)
+    pub fn commit(self, n: usize) {
+        let n = n.min(self.len);
+        // The granted bytes were written through plain references
+        release_fence();
+        self.rb.set_producer(wrap::<SIZE>(self.start + n) as u8);
+        self.rb.record_sent(n);
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> core::ops::Deref for GrantW<'_, SIZE, ID> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.rb.content[self.start..self.start + self.len]
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> core::ops::DerefMut for GrantW<'_, SIZE, ID> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.rb.content[self.start..self.start + self.len]
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> Default for RB<SIZE, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> ByteSink for RB<SIZE, ID> {
+    type Index = u8;
+
+    fn producer(&self) -> u8 {
+        RB::producer(self)
+    }
+
+    fn consumer(&self) -> u8 {
+        RB::consumer(self)
+    }
+
+    fn next(index: u8) -> u8 {
+        next_index::<SIZE>(index)
+    }
+
+    fn write_slot(&mut self, index: u8, byte: u8) {
+        RB::write_slot(self, index as usize, byte)
+    }
+
+    fn publish(&mut self, index: u8) {
+        self.set_producer(index)
+    }
+
+    fn capacity(&self) -> usize {
+        RB::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RB::len(self)
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        RB::record_dropped(self, n)
+    }
+
+    fn host_attached(&self) -> bool {
+        RB::host_attached(self)
+    }
+
+    fn record_sent(&mut self, n: usize) {
+        RB::record_sent(self, n)
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> fmt::Write for RB<SIZE, ID> {
     /// Implements write_src so we can use the write! macro
     fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
         self.send_bytes_blocking(s.as_bytes());
         Ok(())
     }
 }
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize, const ID: u8> ufmt::uWrite for RB<SIZE, ID> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro, which is much lighter than write!
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<const SIZE: usize, const ID: u8> embedded_io::ErrorType for RB<SIZE, ID> {
+    type Error = core::convert::Infallible;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<const SIZE: usize, const ID: u8> embedded_io::Write for RB<SIZE, ID> {
+    /// Sends as many bytes as currently fit. As required by the trait, this only blocks
+    /// while the ring buffer is completely full.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let sent = self.try_send_bytes(buf);
+            if sent > 0 {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// Waits until the consumer has read every byte, see [`RB::flush`]
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        RB::flush(self);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Best-effort reporting of panics through the ring buffer.
+//!
+//! [`report_panic`] writes the panic location and message without ever blocking, so that it
+//! can't hang inside a panic handler. If the message does not fit in the free space, its
+//! beginning is kept and `…` is appended.
+//!
+//! With the `panic-handler` feature, this crate also provides the `#[panic_handler]` of
+//! bare-metal targets: it reports the panic to the [`global`](super::global) ring buffer,
+//! then loops forever.
+
+use core::fmt;
+use core::panic::PanicInfo;
+
+use super::RB;
+
+/// Appended to a panic message that was truncated
+const ELLIPSIS: &str = "…";
+
+/// Writes the location and message of a panic to `rb`, truncating it to the free space.
+/// ```ignore
+///   static mut RING_BUF: RB<64> = RB::<64>::new();
+///
+///   #[panic_handler]
+///   fn panic(info: &PanicInfo) -> ! {
+///       report_panic(unsafe { &mut *core::ptr::addr_of_mut!(RING_BUF) }, info);
+///       loop {}
+///   }
+/// ```
+pub fn report_panic<const SIZE: usize, const ID: u8>(rb: &mut RB<SIZE, ID>, info: &PanicInfo) {
+    let free = rb.free_space();
+    write_truncated(info, free, |bytes| {
+        rb.try_send_bytes(bytes);
+    });
+}
+
+/// Writes the panic report with `send`, which must accept at least `free` bytes
+fn write_truncated(info: &PanicInfo, free: usize, mut send: impl FnMut(&[u8])) {
+    let mut counter = Truncated::new(|_: &[u8]| {}, usize::MAX);
+    write_report(&mut counter, info);
+
+    if counter.len <= free {
+        write_report(&mut Truncated::new(&mut send, free), info);
+    } else if free >= ELLIPSIS.len() {
+        write_report(&mut Truncated::new(&mut send, free - ELLIPSIS.len()), info);
+        send(ELLIPSIS.as_bytes());
+    }
+}
+
+fn write_report(w: &mut impl fmt::Write, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(w, "panicked at {}:{}: ", location.file(), location.line()),
+        None => write!(w, "panicked: "),
+    };
+    let _ = writeln!(w, "{}", info.message());
+}
+
+/// Sends at most `budget` bytes, stopping at the first string that does not fit entirely
+/// so that the output is always a prefix of the full report
+struct Truncated<F: FnMut(&[u8])> {
+    send: F,
+    budget: usize,
+    len: usize,
+    truncated: bool,
+}
+
+impl<F: FnMut(&[u8])> Truncated<F> {
+    fn new(send: F, budget: usize) -> Truncated<F> {
+        Truncated {
+            send,
+            budget,
+            len: 0,
+            truncated: false,
+        }
+    }
+}
+
+impl<F: FnMut(&[u8])> fmt::Write for Truncated<F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+        let mut n = s.len().min(self.budget - self.len);
+        if n < s.len() {
+            self.truncated = true;
+            while !s.is_char_boundary(n) {
+                n -= 1;
+            }
+        }
+        (self.send)(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "panic-handler", target_os = "none"))]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    super::global::with_sink(|sink| {
+        write_truncated(info, sink.free_space(), |bytes| {
+            sink.send_bytes_blocking(bytes)
+        });
+    });
+    loop {
+        core::hint::spin_loop();
+    }
+}
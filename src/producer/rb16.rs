@@ -0,0 +1,309 @@
+//! Ring buffer with 16 bits indices, for buffers larger than 256 bytes.
+
+use core::fmt;
+use core::mem::offset_of;
+
+use super::{wrap, ByteSink};
+use crate::layout::{self, LayoutDescriptor, Offset};
+
+/// Same as [`RB`](super::RB), but with `u16` producer/consumer indices, so that it can hold
+/// up to 65535 bytes. Its magic marker differs from [`RB`](super::RB)'s, which is how the
+/// consumer tells which layout it is reading.
+///
+/// The indices are stored little-endian. The consumer writes its index one byte at a time,
+/// low byte first, unless its `MemoryReader` can write both bytes at once.
+/// ```
+/// use ramlink::producer::RB16;
+///
+/// let mut rb = RB16::<4096>::new();
+/// assert_eq!(rb.try_send_bytes(&[0x42; 300]), 300);
+/// assert_eq!(rb.len(), 300);
+/// ```
+#[repr(C)]
+pub struct RB16<const SIZE: usize, const ID: u8 = 0x88> {
+    /// Same as [`RB`](super::RB)'s magic marker, with a different second byte
+    _magic_marker: [u8; 3],
+    /// Same as [`RB`](super::RB)'s layout version, which also keeps the 16 bits fields
+    /// aligned
+    version: u8,
+    /// Size of the ring buffer
+    size: u16,
+    /// Producer slot
+    producer: u16,
+    /// Consumer slot. If producer = consumer, ring buffer is empty
+    consumer: u16,
+    /// Number of bytes discarded by the producer, wrapping around
+    dropped: u16,
+    /// Which optional fields follow the content. There are none for now
+    features: u8,
+    /// Set to 1 by the consumer while it is attached
+    host_attached: u8,
+    /// The actual buffer
+    content: [u8; SIZE],
+}
+
+// The consumer reads raw offsets
+const _: () = {
+    assert!(offset_of!(RB16<7>, _magic_marker) == 0);
+    assert!(
+        matches!(layout::RB16_HEADER.version, Some(Offset::Start(v)) if v == offset_of!(RB16<7>, version))
+    );
+    assert!(
+        matches!(layout::RB16_HEADER.features, Some(Offset::Start(v)) if v == offset_of!(RB16<7>, features))
+    );
+    assert!(offset_of!(RB16<7>, size) == layout::RB16_HEADER.size);
+    assert!(offset_of!(RB16<7>, producer) == layout::RB16.producer);
+    assert!(offset_of!(RB16<7>, consumer) == layout::RB16.consumer);
+    assert!(
+        matches!(layout::RB16.dropped, Some(Offset::Start(v)) if v == offset_of!(RB16<7>, dropped))
+    );
+    assert!(
+        matches!(layout::RB16.host_attached, Some(Offset::Start(v)) if v == offset_of!(RB16<7>, host_attached))
+    );
+    assert!(offset_of!(RB16<7>, content) == layout::RB16.content);
+};
+
+impl<const SIZE: usize, const ID: u8> RB16<SIZE, ID> {
+    /// Indices are `u16`, evaluated by [`RB16::new`]
+    const CHECK: () = assert!(
+        SIZE > 0 && SIZE <= 65535,
+        "RB16 size must be within 1..=65535"
+    );
+
+    /// Layout of this ring buffer, which has no optional fields, for
+    /// `ProducerDevice::new_with_layout`
+    pub const LAYOUT: LayoutDescriptor = LayoutDescriptor::v1().with_u16_indices().with_id(ID);
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `0x13`
+    pub const fn new() -> Self {
+        Self::new_with_fill(0x13)
+    }
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `fill`
+    pub const fn new_with_fill(fill: u8) -> Self {
+        let () = Self::CHECK;
+        RB16 {
+            _magic_marker: layout::RB16_HEADER.magic(ID),
+            version: layout::VERSION,
+            features: 0,
+            size: (SIZE as u16).to_le(),
+            producer: 0,
+            consumer: 0,
+            dropped: 0,
+            host_attached: 0,
+            content: [fill; SIZE],
+        }
+    }
+
+    /// Returns a new ring buffer of size `SIZE` whose bytes are all zero, so that it lands in
+    /// `.bss`. [`RB16::init`] must be called before the consumer attaches, see
+    /// [`RB::new_zeroed`](super::RB::new_zeroed).
+    pub const fn new_zeroed() -> Self {
+        let () = Self::CHECK;
+        RB16 {
+            _magic_marker: [0; 3],
+            version: 0,
+            features: 0,
+            size: 0,
+            producer: 0,
+            consumer: 0,
+            dropped: 0,
+            host_attached: 0,
+            content: [0; SIZE],
+        }
+    }
+
+    /// Writes the header of a ring buffer created with [`RB16::new_zeroed`]
+    pub fn init(&mut self) {
+        self.version = layout::VERSION;
+        self.size = (SIZE as u16).to_le();
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        unsafe {
+            core::ptr::write_volatile(&mut self._magic_marker, layout::RB16_HEADER.magic(ID))
+        };
+    }
+
+    fn producer(&self) -> usize {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.producer) }) as usize
+    }
+
+    fn set_producer(&mut self, index: usize) {
+        unsafe { core::ptr::write_volatile(&mut self.producer, (index as u16).to_le()) };
+    }
+
+    fn consumer(&self) -> usize {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.consumer) }) as usize
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    /// See [`RB::send_bytes_blocking`](super::RB::send_bytes_blocking).
+    pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`RB16::send_bytes_blocking`], but calls `idle` each time the wait loop
+    /// finds the ring buffer full.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the consumer. Returns the number of bytes that were accepted.
+    /// The producer index is only published once, after the accepted bytes were written.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends as many bytes of `data` as currently fit, and counts the rest as dropped.
+    /// See [`RB::send_bytes_lossy`](super::RB::send_bytes_lossy).
+    pub fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(self, data)
+    }
+
+    /// Sends bytes, blocking only while a consumer is attached.
+    /// See [`RB::send_bytes_auto`](super::RB::send_bytes_auto).
+    pub fn send_bytes_auto(&mut self, data: &[u8]) {
+        ByteSink::send_bytes_auto(self, data)
+    }
+
+    /// Sends `payload` as a frame prefixed with its length as a LEB128 varint, 7 bits per
+    /// byte from the least significant ones, the high bit set on all but the last byte.
+    /// Frames can thus be larger than 255 bytes, and are read with
+    /// `ProducerDevice::frames` set to varint lengths. Blocks like
+    /// [`RB16::send_bytes_blocking`].
+    /// ```
+    /// use ramlink::producer::RB16;
+    ///
+    /// let mut rb = RB16::<1024>::new();
+    /// rb.send_frame_varint(&[0x42; 300]);
+    /// // 300 is 0b10_0101100, sent as 0xAC 0x02
+    /// assert_eq!(rb.len(), 2 + 300);
+    /// ```
+    pub fn send_frame_varint(&mut self, payload: &[u8]) {
+        ByteSink::send_frame_varint(self, payload)
+    }
+
+    /// Returns `true` while a consumer is attached
+    pub fn host_attached(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.host_attached) != 0 }
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        let dropped = self.dropped().wrapping_add(n as u16);
+        unsafe { core::ptr::write_volatile(&mut self.dropped, dropped.to_le()) };
+    }
+
+    /// Returns the number of bytes discarded since startup, wrapping around at `u16::MAX`.
+    pub fn dropped(&self) -> u16 {
+        u16::from_le(unsafe { core::ptr::read_volatile(&self.dropped) })
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is `SIZE - 1`.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the consumer.
+    pub fn len(&self) -> usize {
+        wrap::<SIZE>(self.producer() + SIZE - self.consumer())
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the consumer has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the consumer reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+
+    /// Empties the ring buffer by zeroing both indices. See [`RB::reset`](super::RB::reset).
+    pub fn reset(&mut self) {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        unsafe { core::ptr::write_volatile(&mut self.consumer, 0) };
+        self.set_producer(0);
+    }
+
+    /// Waits until the consumer has read every byte sent so far. Never returns if no
+    /// consumer is attached.
+    pub fn flush(&self) {
+        while !self.is_empty() {}
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> Default for RB16<SIZE, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> ByteSink for RB16<SIZE, ID> {
+    type Index = usize;
+
+    fn producer(&self) -> usize {
+        RB16::producer(self)
+    }
+
+    fn consumer(&self) -> usize {
+        RB16::consumer(self)
+    }
+
+    fn next(index: usize) -> usize {
+        wrap::<SIZE>(index + 1)
+    }
+
+    fn write_slot(&mut self, index: usize, byte: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.content[index], byte) };
+    }
+
+    fn publish(&mut self, index: usize) {
+        self.set_producer(index)
+    }
+
+    fn capacity(&self) -> usize {
+        RB16::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RB16::len(self)
+    }
+
+    fn record_dropped(&mut self, n: usize) {
+        RB16::record_dropped(self, n)
+    }
+
+    fn host_attached(&self) -> bool {
+        RB16::host_attached(self)
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> fmt::Write for RB16<SIZE, ID> {
+    /// Implements write_str so we can use the write! macro
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize, const ID: u8> ufmt::uWrite for RB16<SIZE, ID> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
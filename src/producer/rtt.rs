@@ -0,0 +1,304 @@
+//! Ring buffer laid out as a SEGGER RTT control block, for the host tools that read RTT.
+
+use core::ffi::CStr;
+use core::fmt;
+use core::mem::{offset_of, size_of};
+
+use super::{release_fence, wrap, ByteSink};
+
+/// Searched for in RAM by the host tools. Kept in two parts so that no copy of the whole
+/// string sits in the initialized data, where a tool could find it first.
+const RTT_ID: [&[u8]; 2] = [b"SEGGER", b" RTT"];
+
+/// Descriptor of an up channel, as `SEGGER_RTT_BUFFER_UP`. `unsigned` is a `u32` on the
+/// Cortex-M and RISC-V targets RTT is made for.
+#[repr(C)]
+struct UpBuffer {
+    /// Name of the channel, NUL-terminated
+    name: *const u8,
+    /// Start of the content
+    buffer: *mut u8,
+    size: u32,
+    /// Producer slot
+    write_offset: u32,
+    /// Consumer slot, written by the host. If both are equal, the channel is empty
+    read_offset: u32,
+    /// Operating mode requested by the host, not consulted
+    flags: u32,
+}
+
+/// A ring buffer that is also a SEGGER RTT control block with a single up channel, so that
+/// `probe-rs rtt`, JLinkRTTViewer and the like read it as they would the output of SEGGER's
+/// own library, without `ramlink::consumer`.
+///
+/// RTT tools find the control block by searching RAM for its id string, and follow the
+/// pointers of its descriptor, so the ring buffer must not move once initialized: it lives
+/// in a `static mut`, and is initialized by its first write, or by [`RttRB::init`] to make
+/// it visible earlier. The RTT indices are `u32`, and the buffer can hold up to
+/// `u32::MAX - 1` bytes.
+/// ```
+/// use core::ptr::addr_of_mut;
+/// use ramlink::producer::RttRB;
+///
+/// static mut RTT: RttRB<1024> = RttRB::new(c"Terminal");
+///
+/// let rb = unsafe { &mut *addr_of_mut!(RTT) };
+/// rb.send_bytes_blocking(b"boot\n");
+/// assert_eq!(rb.len(), 5);
+/// ```
+///
+/// Parsed as documented by SEGGER, the id string, then the number of up and down channels
+/// as `int`, then the descriptor of each up channel: its name, content, size, write offset,
+/// read offset and flags, pointers and `unsigned` as the target has them.
+/// ```
+/// use core::ffi::CStr;
+/// use ramlink::producer::RttRB;
+///
+/// let rb: &mut RttRB<16> = Box::leak(Box::new(RttRB::new(c"Terminal")));
+/// rb.send_bytes_blocking(b"hello");
+///
+/// let control_block = rb as *const _ as usize;
+/// let bytes = unsafe { core::slice::from_raw_parts(control_block as *const u8, 24) };
+/// assert_eq!(&bytes[..16], b"SEGGER RTT\0\0\0\0\0\0");
+/// assert_eq!(i32::from_ne_bytes(bytes[16..20].try_into().unwrap()), 1);
+/// assert_eq!(i32::from_ne_bytes(bytes[20..24].try_into().unwrap()), 0);
+///
+/// let ptr = |offset: usize| unsafe { *((control_block + offset) as *const usize) };
+/// let unsigned = |offset: usize| unsafe { *((control_block + offset) as *const u32) };
+/// let width = core::mem::size_of::<usize>();
+/// let up = 24;
+/// let name = unsafe { CStr::from_ptr(ptr(up) as *const _) };
+/// assert_eq!(name, c"Terminal");
+/// let (size, write, read) = (
+///     unsigned(up + 2 * width),
+///     unsigned(up + 2 * width + 4),
+///     unsigned(up + 2 * width + 8),
+/// );
+/// assert_eq!((size, write, read), (16, 5, 0));
+/// let content = unsafe { core::slice::from_raw_parts(ptr(up + width) as *const u8, 16) };
+/// assert_eq!(&content[read as usize..write as usize], b"hello");
+///
+/// // The host moves the read offset, and the writes go on after it
+/// unsafe { *((control_block + up + 2 * width + 8) as *mut u32) = 5 };
+/// assert_eq!(rb.free_space(), 15);
+/// ```
+#[repr(C)]
+pub struct RttRB<const SIZE: usize> {
+    /// `"SEGGER RTT"`, padded with zeros, once initialized
+    id: [u8; 16],
+    /// Number of up channels, as a C `int`
+    max_up_buffers: i32,
+    /// Number of down channels, as a C `int`
+    max_down_buffers: i32,
+    up: UpBuffer,
+    /// Number of bytes discarded by the producer, not part of RTT
+    dropped: u16,
+    /// The actual buffer
+    content: [u8; SIZE],
+}
+
+// Where the RTT tools look, on 32 and 64 bits targets alike
+const _: () = {
+    let width = size_of::<*const u8>();
+    assert!(offset_of!(RttRB<7>, max_up_buffers) == 16);
+    assert!(offset_of!(RttRB<7>, max_down_buffers) == 20);
+    assert!(offset_of!(RttRB<7>, up) == 24);
+    assert!(offset_of!(UpBuffer, buffer) == width);
+    assert!(offset_of!(UpBuffer, size) == 2 * width);
+    assert!(offset_of!(UpBuffer, write_offset) == 2 * width + 4);
+    assert!(offset_of!(UpBuffer, read_offset) == 2 * width + 8);
+    assert!(offset_of!(UpBuffer, flags) == 2 * width + 12);
+};
+
+impl<const SIZE: usize> RttRB<SIZE> {
+    /// The RTT size is a `u32`, evaluated by [`RttRB::new`]
+    const CHECK: () = assert!(
+        SIZE > 1 && SIZE as u64 <= u32::MAX as u64,
+        "RttRB size must be within 2..=u32::MAX"
+    );
+
+    /// Returns a new, empty ring buffer whose channel is named `name`. The host tools don't
+    /// see it until it is initialized.
+    pub const fn new(name: &'static CStr) -> Self {
+        let () = Self::CHECK;
+        RttRB {
+            id: [0; 16],
+            max_up_buffers: 1,
+            max_down_buffers: 0,
+            up: UpBuffer {
+                name: name.as_ptr().cast(),
+                buffer: core::ptr::null_mut(),
+                size: SIZE as u32,
+                write_offset: 0,
+                read_offset: 0,
+                flags: 0,
+            },
+            dropped: 0,
+            content: [0; SIZE],
+        }
+    }
+
+    /// Points the descriptor to the content, then writes the id string, so that a host tool
+    /// never finds a control block that is not complete. Does nothing if it was already
+    /// initialized; the ring buffer must not move afterwards.
+    pub fn init(&mut self) {
+        if self.id[0] != 0 {
+            return;
+        }
+        let content = self.content.as_mut_ptr();
+        unsafe { core::ptr::write_volatile(&mut self.up.buffer, content) };
+        release_fence();
+        let mut i = 0;
+        for part in RTT_ID {
+            for &b in part {
+                unsafe { core::ptr::write_volatile(&mut self.id[i], b) };
+                i += 1;
+            }
+        }
+    }
+
+    fn producer(&self) -> usize {
+        unsafe { core::ptr::read_volatile(&self.up.write_offset) as usize }
+    }
+
+    fn set_producer(&mut self, index: usize) {
+        unsafe { core::ptr::write_volatile(&mut self.up.write_offset, index as u32) };
+    }
+
+    fn consumer(&self) -> usize {
+        unsafe { core::ptr::read_volatile(&self.up.read_offset) as usize }
+    }
+
+    /// Sends bytes on the ring buffer, busy-waiting while it is full.
+    /// See [`RB::send_bytes_blocking`](super::RB::send_bytes_blocking).
+    pub fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Same as [`RttRB::send_bytes_blocking`], but calls `idle` each time the wait loop
+    /// finds the ring buffer full.
+    pub fn send_bytes_blocking_with(&mut self, data: &[u8], idle: impl FnMut()) {
+        ByteSink::send_bytes_blocking_with(self, data, idle)
+    }
+
+    /// Sends as many bytes of `data` as currently fit in the ring buffer, without ever
+    /// waiting for the host. Returns the number of bytes that were accepted.
+    /// The write offset is only published once, after the accepted bytes were written.
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        ByteSink::try_send_bytes(self, data)
+    }
+
+    /// Sends a single byte on the ring buffer without blocking. Returns `false`
+    /// if the ring buffer is full, in which case nothing was written.
+    pub fn try_send_byte(&mut self, b: u8) -> bool {
+        self.try_send_bytes(&[b]) == 1
+    }
+
+    /// Sends as many bytes of `data` as currently fit, and counts the rest as dropped.
+    /// See [`RB::send_bytes_lossy`](super::RB::send_bytes_lossy).
+    pub fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        ByteSink::send_bytes_lossy(self, data)
+    }
+
+    /// Returns the number of bytes discarded since startup, wrapping around at `u16::MAX`.
+    /// RTT has no such counter, so only the firmware sees it.
+    pub fn dropped(&self) -> u16 {
+        self.dropped
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is `SIZE - 1`.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes waiting to be read by the host.
+    pub fn len(&self) -> usize {
+        wrap::<SIZE>(self.producer() + SIZE - self.consumer())
+    }
+
+    /// Returns the number of bytes that can be sent without blocking.
+    pub fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Returns `true` if the host has read every byte sent so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more bytes can be sent until the host reads some.
+    pub fn is_full(&self) -> bool {
+        self.free_space() == 0
+    }
+
+    /// Waits until the host has read every byte sent so far. Never returns if no host
+    /// tool is reading.
+    pub fn flush(&self) {
+        while !self.is_empty() {}
+    }
+}
+
+impl<const SIZE: usize> ByteSink for RttRB<SIZE> {
+    type Index = usize;
+
+    fn producer(&self) -> usize {
+        RttRB::producer(self)
+    }
+
+    fn consumer(&self) -> usize {
+        RttRB::consumer(self)
+    }
+
+    fn next(index: usize) -> usize {
+        wrap::<SIZE>(index + 1)
+    }
+
+    fn write_slot(&mut self, index: usize, byte: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.content[index], byte) };
+    }
+
+    fn publish(&mut self, index: usize) {
+        self.set_producer(index)
+    }
+
+    fn capacity(&self) -> usize {
+        RttRB::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        RttRB::len(self)
+    }
+
+    /// Only counted for the firmware, RTT has no such field
+    fn record_dropped(&mut self, n: usize) {
+        self.dropped = self.dropped.wrapping_add(n as u16);
+    }
+
+    /// RTT has no such flag, and `RttRB` has no `send_bytes_auto`
+    fn host_attached(&self) -> bool {
+        true
+    }
+
+    fn before_send(&mut self) {
+        self.init();
+    }
+}
+
+impl<const SIZE: usize> fmt::Write for RttRB<SIZE> {
+    /// Implements write_str so we can use the write! macro
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const SIZE: usize> ufmt::uWrite for RttRB<SIZE> {
+    type Error = core::convert::Infallible;
+
+    /// Implements write_str so we can use the uwrite! macro
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.send_bytes_blocking(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Ring buffer the host writes to, for commands going back to the target.
+
+use core::mem::offset_of;
+
+use super::{acquire_fence, next_index, release_fence, wrap, RB};
+use crate::layout::{self, Offset};
+
+/// A ring buffer the host writes to and the target reads from, with
+/// `consumer::HostWriter`, e.g. to change the log level or trigger a self-test. It has the
+/// fields of [`RB`], all of them before the content, as no host of the first releases reads
+/// it: the host writes the content and the producer index, and the target moves the
+/// consumer index.
+///
+/// Reading never blocks, so this can be polled from the main loop or a timer interrupt.
+/// ```
+/// use ramlink::producer::RxRB;
+///
+/// let mut rx = RxRB::<16>::new();
+/// assert_eq!(rx.read_byte(), None);
+/// assert!(!rx.host_attached());
+/// ```
+#[repr(C)]
+pub struct RxRB<const SIZE: usize, const ID: u8 = 0x88> {
+    /// Tells the host that this ring buffer is to be written
+    _magic_marker: [u8; 3],
+    /// Version of the layout, the same as [`RB`]'s
+    version: u8,
+    /// Size of the ring buffer, 0 meaning 256
+    size: u8,
+    /// Slot the host writes next
+    producer: u8,
+    /// Slot the target reads next. If producer = consumer, ring buffer is empty
+    consumer: u8,
+    /// Unused, kept so that the fields are [`RB`]'s
+    dropped: [u8; 2],
+    /// No optional fields follow the content
+    features: u8,
+    /// Set to 1 by the host while it is attached
+    host_attached: u8,
+    /// The actual buffer
+    content: [u8; SIZE],
+}
+
+// The host writes raw offsets
+const _: () = {
+    assert!(offset_of!(RxRB<7>, _magic_marker) == 0);
+    assert!(
+        matches!(layout::RX_HEADER.version, Some(Offset::Start(v)) if v == offset_of!(RxRB<7>, version))
+    );
+    assert!(offset_of!(RxRB<7>, size) == layout::RX_HEADER.size);
+    assert!(offset_of!(RxRB<7>, producer) == layout::RX.producer);
+    assert!(offset_of!(RxRB<7>, consumer) == layout::RX.consumer);
+    assert!(
+        matches!(layout::RX.dropped, Some(Offset::Start(v)) if v == offset_of!(RxRB<7>, dropped))
+    );
+    assert!(
+        matches!(layout::RX_HEADER.features, Some(Offset::Start(v)) if v == offset_of!(RxRB<7>, features))
+    );
+    assert!(
+        matches!(layout::RX.host_attached, Some(Offset::Start(v)) if v == offset_of!(RxRB<7>, host_attached))
+    );
+    assert!(offset_of!(RxRB<7>, content) == layout::RX.content);
+};
+
+impl<const SIZE: usize, const ID: u8> RxRB<SIZE, ID> {
+    /// Indices are `u8`, evaluated by [`RxRB::new`]
+    const CHECK: () = assert!(SIZE > 0 && SIZE <= 256, "RxRB size must be within 1..=256");
+
+    /// Returns a new ring buffer of size `SIZE`, its content filled with `0x13`
+    pub const fn new() -> Self {
+        let () = Self::CHECK;
+        RxRB {
+            _magic_marker: layout::RX_HEADER.magic(ID),
+            version: layout::VERSION,
+            size: SIZE as u8,
+            producer: 0,
+            consumer: 0,
+            dropped: [0; 2],
+            features: 0,
+            host_attached: 0,
+            content: [0x13; SIZE],
+        }
+    }
+
+    /// Reads the producer index, written by the host
+    fn producer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.producer) }
+    }
+
+    fn consumer(&self) -> u8 {
+        unsafe { core::ptr::read_volatile(&self.consumer) }
+    }
+
+    fn set_consumer(&mut self, index: u8) {
+        unsafe { core::ptr::write_volatile(&mut self.consumer, index) };
+    }
+
+    /// Reads the next byte sent by the host, or returns `None` if there is none
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        (self.read_into(&mut byte) == 1).then_some(byte[0])
+    }
+
+    /// Reads as many of the bytes sent by the host as fit in `buf`, without waiting.
+    /// Returns how many were read. The consumer index is only written once, after the
+    /// bytes were copied, so that the host can't overwrite them meanwhile.
+    /// ```
+    /// use ramlink::producer::RxRB;
+    ///
+    /// let mut rx = RxRB::<8>::new();
+    /// let mut buf = [0; 4];
+    /// assert_eq!(rx.read_into(&mut buf), 0);
+    /// ```
+    pub fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        let prod = self.producer();
+        acquire_fence();
+        let mut cons = self.consumer();
+        let mut read = 0;
+
+        for byte in buf.iter_mut() {
+            if cons == prod {
+                break;
+            }
+            *byte = unsafe { core::ptr::read_volatile(&self.content[cons as usize]) };
+            cons = next_index::<SIZE>(cons);
+            read += 1;
+        }
+
+        if read > 0 {
+            release_fence();
+            self.set_consumer(cons);
+        }
+        read
+    }
+
+    /// Returns the number of bytes the ring buffer can hold, which is `SIZE - 1`.
+    pub fn capacity(&self) -> usize {
+        SIZE - 1
+    }
+
+    /// Returns the number of bytes sent by the host and not read yet.
+    pub fn len(&self) -> usize {
+        let prod = self.producer() as usize;
+        let cons = self.consumer() as usize;
+        wrap::<SIZE>(prod + SIZE - cons)
+    }
+
+    /// Returns `true` if every byte sent by the host was read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` while a host is attached
+    pub fn host_attached(&self) -> bool {
+        unsafe { core::ptr::read_volatile(&self.host_attached) != 0 }
+    }
+}
+
+impl<const SIZE: usize, const ID: u8> Default for RxRB<SIZE, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An [`RB`] to the host followed by an [`RxRB`] from it, so that the address of the
+/// `DuplexRB` is enough for both directions: the host attaches to the `RB` with
+/// `ProducerDevice::new`, and finds the `RxRB` at `ProducerDevice::end_address`.
+/// ```
+/// use ramlink::producer::DuplexRB;
+///
+/// static mut LINK: DuplexRB<64, 16> = DuplexRB::new();
+///
+/// let link = unsafe { &mut *core::ptr::addr_of_mut!(LINK) };
+/// link.tx.send_bytes_blocking(b"ready\n");
+/// if let Some(command) = link.rx.read_byte() {
+///     // ...
+/// #   let _ = command;
+/// }
+/// ```
+#[repr(C)]
+pub struct DuplexRB<const TX: usize, const RX: usize, const ID: u8 = 0x88> {
+    /// From the target to the host
+    pub tx: RB<TX, ID>,
+    /// From the host to the target
+    pub rx: RxRB<RX, ID>,
+}
+
+impl<const TX: usize, const RX: usize, const ID: u8> DuplexRB<TX, RX, ID> {
+    /// Returns both ring buffers, empty
+    pub const fn new() -> Self {
+        DuplexRB {
+            tx: RB::new(),
+            rx: RxRB::new(),
+        }
+    }
+}
+
+impl<const TX: usize, const RX: usize, const ID: u8> Default for DuplexRB<TX, RX, ID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
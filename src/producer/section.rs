@@ -0,0 +1,71 @@
+//! Placement of ring buffers at addresses known to the consumer.
+
+/// Declares a `static` ring buffer under a symbol name that is not mangled, optionally in
+/// its own linker section, so that the consumer can find it.
+///
+/// The symbol is the name of the static, and its address is in the firmware ELF:
+/// `nm firmware.elf | grep RING_BUF` tells what to give to `ProducerDevice::new`. The
+/// ring buffer is initialized with `new()`, and `mut` declares a `static mut`, as needed
+/// by [`RB`](crate::producer::RB):
+/// ```
+/// use ramlink::producer::{AtomicRB, RB};
+/// use ramlink::ramlink_static;
+///
+/// ramlink_static!(RING_BUF: AtomicRB<64> @ section ".ramlink");
+/// ramlink_static!(pub mut OTHER_BUF: RB<16, 0x42>);
+///
+/// RING_BUF.send_bytes_blocking(b"hello");
+/// let other = unsafe { &mut *core::ptr::addr_of_mut!(OTHER_BUF) };
+/// other.send_bytes_blocking(b"hello");
+/// ```
+///
+/// The address still moves when other statics are added, unless the section is placed first
+/// in RAM by the linker script. It must then stay within `.data`, so that the startup code
+/// copies the header of the ring buffer from flash.
+///
+/// On AVR, copy the default linker script of the part (e.g. `avr5.xn` from avr-libc, for
+/// an ATmega328P) and add the section at the start of `.data`:
+/// ```text
+///   .data :
+///   {
+///      PROVIDE (__data_start = .) ;
+///     KEEP(*(.ramlink))
+///     *(.data)
+///     ...
+/// ```
+/// then link with it, e.g. in `.cargo/config.toml`:
+/// ```text
+/// [target.avr-none]
+/// rustflags = ["-C", "link-arg=-Wl,-T,avr5-ramlink.xn"]
+/// ```
+/// The ring buffer is then at the start of RAM, `0x0100` on an ATmega328P.
+///
+/// On Cortex-M, with `cortex-m-rt`, copy its `link.x` next to `memory.x`, add the section
+/// at the start of `.data` in the same way:
+/// ```text
+///   .data : ALIGN(4)
+///   {
+///     . = ALIGN(4);
+///     __sdata = .;
+///     KEEP(*(.ramlink));
+///     *(.data .data.*);
+///     ...
+/// ```
+/// and link with it instead of the one of `cortex-m-rt`, with `-C link-arg=-Tlink.x` and
+/// the copy first in the linker search path. The ring buffer is then at `ORIGIN(RAM)`,
+/// unless `.data` was moved.
+#[macro_export]
+macro_rules! ramlink_static {
+    ($(#[$attr:meta])* $vis:vis mut $name:ident : $rb:ident<$($arg:tt),+> $(@ section $section:literal)?) => {
+        $(#[$attr])*
+        $(#[link_section = $section])?
+        #[no_mangle]
+        $vis static mut $name: $rb<$($arg),+> = $rb::<$($arg),+>::new();
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident : $rb:ident<$($arg:tt),+> $(@ section $section:literal)?) => {
+        $(#[$attr])*
+        $(#[link_section = $section])?
+        #[no_mangle]
+        $vis static $name: $rb<$($arg),+> = $rb::<$($arg),+>::new();
+    };
+}
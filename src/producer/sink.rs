@@ -0,0 +1,323 @@
+//! Send modes and framing formats, written once for all the ring buffers of the producer.
+
+#[cfg(feature = "compression")]
+use super::compress;
+use super::{acquire_fence, release_fence, SendTimeout, TimestampSource};
+use crate::layout;
+
+/// What the send methods need of a ring buffer: its indices, its content and its counters.
+/// Each ring buffer implements these with the width of its indices and its own accesses,
+/// volatile or atomic, and the send modes and framing formats are provided on top of them,
+/// so that they behave the same whichever ring buffer the firmware uses.
+pub(crate) trait ByteSink {
+    /// Type of the indices, kept as narrow as the ring buffer's, as arithmetic on `usize`
+    /// is twice as wide on AVR
+    type Index: Copy + PartialEq;
+
+    /// Reads the producer index
+    fn producer(&self) -> Self::Index;
+
+    /// Reads the consumer index, written by the consumer
+    fn consumer(&self) -> Self::Index;
+
+    /// Returns the slot following `index`
+    fn next(index: Self::Index) -> Self::Index;
+
+    /// Writes `byte` in the content slot `index`
+    fn write_slot(&mut self, index: Self::Index, byte: u8);
+
+    /// Writes the producer index, once the bytes it covers were written
+    fn publish(&mut self, index: Self::Index);
+
+    /// Returns the number of bytes the ring buffer can hold
+    fn capacity(&self) -> usize;
+
+    /// Returns the number of bytes waiting to be read by the consumer
+    fn len(&self) -> usize;
+
+    /// Counts `n` more discarded bytes
+    fn record_dropped(&mut self, n: usize);
+
+    /// Returns `true` while a consumer is attached
+    fn host_attached(&self) -> bool;
+
+    /// Updates the statistics once `n` more bytes were published to the consumer
+    fn record_sent(&mut self, _n: usize) {}
+
+    /// Called before the indices are read by each send, e.g. to initialize the ring buffer
+    fn before_send(&mut self) {}
+
+    /// Returns the number of bytes that can be sent without blocking
+    fn free_space(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Sends as many bytes of `data` as currently fit, publishing the producer index once,
+    /// after the accepted bytes were written. Returns the number of bytes accepted.
+    fn try_send_bytes(&mut self, data: &[u8]) -> usize {
+        self.before_send();
+        let cons = self.consumer();
+        acquire_fence();
+        let mut prod = self.producer();
+        let mut sent = 0;
+
+        for elem in data.iter() {
+            let next_p = Self::next(prod);
+            if next_p == cons {
+                break;
+            }
+            self.write_slot(prod, *elem);
+            prod = next_p;
+            sent += 1;
+        }
+
+        if sent > 0 {
+            release_fence();
+            self.publish(prod);
+            self.record_sent(sent);
+        }
+        sent
+    }
+
+    /// Sends `data`, calling `idle` each time the ring buffer is found full
+    fn send_bytes_blocking_with(&mut self, data: &[u8], mut idle: impl FnMut()) {
+        let mut data = data;
+        while !data.is_empty() {
+            let sent = self.try_send_bytes(data);
+            if sent == 0 {
+                idle();
+            }
+            data = &data[sent..];
+        }
+    }
+
+    /// Sends `data`, busy-waiting while the ring buffer is full
+    fn send_bytes_blocking(&mut self, data: &[u8]) {
+        self.send_bytes_blocking_with(data, || {})
+    }
+
+    /// Sends `data`, giving up once the ring buffer stayed full for `max_spins` polls
+    fn send_bytes_timeout(&mut self, data: &[u8], max_spins: u32) -> Result<(), SendTimeout> {
+        let mut written = 0;
+        let mut spins = 0;
+
+        while written < data.len() {
+            let sent = self.try_send_bytes(&data[written..]);
+            if sent > 0 {
+                written += sent;
+                spins = 0;
+            } else if spins == max_spins {
+                return Err(SendTimeout { written });
+            } else {
+                spins += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what fits of `data`, and counts the rest as dropped
+    fn send_bytes_lossy(&mut self, data: &[u8]) -> usize {
+        let sent = self.try_send_bytes(data);
+        self.record_dropped(data.len() - sent);
+        sent
+    }
+
+    /// Sends `data`, waiting for space only while a consumer is attached
+    fn send_bytes_auto(&mut self, data: &[u8]) {
+        let mut data = data;
+        loop {
+            data = &data[self.try_send_bytes(data)..];
+            if data.is_empty() {
+                return;
+            }
+            if !self.host_attached() {
+                self.record_dropped(data.len());
+                return;
+            }
+        }
+    }
+
+    /// Sends the channel byte, the length byte, then `payload`
+    fn send_frame_on(&mut self, channel: u8, payload: &[u8]) {
+        assert!(payload.len() <= 255, "frames are at most 255 bytes long");
+        self.send_bytes_blocking(&[channel, payload.len() as u8]);
+        self.send_bytes_blocking(payload);
+    }
+
+    /// Sends a frame of `channel` if it fits as a whole
+    fn try_send_frame_on(&mut self, channel: u8, payload: &[u8]) -> bool {
+        if payload.len() > 255 || self.free_space() < payload.len() + 2 {
+            return false;
+        }
+        self.try_send_bytes(&[channel, payload.len() as u8]);
+        self.try_send_bytes(payload);
+        true
+    }
+
+    /// Sends `text` as a frame tagged as text
+    fn send_text(&mut self, text: &str) {
+        self.send_frame_on(layout::TAG_TEXT, text.as_bytes());
+    }
+
+    /// Sends `data` as a frame tagged as binary
+    fn send_binary(&mut self, data: &[u8]) {
+        self.send_frame_on(layout::TAG_BINARY, data);
+    }
+
+    /// Sends `payload` encoded with COBS, followed by a zero
+    fn send_frame_cobs(&mut self, payload: &[u8]) {
+        let mut rest = payload;
+        loop {
+            // Each block is a code byte, then up to 254 bytes that are not zero. A code below
+            // 0xFF stands for a zero after the block, except for the last block
+            let run = rest
+                .iter()
+                .take(254)
+                .position(|&b| b == 0)
+                .unwrap_or(rest.len().min(254));
+            self.send_bytes_blocking(&[run as u8 + 1]);
+            self.send_bytes_blocking(&rest[..run]);
+            if run == rest.len() {
+                break;
+            }
+            rest = &rest[if run == 254 { run } else { run + 1 }..];
+        }
+        self.send_bytes_blocking(&[0]);
+    }
+
+    /// Sends `payload` escaped with SLIP, between two END bytes
+    fn send_frame_slip(&mut self, payload: &[u8]) {
+        self.send_bytes_blocking(&[layout::SLIP_END]);
+        let mut rest = payload;
+        while !rest.is_empty() {
+            let run = rest
+                .iter()
+                .position(|&b| b == layout::SLIP_END || b == layout::SLIP_ESC)
+                .unwrap_or(rest.len());
+            self.send_bytes_blocking(&rest[..run]);
+            if let Some(&special) = rest.get(run) {
+                let escaped = if special == layout::SLIP_END {
+                    layout::SLIP_ESC_END
+                } else {
+                    layout::SLIP_ESC_ESC
+                };
+                self.send_bytes_blocking(&[layout::SLIP_ESC, escaped]);
+                rest = &rest[run + 1..];
+            } else {
+                rest = &[];
+            }
+        }
+        self.send_bytes_blocking(&[layout::SLIP_END]);
+    }
+
+    /// Sends the length byte, `payload`, then the CRC-8 of both
+    fn send_frame_crc(&mut self, payload: &[u8]) {
+        assert!(payload.len() <= 255, "frames are at most 255 bytes long");
+        let len = payload.len() as u8;
+        let crc = payload.iter().fold(layout::crc8_update(0, len), |crc, &b| {
+            layout::crc8_update(crc, b)
+        });
+        self.send_bytes_blocking(&[len]);
+        self.send_bytes_blocking(payload);
+        self.send_bytes_blocking(&[crc]);
+    }
+
+    /// Sends `payload` compressed with LZSS, or as it is if it does not shrink
+    #[cfg(feature = "compression")]
+    fn send_frame_compressed<const WINDOW_BITS: u32>(&mut self, payload: &[u8]) {
+        const {
+            assert!(
+                WINDOW_BITS >= *layout::LZSS_WINDOW_BITS.start()
+                    && WINDOW_BITS <= *layout::LZSS_WINDOW_BITS.end(),
+                "the window must be within 4..=12 bits"
+            )
+        };
+        let mut len = 0;
+        compress::compress(payload, WINDOW_BITS, |group| len += group.len());
+        if len < payload.len() {
+            assert!(len < 255, "frames are at most 255 bytes long");
+            self.send_bytes_blocking(&[len as u8 + 1, WINDOW_BITS as u8]);
+            compress::compress(payload, WINDOW_BITS, |group| {
+                self.send_bytes_blocking(group)
+            });
+        } else {
+            assert!(payload.len() < 255, "frames are at most 255 bytes long");
+            self.send_bytes_blocking(&[payload.len() as u8 + 1, layout::LZSS_STORED]);
+            self.send_bytes_blocking(payload);
+        }
+    }
+
+    /// Sends the length byte, the time of the clock `T`, then `payload`
+    fn send_frame_timestamped<T: TimestampSource>(&mut self, payload: &[u8]) {
+        let timestamp = T::now();
+        assert!(
+            payload.len() + 4 <= self.capacity(),
+            "timestamped frames must fit in the ring buffer"
+        );
+        self.send_bytes_blocking(&[payload.len() as u8 + 4]);
+        self.send_bytes_blocking(&timestamp.to_le_bytes());
+        self.send_bytes_blocking(payload);
+    }
+
+    /// Sends the length of `payload` as a LEB128 varint, then `payload`
+    fn send_frame_varint(&mut self, payload: &[u8]) {
+        self.send_varint(payload.len());
+        self.send_bytes_blocking(payload);
+    }
+
+    /// Sends `len` as a LEB128 varint
+    fn send_varint(&mut self, len: usize) {
+        let mut prefix = [0; (usize::BITS as usize).div_ceil(7)];
+        let mut len = len;
+        let mut n = 0;
+        loop {
+            prefix[n] = (len & 0x7F) as u8;
+            len >>= 7;
+            n += 1;
+            if len == 0 {
+                break;
+            }
+            prefix[n - 1] |= 0x80;
+        }
+        self.send_bytes_blocking(&prefix[..n]);
+    }
+
+    /// Sends `value` serialized with postcard, as a frame prefixed with its length as a
+    /// LEB128 varint. It is serialized twice, to count its bytes and then to send them, so
+    /// that no buffer is needed. Nothing is sent if it can't be serialized.
+    #[cfg(feature = "postcard")]
+    fn send_postcard<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), postcard::Error>
+    where
+        Self: Sized,
+    {
+        let len = postcard::serialize_with_flavor(value, postcard::ser_flavors::Size::default())?;
+        self.send_varint(len);
+        postcard::serialize_with_flavor(value, PostcardFlavor(self))
+    }
+}
+
+/// Sends the bytes of a value serialized with postcard as they come
+#[cfg(feature = "postcard")]
+struct PostcardFlavor<'s, S>(&'s mut S);
+
+#[cfg(feature = "postcard")]
+impl<S: ByteSink> postcard::ser_flavors::Flavor for PostcardFlavor<'_, S> {
+    type Output = ();
+
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        self.0.send_bytes_blocking(data);
+        Ok(())
+    }
+
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.0.send_bytes_blocking(&[data]);
+        Ok(())
+    }
+
+    fn finalize(self) -> postcard::Result<()> {
+        Ok(())
+    }
+}
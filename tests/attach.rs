@@ -0,0 +1,35 @@
+//! Headers the consumer must refuse to attach to
+
+use ramlink::consumer::testing::InMemoryReader;
+use ramlink::consumer::{ConsumerErrorKind, ProducerDevice};
+
+/// Returns the bytes of an `RB16` of version 1 whose size is `size`, with an empty content
+fn rb16(size: u16) -> Vec<u8> {
+    let mut ram = vec![0; 14 + 16];
+    ram[..4].copy_from_slice(&[0x89, 0x16, 0x88, 1]);
+    ram[4..6].copy_from_slice(&size.to_le_bytes());
+    ram
+}
+
+#[test]
+fn rb16_of_size_0_is_too_small() {
+    // Only the 8 bits indices of `RB` have a size of 0 standing for 256
+    let err = ProducerDevice::new(InMemoryReader::new(rb16(0)), 0)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), ConsumerErrorKind::RingBufferTooSmall));
+}
+
+#[test]
+fn rb16_of_size_1_is_too_small() {
+    let err = ProducerDevice::new(InMemoryReader::new(rb16(1)), 0)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), ConsumerErrorKind::RingBufferTooSmall));
+}
+
+#[test]
+fn rb16_of_size_16_attaches() {
+    let device = ProducerDevice::new(InMemoryReader::new(rb16(16)), 0).unwrap();
+    assert_eq!(device.capacity(), 15);
+}
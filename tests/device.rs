@@ -0,0 +1,225 @@
+//! How a `ProducerDevice` copes with the transfers of its memory reader, through a
+//! `FaultyReader`
+
+use core::ops::ControlFlow;
+use ramlink::consumer::testing::{Faults, FaultyReader, InMemoryReader, Loopback};
+use ramlink::consumer::{ConsumerErrorKind, PollPolicy, ProducerDevice};
+use std::time::Duration;
+
+/// Returns a device reading a loopback through a `FaultyReader`, and its faults
+fn faulty_device<const SIZE: usize>(
+    loopback: &Loopback<SIZE>,
+) -> (ProducerDevice<FaultyReader<InMemoryReader>>, Faults) {
+    let reader = FaultyReader::new(loopback.memory());
+    let faults = reader.faults();
+    (ProducerDevice::new(reader, 0).unwrap(), faults)
+}
+
+#[test]
+fn read_takes_at_most_four_transfers() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    let mut transfers = |expected: &[u8]| {
+        faults.reset_counts();
+        assert_eq!(device.read_bytes().unwrap(), expected);
+        (faults.reads(), faults.writes())
+    };
+
+    assert_eq!(transfers(b""), (1, 0));
+    loopback.send_bytes_blocking(b"abcdef");
+    assert_eq!(transfers(b"abcdef"), (2, 1));
+
+    // Wraps around the end of the ring buffer
+    loopback.send_bytes_blocking(b"hello");
+    assert_eq!(transfers(b"hello"), (3, 1));
+}
+
+#[test]
+fn consumer_cache_saves_reading_the_consumer_index() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    let read_bytes = |device: &mut ProducerDevice<_>| {
+        faults.reset_counts();
+        (device.read_bytes().unwrap(), faults.bytes_read())
+    };
+    assert_eq!(read_bytes(&mut device), (vec![], 2));
+
+    device.set_consumer_cache(true);
+    loopback.send_bytes_blocking(b"abc");
+    assert_eq!(read_bytes(&mut device), (b"abc".to_vec(), 2 + 3));
+    // A quiet poll is a single one byte read
+    assert_eq!(read_bytes(&mut device), (vec![], 1));
+
+    // The producer starts over: "abc" is stale after a reset
+    loopback.with_producer(|rb| rb.reset());
+    loopback.send_bytes_blocking(b"hello");
+    device.refresh();
+    assert_eq!(read_bytes(&mut device), (b"hello".to_vec(), 2 + 5));
+}
+
+#[test]
+fn failed_reads_neither_lose_nor_repeat_bytes() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    faults.fail_every(3);
+    let message: Vec<u8> = (0..100).collect();
+
+    // Without retries, reads fail, but the bytes all arrive once and in order
+    let mut received = Vec::new();
+    let mut failures = 0;
+    for chunk in message.chunks(5) {
+        loopback.send_bytes_blocking(chunk);
+        while !loopback.with_producer(|rb| rb.is_empty()) {
+            match device.read_bytes() {
+                Ok(bytes) => received.extend(bytes),
+                Err(_) => failures += 1,
+            }
+        }
+    }
+    assert_eq!(received, message);
+    assert!(failures > 0);
+
+    // With a retry, no read fails
+    device.set_retry(2, Duration::from_micros(10));
+    received.clear();
+    for chunk in message.chunks(5) {
+        loopback.send_bytes_blocking(chunk);
+        received.extend(device.read_bytes().unwrap());
+    }
+    assert_eq!(received, message);
+}
+
+#[test]
+fn value_read_writes_back_a_failed_consumer_index() {
+    let loopback = Loopback::<16>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    device.set_integrity_check(1);
+
+    loopback.send_bytes_blocking(&7u16.to_le_bytes());
+    faults.set_fail_writes(true);
+    assert_eq!(device.read_u16_le().unwrap(), Some(7));
+    assert_eq!(loopback.with_producer(|rb| rb.len()), 2);
+
+    // The next read writes the consumer index, even if it reads nothing
+    faults.set_fail_writes(false);
+    assert_eq!(device.read_u32_le().unwrap(), None);
+    assert!(loopback.with_producer(|rb| rb.is_empty()));
+
+    // The ring buffer is gone, e.g. the target was reset
+    loopback.memory().memory()[0] = 0;
+    let err = device.read_u16_le().unwrap_err();
+    assert!(matches!(err.kind(), ConsumerErrorKind::Desynchronized));
+}
+
+#[test]
+fn failed_transfer_consumes_only_what_was_returned() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    let waiting = || loopback.with_producer(|rb| rb.len());
+    loopback.send_bytes_blocking(b"abcde");
+    assert_eq!(device.read_bytes().unwrap(), b"abcde");
+
+    // Wraps around: the transfers are the indices, "fgh", "ij", and the consumer index
+    loopback.send_bytes_blocking(b"fghij");
+    faults.fail_transfer(2);
+    assert!(device.read_bytes().is_err());
+    assert_eq!(waiting(), 5);
+
+    faults.fail_transfer(3);
+    assert_eq!(device.read_bytes().unwrap(), b"fgh");
+    assert_eq!(waiting(), 2);
+
+    faults.fail_transfer(3);
+    assert_eq!(device.read_bytes().unwrap(), b"ij");
+    assert_eq!(waiting(), 2);
+    assert_eq!(device.read_bytes().unwrap(), b"");
+    assert_eq!(waiting(), 0);
+}
+
+#[test]
+fn garbage_is_never_read_out_of_the_ring_buffer() {
+    // Random bytes behind the magic marker of an `RB`, or in an `RBCompact`
+    let mut seed = 0x2545_f491_u32;
+    let mut random = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+    let mut attached = 0;
+    for _ in 0..1000 {
+        let mut ram: Vec<u8> = (0..300).map(|_| random()).collect();
+        let size = 1 + random() as usize;
+        let compact = ProducerDevice::new_unchecked(InMemoryReader::new(ram.clone()), 0, size);
+        ram[..3].copy_from_slice(&[0x88, 0x88, 0x88]);
+        let rb = ProducerDevice::new(InMemoryReader::new(ram), 0);
+        for mut device in [rb, compact].into_iter().flatten() {
+            attached += 1;
+            let mut buf = [0; 512];
+            for _ in 0..3 {
+                if let Ok(read) = device.read_into(&mut buf) {
+                    assert!(read <= device.capacity());
+                }
+            }
+        }
+    }
+    assert!(attached > 1000);
+}
+
+#[test]
+fn peek_never_writes() {
+    let loopback = Loopback::<16>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    device.set_integrity_check(1);
+
+    // The consumer index can't be written back, so the next read would retry it
+    loopback.send_bytes_blocking(b"hello");
+    faults.set_fail_writes(true);
+    assert_eq!(device.read_bytes().unwrap(), b"hello");
+    faults.set_fail_writes(false);
+
+    faults.reset_counts();
+    loopback.send_bytes_blocking(b"!");
+    let mut buf = [0; 8];
+    assert_eq!(device.peek_into(&mut buf).unwrap(), 1);
+    assert_eq!(&buf[..1], b"!");
+    assert_eq!(device.peek_available().unwrap(), 1);
+    assert_eq!(loopback.with_producer(|rb| rb.len()), 6);
+    assert_eq!(faults.writes(), 0);
+
+    assert_eq!(device.read_bytes().unwrap(), b"!");
+    assert!(loopback.with_producer(|rb| rb.is_empty()));
+}
+
+#[test]
+fn adaptive_poll_backs_off_while_nothing_arrives() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+
+    // An empty read only reads the indices: the fourth read finds the bytes
+    let producer = loopback.clone();
+    faults.on_read(4, move || producer.send_bytes_blocking(b"late"));
+    let policy = PollPolicy::adaptive(Duration::from_millis(1), Duration::from_millis(2));
+    device.run(policy, |_| ControlFlow::Break(())).unwrap();
+    // Slept 1 ms, 2 ms, then 2 ms again
+    assert_eq!(device.stats().sleeps, [1, 1, 1, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn dump_retries_capped_transfers_in_halves() {
+    let loopback = Loopback::<8>::new();
+    let (mut device, faults) = faulty_device(&loopback);
+    loopback.send_bytes_blocking(b"hello");
+    faults.set_max_read(Some(6));
+
+    // 20 bytes: reading 20 then 10 fails, then 5 bytes at a time works
+    faults.reset_counts();
+    let snapshot = device.dump().unwrap();
+    assert_eq!(snapshot.content, b"hello\x13\x13\x13");
+    assert_eq!(faults.reads(), 2 + 4);
+
+    device.set_block_size(6);
+    faults.reset_counts();
+    assert_eq!(device.dump().unwrap(), snapshot);
+    assert_eq!(faults.reads(), 4);
+}
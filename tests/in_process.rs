@@ -0,0 +1,37 @@
+//! A producer and a consumer in this very process, the consumer reading the memory of the
+//! producer through a `VolatileReader`, as a debug probe reads the RAM of a device
+
+use ramlink::consumer::testing::VolatileReader;
+use ramlink::consumer::ProducerDevice;
+use ramlink::producer::RBIndirect;
+
+#[repr(C)]
+struct IndirectRam {
+    rb: RBIndirect<8>,
+    gap: [u8; 100],
+    content: [u8; 8],
+}
+
+#[test]
+fn rb_indirect_is_read_where_its_pointer_says() {
+    // The consumer accesses the ring buffer meanwhile, so it is only borrowed for a call
+    let ram: *mut IndirectRam = Box::into_raw(Box::new(IndirectRam {
+        rb: unsafe { RBIndirect::new(core::ptr::null_mut()) },
+        gap: [0; 100],
+        content: [0; 8],
+    }));
+    unsafe { (*ram).rb = RBIndirect::new(core::ptr::addr_of_mut!((*ram).content)) };
+    let reader = unsafe { VolatileReader::new(ram as *mut u8, size_of::<IndirectRam>()) };
+    let mut device = ProducerDevice::new(reader, ram as usize).unwrap();
+    assert_eq!(device.capacity(), 7);
+
+    // Wraps around the end of the content
+    unsafe { (*ram).rb.send_bytes_blocking(b"abcde") };
+    assert_eq!(device.read_bytes().unwrap(), b"abcde");
+    unsafe { (*ram).rb.send_bytes_blocking(b"fghij") };
+    assert_eq!(device.read_bytes().unwrap(), b"fghij");
+    assert_eq!(unsafe { (*ram).content }, *b"ijcdefgh");
+
+    drop(device);
+    drop(unsafe { Box::from_raw(ram) });
+}
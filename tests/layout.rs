@@ -0,0 +1,93 @@
+//! The default `RB` is still read by the consumers of the first releases, and the consumer
+//! still reads their ring buffers
+
+use ramlink::consumer::testing::InMemoryReader;
+use ramlink::consumer::{ConsumerErrorKind, ProducerDevice};
+use ramlink::producer::RB;
+use ramlink::LayoutDescriptor;
+
+/// Returns the bytes of `rb`, as a debugger would read them
+fn snapshot<const SIZE: usize>(rb: &RB<SIZE>) -> Vec<u8> {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            rb as *const RB<SIZE> as *const u8,
+            core::mem::size_of_val(rb),
+        )
+    };
+    bytes.to_vec()
+}
+
+/// Returns the bytes of an `RB<8>` of version 0 holding `hi`, followed by `after`
+fn rb_v0(after: &[u8]) -> Vec<u8> {
+    let mut ram = vec![0x88, 0x88, 0x88, 8, 2, 0];
+    ram.extend_from_slice(b"hi\x13\x13\x13\x13\x13\x13");
+    ram.extend_from_slice(after);
+    ram
+}
+
+#[test]
+fn rb_starts_as_version_0() {
+    let mut rb = RB::<8>::new();
+    rb.send_bytes_blocking(b"hi");
+    let ram = snapshot(&rb);
+    assert_eq!(ram[..6], [0x88, 0x88, 0x88, 8, 2, 0]);
+    assert_eq!(ram[6..8], *b"hi");
+
+    // Read as a consumer of the first releases would, through the offsets of version 0
+    let memory = InMemoryReader::new(ram);
+    let mut device =
+        ProducerDevice::new_with_layout(memory.clone(), 0, LayoutDescriptor::v0()).unwrap();
+    assert_eq!(device.version(), 0);
+    assert_eq!(device.read_bytes().unwrap(), b"hi");
+    drop(device);
+    assert_eq!(memory.memory()[5], 2);
+}
+
+#[test]
+fn rb_has_its_fields_after_the_content() {
+    let mut rb = RB::<8>::new();
+    rb.send_bytes_blocking(b"hi");
+    let ram = snapshot(&rb);
+    assert_eq!(ram[14..16], [0x89, 1]);
+
+    let mut device = ProducerDevice::new(InMemoryReader::new(ram), 0).unwrap();
+    assert_eq!(device.version(), 1);
+    assert_eq!(device.read_bytes().unwrap(), b"hi");
+}
+
+#[test]
+fn rb_v0_is_told_apart_by_the_missing_marker() {
+    let mut device = ProducerDevice::new(InMemoryReader::new(rb_v0(&[0; 16])), 0).unwrap();
+    assert_eq!(device.version(), 0);
+    assert_eq!(device.read_bytes().unwrap(), b"hi");
+    assert_eq!(device.dropped_bytes().unwrap(), 0);
+}
+
+#[test]
+fn rb_v0_at_the_end_of_the_memory() {
+    let mut device = ProducerDevice::new(InMemoryReader::new(rb_v0(&[])), 0).unwrap();
+    assert_eq!(device.version(), 0);
+    assert_eq!(device.read_bytes().unwrap(), b"hi");
+}
+
+#[test]
+fn rb_v0_is_not_the_expected_v1() {
+    let memory = InMemoryReader::new(rb_v0(&[0; 16]));
+    let err = ProducerDevice::new_with_layout(memory, 0, LayoutDescriptor::v1())
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), ConsumerErrorKind::MagicMarkerNotFound));
+}
+
+#[test]
+fn rb_of_a_newer_version() {
+    let mut ram = snapshot(&RB::<8>::new());
+    ram[15] = 2;
+    let err = ProducerDevice::new(InMemoryReader::new(ram), 0)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.kind(),
+        ConsumerErrorKind::UnsupportedVersion(2)
+    ));
+}
@@ -0,0 +1,66 @@
+//! A real `RB` against a `ProducerDevice`, through `consumer::testing::Loopback`
+
+use ramlink::consumer::testing::Loopback;
+use std::time::Duration;
+
+#[test]
+fn wraps_around_the_end_of_the_ring_buffer() {
+    let loopback = Loopback::<8>::new();
+    let mut device = loopback.device().unwrap();
+
+    loopback.send_bytes_blocking(b"hello");
+    assert_eq!(device.read_bytes().unwrap(), b"hello");
+    loopback.send_bytes_blocking(b"world!");
+    assert_eq!(loopback.with_producer(|rb| rb.len()), 6);
+    assert_eq!(device.read_bytes().unwrap(), b"world!");
+    assert!(loopback.with_producer(|rb| rb.is_empty()));
+}
+
+#[test]
+fn drains_in_several_reads() {
+    let loopback = Loopback::<8>::new();
+    let mut device = loopback.device().unwrap();
+    loopback.send_bytes_blocking(b"hello");
+    assert_eq!(device.read_bytes().unwrap(), b"hello");
+
+    // Across the end of the ring buffer
+    loopback.send_bytes_blocking(b"world!");
+    assert_eq!(device.read_bytes_max(4).unwrap(), b"worl");
+    loopback.send_bytes_blocking(b"?");
+    assert_eq!(device.read_bytes_max(4).unwrap(), b"d!?");
+    assert_eq!(device.read_bytes().unwrap(), b"");
+    assert!(loopback.with_producer(|rb| rb.is_empty()));
+}
+
+#[test]
+fn full_ring_buffer_blocks_until_the_consumer_reads() {
+    let loopback = Loopback::<4>::new();
+    let mut device = loopback.device().unwrap();
+    let data: Vec<u8> = (0..100).collect();
+
+    let producer = std::thread::spawn({
+        let (loopback, data) = (loopback.clone(), data.clone());
+        move || loopback.send_bytes_blocking(&data)
+    });
+    let mut received = Vec::new();
+    while received.len() < data.len() {
+        received.extend(device.read_bytes().unwrap());
+        std::thread::sleep(Duration::from_micros(100));
+    }
+    producer.join().unwrap();
+    assert_eq!(received, data);
+    assert!(loopback.with_producer(|rb| rb.is_empty()));
+}
+
+#[test]
+fn full_ring_buffer_rejects_what_does_not_fit() {
+    let loopback = Loopback::<4>::new();
+    let mut device = loopback.device().unwrap();
+
+    assert_eq!(loopback.with_producer(|rb| rb.try_send_bytes(b"hello")), 3);
+    assert!(loopback.with_producer(|rb| rb.is_full()));
+    assert!(!loopback.with_producer(|rb| rb.try_send_byte(b'!')));
+    assert_eq!(device.read_bytes().unwrap(), b"hel");
+    assert!(loopback.with_producer(|rb| rb.try_send_byte(b'!')));
+    assert_eq!(device.read_bytes().unwrap(), b"!");
+}
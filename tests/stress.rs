@@ -0,0 +1,47 @@
+//! A producer thread and a consumer thread hammer a ring buffer at once
+
+use ramlink::consumer::testing::VolatileReader;
+use ramlink::consumer::ProducerDevice;
+use ramlink::producer::AtomicRB;
+
+const BYTES: usize = 2_000_000;
+
+/// 251 is prime, so the pattern does not repeat with the size of the ring buffer
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+#[test]
+fn no_byte_is_lost_duplicated_or_read_before_it_was_written() {
+    static RING_BUF: AtomicRB<64> = AtomicRB::new();
+    let reader = VolatileReader::from_atomic_rb(&RING_BUF);
+    let mut device = ProducerDevice::new(reader, &RING_BUF as *const _ as usize).unwrap();
+
+    let producer = std::thread::spawn(|| {
+        let data: Vec<u8> = (0..BYTES).map(pattern).collect();
+        for chunk in data.chunks(1000) {
+            // Yielding lets the consumer run on a single core
+            RING_BUF.send_bytes_blocking_with(chunk, std::thread::yield_now);
+        }
+    });
+
+    let mut buf = [0; 64];
+    let mut received = 0;
+    while received < BYTES {
+        let read = device.read_into(&mut buf).unwrap();
+        for (i, &byte) in buf[..read].iter().enumerate() {
+            assert_eq!(
+                byte,
+                pattern(received + i),
+                "byte {} is wrong",
+                received + i
+            );
+        }
+        received += read;
+        if read == 0 {
+            std::thread::yield_now();
+        }
+    }
+    producer.join().unwrap();
+    assert_eq!(device.available().unwrap(), 0);
+}